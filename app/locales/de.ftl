@@ -0,0 +1,270 @@
+# Chrome
+theme-dark-mode = Dunkler Modus
+theme-light-mode = Heller Modus
+search-users = Benutzer suchen
+search-no-results = Keine Benutzer gefunden
+
+# Timestamps
+date-never = Nie
+date-just-now = gerade eben
+date-minutes-ago = { $count ->
+    [one] vor { $count } Minute
+   *[other] vor { $count } Minuten
+}
+date-hours-ago = { $count ->
+    [one] vor { $count } Stunde
+   *[other] vor { $count } Stunden
+}
+date-days-ago = { $count ->
+    [one] vor { $count } Tag
+   *[other] vor { $count } Tagen
+}
+date-months-ago = { $count ->
+    [one] vor { $count } Monat
+   *[other] vor { $count } Monaten
+}
+date-years-ago = { $count ->
+    [one] vor { $count } Jahr
+   *[other] vor { $count } Jahren
+}
+date-in-minutes = { $count ->
+    [one] in { $count } Minute
+   *[other] in { $count } Minuten
+}
+date-in-hours = { $count ->
+    [one] in { $count } Stunde
+   *[other] in { $count } Stunden
+}
+date-in-days = { $count ->
+    [one] in { $count } Tag
+   *[other] in { $count } Tagen
+}
+date-in-months = { $count ->
+    [one] in { $count } Monat
+   *[other] in { $count } Monaten
+}
+date-in-years = { $count ->
+    [one] in { $count } Jahr
+   *[other] in { $count } Jahren
+}
+
+# Login
+login-title = Anmelden
+login-login = Benutzername
+login-password = Passwort
+login-submit = Anmelden
+login-code = Authentifizierungscode
+login-code-help = Geben Sie den Code aus Ihrer Authenticator-App oder einen Ihrer Wiederherstellungscodes ein.
+login-remember = Diesem Browser 30 Tage lang vertrauen
+login-verify = Bestätigen
+login-or = oder
+login-oidc-with = Mit { $provider } anmelden
+login-oidc-failed = Die Anmeldung beim Identitätsanbieter ist fehlgeschlagen. Versuchen Sie es erneut oder verwenden Sie Ihr Passwort.
+
+# User edit
+user-loading = Wird geladen...
+user-not-found = Benutzer konnte nicht geladen werden
+user-sign-in = Anmelden
+user-server-error = Serverfehler:
+user-tab-information = Benutzerinformationen
+user-tab-access-roles = Zugriffsrollen
+user-tab-emails = E-Mails
+user-tab-browsers = Webbrowser
+user-tab-two-factor = Zwei-Faktor-Authentifizierung
+user-first-name = Vorname
+user-last-name = Nachname
+user-login-email = Anmelde-E-Mail
+user-edit = Bearbeiten
+user-save = Speichern
+user-registered-on = Registriert am
+user-last-login = Letzte Anmeldung
+user-last-failed-login = Letzte fehlgeschlagene Anmeldung
+user-status = Benutzerstatus
+user-theme = Design
+user-language = Sprache
+user-language-browser = Browserstandard
+user-new-password = Neues Passwort
+user-retype-password = Neues Passwort wiederholen
+user-current-password = Aktuelles Passwort
+user-personal-record = Personalakte
+user-download-personal-data = Persönliche Daten herunterladen
+user-remove-picture = Bild entfernen
+
+user-status-active = Aktiv
+user-status-banned = Gesperrt
+user-status-deactivated = Deaktiviert
+user-status-pending = Ausstehend
+
+theme-light = Hell
+theme-dark = Dunkel
+
+# Web browsers
+browsers-browser = Browser
+browsers-os = Betriebssystem
+browsers-ip = IP-Adresse
+browsers-signed-in = Angemeldet
+browsers-last-seen = Zuletzt gesehen
+browsers-this-browser = Dieser Browser
+browsers-revoke = Abmelden
+browsers-sign-out-everywhere = Überall abmelden
+browsers-trusted = Vertrauenswürdige Geräte
+browsers-trusted-none = Kein Browser überspringt die Zwei-Faktor-Authentifizierung.
+browsers-last-used = Zuletzt verwendet
+browsers-trusted-until = Vertraut bis
+browsers-revoke-trust = Vertrauen entziehen
+
+# Two-factor authentication
+two-factor-enabled = Die Zwei-Faktor-Authentifizierung ist aktiviert. { $count ->
+    [one] { $count } Wiederherstellungscode übrig.
+   *[other] { $count } Wiederherstellungscodes übrig.
+}
+two-factor-disabled = Die Zwei-Faktor-Authentifizierung ist nicht aktiviert.
+two-factor-reset = Zwei-Faktor-Authentifizierung zurücksetzen
+two-factor-scan = Scannen Sie diesen Code mit Ihrer Authenticator-App oder geben Sie diesen Schlüssel von Hand ein:
+two-factor-confirm = Bestätigen
+two-factor-set-up = Authenticator-App einrichten
+two-factor-recovery-codes = Bewahren Sie diese Wiederherstellungscodes sicher auf. Jeder kann einmal verwendet werden, falls Sie Ihr Telefon verlieren.
+
+# User list
+users-title = Benutzer
+users-export = CSV exportieren
+users-import = CSV importieren
+users-invite = Benutzer einladen
+users-search = Nach Login oder Name suchen
+users-any-status = Jeder Status
+users-any-role = Jede Rolle
+users-filter = Filtern
+users-roles = Rollen
+
+# Bulk actions
+bulk-select-all = Alle angezeigten Benutzer auswählen
+bulk-select = { $login } auswählen
+bulk-selected = { $count ->
+    [one] 1 Benutzer ausgewählt
+   *[other] { $count } Benutzer ausgewählt
+}
+bulk-ban = Sperren
+bulk-activate = Aktivieren
+bulk-choose-role = Rolle wählen
+bulk-add-role = Rolle hinzufügen
+bulk-force-password-reset = Neues Passwort erzwingen
+bulk-clear = Auswahl aufheben
+bulk-done = { $count ->
+    [one] 1 Benutzer aktualisiert.
+   *[other] { $count } Benutzer aktualisiert.
+}
+bulk-failed = { $count ->
+    [one] 1 Benutzer kann nicht aktualisiert werden, daher wurde nichts geändert.
+   *[other] { $count } Benutzer können nicht aktualisiert werden, daher wurde nichts geändert.
+}
+bulk-not-found = Den Benutzer gibt es nicht mehr.
+bulk-erased = Das Konto wurde gelöscht.
+bulk-yourself = Das ist mit dem eigenen Konto nicht möglich.
+bulk-not-banned = Nur gesperrte Benutzer können aktiviert werden.
+
+# CSV import
+import-title = Benutzer importieren
+import-file = CSV-Datei
+import-upload = Hochladen
+import-help = Die erste Zeile muss die Spalten benennen. Mehrere Rollen mit Semikolons trennen.
+import-column = Spalte
+import-field = Feld
+import-ignore = Ignorieren
+import-check = Prüfen
+import-commit = Importieren
+import-rows = { $count ->
+    [one] 1 Zeile.
+   *[other] { $count } Zeilen.
+}
+import-ready = Keine Probleme gefunden.
+import-invalid = { $count ->
+    [one] 1 Zeile hat Probleme; nichts wird importiert, bis sie behoben ist.
+   *[other] { $count } Zeilen haben Probleme; nichts wird importiert, bis sie behoben sind.
+}
+import-done = { $count ->
+    [one] 1 Benutzer importiert.
+   *[other] { $count } Benutzer importiert.
+}
+import-line = Zeile
+import-problems = Probleme
+import-missing-login = Der Login ist leer
+import-invalid-login = { $value } ist keine E-Mail-Adresse
+import-duplicate-login = { $value } kommt mehrmals in der Datei vor
+import-login-taken = { $value } wird bereits verwendet
+import-unknown-status = Unbekannter Status { $value }
+import-status-not-allowed = Benutzer können nicht als { $value } importiert werden
+import-unknown-role = Unbekannte Rolle { $value }
+import-unknown-language = Unbekannte Sprache { $value }
+
+# Invitations
+invite-title = Benutzer einladen
+invite-submit = Einladung senden
+invite-sent = Die Einladung wurde gesendet.
+invite-pending = Offene Einladungen
+invite-none = Keine Einladungen offen.
+invite-invited = Eingeladen
+invite-expires = Link gültig bis
+invite-expired = Abgelaufen
+invite-resend = Erneut senden
+invite-revoke = Zurückziehen
+invite-accept-title = Konto einrichten
+invite-accept-account = Sie wurden als { $login } eingeladen. Wählen Sie Ihren Namen und ein Passwort.
+invite-accept-submit = Konto erstellen
+
+# Impersonation
+impersonation-start = Als dieser Benutzer anmelden
+impersonation-banner = Sie sind als { $login } angemeldet. Ihre Aktionen werden als { $impersonator } protokolliert.
+impersonation-stop = Identitätswechsel beenden
+
+# Erasure
+erase-title = Persönliche Daten löschen
+erase-help = Ersetzt Name, Login, E-Mails und Bild des Benutzers durch ein Pseudonym, meldet ihn überall ab und deaktiviert das Konto. Das Audit-Protokoll bleibt erhalten.
+erase-confirm = Mir ist bewusst, dass dies nicht rückgängig gemacht werden kann
+erase-submit = Persönliche Daten löschen
+erase-done = Die persönlichen Daten wurden gelöscht und das Konto deaktiviert.
+
+# Single Sign-on-Zustimmung
+oauth-consent-title = { $client } möchte Ihr Konto verwenden
+oauth-consent-account = Angemeldet als { $login }. Die Anwendung darf:
+oauth-scope-openid = Wissen, wer Sie sind
+oauth-scope-profile = Ihren Namen, Benutzernamen und Ihre Sprache sehen
+oauth-scope-email = Ihre E-Mail-Adresse sehen
+oauth-scope-roles = Ihre Rollen sehen
+oauth-scope-offline_access = Angemeldet bleiben, wenn Sie nicht da sind
+oauth-consent-allow = Erlauben
+oauth-consent-deny = Ablehnen
+
+# Webhooks
+webhooks-title = Webhooks
+webhooks-url = URL
+webhooks-events = Ereignisse
+webhooks-add = Webhook hinzufügen
+webhooks-secret = Zustellungen an { $url } werden mit diesem Geheimnis signiert. Kopieren Sie es jetzt, es wird nicht erneut angezeigt:
+webhooks-created = Erstellt
+webhooks-delete = Löschen
+webhooks-deliveries = Letzte Zustellungen
+webhooks-dead-letters = Unzustellbare Ereignisse
+webhooks-refresh = Aktualisieren
+webhooks-no-deliveries = Bisher wurde nichts zugestellt.
+webhooks-event = Ereignis
+webhooks-status = Status
+webhooks-attempts = Versuche
+webhooks-last-result = Letztes Ergebnis
+webhooks-next-attempt = Nächster Versuch
+webhooks-retry = Erneut versuchen
+webhook-status-pending = Ausstehend
+webhook-status-sending = Wird gesendet
+webhook-status-retrying = Wird wiederholt
+webhook-status-delivered = Zugestellt
+webhook-status-dead = Fehlgeschlagen
+
+# Presence
+presence-viewing = { $name } sieht sich diesen Benutzer ebenfalls an
+presence-saved = { $name } hat diesen Benutzer gespeichert, während Sie ihn bearbeitet haben. Wenn Sie jetzt speichern, werden diese Änderungen überschrieben.
+presence-saved-confirm = { $name } hat diesen Benutzer gespeichert, während Sie ihn bearbeitet haben. Trotzdem speichern und diese Änderungen überschreiben?
+presence-reload = Meine Änderungen verwerfen und neu laden
+
+# Form fields
+field-required = Dieses Feld ist erforderlich
+field-too-short = Verwenden Sie mindestens { $min } Zeichen
+field-mismatch = Die beiden Eingaben stimmen nicht überein
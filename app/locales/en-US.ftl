@@ -0,0 +1,270 @@
+# Chrome
+theme-dark-mode = Dark mode
+theme-light-mode = Light mode
+search-users = Search users
+search-no-results = No users found
+
+# Timestamps
+date-never = Never
+date-just-now = just now
+date-minutes-ago = { $count ->
+    [one] { $count } minute ago
+   *[other] { $count } minutes ago
+}
+date-hours-ago = { $count ->
+    [one] { $count } hour ago
+   *[other] { $count } hours ago
+}
+date-days-ago = { $count ->
+    [one] { $count } day ago
+   *[other] { $count } days ago
+}
+date-months-ago = { $count ->
+    [one] { $count } month ago
+   *[other] { $count } months ago
+}
+date-years-ago = { $count ->
+    [one] { $count } year ago
+   *[other] { $count } years ago
+}
+date-in-minutes = { $count ->
+    [one] in { $count } minute
+   *[other] in { $count } minutes
+}
+date-in-hours = { $count ->
+    [one] in { $count } hour
+   *[other] in { $count } hours
+}
+date-in-days = { $count ->
+    [one] in { $count } day
+   *[other] in { $count } days
+}
+date-in-months = { $count ->
+    [one] in { $count } month
+   *[other] in { $count } months
+}
+date-in-years = { $count ->
+    [one] in { $count } year
+   *[other] in { $count } years
+}
+
+# Login
+login-title = Sign in
+login-login = Login
+login-password = Password
+login-submit = Sign in
+login-code = Authentication code
+login-code-help = Enter the code from your authenticator app, or one of your recovery codes.
+login-remember = Trust this browser for 30 days
+login-verify = Verify
+login-or = or
+login-oidc-with = Sign in with { $provider }
+login-oidc-failed = Signing in with the identity provider failed. Try again or use your password.
+
+# User edit
+user-loading = Loading...
+user-not-found = Couldn't get user
+user-sign-in = Sign in
+user-server-error = Server Error:
+user-tab-information = User information
+user-tab-access-roles = Access roles
+user-tab-emails = Emails
+user-tab-browsers = Web browsers
+user-tab-two-factor = Two-factor authentication
+user-first-name = First name
+user-last-name = Last name
+user-login-email = Login email
+user-edit = Edit
+user-save = Save
+user-registered-on = Registered on
+user-last-login = Last login
+user-last-failed-login = Last failed login
+user-status = Status user
+user-theme = Theme
+user-language = Language
+user-language-browser = Browser default
+user-new-password = New password
+user-retype-password = Retype new password
+user-current-password = Current password
+user-personal-record = Personal record
+user-download-personal-data = Download personal data
+user-remove-picture = Remove picture
+
+user-status-active = Active
+user-status-banned = Banned
+user-status-deactivated = Deactivated
+user-status-pending = Pending
+
+theme-light = Light
+theme-dark = Dark
+
+# Web browsers
+browsers-browser = Browser
+browsers-os = Operating system
+browsers-ip = IP address
+browsers-signed-in = Signed in
+browsers-last-seen = Last seen
+browsers-this-browser = This browser
+browsers-revoke = Revoke
+browsers-sign-out-everywhere = Sign out everywhere
+browsers-trusted = Trusted devices
+browsers-trusted-none = No browser skips two-factor authentication.
+browsers-last-used = Last used
+browsers-trusted-until = Trusted until
+browsers-revoke-trust = Revoke trust
+
+# Two-factor authentication
+two-factor-enabled = Two-factor authentication is enabled. { $count ->
+    [one] { $count } recovery code left.
+   *[other] { $count } recovery codes left.
+}
+two-factor-disabled = Two-factor authentication is not enabled.
+two-factor-reset = Reset two-factor authentication
+two-factor-scan = Scan this code with your authenticator app, or enter this key manually:
+two-factor-confirm = Confirm
+two-factor-set-up = Set up authenticator app
+two-factor-recovery-codes = Save these recovery codes somewhere safe. Each can be used once if you lose your phone.
+
+# User list
+users-title = Users
+users-export = Export CSV
+users-import = Import CSV
+users-invite = Invite user
+users-search = Search by login or name
+users-any-status = Any status
+users-any-role = Any role
+users-filter = Filter
+users-roles = Roles
+
+# Bulk actions
+bulk-select-all = Select all users shown
+bulk-select = Select { $login }
+bulk-selected = { $count ->
+    [one] 1 user selected
+   *[other] { $count } users selected
+}
+bulk-ban = Ban
+bulk-activate = Activate
+bulk-choose-role = Choose a role
+bulk-add-role = Add role
+bulk-force-password-reset = Force password reset
+bulk-clear = Clear selection
+bulk-done = { $count ->
+    [one] 1 user updated.
+   *[other] { $count } users updated.
+}
+bulk-failed = { $count ->
+    [one] 1 user cannot be updated, so nothing was changed.
+   *[other] { $count } users cannot be updated, so nothing was changed.
+}
+bulk-not-found = The user no longer exists.
+bulk-erased = The account has been erased.
+bulk-yourself = You cannot do this to your own account.
+bulk-not-banned = Only banned users can be activated.
+
+# CSV import
+import-title = Import users
+import-file = CSV file
+import-upload = Upload
+import-help = The first row must name the columns. Separate several roles with semicolons.
+import-column = Column
+import-field = Field
+import-ignore = Ignore
+import-check = Check
+import-commit = Import
+import-rows = { $count ->
+    [one] 1 row.
+   *[other] { $count } rows.
+}
+import-ready = No problems found.
+import-invalid = { $count ->
+    [one] 1 row has problems; nothing will be imported until it is fixed.
+   *[other] { $count } rows have problems; nothing will be imported until they are fixed.
+}
+import-done = { $count ->
+    [one] 1 user imported.
+   *[other] { $count } users imported.
+}
+import-line = Line
+import-problems = Problems
+import-missing-login = The login is empty
+import-invalid-login = { $value } is not an email address
+import-duplicate-login = { $value } appears more than once in the file
+import-login-taken = { $value } is already in use
+import-unknown-status = Unknown status { $value }
+import-status-not-allowed = Users cannot be imported as { $value }
+import-unknown-role = Unknown role { $value }
+import-unknown-language = Unknown language { $value }
+
+# Invitations
+invite-title = Invite user
+invite-submit = Send invitation
+invite-sent = The invitation was sent.
+invite-pending = Pending invitations
+invite-none = No invitations are pending.
+invite-invited = Invited
+invite-expires = Link expires
+invite-expired = Expired
+invite-resend = Resend
+invite-revoke = Revoke
+invite-accept-title = Set up your account
+invite-accept-account = You were invited as { $login }. Choose your name and a password.
+invite-accept-submit = Create account
+
+# Impersonation
+impersonation-start = Sign in as this user
+impersonation-banner = You are signed in as { $login }. What you do is recorded as { $impersonator }.
+impersonation-stop = Stop impersonating
+
+# Erasure
+erase-title = Erase personal data
+erase-help = Replaces the user's name, login, emails and picture with a pseudonym, signs them out everywhere and deactivates the account. The audit log is kept.
+erase-confirm = I understand this cannot be undone
+erase-submit = Erase personal data
+erase-done = The personal data was erased and the account deactivated.
+
+# Single sign-on consent
+oauth-consent-title = { $client } wants to use your account
+oauth-consent-account = Signed in as { $login }. It will be able to:
+oauth-scope-openid = Know who you are
+oauth-scope-profile = See your name, login and language
+oauth-scope-email = See your email address
+oauth-scope-roles = See your roles
+oauth-scope-offline_access = Stay signed in when you are away
+oauth-consent-allow = Allow
+oauth-consent-deny = Deny
+
+# Webhooks
+webhooks-title = Webhooks
+webhooks-url = URL
+webhooks-events = Events
+webhooks-add = Add webhook
+webhooks-secret = Deliveries to { $url } are signed with this secret. Copy it now, it is not shown again:
+webhooks-created = Created
+webhooks-delete = Delete
+webhooks-deliveries = Recent deliveries
+webhooks-dead-letters = Dead letters
+webhooks-refresh = Refresh
+webhooks-no-deliveries = Nothing was delivered yet.
+webhooks-event = Event
+webhooks-status = Status
+webhooks-attempts = Attempts
+webhooks-last-result = Last result
+webhooks-next-attempt = Next attempt
+webhooks-retry = Retry
+webhook-status-pending = Pending
+webhook-status-sending = Sending
+webhook-status-retrying = Retrying
+webhook-status-delivered = Delivered
+webhook-status-dead = Failed
+
+# Presence
+presence-viewing = { $name } is also viewing this user
+presence-saved = { $name } saved this user while you were editing. Saving now overwrites their changes.
+presence-saved-confirm = { $name } saved this user while you were editing. Save anyway and overwrite their changes?
+presence-reload = Discard my changes and reload
+
+# Form fields
+field-required = This field is required
+field-too-short = Use at least { $min } characters
+field-mismatch = The two entries do not match
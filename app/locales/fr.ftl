@@ -0,0 +1,264 @@
+# Chrome
+theme-dark-mode = Mode sombre
+theme-light-mode = Mode clair
+search-users = Rechercher des utilisateurs
+search-no-results = Aucun utilisateur trouvé
+
+# Timestamps
+date-never = Jamais
+date-just-now = à l’instant
+date-minutes-ago = { $count ->
+    [one] il y a { $count } minute
+   *[other] il y a { $count } minutes
+}
+date-hours-ago = { $count ->
+    [one] il y a { $count } heure
+   *[other] il y a { $count } heures
+}
+date-days-ago = { $count ->
+    [one] il y a { $count } jour
+   *[other] il y a { $count } jours
+}
+date-months-ago = il y a { $count } mois
+date-years-ago = { $count ->
+    [one] il y a { $count } an
+   *[other] il y a { $count } ans
+}
+date-in-minutes = { $count ->
+    [one] dans { $count } minute
+   *[other] dans { $count } minutes
+}
+date-in-hours = { $count ->
+    [one] dans { $count } heure
+   *[other] dans { $count } heures
+}
+date-in-days = { $count ->
+    [one] dans { $count } jour
+   *[other] dans { $count } jours
+}
+date-in-months = dans { $count } mois
+date-in-years = { $count ->
+    [one] dans { $count } an
+   *[other] dans { $count } ans
+}
+
+# Login
+login-title = Connexion
+login-login = Identifiant
+login-password = Mot de passe
+login-submit = Se connecter
+login-code = Code d’authentification
+login-code-help = Saisissez le code de votre application d’authentification ou l’un de vos codes de récupération.
+login-remember = Faire confiance à ce navigateur pendant 30 jours
+login-verify = Vérifier
+login-or = ou
+login-oidc-with = Se connecter avec { $provider }
+login-oidc-failed = La connexion via le fournisseur d’identité a échoué. Réessayez ou utilisez votre mot de passe.
+
+# User edit
+user-loading = Chargement...
+user-not-found = Impossible de charger l’utilisateur
+user-sign-in = Se connecter
+user-server-error = Erreur du serveur :
+user-tab-information = Informations utilisateur
+user-tab-access-roles = Rôles d’accès
+user-tab-emails = E-mails
+user-tab-browsers = Navigateurs
+user-tab-two-factor = Authentification à deux facteurs
+user-first-name = Prénom
+user-last-name = Nom
+user-login-email = E-mail de connexion
+user-edit = Modifier
+user-save = Enregistrer
+user-registered-on = Inscrit le
+user-last-login = Dernière connexion
+user-last-failed-login = Dernier échec de connexion
+user-status = Statut de l’utilisateur
+user-theme = Thème
+user-language = Langue
+user-language-browser = Langue du navigateur
+user-new-password = Nouveau mot de passe
+user-retype-password = Confirmer le nouveau mot de passe
+user-current-password = Mot de passe actuel
+user-personal-record = Dossier personnel
+user-download-personal-data = Télécharger les données personnelles
+user-remove-picture = Supprimer la photo
+
+user-status-active = Actif
+user-status-banned = Banni
+user-status-deactivated = Désactivé
+user-status-pending = En attente
+
+theme-light = Clair
+theme-dark = Sombre
+
+# Web browsers
+browsers-browser = Navigateur
+browsers-os = Système d’exploitation
+browsers-ip = Adresse IP
+browsers-signed-in = Connecté le
+browsers-last-seen = Dernière activité
+browsers-this-browser = Ce navigateur
+browsers-revoke = Déconnecter
+browsers-sign-out-everywhere = Se déconnecter partout
+browsers-trusted = Appareils de confiance
+browsers-trusted-none = Aucun navigateur ne contourne l’authentification à deux facteurs.
+browsers-last-used = Dernière utilisation
+browsers-trusted-until = De confiance jusqu’au
+browsers-revoke-trust = Retirer la confiance
+
+# Two-factor authentication
+two-factor-enabled = L’authentification à deux facteurs est activée. { $count ->
+    [one] { $count } code de récupération restant.
+   *[other] { $count } codes de récupération restants.
+}
+two-factor-disabled = L’authentification à deux facteurs n’est pas activée.
+two-factor-reset = Réinitialiser l’authentification à deux facteurs
+two-factor-scan = Scannez ce code avec votre application d’authentification, ou saisissez cette clé à la main :
+two-factor-confirm = Confirmer
+two-factor-set-up = Configurer une application d’authentification
+two-factor-recovery-codes = Conservez ces codes de récupération en lieu sûr. Chacun peut servir une fois si vous perdez votre téléphone.
+
+# User list
+users-title = Utilisateurs
+users-export = Exporter en CSV
+users-import = Importer un CSV
+users-invite = Inviter un utilisateur
+users-search = Rechercher par identifiant ou nom
+users-any-status = Tous les statuts
+users-any-role = Tous les rôles
+users-filter = Filtrer
+users-roles = Rôles
+
+# Bulk actions
+bulk-select-all = Sélectionner tous les utilisateurs affichés
+bulk-select = Sélectionner { $login }
+bulk-selected = { $count ->
+    [one] 1 utilisateur sélectionné
+   *[other] { $count } utilisateurs sélectionnés
+}
+bulk-ban = Bannir
+bulk-activate = Activer
+bulk-choose-role = Choisir un rôle
+bulk-add-role = Ajouter un rôle
+bulk-force-password-reset = Imposer un nouveau mot de passe
+bulk-clear = Effacer la sélection
+bulk-done = { $count ->
+    [one] 1 utilisateur mis à jour.
+   *[other] { $count } utilisateurs mis à jour.
+}
+bulk-failed = { $count ->
+    [one] 1 utilisateur ne peut pas être mis à jour, rien n’a donc été modifié.
+   *[other] { $count } utilisateurs ne peuvent pas être mis à jour, rien n’a donc été modifié.
+}
+bulk-not-found = L’utilisateur n’existe plus.
+bulk-erased = Le compte a été effacé.
+bulk-yourself = Impossible sur votre propre compte.
+bulk-not-banned = Seuls les utilisateurs bannis peuvent être activés.
+
+# CSV import
+import-title = Importer des utilisateurs
+import-file = Fichier CSV
+import-upload = Envoyer
+import-help = La première ligne doit nommer les colonnes. Séparez plusieurs rôles par des points-virgules.
+import-column = Colonne
+import-field = Champ
+import-ignore = Ignorer
+import-check = Vérifier
+import-commit = Importer
+import-rows = { $count ->
+    [one] 1 ligne.
+   *[other] { $count } lignes.
+}
+import-ready = Aucun problème trouvé.
+import-invalid = { $count ->
+    [one] 1 ligne pose problème ; rien ne sera importé tant qu'elle n'est pas corrigée.
+   *[other] { $count } lignes posent problème ; rien ne sera importé tant qu'elles ne sont pas corrigées.
+}
+import-done = { $count ->
+    [one] 1 utilisateur importé.
+   *[other] { $count } utilisateurs importés.
+}
+import-line = Ligne
+import-problems = Problèmes
+import-missing-login = L'identifiant est vide
+import-invalid-login = { $value } n'est pas une adresse e-mail
+import-duplicate-login = { $value } apparaît plusieurs fois dans le fichier
+import-login-taken = { $value } est déjà utilisé
+import-unknown-status = Statut inconnu { $value }
+import-status-not-allowed = Les utilisateurs ne peuvent pas être importés comme { $value }
+import-unknown-role = Rôle inconnu { $value }
+import-unknown-language = Langue inconnue { $value }
+
+# Invitations
+invite-title = Inviter un utilisateur
+invite-submit = Envoyer l’invitation
+invite-sent = L’invitation a été envoyée.
+invite-pending = Invitations en attente
+invite-none = Aucune invitation en attente.
+invite-invited = Invité le
+invite-expires = Lien valable jusqu’au
+invite-expired = Expirée
+invite-resend = Renvoyer
+invite-revoke = Révoquer
+invite-accept-title = Configurer votre compte
+invite-accept-account = Vous avez été invité en tant que { $login }. Choisissez votre nom et un mot de passe.
+invite-accept-submit = Créer le compte
+
+# Impersonation
+impersonation-start = Se connecter en tant que cet utilisateur
+impersonation-banner = Vous êtes connecté en tant que { $login }. Vos actions sont enregistrées au nom de { $impersonator }.
+impersonation-stop = Revenir à mon compte
+
+# Erasure
+erase-title = Effacer les données personnelles
+erase-help = Remplace le nom, l'identifiant, les e-mails et la photo de l'utilisateur par un pseudonyme, le déconnecte partout et désactive le compte. Le journal d'audit est conservé.
+erase-confirm = Je comprends que cette action est irréversible
+erase-submit = Effacer les données personnelles
+erase-done = Les données personnelles ont été effacées et le compte désactivé.
+
+# Consentement d’authentification unique
+oauth-consent-title = { $client } souhaite utiliser votre compte
+oauth-consent-account = Connecté en tant que { $login }. L’application pourra :
+oauth-scope-openid = Savoir qui vous êtes
+oauth-scope-profile = Voir votre nom, votre identifiant et votre langue
+oauth-scope-email = Voir votre adresse e-mail
+oauth-scope-roles = Voir vos rôles
+oauth-scope-offline_access = Rester connectée en votre absence
+oauth-consent-allow = Autoriser
+oauth-consent-deny = Refuser
+
+# Webhooks
+webhooks-title = Webhooks
+webhooks-url = URL
+webhooks-events = Événements
+webhooks-add = Ajouter un webhook
+webhooks-secret = Les envois vers { $url } sont signés avec ce secret. Copiez-le maintenant, il ne sera plus affiché :
+webhooks-created = Créé le
+webhooks-delete = Supprimer
+webhooks-deliveries = Envois récents
+webhooks-dead-letters = Envois abandonnés
+webhooks-refresh = Actualiser
+webhooks-no-deliveries = Rien n’a encore été envoyé.
+webhooks-event = Événement
+webhooks-status = État
+webhooks-attempts = Tentatives
+webhooks-last-result = Dernier résultat
+webhooks-next-attempt = Prochaine tentative
+webhooks-retry = Réessayer
+webhook-status-pending = En attente
+webhook-status-sending = Envoi en cours
+webhook-status-retrying = Nouvelle tentative prévue
+webhook-status-delivered = Envoyé
+webhook-status-dead = Échec
+
+# Presence
+presence-viewing = { $name } consulte aussi cet utilisateur
+presence-saved = { $name } a enregistré cet utilisateur pendant que vous le modifiiez. Enregistrer maintenant écrasera ses modifications.
+presence-saved-confirm = { $name } a enregistré cet utilisateur pendant que vous le modifiiez. Enregistrer quand même et écraser ses modifications ?
+presence-reload = Abandonner mes modifications et recharger
+
+# Form fields
+field-required = Ce champ est obligatoire
+field-too-short = Utilisez au moins { $min } caractères
+field-mismatch = Les deux saisies ne correspondent pas
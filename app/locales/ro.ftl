@@ -0,0 +1,282 @@
+# Chrome
+theme-dark-mode = Mod întunecat
+theme-light-mode = Mod luminos
+search-users = Caută utilizatori
+search-no-results = Niciun utilizator găsit
+
+# Timestamps
+date-never = Niciodată
+date-just-now = chiar acum
+date-minutes-ago = { $count ->
+    [one] acum { $count } minut
+    [few] acum { $count } minute
+   *[other] acum { $count } de minute
+}
+date-hours-ago = { $count ->
+    [one] acum { $count } oră
+    [few] acum { $count } ore
+   *[other] acum { $count } de ore
+}
+date-days-ago = { $count ->
+    [one] acum { $count } zi
+    [few] acum { $count } zile
+   *[other] acum { $count } de zile
+}
+date-months-ago = { $count ->
+    [one] acum { $count } lună
+    [few] acum { $count } luni
+   *[other] acum { $count } de luni
+}
+date-years-ago = { $count ->
+    [one] acum { $count } an
+    [few] acum { $count } ani
+   *[other] acum { $count } de ani
+}
+date-in-minutes = { $count ->
+    [one] peste { $count } minut
+    [few] peste { $count } minute
+   *[other] peste { $count } de minute
+}
+date-in-hours = { $count ->
+    [one] peste { $count } oră
+    [few] peste { $count } ore
+   *[other] peste { $count } de ore
+}
+date-in-days = { $count ->
+    [one] peste { $count } zi
+    [few] peste { $count } zile
+   *[other] peste { $count } de zile
+}
+date-in-months = { $count ->
+    [one] peste { $count } lună
+    [few] peste { $count } luni
+   *[other] peste { $count } de luni
+}
+date-in-years = { $count ->
+    [one] peste { $count } an
+    [few] peste { $count } ani
+   *[other] peste { $count } de ani
+}
+
+# Login
+login-title = Autentificare
+login-login = Utilizator
+login-password = Parolă
+login-submit = Autentificare
+login-code = Cod de autentificare
+login-code-help = Introduceți codul din aplicația de autentificare sau unul dintre codurile de recuperare.
+login-remember = Ai încredere în acest browser timp de 30 de zile
+login-verify = Verifică
+login-or = sau
+login-oidc-with = Autentificare cu { $provider }
+login-oidc-failed = Autentificarea prin furnizorul de identitate a eșuat. Încercați din nou sau folosiți parola.
+
+# User edit
+user-loading = Se încarcă...
+user-not-found = Utilizatorul nu a putut fi încărcat
+user-sign-in = Autentificare
+user-server-error = Eroare de server:
+user-tab-information = Informații utilizator
+user-tab-access-roles = Roluri de acces
+user-tab-emails = E-mailuri
+user-tab-browsers = Browsere web
+user-tab-two-factor = Autentificare în doi pași
+user-first-name = Prenume
+user-last-name = Nume
+user-login-email = E-mail de autentificare
+user-edit = Editează
+user-save = Salvează
+user-registered-on = Înregistrat la
+user-last-login = Ultima autentificare
+user-last-failed-login = Ultima autentificare eșuată
+user-status = Stare utilizator
+user-theme = Temă
+user-language = Limbă
+user-language-browser = Limba browserului
+user-new-password = Parolă nouă
+user-retype-password = Reintroduceți parola nouă
+user-current-password = Parola actuală
+user-personal-record = Dosar personal
+user-download-personal-data = Descarcă datele personale
+user-remove-picture = Elimină fotografia
+
+user-status-active = Activ
+user-status-banned = Blocat
+user-status-deactivated = Dezactivat
+user-status-pending = În așteptare
+
+theme-light = Luminoasă
+theme-dark = Întunecată
+
+# Web browsers
+browsers-browser = Browser
+browsers-os = Sistem de operare
+browsers-ip = Adresă IP
+browsers-signed-in = Autentificat
+browsers-last-seen = Văzut ultima dată
+browsers-this-browser = Acest browser
+browsers-revoke = Deconectează
+browsers-sign-out-everywhere = Deconectează-te peste tot
+browsers-trusted = Dispozitive de încredere
+browsers-trusted-none = Niciun browser nu sare peste autentificarea în doi pași.
+browsers-last-used = Folosit ultima dată
+browsers-trusted-until = De încredere până la
+browsers-revoke-trust = Retrage încrederea
+
+# Two-factor authentication
+two-factor-enabled = Autentificarea în doi pași este activată. { $count ->
+    [one] A mai rămas { $count } cod de recuperare.
+    [few] Au mai rămas { $count } coduri de recuperare.
+   *[other] Au mai rămas { $count } de coduri de recuperare.
+}
+two-factor-disabled = Autentificarea în doi pași nu este activată.
+two-factor-reset = Resetează autentificarea în doi pași
+two-factor-scan = Scanați acest cod cu aplicația de autentificare sau introduceți manual această cheie:
+two-factor-confirm = Confirmă
+two-factor-set-up = Configurează aplicația de autentificare
+two-factor-recovery-codes = Păstrați aceste coduri de recuperare într-un loc sigur. Fiecare poate fi folosit o singură dată dacă vă pierdeți telefonul.
+
+# User list
+users-title = Utilizatori
+users-export = Exportă CSV
+users-import = Importă CSV
+users-invite = Invită utilizator
+users-search = Caută după login sau nume
+users-any-status = Orice stare
+users-any-role = Orice rol
+users-filter = Filtrează
+users-roles = Roluri
+
+# Bulk actions
+bulk-select-all = Selectează toți utilizatorii afișați
+bulk-select = Selectează { $login }
+bulk-selected = { $count ->
+    [one] 1 utilizator selectat
+   *[other] { $count } utilizatori selectați
+}
+bulk-ban = Blochează
+bulk-activate = Activează
+bulk-choose-role = Alege un rol
+bulk-add-role = Adaugă rol
+bulk-force-password-reset = Impune resetarea parolei
+bulk-clear = Golește selecția
+bulk-done = { $count ->
+    [one] 1 utilizator actualizat.
+   *[other] { $count } utilizatori actualizați.
+}
+bulk-failed = { $count ->
+    [one] 1 utilizator nu poate fi actualizat, așa că nu s-a schimbat nimic.
+   *[other] { $count } utilizatori nu pot fi actualizați, așa că nu s-a schimbat nimic.
+}
+bulk-not-found = Utilizatorul nu mai există.
+bulk-erased = Contul a fost șters.
+bulk-yourself = Nu puteți face asta propriului cont.
+bulk-not-banned = Doar utilizatorii blocați pot fi activați.
+
+# CSV import
+import-title = Importă utilizatori
+import-file = Fișier CSV
+import-upload = Încarcă
+import-help = Primul rând trebuie să numească coloanele. Separați mai multe roluri prin punct și virgulă.
+import-column = Coloană
+import-field = Câmp
+import-ignore = Ignoră
+import-check = Verifică
+import-commit = Importă
+import-rows = { $count ->
+    [one] 1 rând.
+    [few] { $count } rânduri.
+   *[other] { $count } de rânduri.
+}
+import-ready = Nicio problemă găsită.
+import-invalid = { $count ->
+    [one] 1 rând are probleme; nimic nu va fi importat până nu este corectat.
+   *[other] { $count } rânduri au probleme; nimic nu va fi importat până nu sunt corectate.
+}
+import-done = { $count ->
+    [one] 1 utilizator importat.
+   *[other] { $count } utilizatori importați.
+}
+import-line = Rând
+import-problems = Probleme
+import-missing-login = Loginul este gol
+import-invalid-login = { $value } nu este o adresă de email
+import-duplicate-login = { $value } apare de mai multe ori în fișier
+import-login-taken = { $value } este deja folosit
+import-unknown-status = Stare necunoscută { $value }
+import-status-not-allowed = Utilizatorii nu pot fi importați ca { $value }
+import-unknown-role = Rol necunoscut { $value }
+import-unknown-language = Limbă necunoscută { $value }
+
+# Invitations
+invite-title = Invită utilizator
+invite-submit = Trimite invitația
+invite-sent = Invitația a fost trimisă.
+invite-pending = Invitații în așteptare
+invite-none = Nicio invitație în așteptare.
+invite-invited = Invitat
+invite-expires = Linkul expiră
+invite-expired = Expirată
+invite-resend = Retrimite
+invite-revoke = Revocă
+invite-accept-title = Configurează-ți contul
+invite-accept-account = Ai fost invitat ca { $login }. Alege-ți numele și o parolă.
+invite-accept-submit = Creează contul
+
+# Impersonation
+impersonation-start = Conectează-te ca acest utilizator
+impersonation-banner = Sunteți conectat ca { $login }. Acțiunile sunt înregistrate ca { $impersonator }.
+impersonation-stop = Oprește conectarea ca utilizator
+
+# Erasure
+erase-title = Șterge datele personale
+erase-help = Înlocuiește numele, loginul, e-mailurile și poza utilizatorului cu un pseudonim, îl deconectează peste tot și dezactivează contul. Jurnalul de audit se păstrează.
+erase-confirm = Înțeleg că acțiunea nu poate fi anulată
+erase-submit = Șterge datele personale
+erase-done = Datele personale au fost șterse și contul dezactivat.
+
+# Consimțământ pentru autentificare unică
+oauth-consent-title = { $client } dorește să folosească contul dvs.
+oauth-consent-account = Autentificat ca { $login }. Aplicația va putea:
+oauth-scope-openid = Să știe cine sunteți
+oauth-scope-profile = Să vadă numele, utilizatorul și limba dvs.
+oauth-scope-email = Să vadă adresa dvs. de e-mail
+oauth-scope-roles = Să vadă rolurile dvs.
+oauth-scope-offline_access = Să rămână conectată cât timp sunteți absent
+oauth-consent-allow = Permite
+oauth-consent-deny = Refuză
+
+# Webhooks
+webhooks-title = Webhook-uri
+webhooks-url = URL
+webhooks-events = Evenimente
+webhooks-add = Adaugă webhook
+webhooks-secret = Livrările către { $url } sunt semnate cu acest secret. Copiază-l acum, nu va mai fi afișat:
+webhooks-created = Creat
+webhooks-delete = Șterge
+webhooks-deliveries = Livrări recente
+webhooks-dead-letters = Livrări eșuate definitiv
+webhooks-refresh = Reîmprospătează
+webhooks-no-deliveries = Nu s-a livrat nimic încă.
+webhooks-event = Eveniment
+webhooks-status = Stare
+webhooks-attempts = Încercări
+webhooks-last-result = Ultimul rezultat
+webhooks-next-attempt = Următoarea încercare
+webhooks-retry = Reîncearcă
+webhook-status-pending = În așteptare
+webhook-status-sending = Se trimite
+webhook-status-retrying = Se reîncearcă
+webhook-status-delivered = Livrat
+webhook-status-dead = Eșuat
+
+# Presence
+presence-viewing = { $name } vizualizează, de asemenea, acest utilizator
+presence-saved = { $name } a salvat acest utilizator în timp ce îl editați. Dacă salvați acum, modificările sale vor fi suprascrise.
+presence-saved-confirm = { $name } a salvat acest utilizator în timp ce îl editați. Salvați oricum și suprascrieți modificările sale?
+presence-reload = Renunță la modificările mele și reîncarcă
+
+# Form fields
+field-required = Acest câmp este obligatoriu
+field-too-short = Folosiți cel puțin { $min } caractere
+field-mismatch = Cele două valori nu coincid
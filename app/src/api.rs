@@ -0,0 +1,4 @@
+//! Wire types of the versioned JSON API, kept in the `model` crate so that
+//! clients can use them without the UI.
+
+pub use model::api::*;
@@ -0,0 +1,115 @@
+//! Record of what was done to each account, and by whom.
+
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[cfg(feature = "ssr")]
+use std::sync::{Arc, RwLock};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Display)]
+pub enum AuditAction {
+    SignedIn,
+    /// A wrong password or second-factor code.
+    SignInFailed,
+    SignedOut,
+    SessionsRevoked,
+    SecondFactorEnabled,
+    SecondFactorReset,
+    PersonalDataExported,
+    Banned,
+    Activated,
+    RoleGranted,
+    /// The password was cleared, so a new one has to be set.
+    PasswordResetForced,
+    PasswordChanged,
+    LoginChanged,
+    ImpersonationStarted,
+    ImpersonationStopped,
+    /// The account was added by an administrator, e.g. from a CSV import.
+    Created,
+    /// An invitation was sent, or sent again.
+    Invited,
+    InvitationRevoked,
+    InvitationAccepted,
+    /// The user's personal data was erased and the account deactivated.
+    Erased,
+}
+
+impl AuditAction {
+    /// Whether the entry belongs to the account's login history.
+    pub fn is_login(self) -> bool {
+        matches!(
+            self,
+            AuditAction::SignedIn | AuditAction::SignInFailed | AuditAction::SignedOut
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub at: OffsetDateTime,
+    /// The signed-in user who acted, `None` for requests not signed in.
+    pub actor: Option<Uuid>,
+    /// The account acted on.
+    pub subject: Uuid,
+    pub action: AuditAction,
+    pub ip: Option<String>,
+}
+
+/// Append-only log of [`AuditEntry`]s.
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Default)]
+pub struct AuditLog(Arc<RwLock<Vec<AuditEntry>>>);
+
+#[cfg(feature = "ssr")]
+impl AuditLog {
+    pub fn record(&self, entry: AuditEntry) {
+        self.0.write().unwrap().push(entry);
+    }
+
+    /// Forgets the IP addresses recorded with entries about `user`, keeping
+    /// the entries themselves.
+    pub fn scrub_ips(&self, user: Uuid) {
+        for entry in self.0.write().unwrap().iter_mut() {
+            if entry.subject == user || entry.actor == Some(user) {
+                entry.ip = None;
+            }
+        }
+    }
+
+    /// Entries about `user` or by them, oldest first.
+    pub fn for_user(&self, user: Uuid) -> Vec<AuditEntry> {
+        self.0
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|entry| entry.subject == user || entry.actor == Some(user))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Records `action` on `subject` by the user signed in on the current
+/// request, from the requesting client. While impersonating, that is the
+/// administrator, not the user they act as.
+#[cfg(feature = "ssr")]
+pub fn audit(subject: Uuid, action: AuditAction) {
+    use crate::{
+        session::client_info,
+        state::{app_state, current_session},
+    };
+
+    if let Ok(state) = app_state() {
+        state.audit.record(AuditEntry {
+            at: state.clock.now(),
+            actor: current_session()
+                .ok()
+                .map(|session| session.impersonator.unwrap_or(session.user)),
+            subject,
+            action,
+            ip: client_info().ip,
+        });
+    }
+}
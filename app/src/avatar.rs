@@ -0,0 +1,314 @@
+use leptos::prelude::*;
+use leptos::server_fn::codec::{MultipartData, MultipartFormData};
+use uuid::Uuid;
+
+use crate::t;
+
+#[cfg(feature = "ssr")]
+use std::path::{Path, PathBuf};
+
+/// Edge lengths, in pixels, each uploaded picture is resized to.
+pub const AVATAR_SIZES: [u32; 4] = [256, 128, 64, 32];
+/// Largest upload accepted, in bytes.
+pub const MAX_AVATAR_BYTES: usize = 5 * 1024 * 1024;
+
+/// URL of a stored picture at one of the [`AVATAR_SIZES`].
+///
+/// Pictures are addressed by the hash of the uploaded file, so the URL
+/// changes whenever the picture does and can be cached forever.
+pub fn avatar_url(hash: &str, size: u32) -> String {
+    format!("/avatars/{hash}/{size}.png")
+}
+
+/// Up to two letters standing in for a user without a picture.
+pub fn initials(first_name: Option<&str>, last_name: Option<&str>) -> String {
+    [first_name, last_name]
+        .into_iter()
+        .flatten()
+        .filter_map(|name| name.trim().chars().next())
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+/// A background colour that stays the same for a given user.
+fn initials_color(unid: Uuid) -> String {
+    let hue = unid.as_u128() % 360;
+    format!("hsl({hue}, 45%, 45%)")
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Debug, thiserror::Error)]
+pub enum AvatarError {
+    #[error("The picture must be at most {} MB", MAX_AVATAR_BYTES / 1024 / 1024)]
+    TooLarge,
+    #[error("Only PNG, JPEG, WebP and GIF pictures are supported")]
+    UnsupportedType,
+    #[error("The picture could not be read: {0}")]
+    Decode(#[from] image::ImageError),
+    #[error("The picture could not be stored: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Resized profile pictures on disk, one directory per uploaded file.
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug)]
+pub struct AvatarStore {
+    dir: PathBuf,
+}
+
+#[cfg(feature = "ssr")]
+impl Default for AvatarStore {
+    /// Stores pictures under `AVATAR_DIR`, defaulting to `target/avatars`.
+    fn default() -> Self {
+        AvatarStore {
+            dir: std::env::var_os("AVATAR_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from("target/avatars")),
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl AvatarStore {
+    /// Stores pictures under `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        AvatarStore { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Crops the picture to a centred square, writes it at every size and
+    /// returns the hash naming it.
+    pub fn save(&self, bytes: &[u8]) -> Result<String, AvatarError> {
+        use data_encoding::HEXLOWER;
+        use image::{imageops::FilterType, ImageFormat, ImageReader, Limits};
+        use sha2::{Digest, Sha256};
+        use std::io::Cursor;
+
+        if bytes.len() > MAX_AVATAR_BYTES {
+            return Err(AvatarError::TooLarge);
+        }
+        let format = image::guess_format(bytes).map_err(|_| AvatarError::UnsupportedType)?;
+        if !matches!(
+            format,
+            ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP | ImageFormat::Gif
+        ) {
+            return Err(AvatarError::UnsupportedType);
+        }
+
+        let hash = HEXLOWER.encode(&Sha256::digest(bytes));
+        let target = self.dir.join(&hash);
+        if target.exists() {
+            return Ok(hash);
+        }
+
+        let mut limits = Limits::default();
+        limits.max_image_width = Some(8192);
+        limits.max_image_height = Some(8192);
+        let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+        reader.limits(limits);
+        let picture = reader.decode()?;
+
+        let side = picture.width().min(picture.height());
+        let square = picture.crop_imm(
+            (picture.width() - side) / 2,
+            (picture.height() - side) / 2,
+            side,
+            side,
+        );
+
+        // Written to a scratch directory first so a half-written picture is
+        // never served under its final name.
+        let scratch = self.dir.join(format!(".{hash}.{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&scratch)?;
+        for size in AVATAR_SIZES {
+            let mut png = Vec::new();
+            square
+                .resize_exact(size, size, FilterType::Lanczos3)
+                .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+            std::fs::write(scratch.join(format!("{size}.png")), png)?;
+        }
+        if std::fs::rename(&scratch, &target).is_err() {
+            // Another upload of the same file got there first.
+            std::fs::remove_dir_all(&scratch)?;
+        }
+        Ok(hash)
+    }
+}
+
+/// Replaces a user's profile picture. Expects a `user` field with the
+/// user's `unid` followed by an `avatar` file field.
+#[server(input = MultipartFormData)]
+pub async fn upload_avatar(data: MultipartData) -> Result<String, ServerFnError> {
+    use crate::state::{app_state, authorize_self_or_admin, tenant_users};
+
+    let mut data = data
+        .into_inner()
+        .ok_or_else(|| ServerFnError::new("Expected a multipart form"))?;
+    let mut user = None;
+    let mut bytes = Vec::new();
+    while let Some(mut field) = data.next_field().await? {
+        match field.name() {
+            Some("user") => user = field.text().await?.parse::<Uuid>().ok(),
+            Some("avatar") => {
+                while let Some(chunk) = field.chunk().await? {
+                    if bytes.len() + chunk.len() > MAX_AVATAR_BYTES {
+                        return Err(ServerFnError::new(AvatarError::TooLarge));
+                    }
+                    bytes.extend_from_slice(&chunk);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let user = user.ok_or_else(|| ServerFnError::new("Missing user"))?;
+    authorize_self_or_admin(user)?;
+    let avatars = app_state()?.avatars;
+    let hash = tokio::task::spawn_blocking(move || avatars.save(&bytes))
+        .await?
+        .map_err(ServerFnError::new)?;
+    tenant_users()?.update(user, |user| user.avatar = Some(hash.clone()));
+    Ok(hash)
+}
+
+#[server]
+pub async fn remove_avatar(user: Uuid) -> Result<(), ServerFnError> {
+    use crate::state::{authorize_self_or_admin, tenant_users};
+
+    authorize_self_or_admin(user)?;
+    tenant_users()?.update(user, |user| user.avatar = None);
+    Ok(())
+}
+
+/// A user's profile picture, or their initials when they have none.
+#[component]
+pub fn Avatar(
+    unid: Uuid,
+    first_name: Option<String>,
+    last_name: Option<String>,
+    #[prop(into)] avatar: Signal<Option<String>>,
+    #[prop(default = 64)] size: u32,
+) -> impl IntoView {
+    let initials = initials(first_name.as_deref(), last_name.as_deref());
+    let color = initials_color(unid);
+
+    move || match avatar.get() {
+        Some(hash) => {
+            // Serve the smallest stored size that is still sharp at twice
+            // the displayed size.
+            let stored = AVATAR_SIZES
+                .into_iter()
+                .rev()
+                .find(|&stored| stored >= size * 2)
+                .unwrap_or(AVATAR_SIZES[0]);
+            view! {
+                <img
+                    class="rounded-circle"
+                    src=avatar_url(&hash, stored)
+                    width=size
+                    height=size
+                    alt=initials.clone()
+                />
+            }
+                .into_any()
+        }
+        None => {
+            view! {
+                <svg
+                    class="rounded-circle"
+                    xmlns="http://www.w3.org/2000/svg"
+                    viewBox="0 0 100 100"
+                    width=size
+                    height=size
+                    role="img"
+                    aria-label=initials.clone()
+                >
+                    <rect width="100" height="100" fill=color.clone()></rect>
+                    <text
+                        x="50"
+                        y="50"
+                        dy=".35em"
+                        text-anchor="middle"
+                        font-family="sans-serif"
+                        font-size="40"
+                        fill="#fff"
+                    >
+                        {initials.clone()}
+                    </text>
+                </svg>
+            }
+                .into_any()
+        }
+    }
+}
+
+/// The profile picture with controls to upload a new one or remove it.
+#[component]
+pub fn AvatarEditor(
+    unid: Uuid,
+    first_name: Option<String>,
+    last_name: Option<String>,
+    avatar: Option<String>,
+) -> impl IntoView {
+    use leptos::wasm_bindgen::JsCast;
+    use web_sys::{FormData, HtmlFormElement};
+
+    let avatar = RwSignal::new(avatar);
+    let upload = Action::new_local(|data: &FormData| upload_avatar(data.clone().into()));
+    let remove = ServerAction::<RemoveAvatar>::new();
+
+    Effect::new(move || {
+        if let Some(Ok(hash)) = upload.value().get() {
+            avatar.set(Some(hash));
+        }
+    });
+    Effect::new(move || {
+        if let Some(Ok(())) = remove.value().get() {
+            avatar.set(None);
+        }
+    });
+
+    view! {
+        <div class="d-flex align-items-center gap-3">
+            <Avatar unid=unid first_name=first_name last_name=last_name avatar=avatar size=96 />
+            <form
+                class="d-flex flex-column gap-2 fs-6"
+                on:change=move |ev| {
+                    let form = ev.current_target().unwrap().unchecked_into::<HtmlFormElement>();
+                    if let Ok(data) = FormData::new_with_form(&form) {
+                        upload.dispatch_local(data);
+                    }
+                }
+            >
+                <input type="hidden" name="user" value=unid.to_string() />
+                <input
+                    type="file"
+                    name="avatar"
+                    class="form-control form-control-sm"
+                    accept="image/png,image/jpeg,image/webp,image/gif"
+                />
+                <Show when=move || avatar.get().is_some()>
+                    <button
+                        type="button"
+                        class="btn btn-sm btn-outline-secondary align-self-start"
+                        on:click=move |_| {
+                            remove.dispatch(RemoveAvatar { user: unid });
+                        }
+                    >
+                        {t!("user-remove-picture")}
+                    </button>
+                </Show>
+                {move || {
+                    upload
+                        .value()
+                        .get()
+                        .and_then(Result::err)
+                        .map(|err| view! { <div class="text-danger small">{err.to_string()}</div> })
+                }}
+            </form>
+        </div>
+    }
+}
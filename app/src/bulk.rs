@@ -0,0 +1,360 @@
+//! One change applied to many users selected in the user list.
+//!
+//! Like an import, a bulk update changes every selected user or, if any of
+//! them cannot take the change, none, and reports why for each user.
+
+use std::collections::HashSet;
+
+use leptos::prelude::*;
+use leptos::server_fn::codec::Json;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    i18n::{tr, tr_args},
+    t,
+};
+
+/// What a bulk update does to each selected user.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BulkOperation {
+    Ban,
+    /// Lifts the ban of banned users.
+    Activate,
+    AddRole(String),
+    /// Clears the password and signs the user out everywhere, then emails
+    /// them a link to choose a new one.
+    ForcePasswordReset,
+}
+
+/// Why a selected user cannot take the change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BulkProblem {
+    NotFound,
+    Erased,
+    /// The change would lock the administrator out.
+    Yourself,
+    /// Only banned users can be activated.
+    NotBanned,
+}
+
+impl BulkProblem {
+    pub fn message_id(self) -> &'static str {
+        match self {
+            BulkProblem::NotFound => "bulk-not-found",
+            BulkProblem::Erased => "bulk-erased",
+            BulkProblem::Yourself => "bulk-yourself",
+            BulkProblem::NotBanned => "bulk-not-banned",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BulkResult {
+    pub unid: Uuid,
+    /// `None` if the user was not found.
+    pub login: Option<String>,
+    pub problem: Option<BulkProblem>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BulkReport {
+    pub results: Vec<BulkResult>,
+    /// Whether the change was made, which it is only if no user has a
+    /// problem.
+    pub applied: bool,
+}
+
+impl BulkReport {
+    pub fn failed(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|result| result.problem.is_some())
+            .count()
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl BulkOperation {
+    fn audit_action(&self) -> crate::audit::AuditAction {
+        use crate::audit::AuditAction;
+
+        match self {
+            BulkOperation::Ban => AuditAction::Banned,
+            BulkOperation::Activate => AuditAction::Activated,
+            BulkOperation::AddRole(_) => AuditAction::RoleGranted,
+            BulkOperation::ForcePasswordReset => AuditAction::PasswordResetForced,
+        }
+    }
+
+    /// Makes the change to `user` on behalf of `actor`.
+    fn apply(&self, user: &mut crate::User, actor: Uuid) -> Result<(), BulkProblem> {
+        use crate::UserStatus;
+
+        if user.is_erased() {
+            return Err(BulkProblem::Erased);
+        }
+        match self {
+            BulkOperation::Ban | BulkOperation::ForcePasswordReset if user.unid == actor => {
+                return Err(BulkProblem::Yourself)
+            }
+            BulkOperation::Ban => user.status = UserStatus::Banned,
+            BulkOperation::Activate if user.status != UserStatus::Banned => {
+                return Err(BulkProblem::NotBanned)
+            }
+            BulkOperation::Activate => user.status = UserStatus::Active,
+            BulkOperation::AddRole(role) => {
+                user.roles.insert(role.clone());
+            }
+            BulkOperation::ForcePasswordReset => user.hash = String::new(),
+        }
+        Ok(())
+    }
+}
+
+/// Applies `operation` to all of `unids` on behalf of `actor`, or to none
+/// of them if any cannot take it. Returns the report and the users as
+/// changed.
+#[cfg(feature = "ssr")]
+pub fn bulk_update(
+    state: &crate::state::AppState,
+    users: &crate::state::TenantUsers,
+    actor: &crate::session::CurrentSession,
+    unids: &[Uuid],
+    operation: &BulkOperation,
+    ip: Option<String>,
+) -> (BulkReport, Vec<crate::User>) {
+    use crate::{audit::AuditEntry, user_update::revoke_access};
+
+    let outcomes = users.update_all(unids, |user| operation.apply(user, actor.user));
+    let results: Vec<BulkResult> = unids
+        .iter()
+        .zip(&outcomes)
+        .map(|(unid, outcome)| BulkResult {
+            unid: *unid,
+            login: match outcome {
+                Some(Ok(user)) => Some(user.login.clone()),
+                _ => users.get(*unid).map(|user| user.login),
+            },
+            problem: match outcome {
+                None => Some(BulkProblem::NotFound),
+                Some(Err(problem)) => Some(*problem),
+                Some(Ok(_)) => None,
+            },
+        })
+        .collect();
+    let applied = results.iter().all(|result| result.problem.is_none());
+    let changed: Vec<crate::User> = if applied {
+        outcomes.into_iter().flatten().flatten().collect()
+    } else {
+        Vec::new()
+    };
+    let now = state.clock.now();
+    for user in &changed {
+        if matches!(
+            operation,
+            BulkOperation::Ban | BulkOperation::ForcePasswordReset
+        ) {
+            revoke_access(state, user.unid, None);
+        }
+        state.audit.record(AuditEntry {
+            at: now,
+            actor: Some(actor.impersonator.unwrap_or(actor.user)),
+            subject: user.unid,
+            action: operation.audit_action(),
+            ip: ip.clone(),
+        });
+    }
+    (BulkReport { results, applied }, changed)
+}
+
+/// Applies `operation` to all of `users`, or to none of them if any cannot
+/// take it.
+#[server(input = Json)]
+pub async fn bulk_update_users(
+    users: Vec<Uuid>,
+    operation: BulkOperation,
+) -> Result<BulkReport, ServerFnError> {
+    use crate::{
+        invitation::send_password_reset,
+        session::client_info,
+        state::{app_state, authorize_admin, forbid_impersonation, tenant_roles, tenant_users},
+        UserStatus,
+    };
+
+    let session = authorize_admin()?;
+    forbid_impersonation()?;
+    let state = app_state()?;
+    let operation = match operation {
+        BulkOperation::AddRole(role) => BulkOperation::AddRole(
+            tenant_roles()?
+                .find(&role)
+                .ok_or_else(|| ServerFnError::new(format!("Unknown role {role}")))?,
+        ),
+        operation => operation,
+    };
+    let mut seen = HashSet::new();
+    let unids: Vec<Uuid> = users
+        .into_iter()
+        .filter(|unid| seen.insert(*unid))
+        .collect();
+
+    let (report, changed) = bulk_update(
+        &state,
+        &tenant_users()?,
+        &session,
+        &unids,
+        &operation,
+        client_info().ip,
+    );
+    if operation == BulkOperation::ForcePasswordReset {
+        for user in changed
+            .iter()
+            .filter(|user| matches!(user.status, UserStatus::Active | UserStatus::Pending))
+        {
+            // A failure is logged, and resetting again sends a new link.
+            let _ = send_password_reset(user).await;
+        }
+    }
+    Ok(report)
+}
+
+/// Actions for the users selected in the list, and the outcome of the last
+/// one. Clears the selection once an action went through.
+#[component]
+pub fn BulkActionBar(
+    selected: RwSignal<HashSet<Uuid>>,
+    roles: Signal<Vec<String>>,
+    update: ServerAction<BulkUpdateUsers>,
+) -> impl IntoView {
+    let role = RwSignal::new(String::new());
+    let run = move |operation: BulkOperation| {
+        update.dispatch(BulkUpdateUsers {
+            users: selected.get_untracked().into_iter().collect(),
+            operation,
+        });
+    };
+    Effect::new(move || {
+        if update
+            .value()
+            .get()
+            .is_some_and(|report| report.is_ok_and(|report| report.applied))
+        {
+            selected.set(HashSet::new());
+        }
+    });
+    let busy = move || update.pending().get();
+
+    view! {
+        <Show when=move || !selected.with(HashSet::is_empty)>
+            <div class="d-flex flex-wrap align-items-center gap-2 mb-3 p-2 border rounded bg-body-tertiary">
+                <span class="me-2">
+                    {move || {
+                        let count = selected.with(HashSet::len) as i64;
+                        tr_args("bulk-selected", &[("count", count.into())])
+                    }}
+                </span>
+                <button
+                    type="button"
+                    class="btn btn-sm btn-outline-danger"
+                    prop:disabled=busy
+                    on:click=move |_| run(BulkOperation::Ban)
+                >
+                    {t!("bulk-ban")}
+                </button>
+                <button
+                    type="button"
+                    class="btn btn-sm btn-outline-success"
+                    prop:disabled=busy
+                    on:click=move |_| run(BulkOperation::Activate)
+                >
+                    {t!("bulk-activate")}
+                </button>
+                <div class="input-group input-group-sm w-auto">
+                    <select
+                        class="form-select"
+                        on:change=move |ev| role.set(event_target_value(&ev))
+                    >
+                        <option value="">{t!("bulk-choose-role")}</option>
+                        {move || {
+                            roles
+                                .get()
+                                .into_iter()
+                                .map(|name| {
+                                    let value = name.clone();
+                                    view! { <option value=value>{name}</option> }
+                                })
+                                .collect_view()
+                        }}
+                    </select>
+                    <button
+                        type="button"
+                        class="btn btn-outline-secondary"
+                        prop:disabled=move || busy() || role.with(String::is_empty)
+                        on:click=move |_| run(BulkOperation::AddRole(role.get_untracked()))
+                    >
+                        {t!("bulk-add-role")}
+                    </button>
+                </div>
+                <button
+                    type="button"
+                    class="btn btn-sm btn-outline-warning"
+                    prop:disabled=busy
+                    on:click=move |_| run(BulkOperation::ForcePasswordReset)
+                >
+                    {t!("bulk-force-password-reset")}
+                </button>
+                <button
+                    type="button"
+                    class="btn btn-sm btn-link ms-auto"
+                    on:click=move |_| selected.set(HashSet::new())
+                >
+                    {t!("bulk-clear")}
+                </button>
+            </div>
+        </Show>
+        {move || {
+            update
+                .value()
+                .get()
+                .map(|report| match report {
+                    Ok(report) if report.applied => {
+                        let count = report.results.len() as i64;
+                        view! {
+                            <div class="alert alert-success">
+                                {t!("bulk-done", count = count)}
+                            </div>
+                        }
+                            .into_any()
+                    }
+                    Ok(report) => {
+                        let failed = report.failed() as i64;
+                        view! {
+                            <div class="alert alert-warning">
+                                <p>{t!("bulk-failed", count = failed)}</p>
+                                <ul class="mb-0">
+                                    {report
+                                        .results
+                                        .into_iter()
+                                        .filter_map(|result| {
+                                            let problem = result.problem?;
+                                            let login = result
+                                                .login
+                                                .unwrap_or_else(|| result.unid.to_string());
+                                            Some(view! {
+                                                <li>{login} ": " {move || tr(problem.message_id())}</li>
+                                            })
+                                        })
+                                        .collect_view()}
+                                </ul>
+                            </div>
+                        }
+                            .into_any()
+                    }
+                    Err(err) => {
+                        view! { <div class="alert alert-danger">{err.to_string()}</div> }.into_any()
+                    }
+                })
+        }}
+    }
+}
@@ -0,0 +1,237 @@
+use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+use time::{Duration, OffsetDateTime, UtcOffset};
+use time_tz::{timezones, OffsetDateTimeExt};
+
+use crate::{
+    i18n::{negotiate, tr_args},
+    t,
+};
+
+/// Name of the cookie in which the browser reports its IANA timezone, such
+/// as `Europe/Berlin`.
+pub const TZ_COOKIE: &str = "tz";
+/// The timezone of viewers whose browser has not reported one.
+pub const DEFAULT_TIMEZONE: &str = "UTC";
+
+/// Locales dates can be formatted for; the first is the fallback.
+pub const DATE_LOCALES: [&str; 5] = ["en-US", "en-GB", "de", "fr", "ro"];
+
+/// How the viewer wants timestamps shown.
+///
+/// Resolved on the server and sent along with the page, so the first render
+/// in the browser formats exactly like the server did.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplayPrefs {
+    /// IANA name of the viewer's timezone. Each instant is shown at the
+    /// offset the zone had then, so summer and winter times both come out
+    /// right.
+    pub timezone: String,
+    pub locale: String,
+    /// The instant relative hints are measured from.
+    pub now: OffsetDateTime,
+}
+
+impl Default for DisplayPrefs {
+    fn default() -> Self {
+        DisplayPrefs {
+            timezone: DEFAULT_TIMEZONE.to_string(),
+            locale: DATE_LOCALES[0].to_string(),
+            now: OffsetDateTime::UNIX_EPOCH,
+        }
+    }
+}
+
+impl DisplayPrefs {
+    /// `value` in the viewer's timezone and locale, e.g. `Mar 5, 2024, 3:04 PM`.
+    pub fn format(&self, value: OffsetDateTime) -> String {
+        let local = match timezones::get_by_name(&self.timezone) {
+            Some(timezone) => value.to_timezone(timezone),
+            None => value.to_offset(UtcOffset::UTC),
+        };
+        let (year, month, day) = (local.year(), u8::from(local.month()), local.day());
+        let (hour, minute) = (local.hour(), local.minute());
+        let abbr = &MONTHS[month as usize - 1][..3];
+
+        match self.locale.as_str() {
+            "en-GB" => format!("{day} {abbr} {year}, {hour:02}:{minute:02}"),
+            "de" | "ro" => format!("{day:02}.{month:02}.{year}, {hour:02}:{minute:02}"),
+            "fr" => format!("{day:02}/{month:02}/{year} {hour:02}:{minute:02}"),
+            _ => {
+                let (hour12, meridiem) = match hour {
+                    0 => (12, "AM"),
+                    1..=11 => (hour, "AM"),
+                    12 => (12, "PM"),
+                    _ => (hour - 12, "PM"),
+                };
+                format!("{abbr} {day}, {year}, {hour12}:{minute:02} {meridiem}")
+            }
+        }
+    }
+
+    /// How long ago (or how far ahead) `value` is, as a message id for the
+    /// `date-*` translations and the count to fill in, e.g.
+    /// `("date-days-ago", 3)`.
+    pub fn relative(&self, value: OffsetDateTime) -> (&'static str, i64) {
+        let elapsed = self.now - value;
+        let future = elapsed.is_negative();
+        let amount = elapsed.abs();
+
+        let units = [
+            (Duration::days(365), "date-years-ago", "date-in-years"),
+            (Duration::days(30), "date-months-ago", "date-in-months"),
+            (Duration::DAY, "date-days-ago", "date-in-days"),
+            (Duration::HOUR, "date-hours-ago", "date-in-hours"),
+            (Duration::MINUTE, "date-minutes-ago", "date-in-minutes"),
+        ];
+        units
+            .into_iter()
+            .find(|(length, _, _)| amount >= *length)
+            .map(|(length, past, ahead)| {
+                let count = amount.whole_seconds() / length.whole_seconds();
+                (if future { ahead } else { past }, count)
+            })
+            .unwrap_or(("date-just-now", 0))
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// Picks the best of [`DATE_LOCALES`] for the viewer: their UI language if
+/// dates have a format for it, else the `Accept-Language` header.
+#[cfg(feature = "ssr")]
+fn date_locale() -> &'static str {
+    use http::{header::ACCEPT_LANGUAGE, request::Parts};
+
+    let chosen = crate::state::current_user()
+        .ok()
+        .and_then(|user| user.language);
+    let accept_language = use_context::<Parts>()
+        .and_then(|parts| {
+            parts
+                .headers
+                .get(ACCEPT_LANGUAGE)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        })
+        .unwrap_or_default();
+
+    chosen
+        .and_then(|code| negotiate(&code, &DATE_LOCALES))
+        .or_else(|| negotiate(&accept_language, &DATE_LOCALES))
+        .unwrap_or(DATE_LOCALES[0])
+}
+
+#[server]
+pub async fn get_display_prefs() -> Result<DisplayPrefs, ServerFnError> {
+    use crate::session::request_cookie;
+    use crate::state::app_state;
+
+    Ok(DisplayPrefs {
+        timezone: request_cookie(TZ_COOKIE)
+            .filter(|name| timezones::get_by_name(name).is_some())
+            .unwrap_or_else(|| DEFAULT_TIMEZONE.to_string()),
+        locale: date_locale().to_string(),
+        now: app_state()?.clock.now(),
+    })
+}
+
+/// The viewer's [`DisplayPrefs`], provided by [`provide_display_prefs`].
+#[derive(Clone, Copy, Debug)]
+pub struct DisplayContext {
+    prefs: Resource<Result<DisplayPrefs, ServerFnError>>,
+    /// The browser's own timezone and clock, set once hydration is done.
+    browser: RwSignal<Option<DisplayPrefs>>,
+}
+
+impl DisplayContext {
+    pub fn get(&self) -> DisplayPrefs {
+        self.browser
+            .get()
+            .or_else(|| self.prefs.get().and_then(Result::ok))
+            .unwrap_or_default()
+    }
+}
+
+/// Loads the viewer's [`DisplayPrefs`] for [`DateTime`].
+///
+/// After hydration the browser's actual timezone replaces the one the
+/// server guessed, and is stored in a cookie so the next page is rendered
+/// with it straight away.
+pub fn provide_display_prefs() {
+    let context = DisplayContext {
+        prefs: Resource::new(|| (), |_| get_display_prefs()),
+        browser: RwSignal::new(None),
+    };
+    provide_context(context);
+
+    Effect::new(move || {
+        use leptos::wasm_bindgen::JsCast;
+
+        let Some(Ok(prefs)) = context.prefs.get() else {
+            return;
+        };
+        let options =
+            js_sys::Intl::DateTimeFormat::new(&js_sys::Array::new(), &js_sys::Object::new())
+                .resolved_options();
+        let timezone = js_sys::Reflect::get(&options, &"timeZone".into())
+            .ok()
+            .and_then(|value| value.as_string())
+            .filter(|name| timezones::get_by_name(name).is_some())
+            .unwrap_or_else(|| prefs.timezone.clone());
+        if timezone != prefs.timezone {
+            _ = document()
+                .unchecked_into::<web_sys::HtmlDocument>()
+                .set_cookie(&format!(
+                    "{TZ_COOKIE}={timezone}; Path=/; SameSite=Lax; Max-Age=31536000"
+                ));
+        }
+        context.browser.set(Some(DisplayPrefs {
+            timezone,
+            now: OffsetDateTime::now_utc(),
+            ..prefs
+        }));
+    });
+}
+
+/// A timestamp in the viewer's timezone and locale with a translated relative
+/// hint, or "Never" when there is none.
+#[component]
+pub fn DateTime(#[prop(into)] value: Option<OffsetDateTime>) -> impl IntoView {
+    let context = expect_context::<DisplayContext>();
+
+    move || {
+        let prefs = context.get();
+        match value {
+            Some(value) => {
+                let iso = value
+                    .format(&time::format_description::well_known::Rfc3339)
+                    .unwrap_or_default();
+                let (relative, count) = prefs.relative(value);
+                view! {
+                    <time datetime=iso>
+                        {prefs.format(value)}
+                        <small class="text-body-secondary ms-2">
+                            "(" {move || tr_args(relative, &[("count", count.into())])} ")"
+                        </small>
+                    </time>
+                }
+                .into_any()
+            }
+            None => view! { <span>{t!("date-never")}</span> }.into_any(),
+        }
+    }
+}
@@ -0,0 +1,159 @@
+//! Right to erasure: scrubbing a user's personal data while keeping the
+//! account, and everything referring to it by `unid`, in place.
+
+use leptos::prelude::*;
+use uuid::Uuid;
+
+use crate::t;
+
+#[cfg(feature = "ssr")]
+use crate::{
+    state::{AppState, TenantUsers},
+    UserStatus,
+};
+
+pub use model::{erased_login, pseudonym};
+
+/// Erases `unid`'s personal data and deactivates the account. Returns
+/// `Some(false)` if it was already erased and `None` if the tenant has no
+/// such user.
+///
+/// Sessions, trusted browsers, the second factor and what was granted to
+/// other applications are dropped, the links to a directory entry and to
+/// identity providers are forgotten, webhook deliveries carrying the old
+/// data are dropped, IP addresses are removed from the audit log, and the
+/// profile picture is deleted from disk unless another account uses the
+/// same one. Webhook subscribers are then told the user was deleted.
+#[cfg(feature = "ssr")]
+pub fn erase(state: &AppState, users: &TenantUsers, unid: Uuid) -> Option<bool> {
+    let user = users.get(unid)?;
+    if user.is_erased() {
+        return Some(false);
+    }
+
+    // Before the update, which queues the deletion for subscribers.
+    state.webhooks.forget_user(unid);
+    users.update(unid, |user| {
+        user.login = erased_login(unid);
+        user.first_name = Some(pseudonym(unid));
+        user.last_name = None;
+        user.hash = String::new();
+        user.avatar = None;
+        user.roles.clear();
+        user.language = None;
+        user.status = UserStatus::Deactivated;
+    });
+    state.sessions.revoke_all(unid);
+    state.trusted_devices.revoke_all(unid);
+    state.two_factor.reset(unid);
+    state.oauth.revoke_all(unid);
+    state.ldap.unlink(unid);
+    state.oidc.unlink(unid);
+    state.audit.scrub_ips(unid);
+    if let Some(hash) = user.avatar {
+        if !state.users.avatar_in_use(&hash) {
+            _ = std::fs::remove_dir_all(state.avatars.dir().join(hash));
+        }
+    }
+    Some(true)
+}
+
+/// Whether the viewer may erase `user`: administrators may erase anyone in
+/// the tenant but themselves and those already erased.
+#[server]
+pub async fn can_erase(user: Uuid) -> Result<bool, ServerFnError> {
+    use crate::state::{current_session, is_admin, tenant_users};
+
+    let session = current_session()?;
+    Ok(is_admin()?
+        && session.user != user
+        && tenant_users()?
+            .get(user)
+            .is_some_and(|user| !user.is_erased()))
+}
+
+/// Irreversibly erases a user's personal data. Erasing an erased user does
+/// nothing.
+#[server]
+pub async fn erase_user(user: Uuid) -> Result<(), ServerFnError> {
+    use crate::{
+        audit::{audit, AuditAction},
+        state::{app_state, authorize_admin, forbid_impersonation, tenant_users},
+    };
+
+    let session = authorize_admin()?;
+    forbid_impersonation()?;
+    if session.user == user {
+        return Err(ServerFnError::new("You cannot erase your own account"));
+    }
+    match erase(&app_state()?, &tenant_users()?, user) {
+        None => Err(ServerFnError::new("User not found")),
+        Some(true) => {
+            audit(user, AuditAction::Erased);
+            Ok(())
+        }
+        Some(false) => Ok(()),
+    }
+}
+
+/// Administrator control erasing a user's personal data, behind a
+/// confirmation checkbox. Renders nothing for viewers who may not erase.
+#[component]
+pub fn ErasePersonalData(user: Uuid) -> impl IntoView {
+    let erase = ServerAction::<EraseUser>::new();
+    let confirmed = RwSignal::new(false);
+    let allowed = Resource::new(|| (), move |_| can_erase(user));
+    // Stays visible after erasing, to show the outcome.
+    let visible = move || {
+        erase.value().with(Option::is_some) || allowed.get().and_then(Result::ok).unwrap_or(false)
+    };
+
+    view! {
+        <Transition>
+            <Show when=visible>
+                <div class="border border-danger rounded p-3">
+                    <h2 class="h5 text-danger">{t!("erase-title")}</h2>
+                    <p>{t!("erase-help")}</p>
+                    {move || match erase.value().get() {
+                        Some(Ok(())) => {
+                            view! { <div class="alert alert-success mb-0">{t!("erase-done")}</div> }
+                                .into_any()
+                        }
+                        result => {
+                            view! {
+                                {result
+                                    .and_then(Result::err)
+                                    .map(|err| {
+                                        view! { <div class="alert alert-danger">{err.to_string()}</div> }
+                                    })}
+                                <div class="form-check mb-2">
+                                    <input
+                                        id="eraseConfirm"
+                                        type="checkbox"
+                                        class="form-check-input"
+                                        prop:checked=confirmed
+                                        on:change=move |ev| confirmed.set(event_target_checked(&ev))
+                                    />
+                                    <label for="eraseConfirm" class="form-check-label">
+                                        {t!("erase-confirm")}
+                                    </label>
+                                </div>
+                                <button
+                                    type="button"
+                                    class="btn btn-danger"
+                                    prop:disabled=move || !confirmed.get() || erase.pending().get()
+                                    on:click=move |_| {
+                                        erase.dispatch(EraseUser { user });
+                                    }
+                                >
+                                    {t!("erase-submit")}
+                                </button>
+                            }
+                                .into_any()
+                        }
+                    }}
+                </div>
+            </Show>
+        </Transition>
+    }
+}
@@ -0,0 +1,349 @@
+//! Labelled form fields in the app's horizontal layout, each owning its
+//! validation message, disabled state and whether it was changed.
+//!
+//! Fields given a `name` are submitted with their form and report changes
+//! to the [`FormState`] an enclosing component provided, if any. Labels are
+//! message ids, which double as the ids of the inputs they label.
+
+use std::{collections::HashSet, fmt::Display, str::FromStr};
+
+use leptos::prelude::*;
+use strum::IntoEnumIterator;
+
+use crate::i18n::{tr, tr_args};
+
+/// Which named fields of a form differ from the values they started with.
+#[derive(Clone, Copy, Debug)]
+pub struct FormState(RwSignal<HashSet<&'static str>>);
+
+impl FormState {
+    /// Makes a new state available to the fields of the calling component.
+    pub fn provide() -> Self {
+        let state = FormState(RwSignal::new(HashSet::new()));
+        provide_context(state);
+        state
+    }
+
+    /// Whether any field holds a change not yet saved.
+    pub fn is_dirty(self) -> bool {
+        self.0.with(|fields| !fields.is_empty())
+    }
+
+    /// Forgets all changes, once they are saved or thrown away.
+    pub fn reset(self) {
+        self.0.update(HashSet::clear);
+    }
+
+    fn mark(self, name: &'static str, dirty: bool) {
+        // Fields may outlive the form while a page is torn down.
+        if self
+            .0
+            .try_with_untracked(|fields| fields.contains(name) != dirty)
+            == Some(true)
+        {
+            self.0.update(|fields| {
+                if dirty {
+                    fields.insert(name);
+                } else {
+                    fields.remove(name);
+                }
+            });
+        }
+    }
+}
+
+/// What the viewer made of one input.
+#[derive(Clone, Copy)]
+struct FieldState {
+    value: RwSignal<String>,
+    /// Whether to show the problem, once the viewer left the field or tried
+    /// to submit it.
+    touched: RwSignal<bool>,
+    disabled: Signal<bool>,
+    dirty: Memo<bool>,
+    problem: Memo<Option<String>>,
+}
+
+impl FieldState {
+    /// Starts at `initial` and checks each value with `check`. Disabled
+    /// fields are not submitted, so they count as unchanged.
+    fn new(
+        name: Option<&'static str>,
+        initial: String,
+        disabled: Signal<bool>,
+        check: impl Fn(&str) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        let value = RwSignal::new(initial.clone());
+        let dirty = Memo::new(move |_| !disabled.get() && value.with(|value| *value != initial));
+        let problem = Memo::new(move |_| value.with(|value| check(value)));
+        if let (Some(name), Some(form)) = (name, use_context::<FormState>()) {
+            Effect::new(move || form.mark(name, dirty.get()));
+            on_cleanup(move || form.mark(name, false));
+        }
+        FieldState {
+            value,
+            touched: RwSignal::new(false),
+            disabled,
+            dirty,
+            problem,
+        }
+    }
+
+    fn invalid(self) -> bool {
+        self.touched.get() && !self.disabled.get() && self.problem.with(Option::is_some)
+    }
+}
+
+/// The message for a value that is missing or shorter than `min_length`
+/// characters.
+fn length_problem(value: &str, required: bool, min_length: Option<usize>) -> Option<String> {
+    if value.trim().is_empty() {
+        return required.then(|| tr("field-required"));
+    }
+    match min_length {
+        Some(min) if value.chars().count() < min => {
+            Some(tr_args("field-too-short", &[("min", (min as i64).into())]))
+        }
+        _ => None,
+    }
+}
+
+/// A label and whatever it labels, on one row. The other fields are built
+/// on it; use it directly for inputs they do not cover.
+#[component]
+pub fn FieldRow(
+    /// Message id of the label.
+    label: &'static str,
+    /// Id of the labelled input, if there is one.
+    #[prop(optional)]
+    input: Option<&'static str>,
+    #[prop(optional)] required: bool,
+    #[prop(into, optional)] disabled: Signal<bool>,
+    /// Laid out for half the width of the form from medium screens up.
+    #[prop(optional)]
+    half: bool,
+    children: Children,
+) -> impl IntoView {
+    view! {
+        <div class="mb-3 row">
+            <label
+                id=format!("{label}-label")
+                for=input
+                class="col-sm-2 col-form-label text-sm-end"
+                class:col-md-4=half
+                class:required=required
+                class:disabled=disabled
+            >
+                {move || tr(label)}
+            </label>
+            <div class="col-sm">{children()}</div>
+        </div>
+    }
+}
+
+#[component]
+fn FieldInput(
+    id: &'static str,
+    input_type: &'static str,
+    name: Option<String>,
+    state: FieldState,
+    required: bool,
+    min_length: Option<usize>,
+    autocomplete: Option<&'static str>,
+    #[prop(optional_no_strip)] addon: Option<Children>,
+) -> impl IntoView {
+    view! {
+        <div class="input-group has-validation">
+            <input
+                id=id
+                type=input_type
+                name=name
+                class="form-control"
+                class:pristine=move || !state.dirty.get()
+                class:is-invalid=move || state.invalid()
+                autocomplete=autocomplete
+                required=required
+                minlength=min_length
+                prop:disabled=state.disabled
+                value=state.value.get_untracked()
+                prop:value=state.value
+                on:input=move |ev| state.value.set(event_target_value(&ev))
+                on:change=move |_| state.touched.set(true)
+                on:invalid=move |_| state.touched.set(true)
+            />
+            {addon.map(|addon| addon())}
+            <div class="invalid-feedback">{move || state.problem.get()}</div>
+        </div>
+    }
+}
+
+/// A single-line text input.
+#[component]
+pub fn TextField(
+    /// Message id of the label.
+    label: &'static str,
+    #[prop(optional)] name: Option<&'static str>,
+    /// What the field starts with.
+    #[prop(into, optional)]
+    value: String,
+    #[prop(default = "text")] input_type: &'static str,
+    #[prop(optional)] required: bool,
+    #[prop(optional)] min_length: Option<usize>,
+    #[prop(into, optional)] disabled: Signal<bool>,
+    #[prop(optional)] autocomplete: Option<&'static str>,
+    /// Buttons at the end of the input.
+    #[prop(optional)]
+    children: Option<Children>,
+) -> impl IntoView {
+    let state = FieldState::new(name, value, disabled, move |value| {
+        length_problem(value, required, min_length)
+    });
+    view! {
+        <FieldRow label input=label required disabled>
+            <FieldInput
+                id=label
+                input_type
+                name=name.map(String::from)
+                state
+                required
+                min_length
+                autocomplete
+                addon=children
+            />
+        </FieldRow>
+    }
+}
+
+/// A new password, starting out empty, and with `confirm` a second input to
+/// type it again in, shown while the field is enabled. The second input is
+/// named after the first with `_again` appended.
+#[component]
+pub fn PasswordField(
+    /// Message id of the label.
+    label: &'static str,
+    #[prop(optional)] name: Option<&'static str>,
+    #[prop(optional)] required: bool,
+    #[prop(optional)] min_length: Option<usize>,
+    #[prop(into, optional)] disabled: Signal<bool>,
+    /// Message id of the label of the second input.
+    #[prop(optional)]
+    confirm: Option<&'static str>,
+    /// Buttons at the end of the input.
+    #[prop(optional)]
+    children: Option<Children>,
+) -> impl IntoView {
+    let state = FieldState::new(name, String::new(), disabled, move |value| {
+        length_problem(value, required, min_length)
+    });
+    let again = FieldState::new(None, String::new(), disabled, move |again| {
+        state
+            .value
+            .with(|value| again != value.as_str())
+            .then(|| tr("field-mismatch"))
+    });
+    view! {
+        <FieldRow label input=label required disabled>
+            <FieldInput
+                id=label
+                input_type="password"
+                name=name.map(String::from)
+                state
+                required
+                min_length
+                autocomplete=Some("new-password")
+                addon=children
+            />
+        </FieldRow>
+        {confirm
+            .map(|confirm| {
+                view! {
+                    <Show when=move || !disabled.get()>
+                        <FieldRow label=confirm input=confirm required disabled>
+                            <FieldInput
+                                id=confirm
+                                input_type="password"
+                                name=name.map(|name| format!("{name}_again"))
+                                state=again
+                                required
+                                min_length=None
+                                autocomplete=Some("new-password")
+                                addon=None
+                            />
+                        </FieldRow>
+                    </Show>
+                }
+            })}
+    }
+}
+
+/// A choice of one of the values of `E`, submitted as their string form.
+#[component]
+pub fn SelectField<E>(
+    /// Message id of the label.
+    label: &'static str,
+    #[prop(optional)] name: Option<&'static str>,
+    /// What is chosen to start with.
+    value: E,
+    /// Id of each value's name in the translations.
+    message_id: fn(E) -> &'static str,
+    #[prop(into, optional)] disabled: Signal<bool>,
+    /// Called with each value the viewer chooses.
+    #[prop(into, optional)]
+    on_change: Option<Callback<E>>,
+) -> impl IntoView
+where
+    E: IntoEnumIterator + Display + FromStr + Copy + Send + Sync + 'static,
+{
+    let state = FieldState::new(name, value.to_string(), disabled, |_| None);
+    view! {
+        <FieldRow label input=label disabled>
+            <select
+                id=label
+                name=name
+                class="form-select w-auto"
+                class:pristine=move || !state.dirty.get()
+                prop:disabled=disabled
+                on:change=move |ev| {
+                    let value = event_target_value(&ev);
+                    if let (Some(on_change), Ok(chosen)) = (on_change, value.parse()) {
+                        on_change.run(chosen);
+                    }
+                    state.value.set(value);
+                }
+            >
+                {E::iter()
+                    .map(|option| {
+                        let key = option.to_string();
+                        view! {
+                            <option
+                                value=key.clone()
+                                prop:selected=move || state.value.with(|value| *value == key)
+                            >
+                                {move || tr(message_id(option))}
+                            </option>
+                        }
+                    })
+                    .collect_view()}
+            </select>
+        </FieldRow>
+    }
+}
+
+/// A value that is shown but cannot be changed here.
+#[component]
+pub fn ReadonlyField(
+    /// Message id of the label.
+    label: &'static str,
+    /// Laid out for half the width of the form from medium screens up.
+    #[prop(optional)]
+    half: bool,
+    children: Children,
+) -> impl IntoView {
+    view! {
+        <FieldRow label half>
+            <p class="form-control-plaintext" aria-labelledby=format!("{label}-label")>
+                {children()}
+            </p>
+        </FieldRow>
+    }
+}
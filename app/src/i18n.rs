@@ -0,0 +1,245 @@
+//! UI translations, compiled in from the Fluent files under `locales/`.
+
+use std::sync::LazyLock;
+
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource};
+use leptos::prelude::*;
+use unic_langid::LanguageIdentifier;
+use uuid::Uuid;
+
+pub use fluent_bundle::FluentValue;
+
+/// Supported UI languages and their names in themselves; the first is the
+/// fallback for messages missing from the others.
+pub const LANGUAGES: [(&str, &str); 4] = [
+    ("en-US", "English"),
+    ("de", "Deutsch"),
+    ("fr", "Français"),
+    ("ro", "Română"),
+];
+
+const SOURCES: [&str; 4] = [
+    include_str!("../locales/en-US.ftl"),
+    include_str!("../locales/de.ftl"),
+    include_str!("../locales/fr.ftl"),
+    include_str!("../locales/ro.ftl"),
+];
+
+static BUNDLES: LazyLock<Vec<FluentBundle<FluentResource>>> = LazyLock::new(|| {
+    LANGUAGES
+        .iter()
+        .zip(SOURCES)
+        .map(|((code, _), source)| {
+            let language = code
+                .parse::<LanguageIdentifier>()
+                .expect("valid language tag");
+            let resource = FluentResource::try_new(source.to_string())
+                .unwrap_or_else(|(_, errors)| panic!("invalid {code}.ftl: {errors:?}"));
+            let mut bundle = FluentBundle::new_concurrent(vec![language]);
+            bundle.set_use_isolating(false);
+            bundle
+                .add_resource(resource)
+                .unwrap_or_else(|errors| panic!("invalid {code}.ftl: {errors:?}"));
+            bundle
+        })
+        .collect()
+});
+
+/// The supported language code equal to `code`, ignoring case.
+pub fn language(code: &str) -> Option<&'static str> {
+    LANGUAGES
+        .into_iter()
+        .map(|(supported, _)| supported)
+        .find(|supported| supported.eq_ignore_ascii_case(code))
+}
+
+/// Picks the best of `supported` for an `Accept-Language` header, matching
+/// exact tags first and then primary languages, so `de-AT` gets `de`.
+///
+/// Entries are tried from the highest q-value down, in the order listed
+/// among equals (RFC 9110, section 12.5.4). Those with `q=0` are not
+/// acceptable and are skipped.
+pub fn negotiate<'a>(accept_language: &str, supported: &[&'a str]) -> Option<&'a str> {
+    let primary = |tag: &str| {
+        tag.split('-')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase()
+    };
+    let mut entries: Vec<(&str, f32)> = accept_language
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';').map(str::trim);
+            let tag = parts.next()?;
+            let q = parts
+                .find_map(|param| {
+                    let (name, value) = param.split_once('=')?;
+                    name.trim().eq_ignore_ascii_case("q").then(|| value.trim())
+                })
+                .map_or(Some(1.0), |q| q.parse::<f32>().ok())?;
+            (!tag.is_empty() && tag != "*" && q > 0.0).then_some((tag, q))
+        })
+        .collect();
+    // A stable sort keeps the listed order among equal weights.
+    entries.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    entries.into_iter().find_map(|(tag, _)| {
+        supported
+            .iter()
+            .find(|candidate| candidate.eq_ignore_ascii_case(tag))
+            .or_else(|| {
+                supported
+                    .iter()
+                    .find(|candidate| primary(candidate) == primary(tag))
+            })
+            .copied()
+    })
+}
+
+/// Translates message `id` into `language`, falling back to English and then
+/// to the id itself.
+pub fn translate(language: &str, id: &str, args: &[(&str, FluentValue<'_>)]) -> String {
+    let args = (!args.is_empty()).then(|| {
+        let mut fluent_args = FluentArgs::new();
+        for (name, value) in args {
+            fluent_args.set(*name, value.clone());
+        }
+        fluent_args
+    });
+    let index = LANGUAGES
+        .iter()
+        .position(|(code, _)| *code == language)
+        .unwrap_or(0);
+
+    [index, 0]
+        .into_iter()
+        .find_map(|index| {
+            let bundle = &BUNDLES[index];
+            let pattern = bundle.get_message(id)?.value()?;
+            let mut errors = Vec::new();
+            Some(
+                bundle
+                    .format_pattern(pattern, args.as_ref(), &mut errors)
+                    .into_owned(),
+            )
+        })
+        .unwrap_or_else(|| id.to_string())
+}
+
+/// The page's UI language, provided by [`provide_i18n`].
+#[derive(Clone, Copy, Debug)]
+pub struct I18n(pub RwSignal<&'static str>);
+
+/// Translates `id` into the page's language, tracking language changes when
+/// called reactively.
+pub fn tr(id: &str) -> String {
+    tr_args(id, &[])
+}
+
+pub fn tr_args(id: &str, args: &[(&str, FluentValue<'_>)]) -> String {
+    let language = use_context::<I18n>().map_or(LANGUAGES[0].0, |I18n(language)| language.get());
+    translate(language, id, args)
+}
+
+/// A reactive translation for use as a child in `view!`:
+/// `{t!("user-first-name")}` or `{t!("date-days-ago", count = days)}`.
+#[macro_export]
+macro_rules! t {
+    ($id:literal) => {
+        move || $crate::i18n::tr($id)
+    };
+    ($id:literal, $($name:ident = $value:expr),+ $(,)?) => {
+        move || {
+            $crate::i18n::tr_args(
+                $id,
+                &[$((stringify!($name), $crate::i18n::FluentValue::from($value.clone()))),+],
+            )
+        }
+    };
+}
+
+/// UI language to render the page in.
+///
+/// On the server it is the signed-in user's choice, else negotiated from
+/// `Accept-Language`. In the browser it is read back from `<html lang>`, so
+/// hydration starts from the same language.
+pub fn initial_language() -> &'static str {
+    #[cfg(feature = "ssr")]
+    {
+        request_language()
+    }
+    #[cfg(not(feature = "ssr"))]
+    {
+        document()
+            .document_element()
+            .and_then(|html| html.get_attribute("lang"))
+            .and_then(|lang| language(&lang))
+            .unwrap_or(LANGUAGES[0].0)
+    }
+}
+
+/// The language for the current request, see [`initial_language`].
+#[cfg(feature = "ssr")]
+pub fn request_language() -> &'static str {
+    use http::{header::ACCEPT_LANGUAGE, request::Parts};
+
+    if let Some(chosen) = crate::state::current_user()
+        .ok()
+        .and_then(|user| user.language)
+        .and_then(|code| language(&code))
+    {
+        return chosen;
+    }
+    let supported = LANGUAGES.map(|(code, _)| code);
+    use_context::<Parts>()
+        .and_then(|parts| {
+            let header = parts.headers.get(ACCEPT_LANGUAGE)?.to_str().ok()?;
+            negotiate(header, &supported)
+        })
+        .unwrap_or(LANGUAGES[0].0)
+}
+
+/// Provides the [`I18n`] context and keeps `<html lang>` in sync with it
+/// after hydration.
+pub fn provide_i18n() -> I18n {
+    let i18n = I18n(RwSignal::new(initial_language()));
+    provide_context(i18n);
+    Effect::new(move || {
+        let language = i18n.0.get();
+        if let Some(html) = document().document_element() {
+            _ = html.set_attribute("lang", language);
+        }
+    });
+    i18n
+}
+
+/// Saves `user`'s language, `None` meaning the browser's, through
+/// [`crate::user_update::update_user`]. Returns the language the page should
+/// switch to if `user` is the viewer.
+#[server]
+pub async fn set_user_language(
+    user: Uuid,
+    language: Option<String>,
+) -> Result<Option<String>, ServerFnError> {
+    use crate::{
+        session::client_info,
+        state::{app_state, current_session, tenant_roles, tenant_users},
+        user_update::{update_user, UserUpdate},
+    };
+
+    let session = current_session()?;
+    update_user(
+        &app_state()?,
+        &tenant_users()?,
+        &tenant_roles()?,
+        Some(&session),
+        user,
+        UserUpdate {
+            // Empty clears it.
+            language: Some(language.unwrap_or_default()),
+            ..Default::default()
+        },
+        client_info().ip,
+    )
+    .map_err(ServerFnError::new)?;
+    Ok((session.user == user).then(|| request_language().to_string()))
+}
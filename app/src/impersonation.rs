@@ -0,0 +1,208 @@
+//! Administrators signing in as another user, to see exactly what they see.
+//!
+//! Impersonating starts a second session acting as the user that remembers
+//! the administrator and their own session; stopping ends it and returns to
+//! theirs. A banner shows on every page meanwhile. Actions only users may
+//! take themselves are refused, see
+//! [`forbid_impersonation`](crate::state::forbid_impersonation), and the
+//! audit log names the administrator as the actor.
+
+use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::t;
+
+/// The impersonation the viewer's session is.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Impersonation {
+    /// Login of the user acted as.
+    pub login: String,
+    /// Login of the administrator acting.
+    pub impersonator: String,
+}
+
+#[server]
+pub async fn current_impersonation() -> Result<Option<Impersonation>, ServerFnError> {
+    use crate::state::{current_session, tenant_users};
+
+    let Ok(session) = current_session() else {
+        return Ok(None);
+    };
+    let Some(impersonator) = session.impersonator else {
+        return Ok(None);
+    };
+    let users = tenant_users()?;
+    let login = |unid| users.get(unid).map(|user| user.login).unwrap_or_default();
+    Ok(Some(Impersonation {
+        login: login(session.user),
+        impersonator: login(impersonator),
+    }))
+}
+
+/// Whether the viewer may impersonate `user`: administrators may
+/// impersonate anyone in the tenant but themselves and those erased, and
+/// not while already impersonating.
+#[server]
+pub async fn can_impersonate(user: Uuid) -> Result<bool, ServerFnError> {
+    use crate::state::{current_session, is_admin, tenant_users};
+
+    let session = current_session()?;
+    Ok(is_admin()?
+        && session.impersonator.is_none()
+        && session.user != user
+        && tenant_users()?
+            .get(user)
+            .is_some_and(|user| !user.is_erased()))
+}
+
+/// Switches the browser to a session acting as `user`.
+#[server]
+pub async fn start_impersonation(user: Uuid) -> Result<(), ServerFnError> {
+    use crate::{
+        audit::{audit, AuditAction},
+        session::{append_cookie, client_info, session_cookie},
+        state::{app_state, authorize_admin, forbid_impersonation, tenant_users},
+    };
+
+    let session = authorize_admin()?;
+    forbid_impersonation()?;
+    if session.user == user {
+        return Err(ServerFnError::new("You cannot impersonate yourself"));
+    }
+    if !tenant_users()?
+        .get(user)
+        .is_some_and(|user| !user.is_erased())
+    {
+        return Err(ServerFnError::new("User not found"));
+    }
+    let client = client_info();
+    let state = app_state()?;
+    let impersonating = state.sessions.impersonate(
+        session,
+        user,
+        &client.user_agent,
+        client.ip,
+        state.clock.now(),
+    );
+    audit(user, AuditAction::ImpersonationStarted);
+    append_cookie(&session_cookie(impersonating.id));
+    Ok(())
+}
+
+/// Ends the impersonation and switches the browser back to the
+/// administrator's session, returning the user who was impersonated.
+#[server]
+pub async fn stop_impersonation() -> Result<Uuid, ServerFnError> {
+    use crate::{
+        audit::{audit, AuditAction},
+        session::{append_cookie, session_cookie, SESSION_COOKIE},
+        state::{app_state, current_session},
+    };
+
+    let session = current_session()?;
+    if session.impersonator.is_none() {
+        return Err(ServerFnError::new("Not impersonating"));
+    }
+    audit(session.user, AuditAction::ImpersonationStopped);
+    match app_state()?.sessions.end_impersonation(session.id) {
+        Some(own) => append_cookie(&session_cookie(own)),
+        // The administrator was signed out meanwhile.
+        None => append_cookie(&format!("{SESSION_COOKIE}=; Path=/; Max-Age=0")),
+    }
+    Ok(session.user)
+}
+
+/// Loads `path` afresh, as everything on the page depends on who is
+/// signed in.
+fn reload(path: &str) {
+    _ = window().location().set_href(path);
+}
+
+/// Starts impersonating `user`. Renders nothing for viewers who may not.
+#[component]
+pub fn ImpersonateButton(user: Uuid) -> impl IntoView {
+    let start = ServerAction::<StartImpersonation>::new();
+    let allowed = Resource::new(|| (), move |_| can_impersonate(user));
+    Effect::new(move || {
+        if let Some(Ok(())) = start.value().get() {
+            reload("/");
+        }
+    });
+
+    view! {
+        <Transition>
+            <Show when=move || allowed.get().and_then(Result::ok).unwrap_or(false)>
+                <div>
+                    <button
+                        type="button"
+                        class="btn btn-sm btn-outline-warning"
+                        prop:disabled=move || start.pending().get()
+                        on:click=move |_| {
+                            start.dispatch(StartImpersonation { user });
+                        }
+                    >
+                        {t!("impersonation-start")}
+                    </button>
+                    {move || {
+                        start
+                            .value()
+                            .get()
+                            .and_then(Result::err)
+                            .map(|err| view! { <div class="text-danger small">{err.to_string()}</div> })
+                    }}
+                </div>
+            </Show>
+        </Transition>
+    }
+}
+
+/// Reminds administrators that they are acting as someone else, with a way
+/// back. Renders nothing otherwise.
+#[component]
+pub fn ImpersonationBanner() -> impl IntoView {
+    let stop = ServerAction::<StopImpersonation>::new();
+    let impersonation = Resource::new(|| (), |_| current_impersonation());
+    Effect::new(move || {
+        if let Some(Ok(user)) = stop.value().get() {
+            reload(&format!("/users/{user}"));
+        }
+    });
+
+    view! {
+        <Transition>
+            {move || {
+                impersonation
+                    .get()
+                    .and_then(Result::ok)
+                    .flatten()
+                    .map(|impersonation| {
+                        view! {
+                            <div
+                                class="alert alert-warning rounded-0 mb-0 py-2 d-flex align-items-center justify-content-between"
+                                role="status"
+                            >
+                                <span>
+                                    {t!(
+                                        "impersonation-banner",
+                                        login = impersonation.login,
+                                        impersonator = impersonation.impersonator
+                                    )}
+                                </span>
+                                <button
+                                    type="button"
+                                    class="btn btn-sm btn-warning"
+                                    prop:disabled=move || stop.pending().get()
+                                    on:click=move |_| {
+                                        stop.dispatch(StopImpersonation {});
+                                    }
+                                >
+                                    {t!("impersonation-stop")}
+                                </button>
+                            </div>
+                        }
+                    })
+            }}
+        </Transition>
+    }
+}
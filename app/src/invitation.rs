@@ -0,0 +1,704 @@
+//! Onboarding by invitation rather than by an administrator choosing
+//! someone's first password.
+//!
+//! Inviting creates the account as [`crate::UserStatus::Pending`], with its
+//! roles already granted, and emails a link to it. Following the link, the
+//! invitee picks their name and password, which activates the account and
+//! signs them in. A link works once and for a week; resending replaces it,
+//! and revoking deletes the account, which was never used.
+//!
+//! A bulk password reset sends active users the same link, so that they
+//! choose a new password the way an invitee chooses their first.
+
+use leptos::prelude::*;
+use leptos::server_fn::codec::Json;
+use leptos_router::hooks::use_params_map;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{datetime::DateTime, t, users::list_roles};
+
+#[cfg(feature = "ssr")]
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+/// How long an invitation link can be used.
+#[cfg(feature = "ssr")]
+pub const INVITATION_LIFETIME: time::Duration = time::Duration::days(7);
+
+/// An account invited and not yet set up.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingInvitation {
+    pub user: Uuid,
+    pub login: String,
+    /// Sorted.
+    pub roles: Vec<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub invited: OffsetDateTime,
+    /// When the last link sent stops working, `None` if there is none.
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub expires: Option<OffsetDateTime>,
+    pub expired: bool,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug)]
+struct Invitation {
+    /// SHA-256 hash of the token in the link.
+    token_hash: String,
+    expires: OffsetDateTime,
+}
+
+/// The outstanding invitation link of each pending user.
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Default)]
+pub struct Invitations(Arc<RwLock<HashMap<Uuid, Invitation>>>);
+
+#[cfg(feature = "ssr")]
+impl Invitations {
+    /// Returns a new link token for `user`; any earlier one stops working.
+    pub fn issue(&self, user: Uuid, now: OffsetDateTime) -> String {
+        let token = random_token();
+        self.0.write().unwrap().insert(
+            user,
+            Invitation {
+                token_hash: hash_token(&token),
+                expires: now + INVITATION_LIFETIME,
+            },
+        );
+        token
+    }
+
+    /// The user invited with `token`, unless it has expired.
+    pub fn find(&self, token: &str, now: OffsetDateTime) -> Option<Uuid> {
+        let hash = hash_token(token);
+        self.0
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(_, invitation)| invitation.token_hash == hash && invitation.expires > now)
+            .map(|(user, _)| *user)
+    }
+
+    /// Uses up `token`, returning the user it invited.
+    pub fn redeem(&self, token: &str, now: OffsetDateTime) -> Option<Uuid> {
+        let user = self.find(token, now)?;
+        self.0.write().unwrap().remove(&user).map(|_| user)
+    }
+
+    pub fn expires(&self, user: Uuid) -> Option<OffsetDateTime> {
+        self.0
+            .read()
+            .unwrap()
+            .get(&user)
+            .map(|invitation| invitation.expires)
+    }
+
+    pub fn revoke(&self, user: Uuid) -> bool {
+        self.0.write().unwrap().remove(&user).is_some()
+    }
+
+    /// The invitation of `user`, who has not set up their account yet.
+    pub fn pending(&self, user: crate::User, now: OffsetDateTime) -> PendingInvitation {
+        let expires = self.expires(user.unid);
+        let mut roles: Vec<String> = user.roles.into_iter().collect();
+        roles.sort();
+        PendingInvitation {
+            user: user.unid,
+            login: user.login,
+            roles,
+            invited: user.created,
+            expires,
+            expired: expires.is_none_or(|expires| expires <= now),
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn random_token() -> String {
+    use data_encoding::BASE64URL_NOPAD;
+
+    let mut bytes = [0; 32];
+    getrandom::fill(&mut bytes).expect("system RNG is available");
+    BASE64URL_NOPAD.encode(&bytes)
+}
+
+#[cfg(feature = "ssr")]
+fn hash_token(token: &str) -> String {
+    use data_encoding::HEXLOWER;
+    use sha2::{Digest, Sha256};
+
+    HEXLOWER.encode(&Sha256::digest(token.as_bytes()))
+}
+
+/// The address of the site the request was made to, so that links lead
+/// back to the invitee's tenant.
+#[cfg(feature = "ssr")]
+fn site_url() -> String {
+    use http::{header::HOST, request::Parts};
+
+    let parts = use_context::<Parts>();
+    let header = |name: &str| {
+        parts
+            .as_ref()
+            .and_then(|parts| parts.headers.get(name))
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+    let scheme = header("x-forwarded-proto").unwrap_or_else(|| "http".to_string());
+    let host = header(HOST.as_str()).unwrap_or_else(|| "localhost".to_string());
+    format!("{scheme}://{host}")
+}
+
+/// Emails `user` a fresh link, which replaces any sent before.
+#[cfg(feature = "ssr")]
+async fn send_invitation(user: &crate::User) -> Result<(), ServerFnError> {
+    use crate::audit::{audit, AuditAction};
+
+    send_link(
+        user,
+        "You are invited to Blabla",
+        &format!("You have been invited to Blabla as {}.", user.login),
+    )
+    .await?;
+    audit(user.unid, AuditAction::Invited);
+    Ok(())
+}
+
+/// Emails `user`, whose password an administrator cleared, a link to
+/// choose a new one. It is an invitation link and replaces any sent before.
+#[cfg(feature = "ssr")]
+pub(crate) async fn send_password_reset(user: &crate::User) -> Result<(), ServerFnError> {
+    send_link(
+        user,
+        "Choose a new Blabla password",
+        &format!(
+            "An administrator has reset the password of your Blabla account {}.",
+            user.login
+        ),
+    )
+    .await
+}
+
+#[cfg(feature = "ssr")]
+async fn send_link(user: &crate::User, subject: &str, intro: &str) -> Result<(), ServerFnError> {
+    use crate::{mail::Email, state::app_state};
+
+    let state = app_state()?;
+    let token = state.invitations.issue(user.unid, state.clock.now());
+    let email = Email {
+        to: user.login.clone(),
+        subject: subject.to_string(),
+        body: format!(
+            "{intro}\n\n\
+             Choose your name and password to set up your account:\n{}/invite/{token}\n\n\
+             The link can be used once, within {} days.\n",
+            site_url(),
+            INVITATION_LIFETIME.whole_days(),
+        ),
+    };
+    if let Err(err) = state.mailer.send(email).await {
+        leptos::logging::warn!("Invitation for {} not sent: {err}", user.unid);
+        return Err(ServerFnError::new(
+            "The invitation could not be emailed, try resending it",
+        ));
+    }
+    Ok(())
+}
+
+/// The tenant's pending user `unid`.
+#[cfg(feature = "ssr")]
+fn pending_user(unid: Uuid) -> Result<crate::User, ServerFnError> {
+    crate::state::tenant_users()?
+        .get(unid)
+        .filter(|user| user.status == crate::UserStatus::Pending)
+        .ok_or_else(|| ServerFnError::new("Invitation not found"))
+}
+
+/// The tenant's user `unid` if a link can set their password: they are
+/// pending, or active with the password reset.
+#[cfg(feature = "ssr")]
+fn awaiting_password(unid: Uuid) -> Option<crate::User> {
+    use crate::UserStatus;
+
+    crate::state::tenant_users()
+        .ok()?
+        .get(unid)
+        .filter(|user| match user.status {
+            UserStatus::Pending => true,
+            UserStatus::Active => user.hash.is_empty(),
+            UserStatus::Banned | UserStatus::Deactivated => false,
+        })
+}
+
+/// Creates a pending account for `login` with `roles` and emails the
+/// invitation. The account stays when the email fails, to be resent.
+#[server(input = Json)]
+pub async fn invite_user(login: String, roles: Vec<String>) -> Result<Uuid, ServerFnError> {
+    use std::collections::HashSet;
+
+    use crate::{
+        state::{app_state, authorize_admin, tenant_roles, tenant_users},
+        User, UserStatus,
+    };
+
+    authorize_admin()?;
+    let login = login.trim().to_string();
+    if !login.contains('@') {
+        return Err(ServerFnError::new(
+            "Enter an email address to send the invitation to",
+        ));
+    }
+    let catalog = tenant_roles()?;
+    let roles = roles
+        .iter()
+        .map(|role| {
+            catalog
+                .find(role)
+                .ok_or_else(|| ServerFnError::new(format!("Unknown role {role}")))
+        })
+        .collect::<Result<HashSet<_>, _>>()?;
+    let now = app_state()?.clock.now();
+    let user = User {
+        unid: Uuid::new_v4(),
+        created: now,
+        first_name: None,
+        // No password until the invitee chooses one.
+        hash: String::new(),
+        last_failed_login: None,
+        last_login: None,
+        last_password_change: now,
+        last_name: None,
+        login,
+        avatar: None,
+        roles,
+        site_schema: None,
+        status: UserStatus::Pending,
+        theme: Default::default(),
+        language: None,
+    };
+    tenant_users()?
+        .insert_all(vec![user.clone()])
+        .map_err(|taken| ServerFnError::new(format!("Login {taken} is already taken")))?;
+    send_invitation(&user).await?;
+    Ok(user.unid)
+}
+
+/// The tenant's outstanding invitations, sorted by login.
+#[server]
+pub async fn list_invitations() -> Result<Vec<PendingInvitation>, ServerFnError> {
+    use crate::{
+        state::{app_state, authorize_admin, tenant_users},
+        UserStatus,
+    };
+
+    authorize_admin()?;
+    let state = app_state()?;
+    let now = state.clock.now();
+    let mut invitations: Vec<PendingInvitation> = tenant_users()?
+        .all()
+        .into_iter()
+        .filter(|user| user.status == UserStatus::Pending)
+        .map(|user| state.invitations.pending(user, now))
+        .collect();
+    invitations.sort_by_key(|invitation| invitation.login.to_lowercase());
+    Ok(invitations)
+}
+
+/// Emails `user` a new link, for one lost or expired.
+#[server]
+pub async fn resend_invitation(user: Uuid) -> Result<(), ServerFnError> {
+    use crate::state::authorize_admin;
+
+    authorize_admin()?;
+    send_invitation(&pending_user(user)?).await
+}
+
+/// Withdraws the invitation of `user` and deletes the account.
+#[server]
+pub async fn revoke_invitation(user: Uuid) -> Result<(), ServerFnError> {
+    use crate::{
+        audit::{audit, AuditAction},
+        state::{app_state, authorize_admin, tenant_users},
+    };
+
+    authorize_admin()?;
+    pending_user(user)?;
+    app_state()?.invitations.revoke(user);
+    tenant_users()?.remove(user);
+    audit(user, AuditAction::InvitationRevoked);
+    Ok(())
+}
+
+/// The login `token` invites, for the acceptance page.
+#[server]
+pub async fn get_invitation(token: String) -> Result<String, ServerFnError> {
+    use crate::state::app_state;
+
+    let state = app_state()?;
+    state
+        .invitations
+        .find(&token, state.clock.now())
+        .and_then(awaiting_password)
+        .map(|user| user.login)
+        .ok_or_else(|| ServerFnError::new("This invitation has expired or was already used"))
+}
+
+/// Sets up the invited account with the invitee's name and password, and
+/// signs them in.
+#[server]
+pub async fn accept_invitation(
+    token: String,
+    first_name: String,
+    last_name: String,
+    password: String,
+    password_again: String,
+) -> Result<(), ServerFnError> {
+    use crate::{
+        audit::{audit, AuditAction},
+        password::hash_password,
+        session::sign_in,
+        state::{app_state, tenant_users},
+        UserStatus,
+    };
+
+    if password.is_empty() {
+        return Err(ServerFnError::new("Choose a password"));
+    }
+    if password != password_again {
+        return Err(ServerFnError::new("The passwords do not match"));
+    }
+    let state = app_state()?;
+    let now = state.clock.now();
+    let expired = || ServerFnError::new("This invitation has expired or was already used");
+    // Checked before redeeming, so a link of another tenant stays usable.
+    let user = state.invitations.find(&token, now).ok_or_else(expired)?;
+    awaiting_password(user).ok_or_else(expired)?;
+    state.invitations.redeem(&token, now).ok_or_else(expired)?;
+
+    let name = |name: String| Some(name.trim().to_string()).filter(|name| !name.is_empty());
+    let hash = hash_password(&password);
+    tenant_users()?.update(user, |user| {
+        // Left blank, a reset account keeps its name.
+        user.first_name = name(first_name).or(user.first_name.take());
+        user.last_name = name(last_name).or(user.last_name.take());
+        user.hash = hash;
+        user.last_password_change = now;
+        user.status = UserStatus::Active;
+    });
+    audit(user, AuditAction::InvitationAccepted);
+    sign_in(user)?;
+    leptos_axum::redirect("/");
+    Ok(())
+}
+
+/// Form inviting someone by email with roles ticked in advance, and the
+/// invitations still outstanding.
+#[component]
+pub fn InvitePage() -> impl IntoView {
+    let invite = ServerAction::<InviteUser>::new();
+    let resend = ServerAction::<ResendInvitation>::new();
+    let revoke = ServerAction::<RevokeInvitation>::new();
+    let invitations = Resource::new(
+        move || {
+            (
+                invite.version().get(),
+                resend.version().get(),
+                revoke.version().get(),
+            )
+        },
+        |_| list_invitations(),
+    );
+    let roles = Resource::new(|| (), |_| list_roles());
+    let login = RwSignal::new(String::new());
+    let chosen = RwSignal::new(Vec::<String>::new());
+    Effect::new(move || {
+        if let Some(Ok(_)) = invite.value().get() {
+            login.set(String::new());
+            chosen.set(Vec::new());
+        }
+    });
+    let error = move || {
+        [
+            invite.value().get().and_then(Result::err),
+            resend.value().get().and_then(Result::err),
+            revoke.value().get().and_then(Result::err),
+        ]
+        .into_iter()
+        .flatten()
+        .next()
+        .map(|err| view! { <div class="alert alert-danger">{err.to_string()}</div> })
+    };
+
+    view! {
+        <div class="mt-3 px-3">
+            <h1 class="h3 mb-3">{t!("invite-title")}</h1>
+            {error}
+            <Show when=move || matches!(invite.value().get(), Some(Ok(_)))>
+                <div class="alert alert-success">{t!("invite-sent")}</div>
+            </Show>
+            <form
+                class="mb-4"
+                style="max-width: 32rem"
+                on:submit=move |ev| {
+                    ev.prevent_default();
+                    invite
+                        .dispatch(InviteUser {
+                            login: login.get_untracked(),
+                            roles: chosen.get_untracked(),
+                        });
+                }
+            >
+                <div class="mb-3">
+                    <label for="invite-login" class="form-label">
+                        {t!("user-login-email")}
+                    </label>
+                    <input
+                        id="invite-login"
+                        type="email"
+                        class="form-control"
+                        required
+                        prop:value=login
+                        on:input=move |ev| login.set(event_target_value(&ev))
+                    />
+                </div>
+                <fieldset class="mb-3">
+                    <legend class="form-label fs-6">{t!("users-roles")}</legend>
+                    <Transition>
+                        {move || {
+                            roles
+                                .get()
+                                .and_then(Result::ok)
+                                .unwrap_or_default()
+                                .into_iter()
+                                .map(|role| {
+                                    let id = format!("invite-role-{role}");
+                                    let checked = {
+                                        let role = role.clone();
+                                        move || chosen.with(|chosen| chosen.contains(&role))
+                                    };
+                                    let toggle = {
+                                        let role = role.clone();
+                                        move |ev| {
+                                            let on = event_target_checked(&ev);
+                                            chosen
+                                                .update(|chosen| {
+                                                    chosen.retain(|r| *r != role);
+                                                    if on {
+                                                        chosen.push(role.clone());
+                                                    }
+                                                });
+                                        }
+                                    };
+                                    view! {
+                                        <div class="form-check form-check-inline">
+                                            <input
+                                                id=id.clone()
+                                                type="checkbox"
+                                                class="form-check-input"
+                                                prop:checked=checked
+                                                on:change=toggle
+                                            />
+                                            <label for=id class="form-check-label">
+                                                {role}
+                                            </label>
+                                        </div>
+                                    }
+                                })
+                                .collect_view()
+                        }}
+                    </Transition>
+                </fieldset>
+                <button type="submit" class="btn btn-primary" prop:disabled=move || invite.pending().get()>
+                    {t!("invite-submit")}
+                </button>
+            </form>
+            <h2 class="h5">{t!("invite-pending")}</h2>
+            <Transition fallback=|| view! { <p>{t!("user-loading")}</p> }>
+                {move || {
+                    invitations
+                        .get()
+                        .map(|invitations| match invitations {
+                            Ok(invitations) if invitations.is_empty() => {
+                                view! { <p class="text-body-secondary">{t!("invite-none")}</p> }
+                                    .into_any()
+                            }
+                            Ok(invitations) => {
+                                view! {
+                                    <table class="table table-sm align-middle">
+                                        <thead>
+                                            <tr>
+                                                <th>{t!("user-login-email")}</th>
+                                                <th>{t!("users-roles")}</th>
+                                                <th>{t!("invite-invited")}</th>
+                                                <th>{t!("invite-expires")}</th>
+                                                <th></th>
+                                            </tr>
+                                        </thead>
+                                        <tbody>
+                                            {invitations
+                                                .into_iter()
+                                                .map(|invitation| {
+                                                    let user = invitation.user;
+                                                    view! {
+                                                        <tr>
+                                                            <td>{invitation.login}</td>
+                                                            <td>{invitation.roles.join(", ")}</td>
+                                                            <td>
+                                                                <DateTime value=invitation.invited />
+                                                            </td>
+                                                            <td>
+                                                                {if invitation.expired {
+                                                                    view! {
+                                                                        <span class="badge text-bg-secondary">
+                                                                            {t!("invite-expired")}
+                                                                        </span>
+                                                                    }
+                                                                        .into_any()
+                                                                } else {
+                                                                    view! { <DateTime value=invitation.expires /> }.into_any()
+                                                                }}
+                                                            </td>
+                                                            <td class="text-end">
+                                                                <button
+                                                                    type="button"
+                                                                    class="btn btn-sm btn-outline-secondary me-2"
+                                                                    prop:disabled=move || resend.pending().get()
+                                                                    on:click=move |_| {
+                                                                        resend.dispatch(ResendInvitation { user });
+                                                                    }
+                                                                >
+                                                                    {t!("invite-resend")}
+                                                                </button>
+                                                                <button
+                                                                    type="button"
+                                                                    class="btn btn-sm btn-outline-danger"
+                                                                    prop:disabled=move || revoke.pending().get()
+                                                                    on:click=move |_| {
+                                                                        revoke.dispatch(RevokeInvitation { user });
+                                                                    }
+                                                                >
+                                                                    {t!("invite-revoke")}
+                                                                </button>
+                                                            </td>
+                                                        </tr>
+                                                    }
+                                                })
+                                                .collect_view()}
+                                        </tbody>
+                                    </table>
+                                }
+                                    .into_any()
+                            }
+                            Err(err) => {
+                                view! { <div class="alert alert-danger">{err.to_string()}</div> }
+                                    .into_any()
+                            }
+                        })
+                }}
+            </Transition>
+        </div>
+    }
+}
+
+/// Where an invitation link leads: the invitee chooses their name and
+/// password.
+#[component]
+pub fn AcceptInvitationPage() -> impl IntoView {
+    let params = use_params_map();
+    let token = move || params.with(|params| params.get("token").unwrap_or_default());
+    let invitation = Resource::new(token, get_invitation);
+    let accept = ServerAction::<AcceptInvitation>::new();
+
+    view! {
+        <div class="container mt-5" style="max-width: 24rem">
+            <Transition>
+                {move || {
+                    invitation
+                        .get()
+                        .map(|invitation| match invitation {
+                            Ok(login) => {
+                                view! {
+                                    <h1 class="h3 mb-3">{t!("invite-accept-title")}</h1>
+                                    <p>{t!("invite-accept-account", login = login)}</p>
+                                    {move || {
+                                        accept
+                                            .value()
+                                            .get()
+                                            .and_then(Result::err)
+                                            .map(|err| {
+                                                view! { <div class="alert alert-danger">{err.to_string()}</div> }
+                                            })
+                                    }}
+                                    <ActionForm action=accept>
+                                        <input type="hidden" name="token" value=token />
+                                        <div class="mb-3">
+                                            <label for="first_name" class="form-label">
+                                                {t!("user-first-name")}
+                                            </label>
+                                            <input
+                                                id="first_name"
+                                                type="text"
+                                                name="first_name"
+                                                class="form-control"
+                                                autocomplete="given-name"
+                                            />
+                                        </div>
+                                        <div class="mb-3">
+                                            <label for="last_name" class="form-label">
+                                                {t!("user-last-name")}
+                                            </label>
+                                            <input
+                                                id="last_name"
+                                                type="text"
+                                                name="last_name"
+                                                class="form-control"
+                                                autocomplete="family-name"
+                                            />
+                                        </div>
+                                        <div class="mb-3">
+                                            <label for="password" class="form-label">
+                                                {t!("login-password")}
+                                            </label>
+                                            <input
+                                                id="password"
+                                                type="password"
+                                                name="password"
+                                                class="form-control"
+                                                autocomplete="new-password"
+                                                required
+                                            />
+                                        </div>
+                                        <div class="mb-3">
+                                            <label for="password_again" class="form-label">
+                                                {t!("user-retype-password")}
+                                            </label>
+                                            <input
+                                                id="password_again"
+                                                type="password"
+                                                name="password_again"
+                                                class="form-control"
+                                                autocomplete="new-password"
+                                                required
+                                            />
+                                        </div>
+                                        <button type="submit" class="btn btn-primary">
+                                            {t!("invite-accept-submit")}
+                                        </button>
+                                    </ActionForm>
+                                }
+                                    .into_any()
+                            }
+                            Err(err) => {
+                                view! { <div class="alert alert-danger">{err.to_string()}</div> }
+                                    .into_any()
+                            }
+                        })
+                }}
+            </Transition>
+        </div>
+    }
+}
@@ -0,0 +1,529 @@
+//! Users kept in an LDAP directory or Active Directory: a periodic sync of
+//! the entries into `User` records, and optionally checking passwords with
+//! a bind as the user's entry.
+//!
+//! Synced accounts are linked to their entry's DN. The directory decides
+//! their login, name, roles and whether they are active: entries that leave
+//! it, or stop matching the filter, are deactivated, and come back active
+//! if they return. Banned accounts stay banned.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use ldap3::{
+    adapters::{Adapter, EntriesOnly, PagedResults},
+    LdapConn, LdapConnSettings, Scope, SearchEntry,
+};
+use leptos::logging::{log, warn};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    state::{AppState, TenantRoles},
+    tenant::Tenant,
+    User, UserStatus,
+};
+
+/// Entries fetched per round trip; below Active Directory's default limit.
+const PAGE_SIZE: i32 = 500;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How one tenant's directory is reached and read.
+#[derive(Clone, Debug, Deserialize)]
+pub struct LdapConfig {
+    /// Tenant schema, `None` for the default site.
+    #[serde(default)]
+    pub schema: Option<String>,
+    /// `ldap://` or `ldaps://` URL of the server.
+    pub url: String,
+    /// Account the sync searches as; anonymous if empty.
+    #[serde(default)]
+    pub bind_dn: String,
+    #[serde(default)]
+    pub bind_password: String,
+    /// Where users are searched, with the whole subtree.
+    pub base_dn: String,
+    #[serde(default = "default_user_filter")]
+    pub user_filter: String,
+    #[serde(default = "default_login_attribute")]
+    pub login_attribute: String,
+    #[serde(default = "default_first_name_attribute")]
+    pub first_name_attribute: String,
+    #[serde(default = "default_last_name_attribute")]
+    pub last_name_attribute: String,
+    /// Attribute listing the entry's groups by DN.
+    #[serde(default = "default_group_attribute")]
+    pub group_attribute: String,
+    /// Group, by DN or common name, to role. Groups not listed map to the
+    /// role named like their common name; groups naming no role of the
+    /// tenant are ignored.
+    #[serde(default)]
+    pub role_map: HashMap<String, String>,
+    /// Checks the passwords of synced accounts with the directory instead
+    /// of the stored hash.
+    #[serde(default)]
+    pub authenticate: bool,
+    #[serde(default = "default_sync_interval")]
+    pub sync_interval_secs: u64,
+}
+
+fn default_user_filter() -> String {
+    "(objectClass=person)".to_string()
+}
+
+fn default_login_attribute() -> String {
+    "mail".to_string()
+}
+
+fn default_first_name_attribute() -> String {
+    "givenName".to_string()
+}
+
+fn default_last_name_attribute() -> String {
+    "sn".to_string()
+}
+
+fn default_group_attribute() -> String {
+    "memberOf".to_string()
+}
+
+fn default_sync_interval() -> u64 {
+    15 * 60
+}
+
+impl LdapConfig {
+    fn serves(&self, tenant: &Tenant) -> bool {
+        self.schema == tenant.schema
+    }
+
+    /// The roles `groups` map to.
+    fn roles(&self, groups: &[String], catalog: &TenantRoles) -> BTreeSet<String> {
+        let mapped = |key: &str| {
+            self.role_map
+                .iter()
+                .find(|(group, _)| group.eq_ignore_ascii_case(key))
+                .map(|(_, role)| role.as_str())
+        };
+        groups
+            .iter()
+            .filter_map(|group| {
+                let cn = common_name(group);
+                mapped(group).or_else(|| cn.and_then(mapped)).or(cn)
+            })
+            .filter_map(|role| catalog.find(role))
+            .collect()
+    }
+}
+
+/// The value of the first `cn=` part of a DN.
+fn common_name(dn: &str) -> Option<&str> {
+    let (name, value) = dn.split(',').next()?.split_once('=')?;
+    name.trim()
+        .eq_ignore_ascii_case("cn")
+        .then_some(value.trim())
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("LDAP: {0}")]
+pub struct LdapError(pub String);
+
+impl From<ldap3::LdapError> for LdapError {
+    fn from(err: ldap3::LdapError) -> Self {
+        LdapError(err.to_string())
+    }
+}
+
+/// A directory entry with the attributes that were asked for.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DirectoryEntry {
+    pub dn: String,
+    pub attributes: HashMap<String, Vec<String>>,
+}
+
+impl DirectoryEntry {
+    /// The values of attribute `name`, whose case does not matter.
+    fn values(&self, name: &str) -> &[String] {
+        self.attributes
+            .iter()
+            .find(|(attribute, _)| attribute.eq_ignore_ascii_case(name))
+            .map_or(&[], |(_, values)| values)
+    }
+
+    fn first(&self, name: &str) -> Option<String> {
+        self.values(name)
+            .first()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    }
+}
+
+/// Where entries are read from and passwords checked. [`LdapDirectory`]
+/// talks to a server; tests stand in for one in-process.
+///
+/// The calls block, so async code runs them on the blocking pool.
+pub trait Directory: fmt::Debug + Send + Sync {
+    /// The entries under `base_dn` matching `filter`.
+    fn search(
+        &self,
+        base_dn: &str,
+        filter: &str,
+        attributes: &[&str],
+    ) -> Result<Vec<DirectoryEntry>, LdapError>;
+
+    /// Whether `password` is the password of the entry `dn`.
+    fn bind(&self, dn: &str, password: &str) -> Result<bool, LdapError>;
+}
+
+/// A directory server, connected to afresh for every search and bind.
+#[derive(Clone, Debug)]
+pub struct LdapDirectory {
+    url: String,
+    bind_dn: String,
+    bind_password: String,
+}
+
+impl LdapDirectory {
+    pub fn new(config: &LdapConfig) -> Self {
+        LdapDirectory {
+            url: config.url.clone(),
+            bind_dn: config.bind_dn.clone(),
+            bind_password: config.bind_password.clone(),
+        }
+    }
+
+    fn connect(&self) -> Result<LdapConn, LdapError> {
+        let settings = LdapConnSettings::new().set_conn_timeout(CONNECT_TIMEOUT);
+        Ok(LdapConn::with_settings(settings, &self.url)?)
+    }
+}
+
+impl Directory for LdapDirectory {
+    fn search(
+        &self,
+        base_dn: &str,
+        filter: &str,
+        attributes: &[&str],
+    ) -> Result<Vec<DirectoryEntry>, LdapError> {
+        let mut ldap = self.connect()?;
+        if !self.bind_dn.is_empty() {
+            ldap.simple_bind(&self.bind_dn, &self.bind_password)?
+                .success()?;
+        }
+        let adapters: Vec<Box<dyn Adapter<_, _>>> = vec![
+            Box::new(EntriesOnly::new()),
+            Box::new(PagedResults::new(PAGE_SIZE)),
+        ];
+        let mut search = ldap.streaming_search_with(
+            adapters,
+            base_dn,
+            Scope::Subtree,
+            filter,
+            attributes.to_vec(),
+        )?;
+        let mut entries = Vec::new();
+        while let Some(entry) = search.next()? {
+            let entry = SearchEntry::construct(entry);
+            entries.push(DirectoryEntry {
+                dn: entry.dn,
+                attributes: entry.attrs,
+            });
+        }
+        search.result().success()?;
+        _ = ldap.unbind();
+        Ok(entries)
+    }
+
+    fn bind(&self, dn: &str, password: &str) -> Result<bool, LdapError> {
+        let mut ldap = self.connect()?;
+        let result = ldap.simple_bind(dn, password)?;
+        _ = ldap.unbind();
+        Ok(result.rc == 0)
+    }
+}
+
+/// A tenant's directory.
+#[derive(Clone, Debug)]
+pub struct LdapConnector {
+    pub config: LdapConfig,
+    directory: Arc<dyn Directory>,
+}
+
+/// What a sync changed.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SyncReport {
+    pub created: usize,
+    pub updated: usize,
+    pub deactivated: usize,
+    /// Entries without a login, or whose login another account has.
+    pub skipped: usize,
+}
+
+/// The configured directories, and which accounts came from them.
+#[derive(Clone, Debug, Default)]
+pub struct LdapConnectors {
+    connectors: Arc<Vec<LdapConnector>>,
+    /// The DN of each synced account, by `unid`.
+    links: Arc<RwLock<HashMap<Uuid, String>>>,
+}
+
+impl LdapConnectors {
+    /// Reads `LDAP_DIRECTORIES`, a JSON list of [`LdapConfig`]s.
+    ///
+    /// # Panics
+    ///
+    /// If the variable is set but not such a list.
+    pub fn from_env() -> Self {
+        let mut connectors = LdapConnectors::default();
+        if let Ok(configs) = std::env::var("LDAP_DIRECTORIES") {
+            let configs: Vec<LdapConfig> = serde_json::from_str(&configs)
+                .expect("LDAP_DIRECTORIES is a JSON list of directories");
+            for config in configs {
+                let directory = LdapDirectory::new(&config);
+                connectors = connectors.with_directory(config, directory);
+            }
+        }
+        connectors
+    }
+
+    pub fn with_directory(self, config: LdapConfig, directory: impl Directory + 'static) -> Self {
+        let mut connectors = Arc::unwrap_or_clone(self.connectors);
+        connectors.push(LdapConnector {
+            config,
+            directory: Arc::new(directory),
+        });
+        LdapConnectors {
+            connectors: Arc::new(connectors),
+            ..self
+        }
+    }
+
+    pub fn for_tenant(&self, tenant: &Tenant) -> Option<&LdapConnector> {
+        self.connectors
+            .iter()
+            .find(|connector| connector.config.serves(tenant))
+    }
+
+    /// The entry `unid` was synced from.
+    pub fn linked_dn(&self, unid: Uuid) -> Option<String> {
+        self.links.read().unwrap().get(&unid).cloned()
+    }
+
+    fn linked_account(&self, dn: &str) -> Option<Uuid> {
+        self.links
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(_, linked)| linked.eq_ignore_ascii_case(dn))
+            .map(|(unid, _)| *unid)
+    }
+
+    /// Forgets where `unid` came from, as when the account is erased.
+    pub fn unlink(&self, unid: Uuid) {
+        self.links.write().unwrap().remove(&unid);
+    }
+
+    /// Checks `password` with the directory, if `unid` was synced from one
+    /// that authenticates; `None` means the stored hash decides.
+    pub async fn authenticate(
+        &self,
+        tenant: &Tenant,
+        unid: Uuid,
+        password: &str,
+    ) -> Result<Option<bool>, LdapError> {
+        let Some(connector) = self
+            .for_tenant(tenant)
+            .filter(|connector| connector.config.authenticate)
+        else {
+            return Ok(None);
+        };
+        let Some(dn) = self.linked_dn(unid) else {
+            return Ok(None);
+        };
+        // An empty password makes an anonymous bind, which succeeds.
+        if password.is_empty() {
+            return Ok(Some(false));
+        }
+        let directory = connector.directory.clone();
+        let password = password.to_string();
+        let valid = tokio::task::spawn_blocking(move || directory.bind(&dn, &password))
+            .await
+            .map_err(|err| LdapError(err.to_string()))??;
+        Ok(Some(valid))
+    }
+
+    /// Brings the tenant's accounts in line with its directory. Does
+    /// nothing if the tenant has none.
+    pub async fn sync(&self, state: &AppState, tenant: &Tenant) -> Result<SyncReport, LdapError> {
+        let Some(connector) = self.for_tenant(tenant) else {
+            return Ok(SyncReport::default());
+        };
+        let directory = connector.directory.clone();
+        let config = connector.config.clone();
+        let entries = tokio::task::spawn_blocking(move || {
+            directory.search(
+                &config.base_dn,
+                &config.user_filter,
+                &[
+                    config.login_attribute.as_str(),
+                    config.first_name_attribute.as_str(),
+                    config.last_name_attribute.as_str(),
+                    config.group_attribute.as_str(),
+                ],
+            )
+        })
+        .await
+        .map_err(|err| LdapError(err.to_string()))??;
+        Ok(self.apply(connector, entries, state, tenant, state.clock.now()))
+    }
+
+    fn apply(
+        &self,
+        connector: &LdapConnector,
+        entries: Vec<DirectoryEntry>,
+        state: &AppState,
+        tenant: &Tenant,
+        now: OffsetDateTime,
+    ) -> SyncReport {
+        let config = &connector.config;
+        let users = state.users.scoped(tenant);
+        let catalog = state.roles.scoped(tenant);
+        let mut report = SyncReport::default();
+        let mut seen = HashSet::new();
+
+        let listed: HashSet<String> = entries
+            .iter()
+            .map(|entry| entry.dn.to_lowercase())
+            .collect();
+        for entry in &entries {
+            let Some(login) = entry.first(&config.login_attribute) else {
+                report.skipped += 1;
+                continue;
+            };
+            let first_name = entry.first(&config.first_name_attribute);
+            let last_name = entry.first(&config.last_name_attribute);
+            let roles: HashSet<String> = config
+                .roles(entry.values(&config.group_attribute), &catalog)
+                .into_iter()
+                .collect();
+
+            let linked = self
+                .linked_account(&entry.dn)
+                .and_then(|unid| users.get(unid));
+            // An account linked to another listed entry is not taken over,
+            // but one whose entry moved to a new DN is.
+            let existing = linked.or_else(|| {
+                users.find_by_login(&login).filter(|user| {
+                    self.linked_dn(user.unid)
+                        .is_none_or(|dn| !listed.contains(&dn.to_lowercase()))
+                })
+            });
+            let taken = users
+                .find_by_login(&login)
+                .is_some_and(|other| existing.as_ref().is_none_or(|user| user.unid != other.unid));
+            if taken || existing.as_ref().is_some_and(User::is_erased) {
+                report.skipped += 1;
+                continue;
+            }
+
+            let unid = match existing {
+                Some(before) => {
+                    let after = users.update(before.unid, |user| {
+                        user.login = login;
+                        user.first_name = first_name;
+                        user.last_name = last_name;
+                        user.roles = roles;
+                        if user.status == UserStatus::Deactivated {
+                            user.status = UserStatus::Active;
+                        }
+                    });
+                    if after.is_some_and(|after| after != before) {
+                        report.updated += 1;
+                    }
+                    before.unid
+                }
+                None => {
+                    let user = User {
+                        unid: Uuid::new_v4(),
+                        created: now,
+                        first_name,
+                        // The directory checks the password, or one is set
+                        // by a reset.
+                        hash: String::new(),
+                        last_failed_login: None,
+                        last_login: None,
+                        last_password_change: now,
+                        last_name,
+                        login,
+                        avatar: None,
+                        roles,
+                        site_schema: None,
+                        status: UserStatus::Active,
+                        theme: Default::default(),
+                        language: None,
+                    };
+                    let unid = user.unid;
+                    if users.insert_all(vec![user]).is_err() {
+                        report.skipped += 1;
+                        continue;
+                    }
+                    report.created += 1;
+                    unid
+                }
+            };
+            self.links.write().unwrap().insert(unid, entry.dn.clone());
+            seen.insert(unid);
+        }
+
+        // No entries at all is more likely a broken filter than an empty
+        // directory, so nobody is deactivated for it.
+        if entries.is_empty() {
+            return report;
+        }
+        let gone: Vec<Uuid> = self
+            .links
+            .read()
+            .unwrap()
+            .keys()
+            .filter(|unid| !seen.contains(unid))
+            .copied()
+            .collect();
+        for unid in gone {
+            if users
+                .get(unid)
+                .is_some_and(|user| user.status == UserStatus::Active)
+            {
+                users.update(unid, |user| user.status = UserStatus::Deactivated);
+                state.sessions.revoke_all(unid);
+                report.deactivated += 1;
+            }
+        }
+        report
+    }
+}
+
+/// Syncs every configured directory now and then at its interval, for as
+/// long as the server runs.
+pub fn spawn_sync(state: AppState) {
+    for connector in state.ldap.connectors.iter() {
+        let state = state.clone();
+        let tenant = Tenant {
+            schema: connector.config.schema.clone(),
+        };
+        let period = Duration::from_secs(connector.config.sync_interval_secs.max(60));
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                match state.ldap.sync(&state, &tenant).await {
+                    Ok(report) => log!("LDAP sync for {:?}: {report:?}", tenant.schema),
+                    Err(err) => warn!("LDAP sync for {:?} failed: {err}", tenant.schema),
+                }
+            }
+        });
+    }
+}
@@ -1,22 +1,79 @@
-use std::collections::HashSet;
-
-use strum::IntoEnumIterator;
-
 use leptos::prelude::*;
 use leptos_meta::{provide_meta_context, MetaTags, Title};
 use leptos_router::{
     components::{Route, Router, Routes},
+    hooks::use_params_map,
     path,
 };
-use serde::{Deserialize, Serialize};
-use strum_macros::{Display, EnumIter};
-use time::OffsetDateTime;
 use uuid::Uuid;
 
+pub mod api;
+pub mod audit;
+pub mod avatar;
+pub mod bulk;
+pub mod datetime;
+pub mod erasure;
+pub mod form;
+pub mod i18n;
+pub mod impersonation;
+pub mod invitation;
+#[cfg(feature = "ssr")]
+pub mod ldap;
+pub mod live;
+pub mod login;
+#[cfg(feature = "ssr")]
+pub mod mail;
+pub mod oauth;
+pub mod oidc;
+#[cfg(feature = "ssr")]
+pub mod password;
+pub mod personal_data;
+pub mod presence;
+#[cfg(feature = "ssr")]
+pub mod scim;
+pub mod search;
+pub mod session;
+#[cfg(feature = "ssr")]
+pub mod state;
+pub mod tenant;
+pub mod theme;
+#[cfg(feature = "ssr")]
+pub mod totp;
+pub mod trusted_device;
+pub mod two_factor;
+pub mod user_csv;
+#[cfg(feature = "ssr")]
+pub mod user_update;
+pub mod users;
+pub mod webhook;
+
+use avatar::AvatarEditor;
+use datetime::{provide_display_prefs, DateTime};
+use erasure::ErasePersonalData;
+use form::{FieldRow, FormState, PasswordField, ReadonlyField, SelectField, TextField};
+use i18n::{initial_language, provide_i18n, I18n, SetUserLanguage, LANGUAGES};
+use impersonation::{ImpersonateButton, ImpersonationBanner};
+use invitation::{AcceptInvitationPage, InvitePage};
+use live::use_user_events;
+use login::LoginPage;
+use oauth::ConsentPage;
+use personal_data::personal_data_url;
+use presence::{use_edit_presence, OtherViewers, SavedElsewhere};
+use search::UserSearch;
+use session::UserBrowsers;
+use theme::{initial_theme, provide_theme, SetUserTheme, Theme, ThemeContext, ThemeToggle};
+use trusted_device::TrustedDevices;
+use two_factor::TwoFactorSettings;
+use user_csv::UserImport;
+use users::UserList;
+use webhook::WebhooksPage;
+
+pub use model::{User, UserStatus};
+
 pub fn shell(options: LeptosOptions) -> impl IntoView {
     view! {
         <!DOCTYPE html>
-        <html lang="en">
+        <html lang=initial_language() data-bs-theme=initial_theme().as_str()>
             <head>
                 <meta charset="utf-8" />
                 <meta name="viewport" content="width=device-width, initial-scale=1" />
@@ -34,6 +91,9 @@ pub fn shell(options: LeptosOptions) -> impl IntoView {
 #[component]
 pub fn App() -> impl IntoView {
     provide_meta_context();
+    provide_theme();
+    provide_i18n();
+    provide_display_prefs();
 
     view! {
         <Title text="Blabla" />
@@ -46,9 +106,30 @@ pub fn App() -> impl IntoView {
                     </Show>
                 </Transition>
                 <div class="flex-grow-1 position-relative d-flex flex-column">
+                    <ImpersonationBanner />
+                    <nav class="d-flex justify-content-between p-2">
+                        <div class="d-flex align-items-center gap-2">
+                            <a class="btn btn-sm btn-link" href="/users">
+                                {t!("users-title")}
+                            </a>
+                            <a class="btn btn-sm btn-link" href="/webhooks">
+                                {t!("webhooks-title")}
+                            </a>
+                            <UserSearch />
+                        </div>
+                        <ThemeToggle />
+                    </nav>
                     <div>
                         <Routes fallback=move || "not_found".into_view()>
                             <Route path=path!("/") view=UserEdit />
+                            <Route path=path!("/login") view=LoginPage />
+                            <Route path=path!("/consent/:request") view=ConsentPage />
+                            <Route path=path!("/invite/:token") view=AcceptInvitationPage />
+                            <Route path=path!("/users") view=UserList />
+                            <Route path=path!("/users/import") view=UserImport />
+                            <Route path=path!("/users/invite") view=InvitePage />
+                            <Route path=path!("/users/:unid") view=UserEdit />
+                            <Route path=path!("/webhooks") view=WebhooksPage />
                         </Routes>
                     </div>
                 </div>
@@ -73,13 +154,72 @@ pub fn UserEdit() -> impl IntoView {
     let edit_email_disabled = RwSignal::new(true);
     let edit_password_disabled = RwSignal::new(true);
 
-    let user_resource = Resource::new(move || (), async move |_| get_user().await);
+    // `/users/:unid` shows that user, `/` the viewer.
+    let params = use_params_map();
+    let unid = move || params.with(|params| params.get("unid").and_then(|unid| unid.parse().ok()));
+    let user_resource = Resource::new(unid, get_user);
+    let shown = Memo::new(move |_| {
+        user_resource
+            .get()
+            .and_then(Result::ok)
+            .flatten()
+            .map(|user| user.unid)
+    });
+    let form = FormState::provide();
+    // Reload when someone else changes the user on display, unless that
+    // would throw away edits; `SavedElsewhere` tells about those instead.
+    use_user_events(move |event| {
+        if shown.get_untracked() == Some(event.unid) && !untrack(|| form.is_dirty()) {
+            user_resource.refetch();
+        }
+    });
+    // Users changing their own login or password confirm it with the
+    // current one.
+    let viewer = Resource::new(|| (), |_| get_user(None));
+    let is_self = Signal::derive(move || {
+        let viewer = viewer
+            .get()
+            .and_then(Result::ok)
+            .flatten()
+            .map(|viewer| viewer.unid);
+        viewer.is_some() && viewer == shown.get()
+    });
+    let presence = use_edit_presence(shown);
+    let reload = move || {
+        form.reset();
+        presence.saved_by.set(None);
+        user_resource.refetch();
+    };
+    let save = ServerAction::<SaveUser>::new();
+    Effect::new(move || {
+        if let Some(Ok(())) = save.value().get() {
+            reload();
+        }
+    });
+
+    let ThemeContext(page_theme) = expect_context::<ThemeContext>();
+    let set_theme = ServerAction::<SetUserTheme>::new();
+    Effect::new(move || {
+        if let Some(Ok(Some(theme))) = set_theme.value().get() {
+            page_theme.set(theme);
+        }
+    });
+
+    let I18n(page_language) = expect_context::<I18n>();
+    let set_language = ServerAction::<SetUserLanguage>::new();
+    Effect::new(move || {
+        if let Some(Ok(Some(code))) = set_language.value().get() {
+            if let Some(language) = i18n::language(&code) {
+                page_language.set(language);
+            }
+        }
+    });
 
     view! {
         <Suspense fallback=|| {
             view! {
                 <tbody>
-                    <div>Loading...</div>
+                    <div>{t!("user-loading")}</div>
                 </tbody>
             }
         }>
@@ -89,10 +229,32 @@ pub fn UserEdit() -> impl IntoView {
                     view! {
                         <div class="mt-3">
                             <h1>
-                                <div class="d-flex justify-content-between">image</div>
+                                <div class="d-flex justify-content-between">
+                                    {user
+                                        .with_value(|user| {
+                                            view! {
+                                                <AvatarEditor
+                                                    unid=user.unid
+                                                    first_name=user.first_name.clone()
+                                                    last_name=user.last_name.clone()
+                                                    avatar=user.avatar.clone()
+                                                />
+                                            }
+                                        })}
+                                    <div class="d-flex gap-3 align-items-center">
+                                        <OtherViewers presence />
+                                        <ImpersonateButton user=user.with_value(|user| user.unid) />
+                                    </div>
+                                </div>
                             </h1>
+                            <SavedElsewhere presence on_reload=move |_| reload() />
 
-                            <form>
+                            <ActionForm action=save>
+                                <input
+                                    type="hidden"
+                                    name="unid"
+                                    value=user.with_value(|user| user.unid.to_string())
+                                />
                                 <ul class="nav nav-tabs" role="tablist">
                                     <li class="nav-item" role="presentation">
                                         <a
@@ -101,7 +263,7 @@ pub fn UserEdit() -> impl IntoView {
                                             data-bs-toggle="tab"
                                             role="tab"
                                         >
-                                            User information
+                                            {t!("user-tab-information")}
                                         </a>
                                     </li>
                                     <li class="nav-item" role="presentation">
@@ -111,7 +273,7 @@ pub fn UserEdit() -> impl IntoView {
                                             data-bs-toggle="tab"
                                             role="tab"
                                         >
-                                            Access_roles
+                                            {t!("user-tab-access-roles")}
                                         </a>
                                     </li>
                                     <li class="nav-item" role="presentation">
@@ -121,7 +283,7 @@ pub fn UserEdit() -> impl IntoView {
                                             data-bs-toggle="tab"
                                             role="tab"
                                         >
-                                            Emails
+                                            {t!("user-tab-emails")}
                                         </a>
                                     </li>
                                     <li class="nav-item" role="presentation">
@@ -131,7 +293,17 @@ pub fn UserEdit() -> impl IntoView {
                                             data-bs-toggle="tab"
                                             role="tab"
                                         >
-                                            Web browsers
+                                            {t!("user-tab-browsers")}
+                                        </a>
+                                    </li>
+                                    <li class="nav-item" role="presentation">
+                                        <a
+                                            href="#tabTwoFactor"
+                                            class="nav-link"
+                                            data-bs-toggle="tab"
+                                            role="tab"
+                                        >
+                                            {t!("user-tab-two-factor")}
                                         </a>
                                     </li>
                                 </ul>
@@ -141,216 +313,171 @@ pub fn UserEdit() -> impl IntoView {
                                         class="tab-pane pt-3 fade active show"
                                         role="tabpanel"
                                     >
-                                        <div class="mb-3 row">
-                                            <label class="col-sm-2 col-form-label text-sm-end required">
-                                                First name
-                                            </label>
-                                            <div class="col-sm">
-                                                <input
-                                                    type="text"
-                                                    class="form-control pristine"
-                                                    value=user.with_value(|user| user.first_name.clone())
-                                                    data-pristine-value="Adrian-Mihai"
-                                                />
-                                            </div>
-                                        </div>
-                                        <div class="mb-3 row">
-                                            <label class="col-sm-2 col-form-label text-sm-end">
-                                                Last name
-                                            </label>
-                                            <div class="col-sm">
-                                                <input
-                                                    type="text"
-                                                    class="form-control pristine"
-                                                    value=user.with_value(|user| user.last_name.clone())
-                                                />
-                                            </div>
-                                        </div>
-                                        <div class="mb-3 row">
-                                            <label class="col-sm-2 col-form-label text-sm-end required disabled">
-                                                Login email
-                                            </label>
-                                            <div class="col-sm">
-                                                <div class="input-group">
-                                                    <input
-                                                        type="password"
-                                                        class="password form-control pristine"
-                                                        autocomplete="off"
-                                                        value=move || ""
-                                                        prop:disabled=edit_password_disabled
-                                                        id=move || "blabla"
-                                                        maxlength=move || 199
-                                                        name=move || "name"
-                                                        placeholder=move || "placeholder"
-                                                        required=move || false
-                                                        autofocus=move || true
-                                                        class:border-primary=move || !edit_password_disabled.get()
-                                                        class:border-2=move || !edit_password_disabled.get()
-                                                        minlength=move || 8
-                                                        size=move || 20
-                                                    />
-                                                    <button
-                                                        type="button"
-                                                        on:click=move |_| {
-                                                            edit_email_disabled.set(!edit_email_disabled.get())
-                                                        }
-                                                        class="btn btn-secondary"
-                                                    >
-                                                        Edit
-                                                    </button>
-                                                </div>
-                                            </div>
-                                        </div>
-                                        <div class="mb-3 row">
-                                            <label class="col-sm-2 col-form-label text-sm-end">
-                                                Registered on
-                                            </label>
-                                            <div class="col-sm">
-                                                <p class="form-control-plaintext">date</p>
-                                            </div>
-                                        </div>
+                                        <TextField
+                                            label="user-first-name"
+                                            name="first_name"
+                                            value=user.with_value(|user| user.first_name.clone().unwrap_or_default())
+                                            required=true
+                                            autocomplete="given-name"
+                                        />
+                                        <TextField
+                                            label="user-last-name"
+                                            name="last_name"
+                                            value=user.with_value(|user| user.last_name.clone().unwrap_or_default())
+                                            autocomplete="family-name"
+                                        />
+                                        <TextField
+                                            label="user-login-email"
+                                            name="login"
+                                            value=user.with_value(|user| user.login.clone())
+                                            input_type="email"
+                                            required=true
+                                            disabled=edit_email_disabled
+                                        >
+                                            <button
+                                                type="button"
+                                                on:click=move |_| edit_email_disabled.update(|disabled| *disabled = !*disabled)
+                                                class="btn btn-secondary"
+                                            >
+                                                {t!("user-edit")}
+                                            </button>
+                                        </TextField>
+                                        <ReadonlyField label="user-registered-on">
+                                            <DateTime value=user.with_value(|user| user.created) />
+                                        </ReadonlyField>
                                         <div class="row">
                                             <div class="col-md">
-                                                <div class="mb-3 row">
-                                                    <label class="col-sm-2 col-md-4 col-form-label text-sm-end">
-                                                        Last login
-                                                    </label>
-                                                    <div class="col-sm">
-                                                        <p class="form-control-plaintext">date</p>
-                                                    </div>
-                                                </div>
+                                                <ReadonlyField label="user-last-login" half=true>
+                                                    <DateTime value=user.with_value(|user| user.last_login) />
+                                                </ReadonlyField>
                                             </div>
                                             <div class="col-md">
-                                                <div class="mb-3 row">
-                                                    <label class="col-sm-2 col-md-4 col-form-label text-sm-end">
-                                                        Last failed login
-                                                    </label>
-                                                    <div class="col-sm">
-                                                        <p class="form-control-plaintext">date</p>
-                                                    </div>
-                                                </div>
+                                                <ReadonlyField label="user-last-failed-login" half=true>
+                                                    <DateTime value=user.with_value(|user| user.last_failed_login) />
+                                                </ReadonlyField>
                                             </div>
                                         </div>
-                                        <div class="mb-3 row">
-                                            <label class="col-sm-2 col-form-label text-sm-end">
-                                                Status user
-                                            </label>
-                                            <div class="col-sm">
-                                                <select class="form-select w-auto pristine">
-                                                    {move || {
-                                                        UserStatus::iter()
-                                                            .map(|status| {
-                                                                view! {
-                                                                    <option
-                                                                        prop:selected=move || {
-                                                                            user.with_value(|user| user.status == status)
-                                                                        }
-                                                                        value=status as u8
-                                                                    >
-                                                                        {format!("{status}")}
-                                                                    </option>
+                                        <SelectField
+                                            label="user-status"
+                                            name="status"
+                                            value=user.with_value(|user| user.status)
+                                            message_id=UserStatus::message_id
+                                        />
+                                        <SelectField
+                                            label="user-theme"
+                                            value=user.with_value(|user| user.theme)
+                                            message_id=Theme::message_id
+                                            on_change=move |theme: Theme| {
+                                                set_theme
+                                                    .dispatch(SetUserTheme {
+                                                        user: user.with_value(|user| user.unid),
+                                                        theme,
+                                                    });
+                                            }
+                                        />
+                                        <FieldRow label="user-language" input="user-language">
+                                            <select
+                                                id="user-language"
+                                                class="form-select w-auto"
+                                                on:change=move |ev| {
+                                                    let language = event_target_value(&ev);
+                                                    set_language
+                                                        .dispatch(SetUserLanguage {
+                                                            user: user.with_value(|user| user.unid),
+                                                            language: Some(language).filter(|code| !code.is_empty()),
+                                                        });
+                                                }
+                                            >
+                                                <option
+                                                    prop:selected=move || {
+                                                        user.with_value(|user| user.language.is_none())
+                                                    }
+                                                    value=""
+                                                >
+                                                    {t!("user-language-browser")}
+                                                </option>
+                                                {LANGUAGES
+                                                    .into_iter()
+                                                    .map(|(code, name)| {
+                                                        view! {
+                                                            <option
+                                                                prop:selected=move || {
+                                                                    user.with_value(|user| user.language.as_deref() == Some(code))
                                                                 }
-                                                            })
-                                                            .collect_view()
-                                                    }}
-                                                </select>
-                                            </div>
-                                        </div>
-                                        <div class="mb-3 row">
-                                            <label class="col-sm-2 col-form-label text-sm-end disabled">
-                                                New password
-                                            </label>
-                                            <div class="col-sm">
-                                                <div class="input-group">
-                                                    <input
-                                                        type="password"
-                                                        class="password form-control pristine"
-                                                        autocomplete="off"
-                                                        value=move || ""
-                                                        prop:disabled=edit_password_disabled
-                                                        id=move || "blabla"
-                                                        maxlength=move || 199
-                                                        name=move || "name"
-                                                        placeholder=move || "placeholder"
-                                                        required=move || false
-                                                        autofocus=move || true
-                                                        class:border-primary=move || !edit_password_disabled.get()
-                                                        class:border-2=move || !edit_password_disabled.get()
-                                                        minlength=move || 8
-                                                        size=move || 20
-                                                        pattern=move || "^(?=.*[a-z])(?=.*[A-Z])(?=.*\\d)[a-zA-Z\\d]{8,}$"
-                                                        readonly=move || false
-                                                        form=move || "login-form"
-                                                        aria-label=move || "Password"
-                                                        aria-describedby=move || "password-help"
-                                                        data-validate=move || true
-                                                        tabindex=move || 0
-                                                        on:change=move|_| leptos::leptos_dom::logging::console_log("Password changed")
-                                                        on:input=move |_| leptos::leptos_dom::logging::console_log("Password input")
-                                                    />
-                                                    <input
-                                                        type="password"
-                                                        class="password form-control pristine"
-                                                        autocomplete="off"
-                                                        value=move || ""
-                                                        prop:disabled=edit_password_disabled
-                                                        id=move || "blabla"
-                                                        maxlength=move || 199
-                                                        name=move || "name"
-                                                        placeholder=move || "placeholder"
-                                                        required=move || false
-                                                        autofocus=move || true
-                                                        class:border-primary=move || !edit_password_disabled.get()
-                                                        class:border-2=move || !edit_password_disabled.get()
-                                                    />
-                                                    <button
-                                                        type="button"
-                                                        on:click=move |_| {
-                                                            edit_password_disabled.set(!edit_password_disabled.get())
+                                                                value=code
+                                                            >
+                                                                {name}
+                                                            </option>
                                                         }
-                                                        class="btn btn-secondary"
-                                                    >
-                                                        <i class="fa-solid fa-edit"></i>
-                                                        Edit
-                                                    </button>
-                                                </div>
-                                            </div>
-                                        </div>
-
-                                        <Show when=move || !edit_password_disabled.get()>
-                                            <div class="mb-3 row">
-                                                <label class="col-sm-2 col-form-label text-sm-end disabled">
-                                                    Retype new password
-                                                </label>
-                                                <div class="col-sm">
-                                                    <div class="input-group">
-                                                        <input
-                                                            type="password"
-                                                            class="password form-control pristine"
-                                                            autocomplete="off"
-                                                            value=""
-                                                            prop:disabled=edit_password_disabled
-                                                            minlength="6"
-                                                            data-pristine-value=""
-                                                        />
-                                                    </div>
-                                                </div>
-                                            </div>
+                                                    })
+                                                    .collect_view()}
+                                            </select>
+                                        </FieldRow>
+                                        <PasswordField
+                                            label="user-new-password"
+                                            name="password"
+                                            min_length=MIN_PASSWORD_LENGTH
+                                            disabled=edit_password_disabled
+                                            confirm="user-retype-password"
+                                        >
+                                            <button
+                                                type="button"
+                                                on:click=move |_| edit_password_disabled.update(|disabled| *disabled = !*disabled)
+                                                class="btn btn-secondary"
+                                            >
+                                                <i class="fa-solid fa-edit"></i>
+                                                {t!("user-edit")}
+                                            </button>
+                                        </PasswordField>
+                                        <Show when=move || {
+                                            is_self.get()
+                                                && !(edit_email_disabled.get() && edit_password_disabled.get())
+                                        }>
+                                            <TextField
+                                                label="user-current-password"
+                                                name="current_password"
+                                                input_type="password"
+                                                required=true
+                                                autocomplete="current-password"
+                                            />
                                         </Show>
 
                                         <div class="mb-3 row">
-                                            <label class="col-sm-2 col-form-label text-sm-end">
-                                                Personal record
-                                            </label>
-                                            <div class="col-sm">
-                                                <ul class="list-unstyled mt-2">
-                                                    <li>
-                                                        <a href="">Link to</a>
-                                                    </li>
-                                                </ul>
-
+                                            <div class="col-sm offset-sm-2">
+                                                <button
+                                                    type="submit"
+                                                    class="btn btn-primary"
+                                                    on:click=move |ev| {
+                                                        if !presence.confirm_save() {
+                                                            ev.prevent_default();
+                                                        }
+                                                    }
+                                                >
+                                                    {t!("user-save")}
+                                                </button>
+                                                {move || {
+                                                    save.value()
+                                                        .get()
+                                                        .and_then(Result::err)
+                                                        .map(|err| {
+                                                            view! { <div class="text-danger mt-2">{err.to_string()}</div> }
+                                                        })
+                                                }}
                                             </div>
                                         </div>
+
+                                        <FieldRow label="user-personal-record">
+                                            <a
+                                                class="btn btn-outline-secondary"
+                                                href=user.with_value(|user| personal_data_url(user.unid))
+                                                rel="external"
+                                                download
+                                            >
+                                                {t!("user-download-personal-data")}
+                                            </a>
+                                        </FieldRow>
+
+                                        <ErasePersonalData user=user.with_value(|user| user.unid) />
                                     </div>
 
                                     <div
@@ -370,9 +497,18 @@ pub fn UserEdit() -> impl IntoView {
                                         class="tab-pane fade pt-3"
                                         role="tabpanel"
                                     >
+                                        <UserBrowsers user=user.with_value(|user| user.unid) />
+                                        <TrustedDevices user=user.with_value(|user| user.unid) />
+                                    </div>
+                                    <div
+                                        id="tabTwoFactor"
+                                        class="tab-pane fade pt-3"
+                                        role="tabpanel"
+                                    >
+                                        <TwoFactorSettings user=user.with_value(|user| user.unid) />
                                     </div>
                                 </div>
-                            </form>
+                            </ActionForm>
                         </div>
                     }
                         .into_any()
@@ -380,7 +516,8 @@ pub fn UserEdit() -> impl IntoView {
                 Some(Ok(None)) => {
                     view! {
                         <div>
-                            <p>"Couldn't get user"</p>
+                            <p>{t!("user-not-found")}</p>
+                            <a href="/login">{t!("user-sign-in")}</a>
                         </div>
                     }
                         .into_any()
@@ -388,7 +525,7 @@ pub fn UserEdit() -> impl IntoView {
                 Some(Err(err)) => {
                     view! {
                         <div>
-                            <p>"Server Error: " <span>{format!("{err:?}")}</span></p>
+                            <p>{t!("user-server-error")} " " <span>{format!("{err:?}")}</span></p>
                         </div>
                     }
                         .into_any()
@@ -399,50 +536,68 @@ pub fn UserEdit() -> impl IntoView {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
-pub struct User {
-    pub unid: Uuid,
-    pub created: OffsetDateTime,
-    pub first_name: Option<String>,
-    pub hash: String,
-    pub last_failed_login: Option<OffsetDateTime>,
-    pub last_login: Option<OffsetDateTime>,
-    pub last_password_change: OffsetDateTime,
-    pub last_name: Option<String>,
-    pub login: String,
-    pub roles: HashSet<String>,
-    pub site_schema: Option<String>,
-    pub status: UserStatus,
-    pub theme: String,
-}
+/// Shortest password [`save_user`] and the API accept.
+pub const MIN_PASSWORD_LENGTH: usize = 8;
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, Display, PartialEq, Eq, Hash, EnumIter)]
-pub enum UserStatus {
-    Active,
-    Banned,
-}
+/// Saves the names of `unid`, their login and password if given and, when
+/// an administrator changes it, their status, through
+/// [`user_update::update_user`].
+// One argument per field of the form.
+#[allow(clippy::too_many_arguments)]
+#[server]
+pub async fn save_user(
+    unid: Uuid,
+    first_name: String,
+    last_name: String,
+    login: Option<String>,
+    current_password: Option<String>,
+    password: Option<String>,
+    password_again: Option<String>,
+    status: Option<UserStatus>,
+) -> Result<(), ServerFnError> {
+    use crate::{
+        session::client_info,
+        state::{app_state, current_session, tenant_roles, tenant_users},
+        user_update::{update_user, UserUpdate},
+    };
 
-impl User {
-    pub fn get_user() -> Self {
-        User {
-            unid: Uuid::new_v4(),
-            created: OffsetDateTime::now_utc(),
-            first_name: Some("Bob".to_string()),
-            hash: "asdf".to_string(),
-            last_failed_login: None,
-            last_login: None,
-            last_password_change: OffsetDateTime::now_utc(),
-            last_name: None,
-            login: "bob@bob.bob".to_string(),
-            roles: HashSet::new(),
-            site_schema: None,
-            status: UserStatus::Active,
-            theme: "dark".to_string(),
-        }
+    if first_name.trim().is_empty() {
+        return Err(ServerFnError::new("A first name is required"));
+    }
+    let password = password.filter(|password| !password.is_empty());
+    if password.is_some() && password_again != password {
+        return Err(ServerFnError::new("The passwords do not match"));
     }
+    update_user(
+        &app_state()?,
+        &tenant_users()?,
+        &tenant_roles()?,
+        Some(&current_session()?),
+        unid,
+        UserUpdate {
+            login,
+            first_name: Some(first_name),
+            last_name: Some(last_name),
+            password,
+            current_password,
+            status,
+            ..Default::default()
+        },
+        client_info().ip,
+    )
+    .map_err(ServerFnError::new)?;
+    Ok(())
 }
 
+/// The user `unid`, or the viewer if `None`. Only administrators may look at
+/// other users.
 #[server]
-async fn get_user() -> Result<Option<User>, ServerFnError> {
-    Ok(Some(User::get_user()))
+async fn get_user(unid: Option<Uuid>) -> Result<Option<User>, ServerFnError> {
+    match unid {
+        Some(unid) => {
+            state::authorize_self_or_admin(unid)?;
+            Ok(state::tenant_users()?.get(unid).map(User::public))
+        }
+        None => Ok(state::current_user().ok().map(User::public)),
+    }
 }
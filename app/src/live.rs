@@ -0,0 +1,169 @@
+//! Pages that follow changes to users as they happen, such as a ban made by
+//! another administrator.
+//!
+//! Every write to a user is published on [`UserEvents`] and streamed to
+//! browsers as server-sent events from [`EVENTS_PATH`]; [`use_user_events`]
+//! hands them to a component. Administrators get the changes to all users
+//! of their tenant, everyone else only those to their own account.
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::User;
+
+#[cfg(feature = "ssr")]
+use std::{convert::Infallible, sync::Arc};
+
+#[cfg(feature = "ssr")]
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Extension,
+};
+#[cfg(feature = "ssr")]
+use tokio::sync::broadcast;
+
+#[cfg(feature = "ssr")]
+use crate::{
+    session::CurrentSession,
+    state::{AppState, UserChange, UserObserver},
+    tenant::Tenant,
+};
+
+/// Where [`stream`] is served.
+pub const EVENTS_PATH: &str = "/events";
+
+/// Changes published and not yet streamed to a browser before the oldest
+/// are dropped for it.
+#[cfg(feature = "ssr")]
+const BACKLOG: usize = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Removed,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserEvent {
+    pub kind: ChangeKind,
+    pub unid: Uuid,
+    /// The user as stored now, without the password hash; `None` once
+    /// removed.
+    pub user: Option<User>,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Debug)]
+struct Published {
+    schema: Option<String>,
+    event: UserEvent,
+}
+
+/// The bus changes to users are published on, for all tenants.
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug)]
+pub struct UserEvents(broadcast::Sender<Arc<Published>>);
+
+#[cfg(feature = "ssr")]
+impl Default for UserEvents {
+    fn default() -> Self {
+        UserEvents(broadcast::channel(BACKLOG).0)
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl UserObserver for UserEvents {
+    fn changed(&self, change: UserChange<'_>) {
+        let (kind, user) = match (change.before, change.after) {
+            (_, None) => (ChangeKind::Removed, change.before),
+            (None, Some(after)) => (ChangeKind::Created, Some(after)),
+            (Some(before), Some(after)) if before == after => return,
+            (Some(_), Some(after)) => (ChangeKind::Updated, Some(after)),
+        };
+        let Some(user) = user else {
+            return;
+        };
+        // Nobody listening is not an error.
+        _ = self.0.send(Arc::new(Published {
+            schema: user.site_schema.clone(),
+            event: UserEvent {
+                kind,
+                unid: user.unid,
+                user: change.after.cloned().map(User::public),
+            },
+        }));
+    }
+}
+
+/// Streams the changes the signed-in viewer may see as server-sent events,
+/// each a [`UserEvent`] in JSON. The stream goes quiet once the session
+/// ends.
+#[cfg(feature = "ssr")]
+pub async fn stream(
+    State(state): State<AppState>,
+    Extension(tenant): Extension<Tenant>,
+    session: Option<Extension<CurrentSession>>,
+) -> Response {
+    use tokio_stream::{wrappers::BroadcastStream, StreamExt};
+
+    let Some(Extension(session)) = session else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let users = state.users.scoped(&tenant);
+    // Changes missed for falling behind are skipped.
+    let events = BroadcastStream::new(state.events.0.subscribe()).filter_map(move |published| {
+        let published = published.ok()?;
+        let visible = published.schema == tenant.schema
+            && state.sessions.owner(session.id).is_some()
+            && (published.event.unid == session.user || users.is_admin(session.user));
+        if !visible {
+            return None;
+        }
+        Event::default()
+            .json_data(&published.event)
+            .ok()
+            .map(Ok::<_, Infallible>)
+    });
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+/// Calls `on_event` with every change the viewer may see, for as long as
+/// the calling component lives. Does nothing during server rendering.
+pub fn use_user_events(on_event: impl Fn(UserEvent) + 'static) {
+    use std::rc::Rc;
+
+    use leptos::{
+        prelude::*,
+        wasm_bindgen::{closure::Closure, JsCast},
+    };
+    use web_sys::{EventSource, MessageEvent};
+
+    let on_event = Rc::new(on_event);
+    Effect::new(move || {
+        let Ok(source) = EventSource::new(EVENTS_PATH) else {
+            return;
+        };
+        let on_event = on_event.clone();
+        let on_message = Closure::<dyn Fn(MessageEvent)>::new(move |message: MessageEvent| {
+            if let Some(event) = message
+                .data()
+                .as_string()
+                .and_then(|data| serde_json::from_str(&data).ok())
+            {
+                on_event(event);
+            }
+        });
+        source.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        let source = StoredValue::new_local((source, on_message));
+        on_cleanup(move || source.with_value(|(source, _)| source.close()));
+    });
+}
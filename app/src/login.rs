@@ -0,0 +1,263 @@
+use leptos::prelude::*;
+use leptos_router::hooks::use_query_map;
+use serde::{Deserialize, Serialize};
+
+use crate::{oidc::IdentityProviderLogin, t};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LoginOutcome {
+    SignedIn,
+    /// The password was right; a TOTP or recovery code is still needed.
+    SecondFactorRequired,
+}
+
+/// Query parameter the login page is opened with to ask for the second
+/// factor of a sign-in that started elsewhere, such as with an identity
+/// provider.
+pub const SECOND_FACTOR_PARAM: &str = "second_factor";
+
+/// Where to go once signed in: `next` if it is a path on this site, so the
+/// login page cannot be used to send people elsewhere.
+#[cfg(feature = "ssr")]
+fn after_login(next: Option<String>) -> String {
+    next.filter(|next| next.starts_with('/') && !next.starts_with("//") && !next.contains('\\'))
+        .unwrap_or_else(|| "/".to_string())
+}
+
+/// `next` is the page to return to, set when another page sent the browser
+/// here to sign in.
+#[server]
+pub async fn login(
+    login: String,
+    password: String,
+    next: Option<String>,
+) -> Result<LoginOutcome, ServerFnError> {
+    use crate::{
+        audit::{audit, AuditAction},
+        password::verify_password,
+        session::{append_cookie, client_info, request_cookie, sign_in},
+        state::{app_state, tenant_users},
+        tenant::current_tenant,
+        trusted_device::TRUSTED_DEVICE_COOKIE,
+        two_factor::CHALLENGE_COOKIE,
+        UserStatus,
+    };
+
+    let state = app_state()?;
+    let users = tenant_users()?;
+    let now = state.clock.now();
+    let Some(user) = users.find_by_login(&login) else {
+        return Err(ServerFnError::new("Invalid login or password"));
+    };
+    let valid = match state
+        .ldap
+        .authenticate(&current_tenant(), user.unid, &password)
+        .await
+    {
+        Ok(Some(valid)) => valid,
+        Ok(None) => verify_password(&password, &user.hash),
+        Err(err) => {
+            leptos::logging::warn!("LDAP bind for {} failed: {err}", user.unid);
+            return Err(ServerFnError::new(
+                "The directory cannot be reached, try again later",
+            ));
+        }
+    };
+    if !valid {
+        users.update(user.unid, |user| user.last_failed_login = Some(now));
+        audit(user.unid, AuditAction::SignInFailed);
+        return Err(ServerFnError::new("Invalid login or password"));
+    }
+    match user.status {
+        UserStatus::Active => {}
+        UserStatus::Banned => return Err(ServerFnError::new("This account is banned")),
+        UserStatus::Deactivated => {
+            return Err(ServerFnError::new("This account is deactivated"))
+        }
+        UserStatus::Pending => {
+            return Err(ServerFnError::new("This account has not been set up yet"))
+        }
+    }
+
+    let trusted = request_cookie(TRUSTED_DEVICE_COOKIE).is_some_and(|token| {
+        state
+            .trusted_devices
+            .verify(&state.signing_key, &token, user.unid, &client_info(), now)
+    });
+    if state.two_factor.is_enabled(user.unid) && !trusted {
+        let challenge = state.two_factor.challenge(user.unid, now);
+        append_cookie(&format!(
+            "{CHALLENGE_COOKIE}={challenge}; Path=/; HttpOnly; SameSite=Lax; Max-Age=300"
+        ));
+        return Ok(LoginOutcome::SecondFactorRequired);
+    }
+
+    sign_in(user.unid)?;
+    leptos_axum::redirect(&after_login(next));
+    Ok(LoginOutcome::SignedIn)
+}
+
+/// Second step of a login for accounts with two-factor authentication.
+///
+/// `remember` is the "Trust this browser" checkbox, present when ticked.
+#[server]
+pub async fn verify_login_code(
+    code: String,
+    remember: Option<String>,
+    next: Option<String>,
+) -> Result<(), ServerFnError> {
+    use crate::{
+        audit::{audit, AuditAction},
+        session::{append_cookie, client_info, request_cookie, sign_in},
+        state::{app_state, tenant_users},
+        trusted_device::{TRUSTED_DEVICE_COOKIE, TRUST_LIFETIME},
+        two_factor::CHALLENGE_COOKIE,
+    };
+
+    let state = app_state()?;
+    let now = state.clock.now();
+    let challenge = request_cookie(CHALLENGE_COOKIE)
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| ServerFnError::new("The login has expired, please sign in again"))?;
+    let user = state
+        .two_factor
+        .challenged_user(challenge, now)
+        .ok_or_else(|| ServerFnError::new("The login has expired, please sign in again"))?;
+
+    if !state.two_factor.verify(user, &code, now) {
+        tenant_users()?.update(user, |user| user.last_failed_login = Some(now));
+        audit(user, AuditAction::SignInFailed);
+        if !state.two_factor.fail_challenge(challenge) {
+            append_cookie(&format!("{CHALLENGE_COOKIE}=; Path=/; Max-Age=0"));
+            return Err(ServerFnError::new("Too many invalid codes, please sign in again"));
+        }
+        return Err(ServerFnError::new("The code is not valid"));
+    }
+
+    // Roles an identity provider mapped only count once the code is right.
+    if let Some(roles) = state.two_factor.end_challenge(challenge) {
+        tenant_users()?.update(user, |user| user.roles = roles);
+    }
+    append_cookie(&format!("{CHALLENGE_COOKIE}=; Path=/; Max-Age=0"));
+    if remember.is_some() {
+        let token = state
+            .trusted_devices
+            .trust(&state.signing_key, user, &client_info(), now);
+        append_cookie(&format!(
+            "{TRUSTED_DEVICE_COOKIE}={token}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}",
+            TRUST_LIFETIME.whole_seconds()
+        ));
+    }
+    sign_in(user)?;
+    leptos_axum::redirect(&after_login(next));
+    Ok(())
+}
+
+#[server]
+pub async fn logout() -> Result<(), ServerFnError> {
+    use crate::session::sign_out;
+
+    sign_out()?;
+    leptos_axum::redirect("/login");
+    Ok(())
+}
+
+#[component]
+pub fn LoginPage() -> impl IntoView {
+    let login = ServerAction::<Login>::new();
+    let verify = ServerAction::<VerifyLoginCode>::new();
+    let query = use_query_map();
+    let next = move || query.with(|query| query.get("next"));
+
+    let needs_code = move || {
+        matches!(
+            login.value().get(),
+            Some(Ok(LoginOutcome::SecondFactorRequired))
+        ) || query.with(|query| query.get(SECOND_FACTOR_PARAM).is_some())
+    };
+    let error = move || {
+        verify
+            .value()
+            .get()
+            .and_then(Result::err)
+            .or_else(|| login.value().get().and_then(Result::err))
+            .map(|err| view! { <div class="alert alert-danger">{err.to_string()}</div> })
+    };
+
+    view! {
+        <div class="container mt-5" style="max-width: 24rem">
+            <h1 class="h3 mb-3">{t!("login-title")}</h1>
+            {error}
+            <Show
+                when=needs_code
+                fallback=move || {
+                    view! {
+                        <ActionForm action=login>
+                            <input type="hidden" name="next" value=next />
+                            <div class="mb-3">
+                                <label for="login" class="form-label">
+                                    {t!("login-login")}
+                                </label>
+                                <input
+                                    id="login"
+                                    type="text"
+                                    name="login"
+                                    class="form-control"
+                                    autocomplete="username"
+                                    required
+                                />
+                            </div>
+                            <div class="mb-3">
+                                <label for="password" class="form-label">
+                                    {t!("login-password")}
+                                </label>
+                                <input
+                                    id="password"
+                                    type="password"
+                                    name="password"
+                                    class="form-control"
+                                    autocomplete="current-password"
+                                    required
+                                />
+                            </div>
+                            <button type="submit" class="btn btn-primary">
+                                {t!("login-submit")}
+                            </button>
+                        </ActionForm>
+                        <IdentityProviderLogin />
+                    }
+                }
+            >
+                <ActionForm action=verify>
+                    <input type="hidden" name="next" value=next />
+                    <div class="mb-3">
+                        <label for="code" class="form-label">
+                            {t!("login-code")}
+                        </label>
+                        <input
+                            id="code"
+                            type="text"
+                            name="code"
+                            class="form-control"
+                            inputmode="numeric"
+                            autocomplete="one-time-code"
+                            required
+                        />
+                        <div class="form-text">
+                            {t!("login-code-help")}
+                        </div>
+                    </div>
+                    <div class="mb-3 form-check">
+                        <input id="remember" type="checkbox" name="remember" class="form-check-input" />
+                        <label for="remember" class="form-check-label">
+                            {t!("login-remember")}
+                        </label>
+                    </div>
+                    <button type="submit" class="btn btn-primary">
+                        {t!("login-verify")}
+                    </button>
+                </ActionForm>
+            </Show>
+        </div>
+    }
+}
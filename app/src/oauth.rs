@@ -0,0 +1,156 @@
+//! Single sign-on for other applications: this app as an OAuth 2.0
+//! authorization server and OpenID Connect provider.
+//!
+//! Registered clients send people to the authorization endpoint, which
+//! asks them to sign in and to consent on [`ConsentPage`], and redeem the
+//! code for signed JWTs carrying the `User`'s identity and roles.
+
+use leptos::prelude::*;
+use leptos_router::hooks::use_params_map;
+use serde::{Deserialize, Serialize};
+
+use crate::{i18n::tr, t};
+
+#[cfg(feature = "ssr")]
+mod authorization_server;
+#[cfg(feature = "ssr")]
+mod keys;
+
+#[cfg(feature = "ssr")]
+pub use authorization_server::{
+    router, AuthorizationServer, OAuthClient, OAuthConsent, OAuthGrant,
+};
+#[cfg(feature = "ssr")]
+pub use keys::TokenKeys;
+
+/// Where the endpoints are mounted, and the path of the issuer.
+pub const OAUTH_PATH: &str = "/oauth";
+
+/// The scopes clients may ask for; others are ignored.
+pub const SCOPES: [&str; 5] = ["openid", "profile", "email", "roles", "offline_access"];
+
+/// An authorization waiting for the user's consent.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsentRequest {
+    pub client: String,
+    /// Login of the signed-in user, who consents for their own account.
+    pub login: String,
+    pub scopes: Vec<String>,
+    /// Whether the user granted these scopes before, having been sent to
+    /// sign in first; the page then answers by itself.
+    pub consented: bool,
+}
+
+#[server]
+pub async fn get_consent_request(request: String) -> Result<ConsentRequest, ServerFnError> {
+    use crate::{
+        state::{app_state, current_user},
+        tenant::current_tenant,
+    };
+
+    let user = current_user()?;
+    let state = app_state()?;
+    state
+        .oauth
+        .pending(&current_tenant(), &request, &user, state.clock.now())
+        .ok_or_else(|| ServerFnError::new("The sign-in has expired"))
+}
+
+/// Answers a consent request and sends the browser back to the client.
+#[server]
+pub async fn decide_consent(request: String, allow: bool) -> Result<(), ServerFnError> {
+    use crate::{
+        state::{app_state, current_user, forbid_impersonation},
+        tenant::current_tenant,
+    };
+
+    forbid_impersonation()?;
+    let user = current_user()?;
+    let state = app_state()?;
+    let redirect = state
+        .oauth
+        .decide(
+            &current_tenant(),
+            &request,
+            user.unid,
+            allow,
+            state.clock.now(),
+        )
+        .ok_or_else(|| ServerFnError::new("The sign-in has expired"))?;
+    leptos_axum::redirect(&redirect);
+    Ok(())
+}
+
+/// Asks the signed-in user whether a client may act on their behalf.
+#[component]
+pub fn ConsentPage() -> impl IntoView {
+    let params = use_params_map();
+    let request = move || params.with(|params| params.get("request").unwrap_or_default());
+    let consent = Resource::new(request, get_consent_request);
+    let decide = ServerAction::<DecideConsent>::new();
+    Effect::new(move || {
+        if consent
+            .get()
+            .is_some_and(|consent| consent.is_ok_and(|consent| consent.consented))
+        {
+            decide.dispatch(DecideConsent {
+                request: request(),
+                allow: true,
+            });
+        }
+    });
+
+    view! {
+        <div class="container mt-5" style="max-width: 28rem">
+            <Transition>
+                {move || {
+                    consent
+                        .get()
+                        .map(|consent| match consent {
+                            Ok(consent) => {
+                                view! {
+                                    <h1 class="h4 mb-3">
+                                        {t!("oauth-consent-title", client = consent.client.clone())}
+                                    </h1>
+                                    <p>{t!("oauth-consent-account", login = consent.login)}</p>
+                                    <ul class="list-group mb-3">
+                                        {consent
+                                            .scopes
+                                            .into_iter()
+                                            .map(|scope| {
+                                                let id = format!("oauth-scope-{scope}");
+                                                view! {
+                                                    <li class="list-group-item">{move || tr(&id)}</li>
+                                                }
+                                            })
+                                            .collect_view()}
+                                    </ul>
+                                    <div class="d-flex gap-2">
+                                        <ActionForm action=decide>
+                                            <input type="hidden" name="request" value=request />
+                                            <input type="hidden" name="allow" value="true" />
+                                            <button type="submit" class="btn btn-primary">
+                                                {t!("oauth-consent-allow")}
+                                            </button>
+                                        </ActionForm>
+                                        <ActionForm action=decide>
+                                            <input type="hidden" name="request" value=request />
+                                            <input type="hidden" name="allow" value="false" />
+                                            <button type="submit" class="btn btn-outline-secondary">
+                                                {t!("oauth-consent-deny")}
+                                            </button>
+                                        </ActionForm>
+                                    </div>
+                                }
+                                    .into_any()
+                            }
+                            Err(err) => {
+                                view! { <div class="alert alert-danger">{err.to_string()}</div> }
+                                    .into_any()
+                            }
+                        })
+                }}
+            </Transition>
+        </div>
+    }
+}
@@ -0,0 +1,889 @@
+//! The server side of single sign-on: the authorization, token, userinfo
+//! and key set endpoints, and the requests, codes, refresh tokens and
+//! consents behind them.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::{Arc, RwLock},
+};
+
+use axum::{
+    extract::{Query, State},
+    http::{
+        header::{AUTHORIZATION, CACHE_CONTROL, HOST, LOCATION, WWW_AUTHENTICATE},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Extension, Form, Json, Router,
+};
+use data_encoding::{BASE64, BASE64URL_NOPAD, HEXLOWER};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value as JsonValue};
+use sha2::{Digest, Sha256};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use super::{keys::TokenKeys, ConsentRequest, OAUTH_PATH, SCOPES};
+use crate::{
+    session::CurrentSession,
+    state::{AppState, TenantUsers},
+    tenant::Tenant,
+    User, UserStatus,
+};
+
+/// How long the user has to sign in and consent.
+const REQUEST_LIFETIME: Duration = Duration::minutes(10);
+/// How long the client has to redeem an authorization code.
+const CODE_LIFETIME: Duration = Duration::minutes(1);
+const ACCESS_LIFETIME: Duration = Duration::minutes(15);
+const REFRESH_LIFETIME: Duration = Duration::days(30);
+/// The JWT `typ` of access tokens (RFC 9068), so ID tokens cannot stand in
+/// for them.
+const ACCESS_TOKEN_TYPE: &str = "at+jwt";
+const ID_TOKEN_TYPE: &str = "JWT";
+
+/// An application people sign in to with their account here.
+#[derive(Clone, Debug, Deserialize)]
+pub struct OAuthClient {
+    /// The `client_id`.
+    pub id: String,
+    /// Shown on the consent screen.
+    pub name: String,
+    /// Tenant schema, `None` for the default site.
+    #[serde(default)]
+    pub schema: Option<String>,
+    /// `None` for public clients such as single-page apps, which prove
+    /// themselves with PKCE instead. Kept as its SHA-256 hash.
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Where the browser may be sent back to, compared exactly.
+    pub redirect_uris: Vec<String>,
+}
+
+impl OAuthClient {
+    fn serves(&self, tenant: &Tenant) -> bool {
+        self.schema == tenant.schema
+    }
+}
+
+fn secret_hash(secret: &str) -> String {
+    HEXLOWER.encode(&Sha256::digest(secret.as_bytes()))
+}
+
+/// An authorization waiting for the user's consent.
+#[derive(Clone, Debug)]
+struct AuthorizationRequest {
+    tenant: Tenant,
+    client: String,
+    redirect_uri: String,
+    scopes: Vec<String>,
+    state: Option<String>,
+    nonce: Option<String>,
+    challenge: Option<String>,
+    /// Asks for consent even if it was given before (`prompt=consent`).
+    ask_consent: bool,
+    started: OffsetDateTime,
+}
+
+/// What an authorization code or refresh token stands for.
+#[derive(Clone, Debug)]
+struct Grant {
+    tenant: Tenant,
+    client: String,
+    user: Uuid,
+    scopes: Vec<String>,
+    /// Shared by the code and every refresh token rotated from it;
+    /// replaying a used refresh token revokes them all.
+    family: Uuid,
+    issued: OffsetDateTime,
+}
+
+impl Grant {
+    fn is_for(&self, client: &OAuthClient) -> bool {
+        self.client == client.id && self.tenant.schema == client.schema
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Code {
+    grant: Grant,
+    redirect_uri: String,
+    nonce: Option<String>,
+    challenge: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+struct RefreshToken {
+    grant: Grant,
+    used: bool,
+}
+
+/// Scopes a user consented to a client having, sorted, for their personal
+/// data.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct OAuthConsent {
+    pub client: String,
+    pub scopes: Vec<String>,
+}
+
+/// A refresh token a client holds for a user, for their personal data.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct OAuthGrant {
+    pub client: String,
+    pub scopes: Vec<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub issued: OffsetDateTime,
+}
+
+/// Granted scopes by user and client id.
+type Consents = HashMap<(Uuid, String), BTreeSet<String>>;
+
+/// Registered clients, signing keys, and the grants handed out.
+#[derive(Clone, Debug, Default)]
+pub struct AuthorizationServer {
+    clients: Arc<Vec<OAuthClient>>,
+    pub keys: TokenKeys,
+    /// Pending authorizations by id.
+    requests: Arc<RwLock<HashMap<String, AuthorizationRequest>>>,
+    /// Authorization codes by hash.
+    codes: Arc<RwLock<HashMap<String, Code>>>,
+    /// Refresh tokens by hash.
+    refresh_tokens: Arc<RwLock<HashMap<String, RefreshToken>>>,
+    /// Scopes each user granted each client, by user and client id.
+    consents: Arc<RwLock<Consents>>,
+}
+
+impl AuthorizationServer {
+    /// Reads `OAUTH_CLIENTS`, a JSON list of [`OAuthClient`]s.
+    ///
+    /// # Panics
+    ///
+    /// If the variable is set but not such a list.
+    pub fn from_env() -> Self {
+        let mut server = AuthorizationServer::default();
+        if let Ok(clients) = std::env::var("OAUTH_CLIENTS") {
+            let clients: Vec<OAuthClient> =
+                serde_json::from_str(&clients).expect("OAUTH_CLIENTS is a JSON list of clients");
+            for client in clients {
+                server = server.with_client(client);
+            }
+        }
+        server
+    }
+
+    pub fn with_client(self, mut client: OAuthClient) -> Self {
+        client.secret = client.secret.as_deref().map(secret_hash);
+        let mut clients = Arc::unwrap_or_clone(self.clients);
+        clients.push(client);
+        AuthorizationServer {
+            clients: Arc::new(clients),
+            ..self
+        }
+    }
+
+    fn client(&self, tenant: &Tenant, id: &str) -> Option<OAuthClient> {
+        self.clients
+            .iter()
+            .find(|client| client.id == id && client.serves(tenant))
+            .cloned()
+    }
+
+    /// What `user` is asked to consent to by a pending authorization.
+    pub fn pending(
+        &self,
+        tenant: &Tenant,
+        request: &str,
+        user: &User,
+        now: OffsetDateTime,
+    ) -> Option<ConsentRequest> {
+        let request = self.requests.read().unwrap().get(request).cloned()?;
+        if &request.tenant != tenant || now - request.started >= REQUEST_LIFETIME {
+            return None;
+        }
+        let client = self.client(tenant, &request.client)?;
+        Some(ConsentRequest {
+            client: client.name,
+            login: user.login.clone(),
+            consented: !request.ask_consent
+                && self.has_consent(user.unid, &client.id, &request.scopes),
+            scopes: request.scopes,
+        })
+    }
+
+    /// Ends a pending authorization with the user's answer, returning where
+    /// to send the browser.
+    pub fn decide(
+        &self,
+        tenant: &Tenant,
+        request: &str,
+        user: Uuid,
+        allow: bool,
+        now: OffsetDateTime,
+    ) -> Option<String> {
+        let request = self
+            .requests
+            .write()
+            .unwrap()
+            .remove(request)
+            .filter(|request| {
+                &request.tenant == tenant && now - request.started < REQUEST_LIFETIME
+            })?;
+        if !allow {
+            return Some(error_redirect(&request, "access_denied"));
+        }
+        self.consents
+            .write()
+            .unwrap()
+            .entry((user, request.client.clone()))
+            .or_default()
+            .extend(request.scopes.iter().cloned());
+        Some(self.issue_code(request, user, now))
+    }
+
+    fn has_consent(&self, user: Uuid, client: &str, scopes: &[String]) -> bool {
+        self.consents
+            .read()
+            .unwrap()
+            .get(&(user, client.to_string()))
+            .is_some_and(|granted| scopes.iter().all(|scope| granted.contains(scope)))
+    }
+
+    /// Hands out a code for `request` and returns the redirect carrying it.
+    fn issue_code(&self, request: AuthorizationRequest, user: Uuid, now: OffsetDateTime) -> String {
+        let code = random_token();
+        let mut codes = self.codes.write().unwrap();
+        codes.retain(|_, code| now - code.grant.issued < CODE_LIFETIME);
+        codes.insert(
+            secret_hash(&code),
+            Code {
+                grant: Grant {
+                    tenant: request.tenant.clone(),
+                    client: request.client.clone(),
+                    user,
+                    scopes: request.scopes.clone(),
+                    family: Uuid::new_v4(),
+                    issued: now,
+                },
+                redirect_uri: request.redirect_uri.clone(),
+                nonce: request.nonce.clone(),
+                challenge: request.challenge.clone(),
+            },
+        );
+        redirect_with(&request, [("code", code.as_str())])
+    }
+
+    fn issue_refresh_token(&self, grant: Grant) -> String {
+        let token = random_token();
+        let mut tokens = self.refresh_tokens.write().unwrap();
+        let now = grant.issued;
+        tokens.retain(|_, token| now - token.grant.issued < REFRESH_LIFETIME);
+        tokens.insert(secret_hash(&token), RefreshToken { grant, used: false });
+        token
+    }
+
+    /// Tokens for `user` under `grant`: an access token, an ID token if
+    /// `openid` was granted and a refresh token if `offline_access` was.
+    fn issue_tokens(
+        &self,
+        issuer: &str,
+        user: &User,
+        grant: Grant,
+        nonce: Option<String>,
+        now: OffsetDateTime,
+    ) -> TokenResponse {
+        let expires = now + ACCESS_LIFETIME;
+        let has = |scope: &str| grant.scopes.iter().any(|granted| granted == scope);
+        let scope = grant.scopes.join(" ");
+
+        let access_token = self.keys.sign(
+            ACCESS_TOKEN_TYPE,
+            &AccessClaims {
+                iss: issuer.to_string(),
+                sub: user.unid,
+                aud: grant.client.clone(),
+                client_id: grant.client.clone(),
+                exp: expires.unix_timestamp(),
+                iat: now.unix_timestamp(),
+                jti: Uuid::new_v4(),
+                scope: scope.clone(),
+                roles: has("roles").then(|| sorted_roles(user)),
+            },
+            now,
+        );
+        let id_token = has("openid").then(|| {
+            let mut claims = user_claims(user, &grant.scopes);
+            claims.insert("iss".into(), json!(issuer));
+            claims.insert("aud".into(), json!(grant.client));
+            claims.insert("exp".into(), json!(expires.unix_timestamp()));
+            claims.insert("iat".into(), json!(now.unix_timestamp()));
+            if let Some(nonce) = nonce {
+                claims.insert("nonce".into(), json!(nonce));
+            }
+            self.keys.sign(ID_TOKEN_TYPE, &claims, now)
+        });
+        let refresh_token = has("offline_access").then(|| self.issue_refresh_token(grant));
+
+        TokenResponse {
+            access_token,
+            token_type: "Bearer",
+            expires_in: ACCESS_LIFETIME.whole_seconds(),
+            scope,
+            id_token,
+            refresh_token,
+        }
+    }
+
+    /// What `user` consented to, by client id.
+    pub fn consents(&self, user: Uuid) -> Vec<OAuthConsent> {
+        let mut consents: Vec<OAuthConsent> = self
+            .consents
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|((granted_by, _), _)| *granted_by == user)
+            .map(|((_, client), scopes)| OAuthConsent {
+                client: client.clone(),
+                scopes: scopes.iter().cloned().collect(),
+            })
+            .collect();
+        consents.sort_by(|a, b| a.client.cmp(&b.client));
+        consents
+    }
+
+    /// The refresh tokens of `user` still usable at `now`, oldest first.
+    pub fn grants(&self, user: Uuid, now: OffsetDateTime) -> Vec<OAuthGrant> {
+        let mut grants: Vec<OAuthGrant> = self
+            .refresh_tokens
+            .read()
+            .unwrap()
+            .values()
+            .filter(|token| {
+                token.grant.user == user
+                    && !token.used
+                    && now - token.grant.issued < REFRESH_LIFETIME
+            })
+            .map(|token| OAuthGrant {
+                client: token.grant.client.clone(),
+                scopes: token.grant.scopes.clone(),
+                issued: token.grant.issued,
+            })
+            .collect();
+        grants.sort_by_key(|grant| grant.issued);
+        grants
+    }
+
+    /// Drops everything `user` granted: consents, codes and refresh tokens.
+    pub fn revoke_all(&self, user: Uuid) {
+        self.consents
+            .write()
+            .unwrap()
+            .retain(|(granted_by, _), _| *granted_by != user);
+        self.codes
+            .write()
+            .unwrap()
+            .retain(|_, code| code.grant.user != user);
+        self.refresh_tokens
+            .write()
+            .unwrap()
+            .retain(|_, token| token.grant.user != user);
+    }
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes).expect("system RNG is available");
+    BASE64URL_NOPAD.encode(&bytes)
+}
+
+/// `request`'s redirect URI with `params` and the client's `state` added.
+fn redirect_with<'a>(
+    request: &'a AuthorizationRequest,
+    params: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> String {
+    let Ok(mut url) = Url::parse(&request.redirect_uri) else {
+        return request.redirect_uri.clone();
+    };
+    url.query_pairs_mut()
+        .extend_pairs(params)
+        .extend_pairs(request.state.as_deref().map(|state| ("state", state)));
+    url.into()
+}
+
+fn error_redirect(request: &AuthorizationRequest, error: &str) -> String {
+    redirect_with(request, [("error", error)])
+}
+
+/// The issuer URL for the host the request was made to, which keeps each
+/// tenant's tokens apart.
+fn issuer(headers: &HeaderMap) -> String {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    let scheme = header("x-forwarded-proto").unwrap_or("http");
+    let host = header(HOST.as_str()).unwrap_or("localhost");
+    format!("{scheme}://{host}{OAUTH_PATH}")
+}
+
+/// The endpoints, to be nested at [`OAUTH_PATH`].
+pub fn router<S>(state: AppState) -> Router<S> {
+    Router::new()
+        .route("/.well-known/openid-configuration", get(discovery))
+        .route("/authorize", get(authorize))
+        .route("/token", post(token))
+        .route("/userinfo", get(userinfo).post(userinfo))
+        .route("/jwks", get(jwks))
+        .with_state(state)
+}
+
+async fn discovery(headers: HeaderMap) -> Json<JsonValue> {
+    let issuer = issuer(&headers);
+    Json(json!({
+        "issuer": issuer,
+        "authorization_endpoint": format!("{issuer}/authorize"),
+        "token_endpoint": format!("{issuer}/token"),
+        "userinfo_endpoint": format!("{issuer}/userinfo"),
+        "jwks_uri": format!("{issuer}/jwks"),
+        "scopes_supported": SCOPES,
+        "response_types_supported": ["code"],
+        "grant_types_supported": ["authorization_code", "refresh_token"],
+        "subject_types_supported": ["public"],
+        "id_token_signing_alg_values_supported": ["RS256"],
+        "token_endpoint_auth_methods_supported": ["client_secret_basic", "client_secret_post", "none"],
+        "code_challenge_methods_supported": ["S256"],
+        "claims_supported": [
+            "sub", "name", "given_name", "family_name", "preferred_username", "locale", "email", "roles",
+        ],
+    }))
+}
+
+async fn jwks(State(state): State<AppState>) -> Response {
+    let mut response = Json(state.oauth.keys.jwks(state.clock.now())).into_response();
+    response.headers_mut().insert(
+        CACHE_CONTROL,
+        HeaderValue::from_static("public, max-age=3600"),
+    );
+    response
+}
+
+#[derive(Debug, Deserialize)]
+struct AuthorizeQuery {
+    response_type: Option<String>,
+    client_id: Option<String>,
+    redirect_uri: Option<String>,
+    scope: Option<String>,
+    state: Option<String>,
+    nonce: Option<String>,
+    code_challenge: Option<String>,
+    code_challenge_method: Option<String>,
+    prompt: Option<String>,
+}
+
+fn see_other(to: &str) -> Response {
+    match HeaderValue::from_str(to) {
+        Ok(to) => (StatusCode::SEE_OTHER, [(LOCATION, to)]).into_response(),
+        Err(_) => StatusCode::BAD_REQUEST.into_response(),
+    }
+}
+
+/// Starts an authorization: sends the browser back to the client if the
+/// user consented before, else to the consent screen, signing in first if
+/// need be.
+async fn authorize(
+    State(state): State<AppState>,
+    Extension(tenant): Extension<Tenant>,
+    session: Option<Extension<CurrentSession>>,
+    Query(query): Query<AuthorizeQuery>,
+) -> Response {
+    let now = state.clock.now();
+    let Some(client) = query
+        .client_id
+        .as_deref()
+        .and_then(|id| state.oauth.client(&tenant, id))
+    else {
+        return (StatusCode::BAD_REQUEST, "Unknown client").into_response();
+    };
+    // Without a registered redirect URI, errors cannot go back to the
+    // client without making this an open redirect.
+    let redirect_uri = match (query.redirect_uri, client.redirect_uris.as_slice()) {
+        (Some(uri), registered) if registered.contains(&uri) => uri,
+        (None, [only]) => only.clone(),
+        _ => return (StatusCode::BAD_REQUEST, "Unregistered redirect URI").into_response(),
+    };
+    let request = AuthorizationRequest {
+        tenant: tenant.clone(),
+        client: client.id.clone(),
+        redirect_uri,
+        scopes: query
+            .scope
+            .unwrap_or_default()
+            .split(' ')
+            .filter(|scope| SCOPES.contains(scope))
+            .map(str::to_string)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect(),
+        state: query.state,
+        nonce: query.nonce,
+        challenge: query.code_challenge,
+        ask_consent: false,
+        started: now,
+    };
+
+    if query.response_type.as_deref() != Some("code") {
+        return see_other(&error_redirect(&request, "unsupported_response_type"));
+    }
+    if request.scopes.is_empty() {
+        return see_other(&error_redirect(&request, "invalid_scope"));
+    }
+    let pkce_ok = match (&request.challenge, query.code_challenge_method.as_deref()) {
+        (Some(_), Some("S256")) => true,
+        (Some(_), _) => false,
+        (None, _) => client.secret.is_some(),
+    };
+    if !pkce_ok {
+        return see_other(&error_redirect(&request, "invalid_request"));
+    }
+    // Signing in to other applications is for users themselves.
+    if session
+        .as_ref()
+        .is_some_and(|Extension(session)| session.impersonator.is_some())
+    {
+        return see_other(&error_redirect(&request, "access_denied"));
+    }
+
+    let prompt = query.prompt.unwrap_or_default();
+    let prompts: Vec<&str> = prompt.split(' ').collect();
+    let user = session
+        .and_then(|Extension(session)| active_user(&state.users.scoped(&tenant), session.user))
+        .filter(|_| !prompts.contains(&"login"));
+    let consented = user.as_ref().is_some_and(|user| {
+        state
+            .oauth
+            .has_consent(user.unid, &client.id, &request.scopes)
+    });
+    match &user {
+        Some(user) if consented && !prompts.contains(&"consent") => {
+            return see_other(&state.oauth.issue_code(request, user.unid, now));
+        }
+        Some(_) if prompts.contains(&"none") => {
+            return see_other(&error_redirect(&request, "consent_required"));
+        }
+        None if prompts.contains(&"none") => {
+            return see_other(&error_redirect(&request, "login_required"));
+        }
+        _ => {}
+    }
+
+    let id = random_token();
+    let mut requests = state.oauth.requests.write().unwrap();
+    requests.retain(|_, request| now - request.started < REQUEST_LIFETIME);
+    requests.insert(
+        id.clone(),
+        AuthorizationRequest {
+            ask_consent: prompts.contains(&"consent"),
+            ..request
+        },
+    );
+    let consent = format!("/consent/{id}");
+    match user {
+        Some(_) => see_other(&consent),
+        // The consent page is where signing in returns to.
+        None => see_other(&format!("/login?next={consent}")),
+    }
+}
+
+/// An error response of the token and userinfo endpoints (RFC 6749 5.2).
+#[derive(Debug)]
+struct OAuthError {
+    status: StatusCode,
+    error: &'static str,
+    description: &'static str,
+}
+
+impl OAuthError {
+    fn new(error: &'static str, description: &'static str) -> Self {
+        OAuthError {
+            status: StatusCode::BAD_REQUEST,
+            error,
+            description,
+        }
+    }
+
+    fn invalid_grant(description: &'static str) -> Self {
+        Self::new("invalid_grant", description)
+    }
+
+    fn invalid_client() -> Self {
+        OAuthError {
+            status: StatusCode::UNAUTHORIZED,
+            error: "invalid_client",
+            description: "unknown client or wrong secret",
+        }
+    }
+}
+
+impl IntoResponse for OAuthError {
+    fn into_response(self) -> Response {
+        let body = Json(json!({
+            "error": self.error,
+            "error_description": self.description,
+        }));
+        let mut response = (self.status, body).into_response();
+        let challenge = match self.status {
+            StatusCode::UNAUTHORIZED if self.error == "invalid_client" => {
+                Some(r#"Basic realm="oauth""#.to_string())
+            }
+            StatusCode::UNAUTHORIZED => Some(format!(r#"Bearer error="{}""#, self.error)),
+            _ => None,
+        };
+        if let Some(Ok(challenge)) = challenge.map(|value| HeaderValue::from_str(&value)) {
+            response.headers_mut().insert(WWW_AUTHENTICATE, challenge);
+        }
+        response
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenRequest {
+    grant_type: String,
+    code: Option<String>,
+    redirect_uri: Option<String>,
+    code_verifier: Option<String>,
+    refresh_token: Option<String>,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct TokenResponse {
+    access_token: String,
+    token_type: &'static str,
+    expires_in: i64,
+    scope: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refresh_token: Option<String>,
+}
+
+/// Claims of the access tokens, also what userinfo reads back.
+#[derive(Debug, Serialize, Deserialize)]
+struct AccessClaims {
+    iss: String,
+    sub: Uuid,
+    aud: String,
+    client_id: String,
+    exp: i64,
+    iat: i64,
+    jti: Uuid,
+    scope: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    roles: Option<Vec<String>>,
+}
+
+/// The client authenticating at the token endpoint, with HTTP basic
+/// authentication, in the form, or as a public client by id alone.
+fn authenticate_client(
+    state: &AppState,
+    tenant: &Tenant,
+    headers: &HeaderMap,
+    request: &TokenRequest,
+) -> Result<OAuthClient, OAuthError> {
+    let basic = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|value| BASE64.decode(value.trim().as_bytes()).ok())
+        .and_then(|value| String::from_utf8(value).ok());
+    let (id, secret) = match &basic {
+        Some(credentials) => {
+            let (id, secret) = credentials.split_once(':').unwrap_or((credentials, ""));
+            (Some(id), Some(secret))
+        }
+        None => (
+            request.client_id.as_deref(),
+            request.client_secret.as_deref(),
+        ),
+    };
+    let client = id
+        .and_then(|id| state.oauth.client(tenant, id))
+        .ok_or_else(OAuthError::invalid_client)?;
+    match (&client.secret, secret) {
+        (None, None) => Ok(client),
+        (Some(hash), Some(secret)) if *hash == secret_hash(secret) => Ok(client),
+        _ => Err(OAuthError::invalid_client()),
+    }
+}
+
+/// Redeems an authorization code or refresh token for tokens.
+async fn token(
+    State(state): State<AppState>,
+    Extension(tenant): Extension<Tenant>,
+    headers: HeaderMap,
+    Form(request): Form<TokenRequest>,
+) -> Response {
+    let now = state.clock.now();
+    let result = authenticate_client(&state, &tenant, &headers, &request).and_then(|client| {
+        let (grant, nonce) = match request.grant_type.as_str() {
+            "authorization_code" => redeem_code(&state, &client, &request, now)?,
+            "refresh_token" => (redeem_refresh_token(&state, &client, &request, now)?, None),
+            _ => {
+                return Err(OAuthError::new(
+                    "unsupported_grant_type",
+                    "only authorization_code and refresh_token are supported",
+                ))
+            }
+        };
+        let user = active_user(&state.users.scoped(&tenant), grant.user)
+            .ok_or(OAuthError::invalid_grant("the account is not active"))?;
+        Ok(state
+            .oauth
+            .issue_tokens(&issuer(&headers), &user, grant, nonce, now))
+    });
+    match result {
+        Ok(tokens) => {
+            let mut response = Json(tokens).into_response();
+            response
+                .headers_mut()
+                .insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+            response
+        }
+        Err(err) => err.into_response(),
+    }
+}
+
+fn active_user(users: &TenantUsers, unid: Uuid) -> Option<User> {
+    users
+        .get(unid)
+        .filter(|user| user.status == UserStatus::Active)
+}
+
+fn redeem_code(
+    state: &AppState,
+    client: &OAuthClient,
+    request: &TokenRequest,
+    now: OffsetDateTime,
+) -> Result<(Grant, Option<String>), OAuthError> {
+    let code = request
+        .code
+        .as_deref()
+        .ok_or(OAuthError::new("invalid_request", "code is missing"))?;
+    let code = state
+        .oauth
+        .codes
+        .write()
+        .unwrap()
+        .remove(&secret_hash(code))
+        .filter(|code| code.grant.is_for(client) && now - code.grant.issued < CODE_LIFETIME)
+        .ok_or(OAuthError::invalid_grant("unknown or expired code"))?;
+    if request.redirect_uri.as_deref() != Some(&code.redirect_uri) {
+        return Err(OAuthError::invalid_grant("redirect_uri does not match"));
+    }
+    if let Some(challenge) = &code.challenge {
+        let verified = request.code_verifier.as_deref().is_some_and(|verifier| {
+            BASE64URL_NOPAD.encode(&Sha256::digest(verifier.as_bytes())) == *challenge
+        });
+        if !verified {
+            return Err(OAuthError::invalid_grant("code_verifier does not match"));
+        }
+    }
+    Ok((code.grant, code.nonce))
+}
+
+/// Swaps a refresh token for a new one. A token used twice was stolen by
+/// one of its users, so its whole family is revoked.
+fn redeem_refresh_token(
+    state: &AppState,
+    client: &OAuthClient,
+    request: &TokenRequest,
+    now: OffsetDateTime,
+) -> Result<Grant, OAuthError> {
+    let token = request.refresh_token.as_deref().ok_or(OAuthError::new(
+        "invalid_request",
+        "refresh_token is missing",
+    ))?;
+    let mut tokens = state.oauth.refresh_tokens.write().unwrap();
+    let token = tokens
+        .get_mut(&secret_hash(token))
+        .filter(|token| token.grant.is_for(client) && now - token.grant.issued < REFRESH_LIFETIME)
+        .ok_or(OAuthError::invalid_grant(
+            "unknown or expired refresh token",
+        ))?;
+    if token.used {
+        let family = token.grant.family;
+        tokens.retain(|_, token| token.grant.family != family);
+        return Err(OAuthError::invalid_grant("refresh token was already used"));
+    }
+    token.used = true;
+    Ok(Grant {
+        issued: now,
+        ..token.grant.clone()
+    })
+}
+
+/// The claims about `user` the granted `scopes` allow.
+fn user_claims(user: &User, scopes: &[String]) -> Map<String, JsonValue> {
+    let has = |scope: &str| scopes.iter().any(|granted| granted == scope);
+    let mut claims = Map::new();
+    claims.insert("sub".into(), json!(user.unid));
+    if has("profile") {
+        let name = [&user.first_name, &user.last_name]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" ");
+        claims.insert("name".into(), json!(name));
+        claims.insert("given_name".into(), json!(user.first_name));
+        claims.insert("family_name".into(), json!(user.last_name));
+        claims.insert("preferred_username".into(), json!(user.login));
+        claims.insert("locale".into(), json!(user.language));
+    }
+    if has("email") {
+        claims.insert("email".into(), json!(user.login));
+    }
+    if has("roles") {
+        claims.insert("roles".into(), json!(sorted_roles(user)));
+    }
+    claims.retain(|_, value| !value.is_null());
+    claims
+}
+
+fn sorted_roles(user: &User) -> Vec<String> {
+    let mut roles: Vec<String> = user.roles.iter().cloned().collect();
+    roles.sort();
+    roles
+}
+
+/// Claims about the user an access token was issued for.
+async fn userinfo(
+    State(state): State<AppState>,
+    Extension(tenant): Extension<Tenant>,
+    headers: HeaderMap,
+) -> Response {
+    let invalid = OAuthError {
+        status: StatusCode::UNAUTHORIZED,
+        error: "invalid_token",
+        description: "missing, expired or revoked access token",
+    };
+    let claims = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(|token| {
+            state.oauth.keys.verify::<AccessClaims>(
+                token,
+                ACCESS_TOKEN_TYPE,
+                &issuer(&headers),
+                state.clock.now(),
+            )
+        });
+    let Some(claims) = claims else {
+        return invalid.into_response();
+    };
+    let Some(user) = active_user(&state.users.scoped(&tenant), claims.sub) else {
+        return invalid.into_response();
+    };
+    let scopes: Vec<String> = claims.scope.split(' ').map(str::to_string).collect();
+    Json(user_claims(&user, &scopes)).into_response()
+}
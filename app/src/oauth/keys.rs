@@ -0,0 +1,188 @@
+//! The RSA keys tokens are signed with, and their rotation.
+//!
+//! The newest key signs. Once it is [`KEY_LIFETIME`] old the next token is
+//! signed with a fresh one, and the old key stays in the key set for
+//! [`RETIRED_LIFETIME`] so the tokens it signed can still be checked.
+
+use std::{
+    fmt,
+    sync::{Arc, RwLock},
+};
+
+use data_encoding::BASE64URL_NOPAD;
+use jsonwebtoken::{
+    decode, decode_header, encode,
+    jwk::{
+        AlgorithmParameters, CommonParameters, Jwk, JwkSet, KeyAlgorithm, PublicKeyUse,
+        RSAKeyParameters, RSAKeyType,
+    },
+    Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use rsa::{pkcs1::EncodeRsaPrivateKey, rand_core::OsRng, traits::PublicKeyParts, RsaPrivateKey};
+use serde::{de::DeserializeOwned, Serialize};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+/// How long a key signs before it is replaced.
+pub const KEY_LIFETIME: Duration = Duration::days(30);
+/// How long a replaced key is still published; longer than any token it
+/// signed is valid, plus the time clients cache the key set.
+pub const RETIRED_LIFETIME: Duration = Duration::days(1);
+
+const KEY_BITS: usize = 2048;
+
+#[derive(Clone)]
+struct Key {
+    jwk: Jwk,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    created: OffsetDateTime,
+    retired: Option<OffsetDateTime>,
+}
+
+impl Key {
+    fn generate(now: OffsetDateTime) -> Self {
+        let private = RsaPrivateKey::new(&mut OsRng, KEY_BITS).expect("RSA key generation works");
+        let der = private.to_pkcs1_der().expect("RSA keys encode as PKCS#1");
+        let n = BASE64URL_NOPAD.encode(&private.n().to_bytes_be());
+        let e = BASE64URL_NOPAD.encode(&private.e().to_bytes_be());
+        let decoding = DecodingKey::from_rsa_components(&n, &e).expect("the key was just encoded");
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(KeyAlgorithm::RS256),
+                key_id: Some(Uuid::new_v4().simple().to_string()),
+                ..Default::default()
+            },
+            algorithm: AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n,
+                e,
+            }),
+        };
+        Key {
+            jwk,
+            encoding: EncodingKey::from_rsa_der(der.as_bytes()),
+            decoding,
+            created: now,
+            retired: None,
+        }
+    }
+
+    fn kid(&self) -> &str {
+        self.jwk.common.key_id.as_deref().unwrap_or_default()
+    }
+
+    fn is_published(&self, now: OffsetDateTime) -> bool {
+        self.retired
+            .is_none_or(|retired| now - retired < RETIRED_LIFETIME)
+    }
+}
+
+/// The signing keys, newest last. None exist until the first token is
+/// signed.
+#[derive(Clone, Default)]
+pub struct TokenKeys(Arc<RwLock<Vec<Arc<Key>>>>);
+
+impl TokenKeys {
+    /// Replaces the signing key with a fresh one and returns its key id.
+    pub fn rotate(&self, now: OffsetDateTime) -> String {
+        let mut keys = self.0.write().unwrap();
+        Self::add_key(&mut keys, now).kid().to_string()
+    }
+
+    fn add_key(keys: &mut Vec<Arc<Key>>, now: OffsetDateTime) -> Arc<Key> {
+        if let Some(current) = keys.pop() {
+            let mut current = Arc::unwrap_or_clone(current);
+            current.retired = Some(now);
+            keys.push(Arc::new(current));
+        }
+        keys.retain(|key| key.is_published(now));
+        let key = Arc::new(Key::generate(now));
+        keys.push(key.clone());
+        key
+    }
+
+    /// The key to sign with, rotating it first if it is due.
+    fn current(&self, now: OffsetDateTime) -> Arc<Key> {
+        let fresh = |key: &Arc<Key>| now - key.created < KEY_LIFETIME;
+        if let Some(key) = self.0.read().unwrap().last().filter(|key| fresh(key)) {
+            return key.clone();
+        }
+        let mut keys = self.0.write().unwrap();
+        match keys.last().filter(|key| fresh(key)) {
+            // Rotated while waiting for the lock.
+            Some(key) => key.clone(),
+            None => Self::add_key(&mut keys, now),
+        }
+    }
+
+    /// The public keys tokens may be signed with.
+    pub fn jwks(&self, now: OffsetDateTime) -> JwkSet {
+        // Publishes a key before anything is signed with it.
+        self.current(now);
+        JwkSet {
+            keys: self
+                .0
+                .read()
+                .unwrap()
+                .iter()
+                .filter(|key| key.is_published(now))
+                .map(|key| key.jwk.clone())
+                .collect(),
+        }
+    }
+
+    /// Signs `claims` as a JWT of type `typ`.
+    pub fn sign(&self, typ: &str, claims: &impl Serialize, now: OffsetDateTime) -> String {
+        let key = self.current(now);
+        let mut header = Header::new(Algorithm::RS256);
+        header.typ = Some(typ.to_string());
+        header.kid = Some(key.kid().to_string());
+        encode(&header, claims, &key.encoding).expect("claims serialize to JSON")
+    }
+
+    /// The claims of a token of type `typ` this server signed for
+    /// `issuer`, provided it has not expired.
+    pub fn verify<T: DeserializeOwned>(
+        &self,
+        token: &str,
+        typ: &str,
+        issuer: &str,
+        now: OffsetDateTime,
+    ) -> Option<T> {
+        let header = decode_header(token).ok()?;
+        if header.alg != Algorithm::RS256 || header.typ.as_deref() != Some(typ) {
+            return None;
+        }
+        let key = self
+            .0
+            .read()
+            .unwrap()
+            .iter()
+            .find(|key| Some(key.kid()) == header.kid.as_deref() && key.is_published(now))
+            .cloned()?;
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_issuer(&[issuer]);
+        validation.validate_aud = false;
+        // Expiry is checked against the app's clock below.
+        validation.validate_exp = false;
+        let claims = decode::<serde_json::Value>(token, &key.decoding, &validation)
+            .ok()?
+            .claims;
+        let expires = claims.get("exp")?.as_i64()?;
+        if expires < now.unix_timestamp() {
+            return None;
+        }
+        serde_json::from_value(claims).ok()
+    }
+}
+
+impl fmt::Debug for TokenKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keys = self.0.read().unwrap();
+        f.debug_list()
+            .entries(keys.iter().map(|key| key.kid()))
+            .finish()
+    }
+}
@@ -0,0 +1,95 @@
+//! Signing in with an external OpenID Connect identity provider, next to
+//! the login and password.
+//!
+//! Providers are configured per tenant. Someone signing in for the first
+//! time is linked to the account whose login is their verified email, or
+//! gets a new account if the provider is set to provision them. The
+//! provider's groups can be mapped onto roles. Accounts with a second
+//! factor are asked for it as after a password, unless the browser is
+//! trusted, and administrators without one are never linked by email.
+
+use leptos::prelude::*;
+use leptos_router::hooks::use_query_map;
+use serde::{Deserialize, Serialize};
+
+use crate::t;
+
+#[cfg(feature = "ssr")]
+mod relying_party;
+
+#[cfg(feature = "ssr")]
+pub use relying_party::{router, IdClaims, OidcError, OidcLogins, OidcProvider};
+
+/// Where the sign-in routes are mounted.
+pub const OIDC_PATH: &str = "/auth/oidc";
+
+/// Query parameter the login page is opened with after a failed sign-in.
+const FAILED_PARAM: &str = "oidc_failed";
+
+/// An identity provider as offered on the login page.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProviderLink {
+    pub id: String,
+    pub name: String,
+}
+
+impl ProviderLink {
+    pub fn url(&self) -> String {
+        format!("{OIDC_PATH}/{}/login", self.id)
+    }
+}
+
+/// The identity providers of the current tenant.
+#[server]
+pub async fn list_identity_providers() -> Result<Vec<ProviderLink>, ServerFnError> {
+    use crate::{state::app_state, tenant::current_tenant};
+
+    let tenant = current_tenant();
+    Ok(app_state()?
+        .oidc
+        .for_tenant(&tenant)
+        .map(|provider| ProviderLink {
+            id: provider.id.clone(),
+            name: provider.name.clone(),
+        })
+        .collect())
+}
+
+/// A button per identity provider, and the error of a failed sign-in with
+/// one. Renders nothing if the tenant has none.
+#[component]
+pub fn IdentityProviderLogin() -> impl IntoView {
+    let query = use_query_map();
+    let providers = Resource::new(|| (), |_| list_identity_providers());
+    let failed = move || query.with(|query| query.get(FAILED_PARAM).is_some());
+
+    view! {
+        <Show when=failed>
+            <div class="alert alert-danger">{t!("login-oidc-failed")}</div>
+        </Show>
+        <Transition>
+            {move || {
+                let providers = providers.get().and_then(Result::ok).unwrap_or_default();
+                (!providers.is_empty())
+                    .then(|| {
+                        view! {
+                            <p class="text-center text-body-secondary my-3">{t!("login-or")}</p>
+                            <div class="d-grid gap-2">
+                                {providers
+                                    .into_iter()
+                                    .map(|provider| {
+                                        let url = provider.url();
+                                        view! {
+                                            <a class="btn btn-outline-secondary" href=url rel="external">
+                                                {t!("login-oidc-with", provider = provider.name)}
+                                            </a>
+                                        }
+                                    })
+                                    .collect_view()}
+                            </div>
+                        }
+                    })
+            }}
+        </Transition>
+    }
+}
@@ -0,0 +1,680 @@
+//! The server side of OpenID Connect sign-in: the authorization code flow
+//! with PKCE, ID token validation and matching the token to an account.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{
+        header::{LOCATION, SET_COOKIE},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Router,
+};
+use data_encoding::BASE64URL_NOPAD;
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use leptos::logging::warn;
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    login::SECOND_FACTOR_PARAM,
+    session::{cookie_value, session_cookie, start_session, ClientInfo},
+    state::{AppState, TenantRoles, TenantUsers},
+    tenant::Tenant,
+    trusted_device::TRUSTED_DEVICE_COOKIE,
+    two_factor::{TwoFactorStore, CHALLENGE_COOKIE},
+    User, UserStatus,
+};
+
+use super::FAILED_PARAM;
+
+/// Cookie tying a sign-in in progress to the browser that started it.
+const STATE_COOKIE: &str = "oidc_state";
+/// How long a browser has to come back from the provider.
+const LOGIN_LIFETIME: time::Duration = time::Duration::minutes(10);
+/// How long discovery documents and key sets are used before refetching.
+const CACHE_LIFETIME: Duration = Duration::from_secs(60 * 60);
+/// Clock skew tolerated when checking when a token expires.
+const LEEWAY: i64 = 60;
+
+/// An identity provider users of one tenant can sign in with.
+#[derive(Clone, Debug, Deserialize)]
+pub struct OidcProvider {
+    /// Names the provider in URLs; unique within the tenant.
+    pub id: String,
+    /// Shown on the sign-in button.
+    pub name: String,
+    /// Tenant schema, `None` for the default site.
+    #[serde(default)]
+    pub schema: Option<String>,
+    /// Issuer URL, where `/.well-known/openid-configuration` is found.
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// The callback URL registered with the provider, ending in
+    /// `/auth/oidc/{id}/callback`.
+    pub redirect_url: String,
+    #[serde(default = "default_scopes")]
+    pub scopes: Vec<String>,
+    /// Creates accounts for people signing in for the first time.
+    #[serde(default)]
+    pub provision: bool,
+    /// Claim listing the user's groups, e.g. `groups`. When set, the roles
+    /// are replaced by the mapped groups on every sign-in, once it is
+    /// complete.
+    #[serde(default)]
+    pub role_claim: Option<String>,
+    /// Group to role. Groups not listed map to the role of the same name;
+    /// groups naming no role of the tenant are ignored.
+    #[serde(default)]
+    pub role_map: HashMap<String, String>,
+}
+
+fn default_scopes() -> Vec<String> {
+    ["openid", "email", "profile"].map(str::to_string).into()
+}
+
+impl OidcProvider {
+    fn serves(&self, tenant: &Tenant) -> bool {
+        self.schema == tenant.schema
+    }
+
+    /// The roles the groups in `claims` map to.
+    fn roles(&self, claims: &IdClaims, catalog: &TenantRoles) -> Option<Vec<String>> {
+        let claim = claims.other.get(self.role_claim.as_ref()?);
+        let groups: Vec<&str> = match claim {
+            Some(JsonValue::String(group)) => vec![group],
+            Some(JsonValue::Array(groups)) => groups.iter().filter_map(JsonValue::as_str).collect(),
+            _ => Vec::new(),
+        };
+        Some(
+            groups
+                .into_iter()
+                .map(|group| self.role_map.get(group).map_or(group, String::as_str))
+                .filter_map(|role| catalog.find(role))
+                .collect(),
+        )
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum OidcError {
+    #[error("no such identity provider")]
+    UnknownProvider,
+    #[error("the sign-in expired or was started in another browser")]
+    UnknownLogin,
+    #[error("the identity provider refused: {0}")]
+    Refused(String),
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error(transparent)]
+    InvalidToken(#[from] jsonwebtoken::errors::Error),
+    #[error("the ID token is {0}")]
+    RejectedToken(&'static str),
+    #[error("the identity provider did not vouch for an email address")]
+    NoVerifiedEmail,
+    #[error("no account for {0}")]
+    NoAccount(String),
+    #[error("the login {0} is taken")]
+    LoginTaken(String),
+    #[error("the account is not active")]
+    AccountDisabled,
+    /// Linking by email alone would hand the provider an administrator
+    /// account nothing else protects.
+    #[error("the administrator account {0} has no second factor to link it with")]
+    UnprotectedAdmin(String),
+}
+
+/// The provider's discovery document, as far as it is needed.
+#[derive(Clone, Debug, Deserialize)]
+struct Metadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Clone, Debug)]
+struct PendingLogin {
+    tenant: Tenant,
+    provider: String,
+    nonce: String,
+    verifier: String,
+    started: OffsetDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// The claims of an ID token this module looks at.
+#[derive(Clone, Debug, Deserialize)]
+pub struct IdClaims {
+    pub sub: String,
+    pub exp: i64,
+    #[serde(default)]
+    pub nonce: Option<String>,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
+    #[serde(default)]
+    pub given_name: Option<String>,
+    #[serde(default)]
+    pub family_name: Option<String>,
+    #[serde(flatten)]
+    pub other: HashMap<String, JsonValue>,
+}
+
+/// Configured providers, what was fetched from them, and sign-ins in
+/// progress.
+#[derive(Clone, Debug, Default)]
+pub struct OidcLogins {
+    providers: Arc<Vec<OidcProvider>>,
+    http: reqwest::Client,
+    /// Discovery documents by issuer.
+    metadata: Arc<RwLock<HashMap<String, (Metadata, Instant)>>>,
+    /// Key sets by URL.
+    keys: Arc<RwLock<HashMap<String, (JwkSet, Instant)>>>,
+    /// Sign-ins waiting for the browser to return, by `state`.
+    pending: Arc<RwLock<HashMap<String, PendingLogin>>>,
+    /// Accounts linked to a provider's user, by issuer and subject.
+    identities: Arc<RwLock<HashMap<(String, String), Uuid>>>,
+}
+
+impl OidcLogins {
+    /// Reads `OIDC_PROVIDERS`, a JSON list of [`OidcProvider`]s.
+    ///
+    /// # Panics
+    ///
+    /// If the variable is set but not such a list.
+    pub fn from_env() -> Self {
+        let mut logins = OidcLogins::default();
+        if let Ok(providers) = std::env::var("OIDC_PROVIDERS") {
+            let providers: Vec<OidcProvider> = serde_json::from_str(&providers)
+                .expect("OIDC_PROVIDERS is a JSON list of providers");
+            for provider in providers {
+                logins = logins.with_provider(provider);
+            }
+        }
+        logins
+    }
+
+    pub fn with_provider(self, provider: OidcProvider) -> Self {
+        let mut providers = Arc::unwrap_or_clone(self.providers);
+        providers.push(provider);
+        OidcLogins {
+            providers: Arc::new(providers),
+            ..self
+        }
+    }
+
+    /// Links the provider's user `subject` at `issuer` to `unid`, which they
+    /// sign in to from then on.
+    pub fn link(&self, issuer: &str, subject: &str, unid: Uuid) {
+        self.identities
+            .write()
+            .unwrap()
+            .insert((issuer.to_string(), subject.to_string()), unid);
+    }
+
+    /// The issuers and subjects of the providers' users linked to `unid`.
+    pub fn identities(&self, unid: Uuid) -> Vec<(String, String)> {
+        let mut identities: Vec<_> = self
+            .identities
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, linked)| **linked == unid)
+            .map(|(identity, _)| identity.clone())
+            .collect();
+        identities.sort();
+        identities
+    }
+
+    /// Forgets which providers' users sign in to `unid`, as when the account
+    /// is erased.
+    pub fn unlink(&self, unid: Uuid) {
+        self.identities
+            .write()
+            .unwrap()
+            .retain(|_, linked| *linked != unid);
+    }
+
+    /// The providers users of `tenant` can sign in with.
+    pub fn for_tenant<'a>(&'a self, tenant: &'a Tenant) -> impl Iterator<Item = &'a OidcProvider> {
+        self.providers
+            .iter()
+            .filter(move |provider| provider.serves(tenant))
+    }
+
+    fn provider(&self, tenant: &Tenant, id: &str) -> Result<OidcProvider, OidcError> {
+        self.for_tenant(tenant)
+            .find(|provider| provider.id == id)
+            .cloned()
+            .ok_or(OidcError::UnknownProvider)
+    }
+
+    async fn metadata(&self, provider: &OidcProvider) -> Result<Metadata, OidcError> {
+        let cached = self.metadata.read().unwrap().get(&provider.issuer).cloned();
+        if let Some((metadata, fetched)) = cached {
+            if fetched.elapsed() < CACHE_LIFETIME {
+                return Ok(metadata);
+            }
+        }
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            provider.issuer.trim_end_matches('/')
+        );
+        let metadata: Metadata = self
+            .http
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        self.metadata
+            .write()
+            .unwrap()
+            .insert(provider.issuer.clone(), (metadata.clone(), Instant::now()));
+        Ok(metadata)
+    }
+
+    /// The key set at `url`, refetched when stale or when `refresh` is set
+    /// because a token names a key not in the cached set.
+    async fn keys(&self, url: &str, refresh: bool) -> Result<JwkSet, OidcError> {
+        let cached = self.keys.read().unwrap().get(url).cloned();
+        if let Some((keys, fetched)) = cached {
+            if !refresh && fetched.elapsed() < CACHE_LIFETIME {
+                return Ok(keys);
+            }
+        }
+        let keys: JwkSet = self
+            .http
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        self.keys
+            .write()
+            .unwrap()
+            .insert(url.to_string(), (keys.clone(), Instant::now()));
+        Ok(keys)
+    }
+
+    /// Starts signing in to `provider`: returns the URL to send the browser
+    /// to and the `state` it will come back with.
+    async fn start(
+        &self,
+        tenant: &Tenant,
+        provider: &str,
+        now: OffsetDateTime,
+    ) -> Result<(Url, String), OidcError> {
+        let provider = self.provider(tenant, provider)?;
+        let metadata = self.metadata(&provider).await?;
+        let (state, nonce, verifier) = (random_token(), random_token(), random_token());
+        let challenge = BASE64URL_NOPAD.encode(&Sha256::digest(verifier.as_bytes()));
+        let url = Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            [
+                ("response_type", "code"),
+                ("client_id", &provider.client_id),
+                ("redirect_uri", &provider.redirect_url),
+                ("scope", &provider.scopes.join(" ")),
+                ("state", &state),
+                ("nonce", &nonce),
+                ("code_challenge", &challenge),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|_| OidcError::Refused("invalid authorization endpoint".to_string()))?;
+
+        let mut pending = self.pending.write().unwrap();
+        pending.retain(|_, login| now - login.started < LOGIN_LIFETIME);
+        pending.insert(
+            state.clone(),
+            PendingLogin {
+                tenant: tenant.clone(),
+                provider: provider.id,
+                nonce,
+                verifier,
+                started: now,
+            },
+        );
+        Ok((url, state))
+    }
+
+    /// Redeems the `code` the browser came back with for a validated ID
+    /// token.
+    async fn finish(
+        &self,
+        tenant: &Tenant,
+        provider: &str,
+        state: &str,
+        code: &str,
+        now: OffsetDateTime,
+    ) -> Result<(OidcProvider, IdClaims), OidcError> {
+        let login = self
+            .pending
+            .write()
+            .unwrap()
+            .remove(state)
+            .filter(|login| {
+                &login.tenant == tenant
+                    && login.provider == provider
+                    && now - login.started < LOGIN_LIFETIME
+            })
+            .ok_or(OidcError::UnknownLogin)?;
+        let provider = self.provider(tenant, provider)?;
+        let metadata = self.metadata(&provider).await?;
+
+        let response = self
+            .http
+            .post(&metadata.token_endpoint)
+            .basic_auth(&provider.client_id, Some(&provider.client_secret))
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", &provider.redirect_url),
+                ("client_id", &provider.client_id),
+                ("code_verifier", &login.verifier),
+            ])
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(OidcError::Refused(response.text().await?));
+        }
+        let TokenResponse { id_token } = response.json().await?;
+
+        let claims = self.validate(&provider, &metadata, &id_token, now).await?;
+        if claims.nonce.as_deref() != Some(&login.nonce) {
+            return Err(OidcError::RejectedToken("for another sign-in"));
+        }
+        Ok((provider, claims))
+    }
+
+    /// Checks the token's signature against the provider's keys, and its
+    /// issuer, audience and expiry.
+    async fn validate(
+        &self,
+        provider: &OidcProvider,
+        metadata: &Metadata,
+        token: &str,
+        now: OffsetDateTime,
+    ) -> Result<IdClaims, OidcError> {
+        let header = decode_header(token)?;
+        // Keys are public, so a token "signed" with one as an HMAC secret
+        // proves nothing.
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            return Err(OidcError::RejectedToken("not signed with a public key"));
+        }
+        let kid = header
+            .kid
+            .ok_or(OidcError::RejectedToken("missing a key id"))?;
+        let mut keys = self.keys(&metadata.jwks_uri, false).await?;
+        if keys.find(&kid).is_none() {
+            // The provider may have rotated its keys since they were cached.
+            keys = self.keys(&metadata.jwks_uri, true).await?;
+        }
+        let key = keys
+            .find(&kid)
+            .ok_or(OidcError::RejectedToken("signed with an unknown key"))?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&provider.client_id]);
+        // Expiry is checked against the app's clock below.
+        validation.validate_exp = false;
+        validation.set_required_spec_claims(&["iss", "aud", "sub", "exp"]);
+        let claims = decode::<IdClaims>(token, &DecodingKey::from_jwk(key)?, &validation)?.claims;
+        if claims.exp + LEEWAY < now.unix_timestamp() {
+            return Err(OidcError::RejectedToken("expired"));
+        }
+        Ok(claims)
+    }
+
+    /// The account the provider's user signs in to: the one linked to them
+    /// before, else the one whose login is their verified email, else a new
+    /// one if the provider provisions accounts. Administrators are only
+    /// linked by email if they have a second factor, which the sign-in then
+    /// asks for. Returns the roles the provider maps the user to, to give
+    /// them once the sign-in is complete.
+    fn account(
+        &self,
+        users: &TenantUsers,
+        roles: &TenantRoles,
+        two_factor: &TwoFactorStore,
+        provider: &OidcProvider,
+        claims: &IdClaims,
+        now: OffsetDateTime,
+    ) -> Result<(User, Option<HashSet<String>>), OidcError> {
+        let identity = (provider.issuer.clone(), claims.sub.clone());
+        let linked = self
+            .identities
+            .read()
+            .unwrap()
+            .get(&identity)
+            .and_then(|unid| users.get(*unid));
+        let user = match linked {
+            Some(user) => user,
+            None => {
+                let email = claims
+                    .email
+                    .as_deref()
+                    .filter(|_| claims.email_verified)
+                    .ok_or(OidcError::NoVerifiedEmail)?;
+                match users.find_by_login(email) {
+                    Some(user)
+                        if users.is_admin(user.unid) && !two_factor.is_enabled(user.unid) =>
+                    {
+                        return Err(OidcError::UnprotectedAdmin(user.login));
+                    }
+                    Some(user) => user,
+                    None if provider.provision => {
+                        let user = User {
+                            unid: Uuid::new_v4(),
+                            created: now,
+                            first_name: claims.given_name.clone(),
+                            // Signs in through the provider only, until a
+                            // password is set.
+                            hash: String::new(),
+                            last_failed_login: None,
+                            last_login: None,
+                            last_password_change: now,
+                            last_name: claims.family_name.clone(),
+                            login: email.to_string(),
+                            avatar: None,
+                            roles: Default::default(),
+                            site_schema: None,
+                            status: UserStatus::Active,
+                            theme: Default::default(),
+                            language: None,
+                        };
+                        users
+                            .insert_all(vec![user.clone()])
+                            .map_err(OidcError::LoginTaken)?;
+                        user
+                    }
+                    None => return Err(OidcError::NoAccount(email.to_string())),
+                }
+            }
+        };
+        if user.status != UserStatus::Active {
+            return Err(OidcError::AccountDisabled);
+        }
+        self.link(&provider.issuer, &claims.sub, user.unid);
+
+        let user = users
+            .update(user.unid, |user| {
+                if user.first_name.is_none() {
+                    user.first_name = claims.given_name.clone();
+                }
+                if user.last_name.is_none() {
+                    user.last_name = claims.family_name.clone();
+                }
+            })
+            .unwrap_or(user);
+        let mapped = provider
+            .roles(claims, roles)
+            .map(|roles| roles.into_iter().collect());
+        Ok((user, mapped))
+    }
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes).expect("system RNG is available");
+    BASE64URL_NOPAD.encode(&bytes)
+}
+
+/// The sign-in routes, to be nested at [`super::OIDC_PATH`].
+pub fn router<S>(state: AppState) -> Router<S> {
+    Router::new()
+        .route("/{provider}/login", get(login))
+        .route("/{provider}/callback", get(callback))
+        .with_state(state)
+}
+
+fn redirect(to: &str, cookie: String) -> Response {
+    let mut response = StatusCode::SEE_OTHER.into_response();
+    let headers = response.headers_mut();
+    if let Ok(to) = HeaderValue::from_str(to) {
+        headers.insert(LOCATION, to);
+    }
+    if let Ok(cookie) = HeaderValue::from_str(&cookie) {
+        headers.append(SET_COOKIE, cookie);
+    }
+    response
+}
+
+fn clear_state_cookie() -> String {
+    format!("{STATE_COOKIE}=; Path=/; Max-Age=0")
+}
+
+/// Sends the browser to the provider.
+async fn login(
+    State(state): State<AppState>,
+    Extension(tenant): Extension<Tenant>,
+    Path(provider): Path<String>,
+) -> Response {
+    match state
+        .oidc
+        .start(&tenant, &provider, state.clock.now())
+        .await
+    {
+        Ok((url, login)) => redirect(
+            url.as_str(),
+            format!(
+                "{STATE_COOKIE}={login}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}",
+                LOGIN_LIFETIME.whole_seconds()
+            ),
+        ),
+        Err(OidcError::UnknownProvider) => StatusCode::NOT_FOUND.into_response(),
+        Err(err) => {
+            warn!("OIDC sign-in with {provider} could not start: {err}");
+            redirect(&format!("/login?{FAILED_PARAM}=1"), clear_state_cookie())
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+/// Where the provider sends the browser back: signs it in on success.
+async fn callback(
+    State(state): State<AppState>,
+    Extension(tenant): Extension<Tenant>,
+    client: Option<Extension<ClientInfo>>,
+    headers: HeaderMap,
+    Path(provider): Path<String>,
+    Query(query): Query<CallbackQuery>,
+) -> Response {
+    let now = state.clock.now();
+    let CallbackQuery {
+        code,
+        state: login,
+        error,
+    } = query;
+    let result = async {
+        if let Some(error) = error {
+            return Err(OidcError::Refused(error));
+        }
+        let login = login.ok_or(OidcError::UnknownLogin)?;
+        if cookie_value(&headers, STATE_COOKIE) != Some(login.as_str()) {
+            return Err(OidcError::UnknownLogin);
+        }
+        let code = code.ok_or(OidcError::UnknownLogin)?;
+        let (provider, claims) = state
+            .oidc
+            .finish(&tenant, &provider, &login, &code, now)
+            .await?;
+        state.oidc.account(
+            &state.users.scoped(&tenant),
+            &state.roles.scoped(&tenant),
+            &state.two_factor,
+            &provider,
+            &claims,
+            now,
+        )
+    }
+    .await;
+
+    match result {
+        Ok((user, roles)) => {
+            let client = client.map(|Extension(client)| client).unwrap_or_default();
+            let trusted = cookie_value(&headers, TRUSTED_DEVICE_COOKIE).is_some_and(|token| {
+                state
+                    .trusted_devices
+                    .verify(&state.signing_key, token, user.unid, &client, now)
+            });
+            // The rest of the sign-in is the password login's second step,
+            // which also gives the mapped roles.
+            let mut response = if state.two_factor.is_enabled(user.unid) && !trusted {
+                let challenge = state.two_factor.challenge_granting(user.unid, roles, now);
+                redirect(
+                    &format!("/login?{SECOND_FACTOR_PARAM}=1"),
+                    format!(
+                        "{CHALLENGE_COOKIE}={challenge}; Path=/; HttpOnly; SameSite=Lax; Max-Age=300"
+                    ),
+                )
+            } else {
+                let users = state.users.scoped(&tenant);
+                if let Some(roles) = roles {
+                    users.update(user.unid, |user| user.roles = roles);
+                }
+                let session = start_session(&state, &users, user.unid, &client, now);
+                redirect("/", session_cookie(session.id))
+            };
+            if let Ok(clear) = HeaderValue::from_str(&clear_state_cookie()) {
+                response.headers_mut().append(SET_COOKIE, clear);
+            }
+            response
+        }
+        Err(err) => {
+            warn!("OIDC sign-in with {provider} failed: {err}");
+            redirect(&format!("/login?{FAILED_PARAM}=1"), clear_state_cookie())
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! Everything held on a user, as a ZIP archive they can download.
+//!
+//! The archive holds `personal-data.json` with a [`PersonalData`] document,
+//! plus the profile picture if there is one. Besides the profile it lists
+//! what other parts of the app keep about the user: sessions, trusted
+//! browsers, linked identity providers, what they granted other
+//! applications, their invitation and the webhook deliveries about them.
+//! Secrets, i.e. the password hash, TOTP secret, recovery codes and tokens,
+//! are never included.
+
+#[cfg(feature = "ssr")]
+use serde::Serialize;
+#[cfg(feature = "ssr")]
+use std::collections::BTreeSet;
+#[cfg(feature = "ssr")]
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+#[cfg(feature = "ssr")]
+use crate::{
+    audit::AuditEntry,
+    invitation::PendingInvitation,
+    oauth::{OAuthConsent, OAuthGrant},
+    session::SessionInfo,
+    state::{AppState, TenantUsers},
+    theme::Theme,
+    trusted_device::TrustedDeviceInfo,
+    webhook::WebhookPayload,
+    User, UserStatus,
+};
+
+/// URL the archive of `user` is downloaded from.
+pub fn personal_data_url(user: Uuid) -> String {
+    format!("/users/{user}/personal-data.zip")
+}
+
+/// The profile fields of a [`User`], without the password hash.
+#[cfg(feature = "ssr")]
+#[derive(Debug, Serialize)]
+pub struct Profile {
+    pub unid: Uuid,
+    pub login: String,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub status: UserStatus,
+    pub roles: BTreeSet<String>,
+    pub language: Option<String>,
+    pub theme: Theme,
+    pub site_schema: Option<String>,
+    pub created: OffsetDateTime,
+    pub last_login: Option<OffsetDateTime>,
+    pub last_failed_login: Option<OffsetDateTime>,
+    pub last_password_change: OffsetDateTime,
+    /// Path of the profile picture within the archive.
+    pub avatar: Option<String>,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Debug, Serialize)]
+pub struct SecondFactor {
+    pub enabled: bool,
+    pub recovery_codes_left: usize,
+}
+
+/// An identity provider's user who signs in to the account.
+#[cfg(feature = "ssr")]
+#[derive(Debug, Serialize)]
+pub struct LinkedIdentity {
+    pub issuer: String,
+    pub subject: String,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Debug, Serialize)]
+pub struct PersonalData {
+    pub generated: OffsetDateTime,
+    pub profile: Profile,
+    pub emails: Vec<String>,
+    pub sessions: Vec<SessionInfo>,
+    pub trusted_devices: Vec<TrustedDeviceInfo>,
+    pub second_factor: SecondFactor,
+    pub linked_identities: Vec<LinkedIdentity>,
+    /// Scopes granted to other applications.
+    pub oauth_consents: Vec<OAuthConsent>,
+    /// Refresh tokens other applications hold.
+    pub oauth_grants: Vec<OAuthGrant>,
+    /// The invitation, while the account is not set up.
+    pub invitation: Option<PendingInvitation>,
+    /// Events about the user sent, or to be sent, to webhook subscribers.
+    pub webhook_deliveries: Vec<WebhookPayload>,
+    /// Sign-ins, failed sign-ins and sign-outs.
+    pub login_history: Vec<AuditEntry>,
+    /// Every other audit entry about the user or by them.
+    pub audit: Vec<AuditEntry>,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Debug, thiserror::Error)]
+pub enum PersonalDataError {
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Zip(#[from] zip::result::ZipError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Path of the profile picture within the archive.
+#[cfg(feature = "ssr")]
+const AVATAR_ENTRY: &str = "avatar.png";
+
+#[cfg(feature = "ssr")]
+pub fn collect(state: &AppState, user: &User) -> PersonalData {
+    let now = state.clock.now();
+    let (login_history, audit): (Vec<_>, Vec<_>) = state
+        .audit
+        .for_user(user.unid)
+        .into_iter()
+        .partition(|entry| entry.action.is_login());
+    PersonalData {
+        generated: now,
+        profile: Profile {
+            unid: user.unid,
+            login: user.login.clone(),
+            first_name: user.first_name.clone(),
+            last_name: user.last_name.clone(),
+            status: user.status,
+            roles: user.roles.iter().cloned().collect(),
+            language: user.language.clone(),
+            theme: user.theme,
+            site_schema: user.site_schema.clone(),
+            created: user.created,
+            last_login: user.last_login,
+            last_failed_login: user.last_failed_login,
+            last_password_change: user.last_password_change,
+            avatar: user.avatar.as_ref().map(|_| AVATAR_ENTRY.to_string()),
+        },
+        emails: vec![user.login.clone()],
+        sessions: state.sessions.for_user(user.unid, Uuid::nil(), now),
+        trusted_devices: state.trusted_devices.for_user(user.unid, None, now),
+        second_factor: SecondFactor {
+            enabled: state.two_factor.is_enabled(user.unid),
+            recovery_codes_left: state.two_factor.recovery_codes_left(user.unid),
+        },
+        linked_identities: state
+            .oidc
+            .identities(user.unid)
+            .into_iter()
+            .map(|(issuer, subject)| LinkedIdentity { issuer, subject })
+            .collect(),
+        oauth_consents: state.oauth.consents(user.unid),
+        oauth_grants: state.oauth.grants(user.unid, now),
+        invitation: (user.status == UserStatus::Pending)
+            .then(|| state.invitations.pending(user.clone(), now)),
+        webhook_deliveries: state.webhooks.payloads(user.unid),
+        login_history,
+        audit,
+    }
+}
+
+/// The ZIP archive of `unid`'s personal data, or `None` if the tenant has no
+/// such user.
+#[cfg(feature = "ssr")]
+pub fn archive(
+    state: &AppState,
+    users: &TenantUsers,
+    unid: Uuid,
+) -> Option<Result<Vec<u8>, PersonalDataError>> {
+    let user = users.get(unid)?;
+    Some(write_archive(state, &user))
+}
+
+#[cfg(feature = "ssr")]
+fn write_archive(state: &AppState, user: &User) -> Result<Vec<u8>, PersonalDataError> {
+    use std::io::{Cursor, Write};
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use crate::avatar::AVATAR_SIZES;
+
+    let data = collect(state, user);
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+
+    zip.start_file("personal-data.json", options)?;
+    zip.write_all(&serde_json::to_vec_pretty(&data)?)?;
+
+    if let Some(hash) = &user.avatar {
+        let picture = state
+            .avatars
+            .dir()
+            .join(hash)
+            .join(format!("{}.png", AVATAR_SIZES[0]));
+        // A picture missing from disk is left out rather than failing the
+        // whole export.
+        if let Ok(bytes) = std::fs::read(picture) {
+            zip.start_file(AVATAR_ENTRY, options)?;
+            zip.write_all(&bytes)?;
+        }
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
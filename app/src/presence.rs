@@ -0,0 +1,390 @@
+//! Who else has a user's page open, so that two administrators do not
+//! overwrite each other's changes unknowingly.
+//!
+//! Each open [`crate::UserEdit`] page holds a websocket to
+//! [`PRESENCE_PATH`]. The server tells every page who else is viewing the
+//! same user and when one of them saved it.
+
+use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{avatar::Avatar, i18n::tr_args, t};
+
+#[cfg(feature = "ssr")]
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+#[cfg(feature = "ssr")]
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, State,
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension,
+};
+#[cfg(feature = "ssr")]
+use tokio::sync::broadcast;
+
+#[cfg(feature = "ssr")]
+use crate::{session::CurrentSession, state::AppState, tenant::Tenant, User};
+
+/// Where [`connect`] is served; `{user}` is the user whose page is open.
+pub const PRESENCE_PATH: &str = "/presence/{user}";
+
+/// Notices not yet passed on to a page before the oldest are dropped for
+/// it.
+#[cfg(feature = "ssr")]
+const BACKLOG: usize = 256;
+
+/// Someone with a user's page open.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Viewer {
+    pub unid: Uuid,
+    pub login: String,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub avatar: Option<String>,
+}
+
+impl Viewer {
+    /// The viewer's full name, or their login without one.
+    pub fn name(&self) -> String {
+        let name = [self.first_name.as_deref(), self.last_name.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if name.trim().is_empty() {
+            self.login.clone()
+        } else {
+            name
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<&User> for Viewer {
+    fn from(user: &User) -> Self {
+        Viewer {
+            unid: user.unid,
+            login: user.login.clone(),
+            first_name: user.first_name.clone(),
+            last_name: user.last_name.clone(),
+            avatar: user.avatar.clone(),
+        }
+    }
+}
+
+/// What the server sends a page, as JSON.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PresenceMessage {
+    /// Everyone else viewing the user now, once each.
+    Viewers(Vec<Viewer>),
+    /// Someone else saved the user.
+    Saved(Viewer),
+}
+
+/// A user's page in a tenant.
+#[cfg(feature = "ssr")]
+type Page = (Option<String>, Uuid);
+
+#[cfg(feature = "ssr")]
+#[derive(Debug)]
+enum Notice {
+    /// Someone opened or left the page.
+    Changed(Page),
+    Saved(Page, Viewer),
+}
+
+/// The open user pages of all tenants, by connection.
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug)]
+pub struct Presence {
+    pages: Arc<RwLock<HashMap<Page, HashMap<Uuid, Viewer>>>>,
+    notices: broadcast::Sender<Arc<Notice>>,
+}
+
+#[cfg(feature = "ssr")]
+impl Default for Presence {
+    fn default() -> Self {
+        Presence {
+            pages: Default::default(),
+            notices: broadcast::channel(BACKLOG).0,
+        }
+    }
+}
+
+/// A connection's place on a page, given up when dropped.
+#[cfg(feature = "ssr")]
+struct Joined {
+    presence: Presence,
+    page: Page,
+    id: Uuid,
+}
+
+#[cfg(feature = "ssr")]
+impl Drop for Joined {
+    fn drop(&mut self) {
+        let mut pages = self.presence.pages.write().unwrap();
+        if let Some(viewers) = pages.get_mut(&self.page) {
+            viewers.remove(&self.id);
+            if viewers.is_empty() {
+                pages.remove(&self.page);
+            }
+        }
+        drop(pages);
+        _ = self
+            .presence
+            .notices
+            .send(Arc::new(Notice::Changed(self.page.clone())));
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl Presence {
+    fn join(&self, page: Page, viewer: Viewer) -> Joined {
+        let id = Uuid::new_v4();
+        self.pages
+            .write()
+            .unwrap()
+            .entry(page.clone())
+            .or_default()
+            .insert(id, viewer);
+        _ = self.notices.send(Arc::new(Notice::Changed(page.clone())));
+        Joined {
+            presence: self.clone(),
+            page,
+            id,
+        }
+    }
+
+    /// Everyone but `viewer` on `page`, sorted by name.
+    fn others(&self, page: &Page, viewer: Uuid) -> Vec<Viewer> {
+        let mut others: Vec<Viewer> = self
+            .pages
+            .read()
+            .unwrap()
+            .get(page)
+            .into_iter()
+            .flat_map(HashMap::values)
+            .filter(|other| other.unid != viewer)
+            .cloned()
+            .collect();
+        others.sort_by_key(|other| (other.name().to_lowercase(), other.unid));
+        // The same person in two tabs is shown once.
+        others.dedup_by_key(|other| other.unid);
+        others
+    }
+
+    /// Tells everyone else with `user`'s page open that `by` saved it.
+    pub fn saved(&self, user: &User, by: &User) {
+        _ = self.notices.send(Arc::new(Notice::Saved(
+            (user.site_schema.clone(), user.unid),
+            by.into(),
+        )));
+    }
+}
+
+/// Upgrades to a websocket keeping the viewer on `user`'s page until it
+/// closes, sending them [`PresenceMessage`]s. Users may only open their own
+/// page, administrators anyone's in their tenant.
+#[cfg(feature = "ssr")]
+pub async fn connect(
+    upgrade: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Extension(tenant): Extension<Tenant>,
+    session: Option<Extension<CurrentSession>>,
+    Path(user): Path<Uuid>,
+) -> Response {
+    let Some(Extension(session)) = session else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let users = state.users.scoped(&tenant);
+    if session.user != user && !users.is_admin(session.user) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    let Some(viewer) = users.get(session.user) else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let page = (tenant.schema, user);
+    upgrade.on_upgrade(move |socket| serve(socket, state, session, page, (&viewer).into()))
+}
+
+#[cfg(feature = "ssr")]
+async fn serve(
+    mut socket: WebSocket,
+    state: AppState,
+    session: CurrentSession,
+    page: Page,
+    viewer: Viewer,
+) {
+    use tokio::sync::broadcast::error::RecvError;
+
+    let presence = state.presence.clone();
+    let mut notices = presence.notices.subscribe();
+    let _joined = presence.join(page.clone(), viewer.clone());
+    loop {
+        let message = tokio::select! {
+            notice = notices.recv() => match notice {
+                Ok(notice) => match &*notice {
+                    Notice::Changed(changed) if *changed == page => {
+                        PresenceMessage::Viewers(presence.others(&page, viewer.unid))
+                    }
+                    Notice::Saved(saved, by) if *saved == page && by.unid != viewer.unid => {
+                        PresenceMessage::Saved(by.clone())
+                    }
+                    _ => continue,
+                },
+                // Notices were missed; who is here is all that can be
+                // caught up on.
+                Err(RecvError::Lagged(_)) => {
+                    PresenceMessage::Viewers(presence.others(&page, viewer.unid))
+                }
+                Err(RecvError::Closed) => break,
+            },
+            received = socket.recv() => match received {
+                // Pages only ever close the connection.
+                Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+        };
+        if state.sessions.owner(session.id).is_none() {
+            break;
+        }
+        let Ok(text) = serde_json::to_string(&message) else {
+            continue;
+        };
+        if socket.send(Message::Text(text.into())).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Who else is on a user's page, as seen by [`use_edit_presence`].
+#[derive(Clone, Copy, Debug)]
+pub struct EditPresence {
+    pub viewers: ReadSignal<Vec<Viewer>>,
+    /// The last other viewer who saved the user, until cleared.
+    pub saved_by: RwSignal<Option<Viewer>>,
+}
+
+impl EditPresence {
+    /// Asks before saving over someone else's changes, returning whether to
+    /// go ahead.
+    pub fn confirm_save(self) -> bool {
+        match self.saved_by.get_untracked() {
+            Some(by) => window()
+                .confirm_with_message(&tr_args(
+                    "presence-saved-confirm",
+                    &[("name", by.name().into())],
+                ))
+                .unwrap_or(false),
+            None => true,
+        }
+    }
+}
+
+/// Keeps the viewer on the page of `user`, if any, for as long as the
+/// calling component lives. Does nothing during server rendering.
+pub fn use_edit_presence(user: impl Into<Signal<Option<Uuid>>>) -> EditPresence {
+    use leptos::wasm_bindgen::{closure::Closure, JsCast};
+    use web_sys::{MessageEvent, WebSocket};
+
+    let user = user.into();
+    let (viewers, set_viewers) = signal(Vec::new());
+    let saved_by = RwSignal::new(None);
+    Effect::new(move || {
+        let Some(user) = user.get() else {
+            return;
+        };
+        let location = window().location();
+        let scheme = match location.protocol().as_deref() {
+            Ok("https:") => "wss",
+            _ => "ws",
+        };
+        let Ok(host) = location.host() else {
+            return;
+        };
+        let path = PRESENCE_PATH.replace("{user}", &user.to_string());
+        let Ok(socket) = WebSocket::new(&format!("{scheme}://{host}{path}")) else {
+            return;
+        };
+        let on_message = Closure::<dyn Fn(MessageEvent)>::new(move |message: MessageEvent| {
+            match message
+                .data()
+                .as_string()
+                .and_then(|data| serde_json::from_str(&data).ok())
+            {
+                Some(PresenceMessage::Viewers(viewers)) => set_viewers.set(viewers),
+                Some(PresenceMessage::Saved(by)) => saved_by.set(Some(by)),
+                None => {}
+            }
+        });
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        let socket = StoredValue::new_local((socket, on_message));
+        on_cleanup(move || {
+            set_viewers.set(Vec::new());
+            saved_by.set(None);
+            socket.with_value(|(socket, _)| {
+                _ = socket.close();
+            });
+        });
+    });
+    EditPresence { viewers, saved_by }
+}
+
+/// The avatars of everyone else on the page.
+#[component]
+pub fn OtherViewers(presence: EditPresence) -> impl IntoView {
+    view! {
+        <div class="d-flex gap-1 align-items-center">
+            <For each=move || presence.viewers.get() key=|viewer| viewer.unid let:viewer>
+                <span title={
+                    let name = viewer.name();
+                    move || tr_args("presence-viewing", &[("name", name.clone().into())])
+                }>
+                    <Avatar
+                        unid=viewer.unid
+                        first_name=viewer.first_name.clone()
+                        last_name=viewer.last_name.clone()
+                        avatar=viewer.avatar.clone()
+                        size=32
+                    />
+                </span>
+            </For>
+        </div>
+    }
+}
+
+/// Tells that someone else saved the user while the viewer was editing,
+/// offering to throw away the edits and load their version.
+#[component]
+pub fn SavedElsewhere(
+    presence: EditPresence,
+    #[prop(into)] on_reload: Callback<()>,
+) -> impl IntoView {
+    move || {
+        presence.saved_by.get().map(|by| {
+            let name = by.name();
+            view! {
+                <div class="alert alert-warning d-flex justify-content-between align-items-center">
+                    <span>
+                        {move || tr_args("presence-saved", &[("name", name.clone().into())])}
+                    </span>
+                    <button
+                        type="button"
+                        class="btn btn-sm btn-outline-secondary"
+                        on:click=move |_| on_reload.run(())
+                    >
+                        {t!("presence-reload")}
+                    </button>
+                </div>
+            }
+        })
+    }
+}
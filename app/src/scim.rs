@@ -0,0 +1,1134 @@
+//! SCIM 2.0 provisioning (RFC 7643 and RFC 7644), so identity providers can
+//! create, update and remove accounts.
+//!
+//! Users map onto [`User`]: `userName` is the login, `active` is whether the
+//! status is [`UserStatus::Active`], and `groups` are the roles. Groups are
+//! the tenant's roles, identified by name. Deactivating a user bans them
+//! with the same consequences as a ban on the user's page. Deleting a user
+//! erases it, see [`erasure`], after which it no longer exists as far as
+//! SCIM is concerned.
+//!
+//! Clients authenticate with a bearer token, which also picks the tenant.
+
+mod filter;
+
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, Request, State},
+    http::{
+        header::{CONTENT_TYPE, LOCATION, WWW_AUTHENTICATE},
+        HeaderValue, StatusCode,
+    },
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Json, Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value as JsonValue};
+use time::format_description::well_known::Rfc3339;
+use uuid::Uuid;
+
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    erasure,
+    password::hash_password,
+    state::{AppState, TenantRoles, TenantUsers, ADMIN_ROLE},
+    tenant::Tenant,
+    user_update::revoke_access,
+    User, UserStatus,
+};
+use filter::Value;
+
+/// Where [`router`] is mounted.
+pub const BASE_PATH: &str = "/scim/v2";
+
+pub const USER_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:User";
+pub const GROUP_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:Group";
+const LIST_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:ListResponse";
+const PATCH_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:PatchOp";
+const ERROR_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:Error";
+const CONFIG_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:ServiceProviderConfig";
+
+const SCIM_CONTENT_TYPE: &str = "application/scim+json";
+
+/// Most resources returned in one page of a list.
+const MAX_RESULTS: usize = 200;
+
+/// The tenant whose token authenticated a SCIM request.
+#[derive(Clone, Debug)]
+struct ScimTenant(Tenant);
+
+/// The SCIM endpoint, to be nested at [`BASE_PATH`].
+pub fn router<S>(state: AppState) -> Router<S> {
+    Router::new()
+        .route("/ServiceProviderConfig", get(service_provider_config))
+        .route("/Users", get(list_users).post(create_user))
+        .route(
+            "/Users/{id}",
+            get(get_user)
+                .put(replace_user)
+                .patch(patch_user)
+                .delete(delete_user),
+        )
+        .route("/Groups", get(list_groups).post(create_group))
+        .route(
+            "/Groups/{id}",
+            get(get_group)
+                .put(replace_group)
+                .patch(patch_group)
+                .delete(delete_group),
+        )
+        .layer(middleware::from_fn_with_state(state.clone(), authenticate))
+        .with_state(state)
+}
+
+async fn authenticate(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    match state.scim_tokens.authenticate(request.headers()) {
+        Some(tenant) => {
+            request.extensions_mut().insert(ScimTenant(tenant));
+            next.run(request).await
+        }
+        None => {
+            let mut response = ScimError::new(
+                StatusCode::UNAUTHORIZED,
+                None,
+                "Missing or unknown bearer token",
+            )
+            .into_response();
+            response
+                .headers_mut()
+                .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+            response
+        }
+    }
+}
+
+/// A SCIM error response (RFC 7644 §3.12).
+#[derive(Debug)]
+struct ScimError {
+    status: StatusCode,
+    scim_type: Option<&'static str>,
+    detail: String,
+}
+
+impl ScimError {
+    fn new(status: StatusCode, scim_type: Option<&'static str>, detail: impl Into<String>) -> Self {
+        ScimError {
+            status,
+            scim_type,
+            detail: detail.into(),
+        }
+    }
+
+    fn bad_request(scim_type: &'static str, detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, Some(scim_type), detail)
+    }
+
+    fn not_found(kind: &str, id: &str) -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            None,
+            format!("{kind} {id} not found"),
+        )
+    }
+
+    fn uniqueness(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::CONFLICT, Some("uniqueness"), detail)
+    }
+}
+
+impl IntoResponse for ScimError {
+    fn into_response(self) -> Response {
+        let mut body = json!({
+            "schemas": [ERROR_SCHEMA],
+            "status": self.status.as_u16().to_string(),
+            "detail": self.detail,
+        });
+        if let Some(scim_type) = self.scim_type {
+            body["scimType"] = json!(scim_type);
+        }
+        scim_response(self.status, body)
+    }
+}
+
+type ScimResult = Result<Response, ScimError>;
+
+fn scim_response(status: StatusCode, body: JsonValue) -> Response {
+    (
+        status,
+        [(CONTENT_TYPE, SCIM_CONTENT_TYPE)],
+        Json(without_nulls(body)),
+    )
+        .into_response()
+}
+
+fn created(location: String, body: JsonValue) -> Response {
+    let mut response = scim_response(StatusCode::CREATED, body);
+    if let Ok(location) = HeaderValue::from_str(&location) {
+        response.headers_mut().insert(LOCATION, location);
+    }
+    response
+}
+
+/// Drops `null` attributes, which SCIM treats as unassigned.
+fn without_nulls(value: JsonValue) -> JsonValue {
+    match value {
+        JsonValue::Object(object) => object
+            .into_iter()
+            .filter(|(_, value)| !value.is_null())
+            .map(|(key, value)| (key, without_nulls(value)))
+            .collect(),
+        JsonValue::Array(items) => items.into_iter().map(without_nulls).collect(),
+        value => value,
+    }
+}
+
+async fn service_provider_config() -> Response {
+    scim_response(
+        StatusCode::OK,
+        json!({
+            "schemas": [CONFIG_SCHEMA],
+            "patch": { "supported": true },
+            "bulk": { "supported": false, "maxOperations": 0, "maxPayloadSize": 0 },
+            "filter": { "supported": true, "maxResults": MAX_RESULTS },
+            "changePassword": { "supported": true },
+            "sort": { "supported": false },
+            "etag": { "supported": false },
+            "authenticationSchemes": [{
+                "type": "oauthbearertoken",
+                "name": "Bearer token",
+                "description": "A token from SCIM_TOKENS in the Authorization header",
+                "primary": true,
+            }],
+        }),
+    )
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListQuery {
+    filter: Option<String>,
+    start_index: Option<usize>,
+    count: Option<usize>,
+}
+
+/// A page of `resources` matching the query, as a ListResponse.
+fn list_response<T>(
+    query: ListQuery,
+    resources: Vec<T>,
+    attribute: impl Fn(&T, &str) -> Vec<Value>,
+    render: impl Fn(&T) -> JsonValue,
+) -> ScimResult {
+    let filter = query
+        .filter
+        .as_deref()
+        .map(filter::parse)
+        .transpose()
+        .map_err(|err| ScimError::bad_request("invalidFilter", err))?;
+    let matching: Vec<T> = resources
+        .into_iter()
+        .filter(|resource| {
+            filter
+                .as_ref()
+                .is_none_or(|filter| filter.matches(&|path| attribute(resource, path)))
+        })
+        .collect();
+    let start_index = query.start_index.unwrap_or(1).max(1);
+    let page: Vec<JsonValue> = matching
+        .iter()
+        .skip(start_index - 1)
+        .take(query.count.unwrap_or(MAX_RESULTS).min(MAX_RESULTS))
+        .map(render)
+        .collect();
+    Ok(scim_response(
+        StatusCode::OK,
+        json!({
+            "schemas": [LIST_SCHEMA],
+            "totalResults": matching.len(),
+            "startIndex": start_index,
+            "itemsPerPage": page.len(),
+            "Resources": page,
+        }),
+    ))
+}
+
+/// Strips the schema URN a client may prefix attribute paths with.
+fn attribute_path(path: &str, schema: &str) -> String {
+    let path = path.to_lowercase();
+    let prefix = format!("{}:", schema.to_lowercase());
+    path.strip_prefix(&prefix).unwrap_or(&path).to_string()
+}
+
+fn rfc3339(at: time::OffsetDateTime) -> String {
+    at.format(&Rfc3339).unwrap_or_default()
+}
+
+fn user_location(unid: Uuid) -> String {
+    format!("{BASE_PATH}/Users/{unid}")
+}
+
+fn group_location(role: &str) -> String {
+    format!("{BASE_PATH}/Groups/{role}")
+}
+
+/// Users visible over SCIM: erased accounts are gone as far as it is
+/// concerned.
+fn scim_users(users: &TenantUsers) -> Vec<User> {
+    let mut users: Vec<User> = users
+        .all()
+        .into_iter()
+        .filter(|user| !user.is_erased())
+        .collect();
+    users.sort_by_key(|user| user.login.to_lowercase());
+    users
+}
+
+fn find_user(users: &TenantUsers, id: &str) -> Result<User, ScimError> {
+    id.parse()
+        .ok()
+        .and_then(|unid| users.get(unid))
+        .filter(|user| !user.is_erased())
+        .ok_or_else(|| ScimError::not_found("User", id))
+}
+
+fn user_resource(user: &User) -> JsonValue {
+    let mut roles: Vec<&String> = user.roles.iter().collect();
+    roles.sort();
+    let formatted = [user.first_name.as_deref(), user.last_name.as_deref()]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let formatted = (!formatted.is_empty()).then_some(formatted);
+    json!({
+        "schemas": [USER_SCHEMA],
+        "id": user.unid,
+        "userName": user.login,
+        "name": {
+            "givenName": user.first_name,
+            "familyName": user.last_name,
+            "formatted": formatted,
+        },
+        "displayName": formatted.as_ref().unwrap_or(&user.login),
+        "active": user.status == UserStatus::Active,
+        "emails": [{ "value": user.login, "type": "work", "primary": true }],
+        "preferredLanguage": user.language,
+        "groups": roles
+            .iter()
+            .map(|role| json!({ "value": role, "display": role, "$ref": group_location(role) }))
+            .collect::<Vec<_>>(),
+        "meta": {
+            "resourceType": "User",
+            "created": rfc3339(user.created),
+            "lastModified": rfc3339(user.last_password_change.max(user.created)),
+            "location": user_location(user.unid),
+        },
+    })
+}
+
+fn user_attribute(user: &User, path: &str) -> Vec<Value> {
+    let text = |value: &str| vec![Value::String(value.to_string())];
+    match attribute_path(path, USER_SCHEMA).as_str() {
+        "id" => text(&user.unid.to_string()),
+        "username" | "emails" | "emails.value" => text(&user.login),
+        "name.givenname" => user.first_name.as_deref().map(text).unwrap_or_default(),
+        "name.familyname" => user.last_name.as_deref().map(text).unwrap_or_default(),
+        "active" => vec![Value::Bool(user.status == UserStatus::Active)],
+        "groups" | "groups.value" | "groups.display" => user
+            .roles
+            .iter()
+            .map(|role| Value::String(role.clone()))
+            .collect(),
+        "meta.created" => text(&rfc3339(user.created)),
+        _ => Vec::new(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UserInput {
+    user_name: String,
+    #[serde(default)]
+    name: NameInput,
+    active: Option<bool>,
+    password: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NameInput {
+    given_name: Option<String>,
+    family_name: Option<String>,
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Parses a request body, answering malformed ones with `invalidSyntax`.
+///
+/// Clients send `application/scim+json`, which axum's `Json` extractor
+/// would turn away, so bodies are read as bytes.
+fn parse_request<T: serde::de::DeserializeOwned>(body: &[u8]) -> Result<T, ScimError> {
+    serde_json::from_slice(body)
+        .map_err(|err| ScimError::bad_request("invalidSyntax", err.to_string()))
+}
+
+/// Like [`parse_request`], for part of an already parsed body.
+fn parse_body<T: serde::de::DeserializeOwned>(body: JsonValue) -> Result<T, ScimError> {
+    serde_json::from_value(body)
+        .map_err(|err| ScimError::bad_request("invalidSyntax", err.to_string()))
+}
+
+/// What a request changes about a user, applied field by field so that
+/// writes made in the meantime by anyone else are kept. Fields left `None`
+/// stay as they are.
+#[derive(Debug, Default)]
+struct UserChanges {
+    login: Option<String>,
+    first_name: Option<Option<String>>,
+    last_name: Option<Option<String>>,
+    active: Option<bool>,
+    hash: Option<String>,
+}
+
+/// Deactivating bans an active user; activating lifts a ban. Pending and
+/// erased users keep their status.
+fn set_active(user: &mut User, active: bool) {
+    match (user.status, active) {
+        (UserStatus::Active, false) => user.status = UserStatus::Banned,
+        (UserStatus::Banned, true) => user.status = UserStatus::Active,
+        _ => {}
+    }
+}
+
+/// `login` as the login of `unid`, if no other user has it.
+fn check_user_name(users: &TenantUsers, unid: Uuid, login: &str) -> Result<String, ScimError> {
+    let login = login.trim();
+    if login.is_empty() {
+        return Err(ScimError::bad_request(
+            "invalidValue",
+            "userName is required",
+        ));
+    }
+    if users
+        .find_by_login(login)
+        .is_some_and(|other| other.unid != unid)
+    {
+        return Err(ScimError::uniqueness(format!("userName {login} is taken")));
+    }
+    Ok(login.to_string())
+}
+
+async fn list_users(
+    State(state): State<AppState>,
+    Extension(ScimTenant(tenant)): Extension<ScimTenant>,
+    Query(query): Query<ListQuery>,
+) -> ScimResult {
+    let users = scim_users(&state.users.scoped(&tenant));
+    list_response(query, users, user_attribute, user_resource)
+}
+
+async fn get_user(
+    State(state): State<AppState>,
+    Extension(ScimTenant(tenant)): Extension<ScimTenant>,
+    Path(id): Path<String>,
+) -> ScimResult {
+    let user = find_user(&state.users.scoped(&tenant), &id)?;
+    Ok(scim_response(StatusCode::OK, user_resource(&user)))
+}
+
+async fn create_user(
+    State(state): State<AppState>,
+    Extension(ScimTenant(tenant)): Extension<ScimTenant>,
+    body: Bytes,
+) -> ScimResult {
+    let input: UserInput = parse_request(&body)?;
+    let users = state.users.scoped(&tenant);
+    let now = state.clock.now();
+    let unid = Uuid::new_v4();
+    let mut user = User {
+        unid,
+        created: now,
+        first_name: non_empty(input.name.given_name),
+        // Without a password the account can only be signed in to once one
+        // is set.
+        hash: String::new(),
+        last_failed_login: None,
+        last_login: None,
+        last_password_change: now,
+        last_name: non_empty(input.name.family_name),
+        login: check_user_name(&users, unid, &input.user_name)?,
+        avatar: None,
+        roles: Default::default(),
+        site_schema: None,
+        status: UserStatus::Active,
+        theme: Default::default(),
+        language: None,
+    };
+    set_active(&mut user, input.active.unwrap_or(true));
+    if let Some(password) = input.password {
+        user.hash = hash_password(&password);
+    }
+    let resource = user_resource(&user);
+    let location = user_location(user.unid);
+    users
+        .insert_all(vec![user])
+        .map_err(|login| ScimError::uniqueness(format!("userName {login} is taken")))?;
+    Ok(created(location, resource))
+}
+
+async fn replace_user(
+    State(state): State<AppState>,
+    Extension(ScimTenant(tenant)): Extension<ScimTenant>,
+    Path(id): Path<String>,
+    body: Bytes,
+) -> ScimResult {
+    let input: UserInput = parse_request(&body)?;
+    let users = state.users.scoped(&tenant);
+    let user = find_user(&users, &id)?;
+    let changes = UserChanges {
+        login: Some(check_user_name(&users, user.unid, &input.user_name)?),
+        first_name: Some(non_empty(input.name.given_name)),
+        last_name: Some(non_empty(input.name.family_name)),
+        active: input.active,
+        hash: input.password.as_deref().map(hash_password),
+    };
+    store_user(&state, &users, user, changes)
+}
+
+/// Applies `changes` to `user` and answers with the result.
+///
+/// A user deactivated here is banned the same way as on the user's page,
+/// and one whose login or password changed is signed out the same way, see
+/// [`crate::user_update::update_user`].
+fn store_user(
+    state: &AppState,
+    users: &TenantUsers,
+    user: User,
+    changes: UserChanges,
+) -> ScimResult {
+    let unid = user.unid;
+    let now = state.clock.now();
+    let mut before = None;
+    let after = users
+        .update(unid, |user| {
+            before = Some(user.clone());
+            if let Some(login) = &changes.login {
+                user.login.clone_from(login);
+            }
+            if let Some(first_name) = &changes.first_name {
+                user.first_name.clone_from(first_name);
+            }
+            if let Some(last_name) = &changes.last_name {
+                user.last_name.clone_from(last_name);
+            }
+            if let Some(active) = changes.active {
+                set_active(user, active);
+            }
+            if let Some(hash) = &changes.hash {
+                user.hash.clone_from(hash);
+                user.last_password_change = now;
+            }
+        })
+        .ok_or_else(|| ScimError::not_found("User", &unid.to_string()))?;
+    let before = before.unwrap_or(user);
+
+    let audit = |action| {
+        state.audit.record(AuditEntry {
+            at: now,
+            actor: None,
+            subject: unid,
+            action,
+            ip: None,
+        })
+    };
+    if after.login != before.login {
+        audit(AuditAction::LoginChanged);
+    }
+    if changes.hash.is_some() {
+        audit(AuditAction::PasswordChanged);
+    }
+    let banned = match (before.status, after.status) {
+        (UserStatus::Active, UserStatus::Banned) => {
+            audit(AuditAction::Banned);
+            true
+        }
+        (UserStatus::Banned, UserStatus::Active) => {
+            audit(AuditAction::Activated);
+            false
+        }
+        _ => false,
+    };
+    if banned || after.login != before.login || changes.hash.is_some() {
+        revoke_access(state, unid, None);
+    }
+    Ok(scim_response(StatusCode::OK, user_resource(&after)))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PatchRequest {
+    #[serde(default)]
+    schemas: Vec<String>,
+    #[serde(rename = "Operations")]
+    operations: Vec<PatchOperation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PatchOperation {
+    op: String,
+    path: Option<String>,
+    value: Option<JsonValue>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PatchOp {
+    Add,
+    Replace,
+    Remove,
+}
+
+impl PatchRequest {
+    fn parse(body: &[u8]) -> Result<Self, ScimError> {
+        let request: PatchRequest = parse_request(body)?;
+        if !request.schemas.iter().any(|schema| schema == PATCH_SCHEMA) {
+            return Err(ScimError::bad_request(
+                "invalidSyntax",
+                format!("Expected the {PATCH_SCHEMA} schema"),
+            ));
+        }
+        Ok(request)
+    }
+}
+
+impl PatchOperation {
+    fn op(&self) -> Result<PatchOp, ScimError> {
+        match self.op.to_ascii_lowercase().as_str() {
+            "add" => Ok(PatchOp::Add),
+            "replace" => Ok(PatchOp::Replace),
+            "remove" => Ok(PatchOp::Remove),
+            other => Err(ScimError::bad_request(
+                "invalidSyntax",
+                format!("Unknown operation {other}"),
+            )),
+        }
+    }
+}
+
+fn string_value(value: &JsonValue, path: &str) -> Result<String, ScimError> {
+    value
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| ScimError::bad_request("invalidValue", format!("{path} must be a string")))
+}
+
+/// Some identity providers send booleans as the strings `"True"` and
+/// `"False"`.
+fn bool_value(value: &JsonValue, path: &str) -> Result<bool, ScimError> {
+    match value {
+        JsonValue::Bool(value) => Ok(*value),
+        JsonValue::String(text) if text.eq_ignore_ascii_case("true") => Ok(true),
+        JsonValue::String(text) if text.eq_ignore_ascii_case("false") => Ok(false),
+        _ => Err(ScimError::bad_request(
+            "invalidValue",
+            format!("{path} must be a boolean"),
+        )),
+    }
+}
+
+/// Applies one attribute of a user PATCH. Attributes that are derived from
+/// others, like `displayName` and `emails`, are accepted and ignored.
+fn patch_user_attribute(
+    users: &TenantUsers,
+    user: &User,
+    changes: &mut UserChanges,
+    op: PatchOp,
+    path: &str,
+    value: Option<&JsonValue>,
+) -> Result<(), ScimError> {
+    let path = attribute_path(path, USER_SCHEMA);
+    let Some(value) = value.filter(|_| op != PatchOp::Remove) else {
+        match path.as_str() {
+            "name.givenname" => changes.first_name = Some(None),
+            "name.familyname" => changes.last_name = Some(None),
+            "name" => {
+                changes.first_name = Some(None);
+                changes.last_name = Some(None);
+            }
+            "displayname" | "externalid" | "emails" => {}
+            _ => {
+                return Err(ScimError::bad_request(
+                    "mutability",
+                    format!("{path} cannot be removed"),
+                ))
+            }
+        }
+        return Ok(());
+    };
+    match path.as_str() {
+        "username" => {
+            changes.login = Some(check_user_name(
+                users,
+                user.unid,
+                &string_value(value, &path)?,
+            )?)
+        }
+        "active" => changes.active = Some(bool_value(value, &path)?),
+        "password" => changes.hash = Some(hash_password(&string_value(value, &path)?)),
+        "name.givenname" => changes.first_name = Some(non_empty(Some(string_value(value, &path)?))),
+        "name.familyname" => changes.last_name = Some(non_empty(Some(string_value(value, &path)?))),
+        "name" => {
+            let JsonValue::Object(name) = value else {
+                return Err(ScimError::bad_request(
+                    "invalidValue",
+                    "name must be an object",
+                ));
+            };
+            for (key, value) in name {
+                patch_user_attribute(
+                    users,
+                    user,
+                    changes,
+                    op,
+                    &format!("name.{key}"),
+                    Some(value),
+                )?;
+            }
+        }
+        "displayname" | "externalid" | "emails" | "name.formatted" => {}
+        _ => {
+            return Err(ScimError::bad_request(
+                "invalidPath",
+                format!("Unsupported attribute {path}"),
+            ))
+        }
+    }
+    Ok(())
+}
+
+async fn patch_user(
+    State(state): State<AppState>,
+    Extension(ScimTenant(tenant)): Extension<ScimTenant>,
+    Path(id): Path<String>,
+    body: Bytes,
+) -> ScimResult {
+    let request = PatchRequest::parse(&body)?;
+    let users = state.users.scoped(&tenant);
+    let user = find_user(&users, &id)?;
+    let mut changes = UserChanges::default();
+    for operation in &request.operations {
+        let op = operation.op()?;
+        match (&operation.path, &operation.value) {
+            (Some(path), value) => {
+                patch_user_attribute(&users, &user, &mut changes, op, path, value.as_ref())?
+            }
+            (None, Some(JsonValue::Object(attributes))) if op != PatchOp::Remove => {
+                for (path, value) in attributes {
+                    patch_user_attribute(&users, &user, &mut changes, op, path, Some(value))?;
+                }
+            }
+            _ => {
+                return Err(ScimError::bad_request(
+                    "noTarget",
+                    "The operation needs a path or an object value",
+                ))
+            }
+        }
+    }
+    store_user(&state, &users, user, changes)
+}
+
+async fn delete_user(
+    State(state): State<AppState>,
+    Extension(ScimTenant(tenant)): Extension<ScimTenant>,
+    Path(id): Path<String>,
+) -> ScimResult {
+    let users = state.users.scoped(&tenant);
+    let user = find_user(&users, &id)?;
+    if erasure::erase(&state, &users, user.unid) == Some(true) {
+        state.audit.record(AuditEntry {
+            at: state.clock.now(),
+            actor: None,
+            subject: user.unid,
+            action: AuditAction::Erased,
+            ip: None,
+        });
+    }
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// A role with the users holding it.
+struct Group {
+    role: String,
+    members: Vec<User>,
+}
+
+fn groups(roles: &TenantRoles, users: &TenantUsers) -> Vec<Group> {
+    let users = scim_users(users);
+    roles
+        .all()
+        .into_iter()
+        .map(|role| Group {
+            members: users
+                .iter()
+                .filter(|user| user.roles.contains(&role))
+                .cloned()
+                .collect(),
+            role,
+        })
+        .collect()
+}
+
+fn find_group(roles: &TenantRoles, users: &TenantUsers, id: &str) -> Result<Group, ScimError> {
+    let role = roles
+        .find(id)
+        .ok_or_else(|| ScimError::not_found("Group", id))?;
+    Ok(groups(roles, users)
+        .into_iter()
+        .find(|group| group.role == role)
+        .expect("the role was just found"))
+}
+
+fn group_resource(group: &Group) -> JsonValue {
+    json!({
+        "schemas": [GROUP_SCHEMA],
+        "id": group.role,
+        "displayName": group.role,
+        "members": group
+            .members
+            .iter()
+            .map(|user| json!({
+                "value": user.unid,
+                "display": user.login,
+                "$ref": user_location(user.unid),
+            }))
+            .collect::<Vec<_>>(),
+        "meta": {
+            "resourceType": "Group",
+            "location": group_location(&group.role),
+        },
+    })
+}
+
+fn group_attribute(group: &Group, path: &str) -> Vec<Value> {
+    match attribute_path(path, GROUP_SCHEMA).as_str() {
+        "id" | "displayname" => vec![Value::String(group.role.clone())],
+        "members" | "members.value" => group
+            .members
+            .iter()
+            .map(|user| Value::String(user.unid.to_string()))
+            .collect(),
+        "members.display" => group
+            .members
+            .iter()
+            .map(|user| Value::String(user.login.clone()))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GroupInput {
+    display_name: String,
+    #[serde(default)]
+    members: Vec<MemberInput>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MemberInput {
+    value: String,
+}
+
+/// The users `members` names, all of which must exist.
+fn member_ids(users: &TenantUsers, members: &[MemberInput]) -> Result<Vec<Uuid>, ScimError> {
+    members
+        .iter()
+        .map(|member| {
+            find_user(users, &member.value)
+                .map(|user| user.unid)
+                .map_err(|_| {
+                    ScimError::bad_request("invalidValue", format!("No user {}", member.value))
+                })
+        })
+        .collect()
+}
+
+fn members_value(value: Option<&JsonValue>) -> Result<Vec<MemberInput>, ScimError> {
+    match value {
+        None => Ok(Vec::new()),
+        Some(value @ JsonValue::Object(_)) => Ok(vec![parse_body(value.clone())?]),
+        Some(value) => parse_body(value.clone()),
+    }
+}
+
+fn grant(users: &TenantUsers, role: &str, members: &[Uuid]) {
+    for unid in members {
+        users.update(*unid, |user| {
+            user.roles.insert(role.to_string());
+        });
+    }
+}
+
+fn revoke(users: &TenantUsers, role: &str, members: &[Uuid]) {
+    for unid in members {
+        users.update(*unid, |user| {
+            user.roles.remove(role);
+        });
+    }
+}
+
+/// Makes `members` exactly the users holding `role`.
+fn set_members(users: &TenantUsers, role: &str, members: &[Uuid]) {
+    let current: Vec<Uuid> = scim_users(users)
+        .into_iter()
+        .filter(|user| user.roles.contains(role))
+        .map(|user| user.unid)
+        .filter(|unid| !members.contains(unid))
+        .collect();
+    revoke(users, role, &current);
+    grant(users, role, members);
+}
+
+/// Renames `role` in the catalog and on every user holding it.
+fn rename_group(
+    roles: &TenantRoles,
+    users: &TenantUsers,
+    role: &str,
+    name: &str,
+) -> Result<String, ScimError> {
+    let name = name.trim();
+    if name == role {
+        return Ok(role.to_string());
+    }
+    if name.is_empty() {
+        return Err(ScimError::bad_request(
+            "invalidValue",
+            "displayName is required",
+        ));
+    }
+    if role == ADMIN_ROLE {
+        return Err(ScimError::bad_request(
+            "mutability",
+            format!("The {ADMIN_ROLE} group cannot be renamed"),
+        ));
+    }
+    if !roles.insert(name) {
+        return Err(ScimError::uniqueness(format!("Group {name} exists")));
+    }
+    roles.remove(role);
+    for user in users
+        .all()
+        .into_iter()
+        .filter(|user| user.roles.contains(role))
+    {
+        users.update(user.unid, |user| {
+            user.roles.remove(role);
+            user.roles.insert(name.to_string());
+        });
+    }
+    Ok(name.to_string())
+}
+
+async fn list_groups(
+    State(state): State<AppState>,
+    Extension(ScimTenant(tenant)): Extension<ScimTenant>,
+    Query(query): Query<ListQuery>,
+) -> ScimResult {
+    let groups = groups(&state.roles.scoped(&tenant), &state.users.scoped(&tenant));
+    list_response(query, groups, group_attribute, group_resource)
+}
+
+async fn get_group(
+    State(state): State<AppState>,
+    Extension(ScimTenant(tenant)): Extension<ScimTenant>,
+    Path(id): Path<String>,
+) -> ScimResult {
+    let group = find_group(
+        &state.roles.scoped(&tenant),
+        &state.users.scoped(&tenant),
+        &id,
+    )?;
+    Ok(scim_response(StatusCode::OK, group_resource(&group)))
+}
+
+async fn create_group(
+    State(state): State<AppState>,
+    Extension(ScimTenant(tenant)): Extension<ScimTenant>,
+    body: Bytes,
+) -> ScimResult {
+    let input: GroupInput = parse_request(&body)?;
+    let (roles, users) = (state.roles.scoped(&tenant), state.users.scoped(&tenant));
+    let role = input.display_name.trim();
+    if role.is_empty() {
+        return Err(ScimError::bad_request(
+            "invalidValue",
+            "displayName is required",
+        ));
+    }
+    let members = member_ids(&users, &input.members)?;
+    if !roles.insert(role) {
+        return Err(ScimError::uniqueness(format!("Group {role} exists")));
+    }
+    grant(&users, role, &members);
+    let group = find_group(&roles, &users, role)?;
+    Ok(created(group_location(role), group_resource(&group)))
+}
+
+async fn replace_group(
+    State(state): State<AppState>,
+    Extension(ScimTenant(tenant)): Extension<ScimTenant>,
+    Path(id): Path<String>,
+    body: Bytes,
+) -> ScimResult {
+    let input: GroupInput = parse_request(&body)?;
+    let (roles, users) = (state.roles.scoped(&tenant), state.users.scoped(&tenant));
+    let group = find_group(&roles, &users, &id)?;
+    let members = member_ids(&users, &input.members)?;
+    let role = rename_group(&roles, &users, &group.role, &input.display_name)?;
+    set_members(&users, &role, &members);
+    let group = find_group(&roles, &users, &role)?;
+    Ok(scim_response(StatusCode::OK, group_resource(&group)))
+}
+
+/// The user a `members[value eq "…"]` path picks, if the path is one.
+fn member_path(users: &TenantUsers, path: &str) -> Result<Option<Vec<Uuid>>, ScimError> {
+    let Some(inner) = path
+        .strip_prefix("members[")
+        .and_then(|rest| rest.strip_suffix(']'))
+    else {
+        return Ok(None);
+    };
+    let filter = filter::parse(inner).map_err(|err| ScimError::bad_request("invalidPath", err))?;
+    Ok(Some(
+        scim_users(users)
+            .into_iter()
+            .filter(|user| {
+                filter.matches(&|path| match path {
+                    "value" => vec![Value::String(user.unid.to_string())],
+                    "display" => vec![Value::String(user.login.clone())],
+                    _ => Vec::new(),
+                })
+            })
+            .map(|user| user.unid)
+            .collect(),
+    ))
+}
+
+async fn patch_group(
+    State(state): State<AppState>,
+    Extension(ScimTenant(tenant)): Extension<ScimTenant>,
+    Path(id): Path<String>,
+    body: Bytes,
+) -> ScimResult {
+    let request = PatchRequest::parse(&body)?;
+    let (roles, users) = (state.roles.scoped(&tenant), state.users.scoped(&tenant));
+    let mut role = find_group(&roles, &users, &id)?.role;
+    for operation in &request.operations {
+        let op = operation.op()?;
+        let value = operation.value.as_ref();
+        let path = operation
+            .path
+            .as_deref()
+            .map(|path| path.trim().to_lowercase());
+        if let Some(picked) = path
+            .as_deref()
+            .map(|path| member_path(&users, path))
+            .transpose()?
+            .flatten()
+        {
+            if op != PatchOp::Remove {
+                return Err(ScimError::bad_request(
+                    "invalidPath",
+                    "Members can only be removed by filter",
+                ));
+            }
+            revoke(&users, &role, &picked);
+            continue;
+        }
+        match (op, path.as_deref()) {
+            (PatchOp::Add, Some("members")) => {
+                grant(&users, &role, &member_ids(&users, &members_value(value)?)?)
+            }
+            (PatchOp::Replace, Some("members")) => {
+                set_members(&users, &role, &member_ids(&users, &members_value(value)?)?)
+            }
+            (PatchOp::Remove, Some("members")) => match value {
+                Some(_) => revoke(&users, &role, &member_ids(&users, &members_value(value)?)?),
+                None => set_members(&users, &role, &[]),
+            },
+            (PatchOp::Replace, Some("displayname")) => {
+                let name = string_value(value.unwrap_or(&JsonValue::Null), "displayName")?;
+                role = rename_group(&roles, &users, &role, &name)?;
+            }
+            (PatchOp::Add | PatchOp::Replace, None) => {
+                let Some(JsonValue::Object(attributes)) = value else {
+                    return Err(ScimError::bad_request(
+                        "noTarget",
+                        "The operation needs a path or an object value",
+                    ));
+                };
+                for (key, value) in attributes {
+                    match key.to_lowercase().as_str() {
+                        "displayname" => {
+                            role = rename_group(&roles, &users, &role, &string_value(value, key)?)?
+                        }
+                        "members" => {
+                            let members = member_ids(&users, &members_value(Some(value))?)?;
+                            if op == PatchOp::Add {
+                                grant(&users, &role, &members);
+                            } else {
+                                set_members(&users, &role, &members);
+                            }
+                        }
+                        "id" | "externalid" => {}
+                        other => {
+                            return Err(ScimError::bad_request(
+                                "invalidPath",
+                                format!("Unsupported attribute {other}"),
+                            ))
+                        }
+                    }
+                }
+            }
+            (_, path) => {
+                return Err(ScimError::bad_request(
+                    "invalidPath",
+                    format!("Unsupported path {}", path.unwrap_or_default()),
+                ))
+            }
+        }
+    }
+    let group = find_group(&roles, &users, &role)?;
+    Ok(scim_response(StatusCode::OK, group_resource(&group)))
+}
+
+/// Deletes the role from the catalog and takes it from every user. The
+/// administrator role cannot be deleted.
+async fn delete_group(
+    State(state): State<AppState>,
+    Extension(ScimTenant(tenant)): Extension<ScimTenant>,
+    Path(id): Path<String>,
+) -> ScimResult {
+    let (roles, users) = (state.roles.scoped(&tenant), state.users.scoped(&tenant));
+    let group = find_group(&roles, &users, &id)?;
+    if group.role == ADMIN_ROLE {
+        return Err(ScimError::bad_request(
+            "mutability",
+            format!("The {ADMIN_ROLE} group cannot be deleted"),
+        ));
+    }
+    roles.remove(&group.role);
+    let members: Vec<Uuid> = users
+        .all()
+        .into_iter()
+        .filter(|user| user.roles.contains(&group.role))
+        .map(|user| user.unid)
+        .collect();
+    revoke(&users, &group.role, &members);
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
@@ -0,0 +1,277 @@
+//! SCIM filter expressions (RFC 7644 §3.4.2.2), such as
+//! `userName eq "bjensen" and not (active eq false)`.
+//!
+//! Attribute names and string comparisons ignore case. Value paths like
+//! `emails[type eq "work"]` are not supported in filters.
+
+use std::cmp::Ordering;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Filter {
+    Compare {
+        attribute: String,
+        op: Op,
+        value: Value,
+    },
+    Present(String),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Co,
+    Sw,
+    Ew,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+/// An attribute value or a literal in a filter.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Bool(bool),
+    Number(f64),
+    Null,
+}
+
+impl Value {
+    fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::String(a), Value::String(b)) => Some(a.to_lowercase().cmp(&b.to_lowercase())),
+            (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+            (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+            (Value::Null, Value::Null) => Some(Ordering::Equal),
+            _ => None,
+        }
+    }
+
+    fn matches(&self, op: Op, other: &Value) -> bool {
+        let text = |value: &Value| match value {
+            Value::String(text) => Some(text.to_lowercase()),
+            _ => None,
+        };
+        match op {
+            Op::Eq => self.compare(other) == Some(Ordering::Equal),
+            Op::Ne => self.compare(other) != Some(Ordering::Equal),
+            Op::Co | Op::Sw | Op::Ew => match (text(self), text(other)) {
+                (Some(value), Some(part)) => match op {
+                    Op::Co => value.contains(&part),
+                    Op::Sw => value.starts_with(&part),
+                    _ => value.ends_with(&part),
+                },
+                _ => false,
+            },
+            Op::Gt => self.compare(other) == Some(Ordering::Greater),
+            Op::Ge => matches!(
+                self.compare(other),
+                Some(Ordering::Greater | Ordering::Equal)
+            ),
+            Op::Lt => self.compare(other) == Some(Ordering::Less),
+            Op::Le => matches!(self.compare(other), Some(Ordering::Less | Ordering::Equal)),
+        }
+    }
+}
+
+impl Filter {
+    /// Whether a resource matches. `attribute` returns every value of a
+    /// lowercase attribute path, none if the resource lacks it.
+    pub fn matches(&self, attribute: &impl Fn(&str) -> Vec<Value>) -> bool {
+        match self {
+            Filter::Compare {
+                attribute: path,
+                op: Op::Ne,
+                value,
+            } => attribute(path)
+                .iter()
+                .all(|actual| actual.matches(Op::Ne, value)),
+            Filter::Compare {
+                attribute: path,
+                op,
+                value,
+            } => attribute(path)
+                .iter()
+                .any(|actual| actual.matches(*op, value)),
+            Filter::Present(path) => attribute(path).iter().any(|value| match value {
+                Value::Null => false,
+                Value::String(text) => !text.is_empty(),
+                _ => true,
+            }),
+            Filter::And(a, b) => a.matches(attribute) && b.matches(attribute),
+            Filter::Or(a, b) => a.matches(attribute) || b.matches(attribute),
+            Filter::Not(filter) => !filter.matches(attribute),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Word(String),
+    String(String),
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '"' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped) => text.push(escaped),
+                            None => return Err("Unterminated string".to_string()),
+                        },
+                        Some(c) => text.push(c),
+                        None => return Err("Unterminated string".to_string()),
+                    }
+                }
+                tokens.push(Token::String(text));
+            }
+            '[' | ']' => return Err("Value paths are not supported in filters".to_string()),
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '(' | ')' | '"' | '[' | ']') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.tokens.get(self.position), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Filter, String> {
+        let mut filter = self.and()?;
+        while self.peek_keyword("or") {
+            self.position += 1;
+            filter = Filter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> Result<Filter, String> {
+        let mut filter = self.unary()?;
+        while self.peek_keyword("and") {
+            self.position += 1;
+            filter = Filter::And(Box::new(filter), Box::new(self.unary()?));
+        }
+        Ok(filter)
+    }
+
+    fn unary(&mut self) -> Result<Filter, String> {
+        if self.peek_keyword("not") {
+            self.position += 1;
+            if self.next() != Some(Token::Open) {
+                return Err("Expected ( after not".to_string());
+            }
+            let filter = self.group()?;
+            return Ok(Filter::Not(Box::new(filter)));
+        }
+        match self.next() {
+            Some(Token::Open) => self.group(),
+            Some(Token::Word(attribute)) => self.comparison(attribute.to_lowercase()),
+            other => Err(format!("Expected an attribute, found {other:?}")),
+        }
+    }
+
+    /// The rest of a parenthesized expression, after the `(`.
+    fn group(&mut self) -> Result<Filter, String> {
+        let filter = self.or()?;
+        match self.next() {
+            Some(Token::Close) => Ok(filter),
+            _ => Err("Expected )".to_string()),
+        }
+    }
+
+    fn comparison(&mut self, attribute: String) -> Result<Filter, String> {
+        let Some(Token::Word(op)) = self.next() else {
+            return Err(format!("Expected an operator after {attribute}"));
+        };
+        let op = match op.to_ascii_lowercase().as_str() {
+            "pr" => return Ok(Filter::Present(attribute)),
+            "eq" => Op::Eq,
+            "ne" => Op::Ne,
+            "co" => Op::Co,
+            "sw" => Op::Sw,
+            "ew" => Op::Ew,
+            "gt" => Op::Gt,
+            "ge" => Op::Ge,
+            "lt" => Op::Lt,
+            "le" => Op::Le,
+            other => return Err(format!("Unknown operator {other}")),
+        };
+        let value = match self.next() {
+            Some(Token::String(text)) => Value::String(text),
+            Some(Token::Word(word)) => match word.to_ascii_lowercase().as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                "null" => Value::Null,
+                _ => word
+                    .parse()
+                    .map(Value::Number)
+                    .map_err(|_| format!("Invalid value {word}"))?,
+            },
+            other => return Err(format!("Expected a value, found {other:?}")),
+        };
+        Ok(Filter::Compare {
+            attribute,
+            op,
+            value,
+        })
+    }
+}
+
+pub fn parse(input: &str) -> Result<Filter, String> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        position: 0,
+    };
+    let filter = parser.or()?;
+    if parser.position < parser.tokens.len() {
+        return Err("Unexpected input after the filter".to_string());
+    }
+    Ok(filter)
+}
@@ -0,0 +1,340 @@
+//! Finding users by any part of their login or name, forgiving typos, from
+//! the search box in the header.
+//!
+//! Names and logins are split into words, lowercased and stripped of
+//! accents, so "adrian mih" finds "Adrian-Mihai" and "stefan" finds
+//! "Ștefan". Every word of the query has to match a word of the user,
+//! exactly, as its beginning, or with a typo or two; users are ranked by
+//! how closely their words match.
+
+use leptos::prelude::*;
+use leptos_router::hooks::use_navigate;
+use uuid::Uuid;
+
+use crate::{avatar::Avatar, i18n::tr, t, User};
+
+#[cfg(feature = "ssr")]
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, RwLock},
+};
+
+/// Most users the search box lists.
+pub const SEARCH_LIMIT: usize = 8;
+
+#[cfg(feature = "ssr")]
+const EXACT: u32 = 8;
+#[cfg(feature = "ssr")]
+const PREFIX: u32 = 4;
+#[cfg(feature = "ssr")]
+const FUZZY: u32 = 2;
+#[cfg(feature = "ssr")]
+const FUZZY_PREFIX: u32 = 1;
+
+/// The words of `text`, lowercased and without accents.
+#[cfg(feature = "ssr")]
+fn words(text: &str) -> Vec<String> {
+    use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+    let plain: String = text.nfd().filter(|c| !is_combining_mark(*c)).collect();
+    plain
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Typos forgiven in a query word of `len` characters; short words have
+/// too many neighbours to allow any.
+#[cfg(feature = "ssr")]
+fn allowed_typos(len: usize) -> usize {
+    match len {
+        0..4 => 0,
+        4..8 => 1,
+        _ => 2,
+    }
+}
+
+/// Edits turning `a` into `b`, counting a swap of neighbours as one.
+#[cfg(feature = "ssr")]
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut rows = vec![(0..=b.len()).collect::<Vec<_>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let substitution = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (rows[i - 1][j] + 1)
+                .min(row[j - 1] + 1)
+                .min(rows[i - 1][j - 1] + substitution);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+/// An inverted index from words to the users having them, for all tenants.
+///
+/// [`UserRepository`](crate::state::UserRepository) updates it on every
+/// write, so it never lags behind the users.
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Default)]
+pub struct SearchIndex(Arc<RwLock<Index>>);
+
+#[cfg(feature = "ssr")]
+#[derive(Debug, Default)]
+struct Index {
+    users_by_word: HashMap<String, HashSet<Uuid>>,
+    /// Each user's words, to take them out again.
+    words_by_user: HashMap<Uuid, Vec<String>>,
+}
+
+#[cfg(feature = "ssr")]
+impl Index {
+    /// The users with a word matching `word`, scored by their best match.
+    fn matches(&self, word: &str) -> HashMap<Uuid, u32> {
+        let query: Vec<char> = word.chars().collect();
+        let typos = allowed_typos(query.len());
+        let mut scores = HashMap::new();
+        for (candidate, users) in &self.users_by_word {
+            let score = if candidate == word {
+                EXACT
+            } else if candidate.starts_with(word) {
+                PREFIX
+            } else if typos == 0 {
+                continue;
+            } else {
+                let candidate: Vec<char> = candidate.chars().collect();
+                if candidate.len().abs_diff(query.len()) <= typos
+                    && edit_distance(&query, &candidate) <= typos
+                {
+                    FUZZY
+                } else if candidate.len() > query.len()
+                    && edit_distance(&query, &candidate[..query.len()]) <= typos
+                {
+                    FUZZY_PREFIX
+                } else {
+                    continue;
+                }
+            };
+            for unid in users {
+                let best = scores.entry(*unid).or_insert(0);
+                *best = score.max(*best);
+            }
+        }
+        scores
+    }
+
+    fn remove(&mut self, unid: Uuid) {
+        for word in self.words_by_user.remove(&unid).unwrap_or_default() {
+            if let Some(users) = self.users_by_word.get_mut(&word) {
+                users.remove(&unid);
+                if users.is_empty() {
+                    self.users_by_word.remove(&word);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl SearchIndex {
+    /// Indexes `user`, replacing what was indexed for them before.
+    pub fn insert(&self, user: &User) {
+        let mut user_words: Vec<String> = [
+            Some(&user.login),
+            user.first_name.as_ref(),
+            user.last_name.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .flat_map(words)
+        .collect();
+        user_words.sort();
+        user_words.dedup();
+
+        let mut index = self.0.write().unwrap();
+        index.remove(user.unid);
+        for word in &user_words {
+            index
+                .users_by_word
+                .entry(word.clone())
+                .or_default()
+                .insert(user.unid);
+        }
+        index.words_by_user.insert(user.unid, user_words);
+    }
+
+    pub fn remove(&self, unid: Uuid) {
+        self.0.write().unwrap().remove(unid);
+    }
+
+    /// The users matching every word of `query`, with their scores; the
+    /// higher, the better the match.
+    pub fn search(&self, query: &str) -> HashMap<Uuid, u32> {
+        let index = self.0.read().unwrap();
+        words(query)
+            .iter()
+            .map(|word| index.matches(word))
+            .reduce(|total, matches| {
+                total
+                    .into_iter()
+                    .filter_map(|(unid, score)| Some((unid, score + matches.get(&unid)?)))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// The tenant's users best matching `query`.
+#[server]
+pub async fn search_users(query: String) -> Result<Vec<User>, ServerFnError> {
+    use crate::state::{authorize_admin, tenant_users};
+
+    authorize_admin()?;
+    Ok(tenant_users()?
+        .search(&query, SEARCH_LIMIT)
+        .into_iter()
+        .map(User::public)
+        .collect())
+}
+
+/// Search box listing matching users as one types. The arrow keys move
+/// through the results, Enter opens the highlighted user and Escape closes
+/// the list.
+#[component]
+pub fn UserSearch() -> impl IntoView {
+    let query = RwSignal::new(String::new());
+    let open = RwSignal::new(false);
+    let highlighted = RwSignal::new(0_usize);
+    let results = Resource::new(
+        move || query.get(),
+        |query| async move {
+            if query.trim().is_empty() {
+                Ok(Vec::new())
+            } else {
+                search_users(query).await
+            }
+        },
+    );
+    let users = move || results.get().and_then(Result::ok).unwrap_or_default();
+    // Stored so that `go_to` is `Copy` and every handler can have it.
+    let navigate = StoredValue::new(use_navigate());
+    let go_to = move |unid: Uuid| {
+        open.set(false);
+        query.set(String::new());
+        navigate.with_value(|navigate| navigate(&format!("/users/{unid}"), Default::default()));
+    };
+
+    view! {
+        <div class="position-relative" style="width: 18rem">
+            <input
+                type="search"
+                class="form-control form-control-sm"
+                role="combobox"
+                aria-autocomplete="list"
+                aria-controls="user-search-results"
+                aria-expanded=move || open.get().to_string()
+                aria-activedescendant=move || {
+                    open.get().then(|| format!("user-search-{}", highlighted.get()))
+                }
+                placeholder=move || tr("search-users")
+                prop:value=query
+                on:input=move |ev| {
+                    query.set(event_target_value(&ev));
+                    highlighted.set(0);
+                    open.set(true);
+                }
+                on:focus=move |_| open.set(true)
+                on:blur=move |_| open.set(false)
+                on:keydown=move |ev| {
+                    let users = results.get_untracked().and_then(Result::ok).unwrap_or_default();
+                    let count = users.len();
+                    match ev.key().as_str() {
+                        "ArrowDown" if count > 0 => {
+                            ev.prevent_default();
+                            open.set(true);
+                            highlighted.update(|i| *i = (*i + 1) % count);
+                        }
+                        "ArrowUp" if count > 0 => {
+                            ev.prevent_default();
+                            open.set(true);
+                            highlighted.update(|i| *i = (*i + count - 1) % count);
+                        }
+                        "Enter" => {
+                            if let Some(user) = users.get(highlighted.get_untracked()) {
+                                ev.prevent_default();
+                                go_to(user.unid);
+                            }
+                        }
+                        "Escape" => open.set(false),
+                        _ => {}
+                    }
+                }
+            />
+            <Show when=move || open.get() && !query.get().trim().is_empty()>
+                <ul
+                    id="user-search-results"
+                    class="list-group position-absolute w-100 shadow-sm mt-1"
+                    style="z-index: 1050"
+                    role="listbox"
+                >
+                    <Transition>
+                        {move || {
+                            let users = users();
+                            if users.is_empty() {
+                                return view! {
+                                    <li class="list-group-item text-body-secondary">
+                                        {t!("search-no-results")}
+                                    </li>
+                                }
+                                    .into_any();
+                            }
+                            users
+                                .into_iter()
+                                .enumerate()
+                                .map(|(i, user)| {
+                                    let name = [user.first_name.clone(), user.last_name.clone()]
+                                        .into_iter()
+                                        .flatten()
+                                        .collect::<Vec<_>>()
+                                        .join(" ");
+                                    view! {
+                                        <li
+                                            id=format!("user-search-{i}")
+                                            role="option"
+                                            class="list-group-item list-group-item-action d-flex align-items-center gap-2"
+                                            class:active=move || highlighted.get() == i
+                                            aria-selected=move || (highlighted.get() == i).to_string()
+                                            style="cursor: pointer"
+                                            // Keeps the focus, and the list, until the click.
+                                            on:mousedown=|ev| ev.prevent_default()
+                                            on:mouseenter=move |_| highlighted.set(i)
+                                            on:click=move |_| go_to(user.unid)
+                                        >
+                                            <Avatar
+                                                unid=user.unid
+                                                first_name=user.first_name
+                                                last_name=user.last_name
+                                                avatar=user.avatar
+                                                size=24
+                                            />
+                                            <div class="text-truncate">
+                                                <div>{name}</div>
+                                                <small class="opacity-75">{user.login}</small>
+                                            </div>
+                                        </li>
+                                    }
+                                })
+                                .collect_view()
+                                .into_any()
+                        }}
+                    </Transition>
+                </ul>
+            </Show>
+        </div>
+    }
+}
@@ -0,0 +1,561 @@
+use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::{datetime::DateTime, t};
+
+#[cfg(feature = "ssr")]
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, RwLock},
+};
+
+#[cfg(feature = "ssr")]
+use http::{header, request::Parts, HeaderMap, HeaderValue};
+
+pub use model::SESSION_COOKIE;
+/// How long a session survives without a request.
+pub const SESSION_IDLE_TIMEOUT: Duration = Duration::days(14);
+/// How long a session survives at most, however active.
+pub const SESSION_LIFETIME: Duration = Duration::days(90);
+
+/// A signed-in browser session as shown in the "Web browsers" tab.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub id: Uuid,
+    pub user: Uuid,
+    pub browser: String,
+    pub os: String,
+    pub ip: Option<String>,
+    pub created: OffsetDateTime,
+    pub last_seen: OffsetDateTime,
+    /// Whether this is the session making the request.
+    pub current: bool,
+}
+
+/// Browser and operating system extracted from a `User-Agent` header.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserAgent {
+    pub browser: String,
+    pub os: String,
+}
+
+impl UserAgent {
+    /// Recognises the common browsers and platforms, falling back to "Unknown".
+    ///
+    /// The order of the checks matters: Edge and Opera also advertise
+    /// `Chrome/`, and Chrome advertises `Safari/`.
+    pub fn parse(header: &str) -> Self {
+        let browser = [
+            ("Edg/", "Edge"),
+            ("OPR/", "Opera"),
+            ("Firefox/", "Firefox"),
+            ("Chrome/", "Chrome"),
+            ("Safari/", "Safari"),
+        ]
+        .into_iter()
+        .find(|(token, _)| header.contains(token))
+        .map(|(token, name)| match version_after(header, token) {
+            Some(version) => format!("{name} {version}"),
+            None => name.to_string(),
+        })
+        .unwrap_or_else(|| "Unknown".to_string());
+
+        let os = [
+            ("Windows", "Windows"),
+            ("Android", "Android"),
+            ("iPhone", "iOS"),
+            ("iPad", "iPadOS"),
+            ("CrOS", "ChromeOS"),
+            ("Mac OS X", "macOS"),
+            ("Linux", "Linux"),
+        ]
+        .into_iter()
+        .find(|(token, _)| header.contains(token))
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| "Unknown".to_string());
+
+        UserAgent { browser, os }
+    }
+}
+
+/// Major version following `token`, e.g. `124` for `Chrome/124.0.6367.60`.
+fn version_after<'a>(header: &'a str, token: &str) -> Option<&'a str> {
+    let start = header.find(token)? + token.len();
+    let rest = &header[start..];
+    let end = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    (end > 0).then(|| &rest[..end])
+}
+
+/// The session attached to the current request by the session middleware.
+#[cfg(feature = "ssr")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CurrentSession {
+    pub id: Uuid,
+    pub user: Uuid,
+    /// The administrator acting as `user`, if this session impersonates
+    /// them.
+    pub impersonator: Option<Uuid>,
+}
+
+/// Where a request came from, attached by the session middleware.
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: String,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug)]
+struct Session {
+    user: Uuid,
+    agent: UserAgent,
+    ip: Option<String>,
+    created: OffsetDateTime,
+    last_seen: OffsetDateTime,
+    impersonation: Option<Impersonation>,
+}
+
+/// Who started an impersonating session, and the session they return to.
+#[cfg(feature = "ssr")]
+#[derive(Clone, Copy, Debug)]
+struct Impersonation {
+    actor: Uuid,
+    actor_session: Uuid,
+}
+
+#[cfg(feature = "ssr")]
+impl Session {
+    fn expired(&self, now: OffsetDateTime) -> bool {
+        now - self.last_seen >= SESSION_IDLE_TIMEOUT || now - self.created >= SESSION_LIFETIME
+    }
+
+    fn current(&self, id: Uuid) -> CurrentSession {
+        CurrentSession {
+            id,
+            user: self.user,
+            impersonator: self.impersonation.map(|impersonation| impersonation.actor),
+        }
+    }
+}
+
+/// Server-side registry of live sessions.
+///
+/// A session that is not in the store is treated as signed out, so removing
+/// an entry revokes it on the very next request. Sessions expire after
+/// [`SESSION_IDLE_TIMEOUT`] without a request, or [`SESSION_LIFETIME`] after
+/// signing in.
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Default)]
+pub struct SessionStore(Arc<RwLock<HashMap<Uuid, Session>>>);
+
+#[cfg(feature = "ssr")]
+impl SessionStore {
+    pub fn create(
+        &self,
+        user: Uuid,
+        user_agent: &str,
+        ip: Option<String>,
+        now: OffsetDateTime,
+    ) -> CurrentSession {
+        self.insert(user, user_agent, ip, None, now)
+    }
+
+    /// Starts a session in which the signed-in `actor` acts as `user`.
+    pub fn impersonate(
+        &self,
+        actor: CurrentSession,
+        user: Uuid,
+        user_agent: &str,
+        ip: Option<String>,
+        now: OffsetDateTime,
+    ) -> CurrentSession {
+        let impersonation = Impersonation {
+            actor: actor.user,
+            actor_session: actor.id,
+        };
+        self.insert(user, user_agent, ip, Some(impersonation), now)
+    }
+
+    fn insert(
+        &self,
+        user: Uuid,
+        user_agent: &str,
+        ip: Option<String>,
+        impersonation: Option<Impersonation>,
+        now: OffsetDateTime,
+    ) -> CurrentSession {
+        let id = Uuid::new_v4();
+        let session = Session {
+            user,
+            agent: UserAgent::parse(user_agent),
+            ip,
+            created: now,
+            last_seen: now,
+            impersonation,
+        };
+        let current = session.current(id);
+        self.0.write().unwrap().insert(id, session);
+        current
+    }
+
+    /// Ends the impersonating session `id`, returning the session of the
+    /// administrator who started it if they are still signed in there.
+    pub fn end_impersonation(&self, id: Uuid) -> Option<Uuid> {
+        let mut sessions = self.0.write().unwrap();
+        let impersonation = sessions.get(&id)?.impersonation?;
+        sessions.remove(&id);
+        sessions
+            .contains_key(&impersonation.actor_session)
+            .then_some(impersonation.actor_session)
+    }
+
+    /// Records activity on a session at `now`, returning `None` if it does
+    /// not exist, has been revoked or has expired.
+    pub fn touch(
+        &self,
+        id: Uuid,
+        ip: Option<String>,
+        now: OffsetDateTime,
+    ) -> Option<CurrentSession> {
+        let mut sessions = self.0.write().unwrap();
+        sessions.retain(|_, session| !session.expired(now));
+        let session = sessions.get_mut(&id)?;
+        session.last_seen = now;
+        if ip.is_some() {
+            session.ip = ip;
+        }
+        Some(session.current(id))
+    }
+
+    /// Sessions belonging to `user` and not expired at `now`, most recently
+    /// active first.
+    pub fn for_user(&self, user: Uuid, current: Uuid, now: OffsetDateTime) -> Vec<SessionInfo> {
+        let mut list = self
+            .0
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, session)| session.user == user && !session.expired(now))
+            .map(|(&id, session)| SessionInfo {
+                id,
+                user: session.user,
+                browser: session.agent.browser.clone(),
+                os: session.agent.os.clone(),
+                ip: session.ip.clone(),
+                created: session.created,
+                last_seen: session.last_seen,
+                current: id == current,
+            })
+            .collect::<Vec<_>>();
+        list.sort_by_key(|session| Reverse(session.last_seen));
+        list
+    }
+
+    pub fn owner(&self, id: Uuid) -> Option<Uuid> {
+        self.0.read().unwrap().get(&id).map(|session| session.user)
+    }
+
+    pub fn revoke(&self, id: Uuid) -> bool {
+        self.0.write().unwrap().remove(&id).is_some()
+    }
+
+    /// Revokes every session of `user`, and those in which they
+    /// impersonate someone, and returns how many were removed.
+    pub fn revoke_all(&self, user: Uuid) -> usize {
+        let mut sessions = self.0.write().unwrap();
+        let before = sessions.len();
+        sessions.retain(|_, session| {
+            session.user != user
+                && session
+                    .impersonation
+                    .is_none_or(|impersonation| impersonation.actor != user)
+        });
+        before - sessions.len()
+    }
+
+    /// Revokes every session of `user` but `keep`, and returns how many
+    /// were removed.
+    pub fn revoke_others(&self, user: Uuid, keep: Uuid) -> usize {
+        let mut sessions = self.0.write().unwrap();
+        let before = sessions.len();
+        sessions.retain(|id, session| *id == keep || session.user != user);
+        before - sessions.len()
+    }
+}
+
+/// Proxies whose `X-Forwarded-For` header is believed, read from
+/// `TRUSTED_PROXIES` (`10.0.0.1,10.0.0.2`). Requests from anywhere else are
+/// taken to come from their peer address, so the header cannot fake one.
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Default)]
+pub struct TrustedProxies(Vec<IpAddr>);
+
+#[cfg(feature = "ssr")]
+impl TrustedProxies {
+    /// # Panics
+    ///
+    /// If `TRUSTED_PROXIES` holds something that is not an IP address.
+    pub fn from_env() -> Self {
+        match std::env::var("TRUSTED_PROXIES") {
+            Ok(proxies) => TrustedProxies::new(proxies.split(',').map(|proxy| {
+                proxy
+                    .trim()
+                    .parse()
+                    .unwrap_or_else(|err| panic!("invalid TRUSTED_PROXIES entry {proxy:?}: {err}"))
+            })),
+            Err(_) => TrustedProxies::default(),
+        }
+    }
+
+    pub fn new(proxies: impl IntoIterator<Item = IpAddr>) -> Self {
+        TrustedProxies(proxies.into_iter().collect())
+    }
+
+    /// The address of the client behind `peer`: `peer` itself, or when that
+    /// is a trusted proxy, the nearest hop in the `X-Forwarded-For` value
+    /// `forwarded` that is not one.
+    pub fn client_ip(&self, peer: Option<IpAddr>, forwarded: Option<&str>) -> Option<String> {
+        let mut client = peer?;
+        if self.0.contains(&client) {
+            let hops = forwarded.unwrap_or_default().rsplit(',');
+            for hop in hops.map(|hop| hop.trim().parse::<IpAddr>()) {
+                let Ok(hop) = hop else {
+                    break;
+                };
+                client = hop;
+                if !self.0.contains(&hop) {
+                    break;
+                }
+            }
+        }
+        Some(client.to_string())
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub fn cookie_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// A cookie sent with the request the current server function is handling.
+#[cfg(feature = "ssr")]
+pub fn request_cookie(name: &str) -> Option<String> {
+    use_context::<Parts>().and_then(|parts| cookie_value(&parts.headers, name).map(str::to_string))
+}
+
+/// Adds a `Set-Cookie` header to the response of the current server function.
+#[cfg(feature = "ssr")]
+pub fn append_cookie(cookie: &str) {
+    if let (Some(response), Ok(value)) = (
+        use_context::<leptos_axum::ResponseOptions>(),
+        HeaderValue::from_str(cookie),
+    ) {
+        response.append_header(header::SET_COOKIE, value);
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub fn session_cookie(id: Uuid) -> String {
+    format!("{SESSION_COOKIE}={id}; Path=/; HttpOnly; SameSite=Lax")
+}
+
+/// The [`ClientInfo`] of the request the current server function is handling.
+#[cfg(feature = "ssr")]
+pub fn client_info() -> ClientInfo {
+    use_context::<Parts>()
+        .and_then(|parts| parts.extensions.get::<ClientInfo>().cloned())
+        .unwrap_or_default()
+}
+
+/// Starts a session for `user` on `client`'s browser and records the login,
+/// however they signed in. The caller sets the cookie.
+#[cfg(feature = "ssr")]
+pub fn start_session(
+    state: &crate::state::AppState,
+    users: &crate::state::TenantUsers,
+    user: Uuid,
+    client: &ClientInfo,
+    now: OffsetDateTime,
+) -> CurrentSession {
+    use crate::audit::{AuditAction, AuditEntry};
+
+    let session = state
+        .sessions
+        .create(user, &client.user_agent, client.ip.clone(), now);
+    users.update(user, |user| user.last_login = Some(now));
+    state.audit.record(AuditEntry {
+        at: now,
+        actor: Some(user),
+        subject: user,
+        action: AuditAction::SignedIn,
+        ip: client.ip.clone(),
+    });
+    session
+}
+
+/// Starts a session for `user` on the requesting browser and records the
+/// login.
+#[cfg(feature = "ssr")]
+pub fn sign_in(user: Uuid) -> Result<CurrentSession, ServerFnError> {
+    let state = crate::state::app_state()?;
+    let session = start_session(
+        &state,
+        &crate::state::tenant_users()?,
+        user,
+        &client_info(),
+        state.clock.now(),
+    );
+    append_cookie(&session_cookie(session.id));
+    Ok(session)
+}
+
+#[cfg(feature = "ssr")]
+pub fn sign_out() -> Result<(), ServerFnError> {
+    if let Ok(session) = crate::state::current_session() {
+        crate::audit::audit(session.user, crate::audit::AuditAction::SignedOut);
+        crate::state::app_state()?.sessions.revoke(session.id);
+    }
+    append_cookie(&format!("{SESSION_COOKIE}=; Path=/; Max-Age=0"));
+    Ok(())
+}
+
+#[server]
+pub async fn list_sessions(user: Uuid) -> Result<Vec<SessionInfo>, ServerFnError> {
+    use crate::state::{app_state, authorize_self_or_admin};
+
+    let current = authorize_self_or_admin(user)?;
+    let state = app_state()?;
+    Ok(state.sessions.for_user(user, current.id, state.clock.now()))
+}
+
+#[server]
+pub async fn revoke_session(id: Uuid) -> Result<(), ServerFnError> {
+    use crate::state::{app_state, authorize_self_or_admin};
+
+    let state = app_state()?;
+    let Some(owner) = state.sessions.owner(id) else {
+        return Ok(());
+    };
+    authorize_self_or_admin(owner)?;
+    state.sessions.revoke(id);
+    Ok(())
+}
+
+/// "Sign out everywhere": revokes all sessions of `user`, including the
+/// caller's own if it belongs to that user.
+#[server]
+pub async fn revoke_all_sessions(user: Uuid) -> Result<usize, ServerFnError> {
+    use crate::{
+        audit::{audit, AuditAction},
+        state::{app_state, authorize_self_or_admin, forbid_impersonation},
+    };
+
+    authorize_self_or_admin(user)?;
+    forbid_impersonation()?;
+    audit(user, AuditAction::SessionsRevoked);
+    Ok(app_state()?.sessions.revoke_all(user))
+}
+
+#[component]
+pub fn UserBrowsers(user: Uuid) -> impl IntoView {
+    let revoke = ServerAction::<RevokeSession>::new();
+    let revoke_all = ServerAction::<RevokeAllSessions>::new();
+
+    let sessions = Resource::new(
+        move || (revoke.version().get(), revoke_all.version().get()),
+        move |_| list_sessions(user),
+    );
+
+    view! {
+        <Transition fallback=|| view! { <p>{t!("user-loading")}</p> }>
+            {move || match sessions.get() {
+                Some(Ok(sessions)) => {
+                    view! {
+                        <table class="table table-sm align-middle">
+                            <thead>
+                                <tr>
+                                    <th>{t!("browsers-browser")}</th>
+                                    <th>{t!("browsers-os")}</th>
+                                    <th>{t!("browsers-ip")}</th>
+                                    <th>{t!("browsers-signed-in")}</th>
+                                    <th>{t!("browsers-last-seen")}</th>
+                                    <th></th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                {sessions
+                                    .into_iter()
+                                    .map(|session| {
+                                        let id = session.id;
+                                        view! {
+                                            <tr>
+                                                <td>
+                                                    {session.browser}
+                                                    <Show when=move || session.current>
+                                                        <span class="badge text-bg-primary ms-2">
+                                                            {t!("browsers-this-browser")}
+                                                        </span>
+                                                    </Show>
+                                                </td>
+                                                <td>{session.os}</td>
+                                                <td>{session.ip.unwrap_or_default()}</td>
+                                                <td>
+                                                    <DateTime value=session.created />
+                                                </td>
+                                                <td>
+                                                    <DateTime value=session.last_seen />
+                                                </td>
+                                                <td class="text-end">
+                                                    <button
+                                                        type="button"
+                                                        class="btn btn-sm btn-outline-danger"
+                                                        on:click=move |_| {
+                                                            revoke.dispatch(RevokeSession { id });
+                                                        }
+                                                    >
+                                                        {t!("browsers-revoke")}
+                                                    </button>
+                                                </td>
+                                            </tr>
+                                        }
+                                    })
+                                    .collect_view()}
+                            </tbody>
+                        </table>
+                        <button
+                            type="button"
+                            class="btn btn-danger"
+                            on:click=move |_| {
+                                revoke_all.dispatch(RevokeAllSessions { user });
+                            }
+                        >
+                            {t!("browsers-sign-out-everywhere")}
+                        </button>
+                    }
+                        .into_any()
+                }
+                Some(Err(err)) => {
+                    view! {
+                        <p>{t!("user-server-error")} " " <span>{format!("{err:?}")}</span></p>
+                    }
+                        .into_any()
+                }
+                None => view! { <div></div> }.into_any(),
+            }}
+        </Transition>
+    }
+}
@@ -0,0 +1,499 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, HashMap},
+    fmt,
+    sync::{Arc, RwLock},
+};
+
+use data_encoding::HEXLOWER;
+use hmac::{Hmac, Mac};
+use http::request::Parts;
+use leptos::prelude::*;
+use sha2::Sha256;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    audit::AuditLog,
+    avatar::AvatarStore,
+    invitation::Invitations,
+    ldap::LdapConnectors,
+    live::UserEvents,
+    mail::Mailer,
+    oauth::AuthorizationServer,
+    oidc::OidcLogins,
+    password::hash_password,
+    presence::Presence,
+    search::SearchIndex,
+    session::{CurrentSession, SessionStore, TrustedProxies},
+    tenant::{current_tenant, ServiceTokens, Tenant, TenantRegistry},
+    trusted_device::TrustedDeviceStore,
+    two_factor::TwoFactorStore,
+    user_csv::ImportStore,
+    users::ROLES,
+    webhook::Webhooks,
+    User,
+};
+
+/// Role granting access to other users' accounts.
+pub const ADMIN_ROLE: &str = "admin";
+
+/// Server-side state shared by the axum router and the server functions.
+///
+/// The server provides it to every request via context, so server functions
+/// get at it with [`app_state`].
+#[derive(Clone, Debug, Default)]
+pub struct AppState {
+    pub users: UserRepository,
+    pub roles: RoleCatalog,
+    pub sessions: SessionStore,
+    pub proxies: TrustedProxies,
+    pub two_factor: TwoFactorStore,
+    pub trusted_devices: TrustedDeviceStore,
+    pub signing_key: SigningKey,
+    pub tenants: TenantRegistry,
+    pub avatars: AvatarStore,
+    pub imports: ImportStore,
+    pub audit: AuditLog,
+    pub scim_tokens: ServiceTokens,
+    pub api_tokens: ServiceTokens,
+    pub oidc: OidcLogins,
+    pub oauth: AuthorizationServer,
+    pub ldap: LdapConnectors,
+    pub invitations: Invitations,
+    pub mailer: Mailer,
+    pub webhooks: Webhooks,
+    pub events: UserEvents,
+    pub presence: Presence,
+    pub clock: Clock,
+}
+
+impl AppState {
+    /// State seeded with the demo user returned by [`User::get_user`], who
+    /// is an administrator signing in with the password `demo`.
+    pub fn new() -> Self {
+        let state = AppState {
+            tenants: TenantRegistry::from_env(),
+            scim_tokens: ServiceTokens::from_env("SCIM_TOKENS"),
+            api_tokens: ServiceTokens::from_env("API_TOKENS"),
+            oidc: OidcLogins::from_env(),
+            oauth: AuthorizationServer::from_env(),
+            ldap: LdapConnectors::from_env(),
+            mailer: Mailer::from_env(),
+            proxies: TrustedProxies::from_env(),
+            ..Self::default()
+        };
+        state.users.observe(state.webhooks.clone());
+        state.users.observe(state.events.clone());
+        let mut demo = User::get_user();
+        demo.hash = hash_password("demo");
+        demo.roles.insert(ADMIN_ROLE.to_string());
+        state.users.insert(demo);
+        state
+    }
+}
+
+/// Source of the current time, replaceable by a fixed instant so that
+/// time-based codes can be checked deterministically.
+#[derive(Clone)]
+pub struct Clock(Arc<dyn Fn() -> OffsetDateTime + Send + Sync>);
+
+impl Clock {
+    pub fn system() -> Self {
+        Clock(Arc::new(OffsetDateTime::now_utc))
+    }
+
+    pub fn fixed(at: OffsetDateTime) -> Self {
+        Clock(Arc::new(move || at))
+    }
+
+    pub fn now(&self) -> OffsetDateTime {
+        (self.0)()
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock::system()
+    }
+}
+
+impl fmt::Debug for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Clock").field(&self.now()).finish()
+    }
+}
+
+/// Secret for HMAC-signed tokens handed to browsers.
+///
+/// It is generated on every start, which invalidates all outstanding tokens.
+#[derive(Clone)]
+pub struct SigningKey(Arc<[u8; 32]>);
+
+impl SigningKey {
+    pub fn sign(&self, message: &[u8]) -> String {
+        HEXLOWER.encode(&self.mac(message).finalize().into_bytes())
+    }
+
+    /// Checks a signature produced by [`SigningKey::sign`] in constant time.
+    pub fn verify(&self, message: &[u8], signature: &str) -> bool {
+        HEXLOWER
+            .decode(signature.as_bytes())
+            .is_ok_and(|signature| self.mac(message).verify_slice(&signature).is_ok())
+    }
+
+    fn mac(&self, message: &[u8]) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&*self.0).expect("HMAC accepts any key length");
+        mac.update(message);
+        mac
+    }
+}
+
+impl Default for SigningKey {
+    fn default() -> Self {
+        let mut key = [0; 32];
+        getrandom::fill(&mut key).expect("system RNG is available");
+        SigningKey(Arc::new(key))
+    }
+}
+
+impl fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SigningKey(..)")
+    }
+}
+
+/// A write to a user as [`UserObserver`]s see it: `before` is `None` for a
+/// new user, `after` for one removed.
+#[derive(Clone, Copy, Debug)]
+pub struct UserChange<'a> {
+    pub before: Option<&'a User>,
+    pub after: Option<&'a User>,
+}
+
+/// Told of every write to a user, in order, while it is being made, so it
+/// must not touch the users itself.
+pub trait UserObserver: fmt::Debug + Send + Sync {
+    fn changed(&self, change: UserChange<'_>);
+}
+
+/// In-memory store of users keyed by `unid`.
+///
+/// Reads and writes go through [`UserRepository::scoped`], so that a query
+/// can only ever see the users of one tenant.
+#[derive(Clone, Debug, Default)]
+pub struct UserRepository {
+    users: Arc<RwLock<HashMap<Uuid, User>>>,
+    /// Kept up to date under the write lock of `users`.
+    index: SearchIndex,
+    observers: Arc<RwLock<Vec<Arc<dyn UserObserver>>>>,
+}
+
+impl UserRepository {
+    /// Stores `user` as is, in whichever tenant its `site_schema` names.
+    pub fn insert(&self, user: User) {
+        let mut users = self.users.write().unwrap();
+        self.index.insert(&user);
+        let before = users.insert(user.unid, user.clone());
+        self.notify(before.as_ref(), Some(&user));
+    }
+
+    /// Tells `observer` of every write from now on.
+    pub fn observe(&self, observer: impl UserObserver + 'static) {
+        self.observers.write().unwrap().push(Arc::new(observer));
+    }
+
+    fn notify(&self, before: Option<&User>, after: Option<&User>) {
+        for observer in self.observers.read().unwrap().iter() {
+            observer.changed(UserChange { before, after });
+        }
+    }
+
+    /// Whether any user of any tenant has the profile picture `hash`.
+    pub fn avatar_in_use(&self, hash: &str) -> bool {
+        self.users
+            .read()
+            .unwrap()
+            .values()
+            .any(|user| user.avatar.as_deref() == Some(hash))
+    }
+
+    pub fn scoped(&self, tenant: &Tenant) -> TenantUsers {
+        TenantUsers {
+            users: self.clone(),
+            schema: tenant.schema.clone(),
+        }
+    }
+}
+
+/// Roles each tenant can grant. A tenant starts out with [`ROLES`].
+#[derive(Clone, Debug, Default)]
+pub struct RoleCatalog(Arc<RwLock<HashMap<Option<String>, BTreeSet<String>>>>);
+
+impl RoleCatalog {
+    pub fn scoped(&self, tenant: &Tenant) -> TenantRoles {
+        TenantRoles {
+            catalog: self.clone(),
+            schema: tenant.schema.clone(),
+        }
+    }
+}
+
+/// The roles of a single tenant.
+#[derive(Clone, Debug)]
+pub struct TenantRoles {
+    catalog: RoleCatalog,
+    schema: Option<String>,
+}
+
+impl TenantRoles {
+    fn with<T>(&self, f: impl FnOnce(&mut BTreeSet<String>) -> T) -> T {
+        let mut catalog = self.catalog.0.write().unwrap();
+        let roles = catalog
+            .entry(self.schema.clone())
+            .or_insert_with(|| ROLES.map(str::to_string).into());
+        f(roles)
+    }
+
+    /// All roles, sorted.
+    pub fn all(&self) -> Vec<String> {
+        self.with(|roles| roles.iter().cloned().collect())
+    }
+
+    /// The role named `name`, ignoring case.
+    pub fn find(&self, name: &str) -> Option<String> {
+        self.with(|roles| {
+            roles
+                .iter()
+                .find(|role| role.eq_ignore_ascii_case(name))
+                .cloned()
+        })
+    }
+
+    /// Adds `role`, returning `false` if it already exists.
+    pub fn insert(&self, role: &str) -> bool {
+        self.find(role).is_none() && self.with(|roles| roles.insert(role.to_string()))
+    }
+
+    /// Removes `role` from the catalog. Users keep it until it is taken
+    /// from them too.
+    pub fn remove(&self, role: &str) -> bool {
+        self.with(|roles| roles.remove(role))
+    }
+}
+
+/// The users of a single tenant.
+#[derive(Clone, Debug)]
+pub struct TenantUsers {
+    users: UserRepository,
+    schema: Option<String>,
+}
+
+impl TenantUsers {
+    fn visible(&self, user: &User) -> bool {
+        user.site_schema == self.schema
+    }
+
+    pub fn get(&self, unid: Uuid) -> Option<User> {
+        self.users
+            .users
+            .read()
+            .unwrap()
+            .get(&unid)
+            .filter(|user| self.visible(user))
+            .cloned()
+    }
+
+    /// Stores `user` in this tenant, whatever its `site_schema` said.
+    pub fn insert(&self, mut user: User) {
+        user.site_schema = self.schema.clone();
+        self.users.insert(user);
+    }
+
+    pub fn all(&self) -> Vec<User> {
+        self.users
+            .users
+            .read()
+            .unwrap()
+            .values()
+            .filter(|user| self.visible(user))
+            .cloned()
+            .collect()
+    }
+
+    /// Looks a user up by login, ignoring case.
+    pub fn find_by_login(&self, login: &str) -> Option<User> {
+        self.users
+            .users
+            .read()
+            .unwrap()
+            .values()
+            .filter(|user| self.visible(user))
+            .find(|user| user.login.eq_ignore_ascii_case(login))
+            .cloned()
+    }
+
+    /// The users best matching `query`, at most `limit` of them; see
+    /// [`crate::search`].
+    pub fn search(&self, query: &str, limit: usize) -> Vec<User> {
+        let mut found: Vec<(u32, User)> = self
+            .users
+            .index
+            .search(query)
+            .into_iter()
+            .filter_map(|(unid, score)| Some((score, self.get(unid)?)))
+            .collect();
+        found.sort_by_cached_key(|(score, user)| {
+            (Reverse(*score), user.login.to_lowercase())
+        });
+        found.into_iter().take(limit).map(|(_, user)| user).collect()
+    }
+
+    /// Stores all of `users` in this tenant, or none of them if one's login
+    /// is already taken, which is returned.
+    pub fn insert_all(&self, users: Vec<User>) -> Result<(), String> {
+        let mut stored = self.users.users.write().unwrap();
+        if let Some(taken) = users.iter().find(|user| {
+            stored
+                .values()
+                .filter(|existing| self.visible(existing))
+                .any(|existing| existing.login.eq_ignore_ascii_case(&user.login))
+        }) {
+            return Err(taken.login.clone());
+        }
+        for mut user in users {
+            user.site_schema = self.schema.clone();
+            self.users.index.insert(&user);
+            self.users.notify(None, Some(&user));
+            stored.insert(user.unid, user);
+        }
+        Ok(())
+    }
+
+    /// Applies `f` to each of `unids`, storing all the changes or, if `f`
+    /// fails for any user or one is not in this tenant (`None`), none.
+    /// Returns each user's outcome, in order.
+    pub fn update_all<E>(
+        &self,
+        unids: &[Uuid],
+        f: impl Fn(&mut User) -> Result<(), E>,
+    ) -> Vec<Option<Result<User, E>>> {
+        let mut stored = self.users.users.write().unwrap();
+        let outcomes: Vec<Option<Result<User, E>>> = unids
+            .iter()
+            .map(|unid| {
+                let mut user = stored.get(unid).filter(|user| self.visible(user))?.clone();
+                Some(f(&mut user).map(|()| User {
+                    site_schema: self.schema.clone(),
+                    ..user
+                }))
+            })
+            .collect();
+        if outcomes.iter().all(|outcome| matches!(outcome, Some(Ok(_)))) {
+            for user in outcomes.iter().flatten().flatten() {
+                self.users.index.insert(user);
+                let before = stored.insert(user.unid, user.clone());
+                self.users.notify(before.as_ref(), Some(user));
+            }
+        }
+        outcomes
+    }
+
+    /// Deletes the user outright, which only suits accounts never used,
+    /// such as invitations revoked. Others are erased instead, see
+    /// [`crate::erasure`].
+    pub fn remove(&self, unid: Uuid) -> Option<User> {
+        let mut users = self.users.users.write().unwrap();
+        users.get(&unid).filter(|user| self.visible(user))?;
+        self.users.index.remove(unid);
+        let removed = users.remove(&unid);
+        self.users.notify(removed.as_ref(), None);
+        removed
+    }
+
+    pub fn is_admin(&self, unid: Uuid) -> bool {
+        self.get(unid)
+            .is_some_and(|user| user.roles.contains(ADMIN_ROLE))
+    }
+
+    /// Applies `f` to the stored user, returning the updated copy.
+    ///
+    /// `f` cannot move the user to another tenant.
+    pub fn update(&self, unid: Uuid, f: impl FnOnce(&mut User)) -> Option<User> {
+        let mut users = self.users.users.write().unwrap();
+        let user = users.get_mut(&unid).filter(|user| self.visible(user))?;
+        let before = user.clone();
+        f(user);
+        user.site_schema = self.schema.clone();
+        self.users.index.insert(user);
+        self.users.notify(Some(&before), Some(user));
+        Some(user.clone())
+    }
+}
+
+pub fn app_state() -> Result<AppState, ServerFnError> {
+    use_context::<AppState>().ok_or_else(|| ServerFnError::new("AppState not provided"))
+}
+
+/// The session the session middleware attached to the current request.
+pub fn current_session() -> Result<CurrentSession, ServerFnError> {
+    use_context::<Parts>()
+        .and_then(|parts| parts.extensions.get::<CurrentSession>().copied())
+        .ok_or_else(|| ServerFnError::new("Not signed in"))
+}
+
+/// The roles of the tenant the current request is for.
+pub fn tenant_roles() -> Result<TenantRoles, ServerFnError> {
+    Ok(app_state()?.roles.scoped(&current_tenant()))
+}
+
+/// The users of the tenant the current request is for.
+pub fn tenant_users() -> Result<TenantUsers, ServerFnError> {
+    Ok(app_state()?.users.scoped(&current_tenant()))
+}
+
+/// The signed-in user, provided they belong to the current tenant.
+pub fn current_user() -> Result<User, ServerFnError> {
+    let session = current_session()?;
+    tenant_users()?
+        .get(session.user)
+        .ok_or_else(|| ServerFnError::new("Not signed in"))
+}
+
+/// Refuses actions only users may take themselves, such as changing how
+/// they sign in, when an administrator impersonates them.
+pub fn forbid_impersonation() -> Result<(), ServerFnError> {
+    match current_session()?.impersonator {
+        Some(_) => Err(ServerFnError::new("Not allowed while impersonating")),
+        None => Ok(()),
+    }
+}
+
+pub fn is_admin() -> Result<bool, ServerFnError> {
+    Ok(tenant_users()?.is_admin(current_session()?.user))
+}
+
+pub fn authorize_admin() -> Result<CurrentSession, ServerFnError> {
+    let session = current_session()?;
+    if is_admin()? {
+        Ok(session)
+    } else {
+        Err(ServerFnError::new("Forbidden"))
+    }
+}
+
+/// Allows the request if it acts on the signed-in user's own account or the
+/// signed-in user is an administrator, and the account belongs to the
+/// current tenant.
+pub fn authorize_self_or_admin(user: Uuid) -> Result<CurrentSession, ServerFnError> {
+    let session = current_session()?;
+    if tenant_users()?.get(user).is_none() {
+        return Err(ServerFnError::new("User not found"));
+    }
+    if session.user == user || is_admin()? {
+        Ok(session)
+    } else {
+        Err(ServerFnError::new("Forbidden"))
+    }
+}
@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "ssr")]
+use std::{collections::HashMap, sync::Arc};
+
+#[cfg(feature = "ssr")]
+use data_encoding::HEXLOWER;
+#[cfg(feature = "ssr")]
+use http::{header::AUTHORIZATION, HeaderMap};
+#[cfg(feature = "ssr")]
+use sha2::{Digest, Sha256};
+
+/// The site a request is for. Users whose `site_schema` differs from the
+/// tenant's are invisible to it.
+///
+/// `schema: None` is the default site, served on any host name that is not
+/// mapped to a tenant.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Tenant {
+    pub schema: Option<String>,
+}
+
+/// Maps host names to tenant schemas.
+///
+/// A host is looked up exactly first; failing that, a subdomain of the base
+/// domain names its schema, so `acme.example.com` is tenant `acme` when the
+/// base domain is `example.com` and `acme` is a known schema.
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Default)]
+pub struct TenantRegistry {
+    hosts: HashMap<String, String>,
+    base_domain: Option<String>,
+    schemas: Vec<String>,
+}
+
+#[cfg(feature = "ssr")]
+impl TenantRegistry {
+    /// Reads `TENANT_HOSTS` (`host=schema,host=schema`), `TENANT_SCHEMAS`
+    /// (`schema,schema`) and `TENANT_BASE_DOMAIN`.
+    pub fn from_env() -> Self {
+        let mut registry = TenantRegistry::default();
+        if let Ok(hosts) = std::env::var("TENANT_HOSTS") {
+            for (host, schema) in hosts
+                .split(',')
+                .filter_map(|pair| pair.trim().split_once('='))
+            {
+                registry = registry.with_host(host, schema);
+            }
+        }
+        if let Ok(schemas) = std::env::var("TENANT_SCHEMAS") {
+            for schema in schemas.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                registry = registry.with_schema(schema);
+            }
+        }
+        if let Ok(base_domain) = std::env::var("TENANT_BASE_DOMAIN") {
+            registry = registry.with_base_domain(&base_domain);
+        }
+        registry
+    }
+
+    pub fn with_host(mut self, host: &str, schema: &str) -> Self {
+        self.hosts
+            .insert(host.to_ascii_lowercase(), schema.to_string());
+        self.with_schema(schema)
+    }
+
+    pub fn with_schema(mut self, schema: &str) -> Self {
+        if !self.schemas.iter().any(|known| known == schema) {
+            self.schemas.push(schema.to_string());
+        }
+        self
+    }
+
+    pub fn with_base_domain(mut self, base_domain: &str) -> Self {
+        self.base_domain = Some(base_domain.to_ascii_lowercase());
+        self
+    }
+
+    /// The tenant for a `Host` header value, which may include a port.
+    pub fn resolve(&self, host: &str) -> Tenant {
+        let host = host.to_ascii_lowercase();
+        let host = host
+            .rsplit_once(':')
+            .filter(|(_, port)| port.bytes().all(|b| b.is_ascii_digit()))
+            .map_or(host.as_str(), |(name, _)| name);
+
+        let schema = self.hosts.get(host).cloned().or_else(|| {
+            let base_domain = self.base_domain.as_deref()?;
+            let subdomain = host.strip_suffix(base_domain)?.strip_suffix('.')?;
+            self.schemas
+                .iter()
+                .find(|schema| schema.eq_ignore_ascii_case(subdomain))
+                .cloned()
+        });
+        Tenant { schema }
+    }
+}
+
+/// Bearer tokens other services authenticate with, each bound to one
+/// tenant. Only SHA-256 hashes of the tokens are kept.
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Default)]
+pub struct ServiceTokens(Arc<HashMap<String, Tenant>>);
+
+#[cfg(feature = "ssr")]
+impl ServiceTokens {
+    /// Reads the variable `name` (`schema:token,schema:token`); a token
+    /// without a schema is for the default site.
+    pub fn from_env(name: &str) -> Self {
+        let mut tokens = ServiceTokens::default();
+        if let Ok(pairs) = std::env::var(name) {
+            for pair in pairs.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+                let (schema, token) = match pair.split_once(':') {
+                    Some((schema, token)) => (Some(schema.to_string()), token),
+                    None => (None, pair),
+                };
+                tokens = tokens.with_token(Tenant { schema }, token);
+            }
+        }
+        tokens
+    }
+
+    pub fn with_token(self, tenant: Tenant, token: &str) -> Self {
+        let mut tokens = Arc::unwrap_or_clone(self.0);
+        tokens.insert(token_hash(token), tenant);
+        ServiceTokens(Arc::new(tokens))
+    }
+
+    /// The tenant `token` grants access to.
+    pub fn tenant(&self, token: &str) -> Option<Tenant> {
+        self.0.get(&token_hash(token)).cloned()
+    }
+
+    /// The tenant of the bearer token in a request's `Authorization` header,
+    /// if it has a known one.
+    pub fn authenticate(&self, headers: &HeaderMap) -> Option<Tenant> {
+        headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .and_then(|token| self.tenant(token.trim()))
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn token_hash(token: &str) -> String {
+    HEXLOWER.encode(&Sha256::digest(token.as_bytes()))
+}
+
+/// The tenant the tenant middleware resolved for the current request.
+#[cfg(feature = "ssr")]
+pub fn current_tenant() -> Tenant {
+    leptos::prelude::use_context::<http::request::Parts>()
+        .and_then(|parts| parts.extensions.get::<Tenant>().cloned())
+        .unwrap_or_default()
+}
@@ -0,0 +1,132 @@
+use leptos::prelude::*;
+use uuid::Uuid;
+
+use crate::i18n::tr;
+
+pub use model::Theme;
+
+/// Name of the cookie remembering the theme between requests, so the server
+/// can render the right one before anyone has signed in.
+pub const THEME_COOKIE: &str = "theme";
+
+/// The theme of the page, provided by [`provide_theme`].
+#[derive(Clone, Copy, Debug)]
+pub struct ThemeContext(pub RwSignal<Theme>);
+
+/// Theme to render the page with.
+///
+/// On the server it is the signed-in user's choice, else the cookie. In the
+/// browser it is read back from the `<html>` element the server rendered, so
+/// hydration starts from the same value.
+pub fn initial_theme() -> Theme {
+    #[cfg(feature = "ssr")]
+    {
+        crate::state::current_user()
+            .map(|user| user.theme)
+            .ok()
+            .or_else(|| {
+                crate::session::request_cookie(THEME_COOKIE).and_then(|value| value.parse().ok())
+            })
+            .unwrap_or_default()
+    }
+    #[cfg(not(feature = "ssr"))]
+    {
+        document()
+            .document_element()
+            .and_then(|html| html.get_attribute("data-bs-theme"))
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Provides the [`ThemeContext`] and keeps `<html data-bs-theme>` in sync
+/// with it after hydration.
+pub fn provide_theme() -> ThemeContext {
+    let theme = ThemeContext(RwSignal::new(initial_theme()));
+    provide_context(theme);
+    Effect::new(move || {
+        let value = theme.0.get();
+        if let Some(html) = document().document_element() {
+            _ = html.set_attribute("data-bs-theme", value.as_str());
+        }
+    });
+    theme
+}
+
+#[cfg(feature = "ssr")]
+fn remember_theme(theme: Theme) {
+    crate::session::append_cookie(&format!(
+        "{THEME_COOKIE}={}; Path=/; SameSite=Lax; Max-Age=31536000",
+        theme.as_str()
+    ));
+}
+
+/// Saves the viewer's theme: always in the cookie, and on their account if
+/// they are signed in.
+#[server]
+pub async fn save_theme(theme: Theme) -> Result<(), ServerFnError> {
+    use crate::state::{current_session, tenant_users};
+
+    if let Ok(session) = current_session() {
+        tenant_users()?.update(session.user, |user| user.theme = theme);
+    }
+    remember_theme(theme);
+    Ok(())
+}
+
+/// Saves `user`'s theme through [`crate::user_update::update_user`],
+/// returning it if that is also the viewer, whose page should switch
+/// straight away.
+#[server]
+pub async fn set_user_theme(user: Uuid, theme: Theme) -> Result<Option<Theme>, ServerFnError> {
+    use crate::{
+        session::client_info,
+        state::{app_state, current_session, tenant_roles, tenant_users},
+        user_update::{update_user, UserUpdate},
+    };
+
+    let session = current_session()?;
+    update_user(
+        &app_state()?,
+        &tenant_users()?,
+        &tenant_roles()?,
+        Some(&session),
+        user,
+        UserUpdate {
+            theme: Some(theme),
+            ..Default::default()
+        },
+        client_info().ip,
+    )
+    .map_err(ServerFnError::new)?;
+    if session.user == user {
+        remember_theme(theme);
+        Ok(Some(theme))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Light/dark switch for the app chrome.
+#[component]
+pub fn ThemeToggle() -> impl IntoView {
+    let ThemeContext(theme) = expect_context::<ThemeContext>();
+    let save = ServerAction::<SaveTheme>::new();
+
+    view! {
+        <button
+            type="button"
+            class="btn btn-sm btn-outline-secondary"
+            on:click=move |_| {
+                let next = theme.get_untracked().toggled();
+                theme.set(next);
+                save.dispatch(SaveTheme { theme: next });
+            }
+        >
+            {move || match theme.get() {
+                Theme::Light => tr("theme-dark-mode"),
+                Theme::Dark => tr("theme-light-mode"),
+            }}
+        </button>
+    }
+}
@@ -0,0 +1,92 @@
+//! RFC 6238 time-based one-time passwords: HMAC-SHA1, six digits, 30 second
+//! steps, the parameters every authenticator app understands.
+
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use qrcode::{render::svg, QrCode};
+use sha1::Sha1;
+
+const STEP: i64 = 30;
+const DIGITS: u32 = 6;
+/// Number of steps either side of the current one still accepted, to allow
+/// for clock drift on the user's phone.
+const SKEW: i64 = 1;
+
+pub fn generate_secret() -> Vec<u8> {
+    let mut secret = vec![0; 20];
+    getrandom::fill(&mut secret).expect("system RNG is available");
+    secret
+}
+
+pub fn encode_secret(secret: &[u8]) -> String {
+    BASE32_NOPAD.encode(secret)
+}
+
+/// The code shown by an authenticator app at `unix_time`.
+pub fn code_at(secret: &[u8], unix_time: i64) -> String {
+    format!(
+        "{:0width$}",
+        hotp(secret, (unix_time / STEP) as u64),
+        width = DIGITS as usize
+    )
+}
+
+/// Checks `code` against the steps around `unix_time` and returns the
+/// matching step.
+///
+/// Steps at or before `last_step` are rejected so a code cannot be replayed.
+pub fn verify(secret: &[u8], code: &str, unix_time: i64, last_step: Option<u64>) -> Option<u64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let current = unix_time / STEP;
+    (current - SKEW..=current + SKEW)
+        .filter(|&step| step >= 0)
+        .map(|step| step as u64)
+        .filter(|&step| last_step.is_none_or(|last| step > last))
+        .find(|&step| code_at(secret, step as i64 * STEP) == code)
+}
+
+/// Provisioning URI understood by authenticator apps.
+pub fn otpauth_uri(issuer: &str, account: &str, secret: &[u8]) -> String {
+    let issuer = percent_encode(issuer);
+    format!(
+        "otpauth://totp/{issuer}:{}?secret={}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={STEP}",
+        percent_encode(account),
+        encode_secret(secret),
+    )
+}
+
+/// The provisioning URI as a QR code, as an inline `<svg>` element.
+pub fn qr_svg(uri: &str) -> String {
+    QrCode::new(uri.as_bytes())
+        .map(|code| code.render::<svg::Color>().min_dimensions(200, 200).build())
+        .unwrap_or_default()
+}
+
+fn hotp(secret: &[u8], counter: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    binary % 10u32.pow(DIGITS)
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
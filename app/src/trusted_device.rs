@@ -0,0 +1,305 @@
+use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{datetime::DateTime, t};
+
+#[cfg(feature = "ssr")]
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+#[cfg(feature = "ssr")]
+use time::Duration;
+
+#[cfg(feature = "ssr")]
+use crate::{
+    session::{ClientInfo, UserAgent},
+    state::SigningKey,
+};
+
+/// Name of the cookie carrying the trusted-device token.
+#[cfg(feature = "ssr")]
+pub const TRUSTED_DEVICE_COOKIE: &str = "trusted_device";
+#[cfg(feature = "ssr")]
+pub const TRUST_LIFETIME: Duration = Duration::days(30);
+
+/// A browser allowed to skip the second factor, as shown in the
+/// "Web browsers" tab.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustedDeviceInfo {
+    pub id: Uuid,
+    pub user: Uuid,
+    pub browser: String,
+    pub os: String,
+    pub ip: Option<String>,
+    pub created: OffsetDateTime,
+    pub last_used: OffsetDateTime,
+    pub expires: OffsetDateTime,
+    /// Whether this is the browser making the request.
+    pub current: bool,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug)]
+struct TrustedDevice {
+    user: Uuid,
+    fingerprint: String,
+    agent: UserAgent,
+    ip: Option<String>,
+    created: OffsetDateTime,
+    last_used: OffsetDateTime,
+    expires: OffsetDateTime,
+}
+
+/// Devices whose trusted-device token is still honoured.
+///
+/// The token is signed so it cannot be forged, but is also looked up here so
+/// that trust can be withdrawn before it expires.
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Default)]
+pub struct TrustedDeviceStore(Arc<RwLock<HashMap<Uuid, TrustedDevice>>>);
+
+#[cfg(feature = "ssr")]
+impl TrustedDeviceStore {
+    /// Trusts the requesting browser for `user` and returns the token to
+    /// store in its cookie.
+    pub fn trust(
+        &self,
+        key: &SigningKey,
+        user: Uuid,
+        client: &ClientInfo,
+        now: OffsetDateTime,
+    ) -> String {
+        let id = Uuid::new_v4();
+        let agent = UserAgent::parse(&client.user_agent);
+        let device = TrustedDevice {
+            user,
+            fingerprint: fingerprint(&agent),
+            agent,
+            ip: client.ip.clone(),
+            created: now,
+            last_used: now,
+            expires: now + TRUST_LIFETIME,
+        };
+        let expires = device.expires.unix_timestamp();
+        let signature = key.sign(signed_message(id, &device, expires).as_bytes());
+        self.0.write().unwrap().insert(id, device);
+        format!("{id}.{expires}.{signature}")
+    }
+
+    /// Whether `token` was issued to `user` on a browser like the requesting
+    /// one and has been neither revoked nor expired.
+    pub fn verify(
+        &self,
+        key: &SigningKey,
+        token: &str,
+        user: Uuid,
+        client: &ClientInfo,
+        now: OffsetDateTime,
+    ) -> bool {
+        let mut parts = token.splitn(3, '.');
+        let (Some(id), Some(expires), Some(signature)) = (
+            parts.next().and_then(|id| id.parse::<Uuid>().ok()),
+            parts.next().and_then(|expires| expires.parse::<i64>().ok()),
+            parts.next(),
+        ) else {
+            return false;
+        };
+
+        let mut devices = self.0.write().unwrap();
+        let Some(device) = devices.get_mut(&id) else {
+            return false;
+        };
+        let valid = device.user == user
+            && device.expires > now
+            && device.expires.unix_timestamp() == expires
+            && device.fingerprint == fingerprint(&UserAgent::parse(&client.user_agent))
+            && key.verify(signed_message(id, device, expires).as_bytes(), signature);
+        if valid {
+            device.last_used = now;
+        }
+        valid
+    }
+
+    /// Trusted devices of `user`, most recently used first. `current` is the
+    /// id from the requesting browser's token, if any.
+    pub fn for_user(
+        &self,
+        user: Uuid,
+        current: Option<Uuid>,
+        now: OffsetDateTime,
+    ) -> Vec<TrustedDeviceInfo> {
+        let mut list = self
+            .0
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, device)| device.user == user && device.expires > now)
+            .map(|(&id, device)| TrustedDeviceInfo {
+                id,
+                user: device.user,
+                browser: device.agent.browser.clone(),
+                os: device.agent.os.clone(),
+                ip: device.ip.clone(),
+                created: device.created,
+                last_used: device.last_used,
+                expires: device.expires,
+                current: Some(id) == current,
+            })
+            .collect::<Vec<_>>();
+        list.sort_by_key(|device| Reverse(device.last_used));
+        list
+    }
+
+    pub fn owner(&self, id: Uuid) -> Option<Uuid> {
+        self.0.read().unwrap().get(&id).map(|device| device.user)
+    }
+
+    pub fn revoke(&self, id: Uuid) -> bool {
+        self.0.write().unwrap().remove(&id).is_some()
+    }
+
+    pub fn revoke_all(&self, user: Uuid) -> usize {
+        let mut devices = self.0.write().unwrap();
+        let before = devices.len();
+        devices.retain(|_, device| device.user != user);
+        before - devices.len()
+    }
+}
+
+/// Identifies a browser by family and platform rather than exact version, so
+/// that trust survives browser updates.
+#[cfg(feature = "ssr")]
+fn fingerprint(agent: &UserAgent) -> String {
+    let family = agent.browser.split(' ').next().unwrap_or_default();
+    format!("{family}/{}", agent.os)
+}
+
+#[cfg(feature = "ssr")]
+fn signed_message(id: Uuid, device: &TrustedDevice, expires: i64) -> String {
+    format!("{id}.{}.{}.{expires}", device.user, device.fingerprint)
+}
+
+/// The device id in the requesting browser's trusted-device token.
+#[cfg(feature = "ssr")]
+pub fn current_device() -> Option<Uuid> {
+    crate::session::request_cookie(TRUSTED_DEVICE_COOKIE)?
+        .split('.')
+        .next()?
+        .parse()
+        .ok()
+}
+
+#[server]
+pub async fn list_trusted_devices(user: Uuid) -> Result<Vec<TrustedDeviceInfo>, ServerFnError> {
+    use crate::state::{app_state, authorize_self_or_admin};
+
+    authorize_self_or_admin(user)?;
+    let state = app_state()?;
+    Ok(state
+        .trusted_devices
+        .for_user(user, current_device(), state.clock.now()))
+}
+
+#[server]
+pub async fn revoke_trusted_device(id: Uuid) -> Result<(), ServerFnError> {
+    use crate::state::{app_state, authorize_self_or_admin};
+
+    let state = app_state()?;
+    let Some(owner) = state.trusted_devices.owner(id) else {
+        return Ok(());
+    };
+    authorize_self_or_admin(owner)?;
+    state.trusted_devices.revoke(id);
+    Ok(())
+}
+
+#[component]
+pub fn TrustedDevices(user: Uuid) -> impl IntoView {
+    let revoke = ServerAction::<RevokeTrustedDevice>::new();
+
+    let devices = Resource::new(
+        move || revoke.version().get(),
+        move |_| list_trusted_devices(user),
+    );
+
+    view! {
+        <h5 class="mt-4">{t!("browsers-trusted")}</h5>
+        <Transition fallback=|| view! { <p>{t!("user-loading")}</p> }>
+            {move || match devices.get() {
+                Some(Ok(devices)) if devices.is_empty() => {
+                    view! {
+                        <p class="text-body-secondary">{t!("browsers-trusted-none")}</p>
+                    }
+                        .into_any()
+                }
+                Some(Ok(devices)) => {
+                    view! {
+                        <table class="table table-sm align-middle">
+                            <thead>
+                                <tr>
+                                    <th>{t!("browsers-browser")}</th>
+                                    <th>{t!("browsers-os")}</th>
+                                    <th>{t!("browsers-ip")}</th>
+                                    <th>{t!("browsers-last-used")}</th>
+                                    <th>{t!("browsers-trusted-until")}</th>
+                                    <th></th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                {devices
+                                    .into_iter()
+                                    .map(|device| {
+                                        let id = device.id;
+                                        view! {
+                                            <tr>
+                                                <td>
+                                                    {device.browser}
+                                                    <Show when=move || device.current>
+                                                        <span class="badge text-bg-primary ms-2">
+                                                            {t!("browsers-this-browser")}
+                                                        </span>
+                                                    </Show>
+                                                </td>
+                                                <td>{device.os}</td>
+                                                <td>{device.ip.unwrap_or_default()}</td>
+                                                <td>
+                                                    <DateTime value=device.last_used />
+                                                </td>
+                                                <td>
+                                                    <DateTime value=device.expires />
+                                                </td>
+                                                <td class="text-end">
+                                                    <button
+                                                        type="button"
+                                                        class="btn btn-sm btn-outline-danger"
+                                                        on:click=move |_| {
+                                                            revoke.dispatch(RevokeTrustedDevice { id });
+                                                        }
+                                                    >
+                                                        {t!("browsers-revoke-trust")}
+                                                    </button>
+                                                </td>
+                                            </tr>
+                                        }
+                                    })
+                                    .collect_view()}
+                            </tbody>
+                        </table>
+                    }
+                        .into_any()
+                }
+                Some(Err(err)) => {
+                    view! {
+                        <p>{t!("user-server-error")} " " <span>{format!("{err:?}")}</span></p>
+                    }
+                        .into_any()
+                }
+                None => view! { <div></div> }.into_any(),
+            }}
+        </Transition>
+    }
+}
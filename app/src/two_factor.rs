@@ -0,0 +1,432 @@
+use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::t;
+
+#[cfg(feature = "ssr")]
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, RwLock},
+};
+#[cfg(feature = "ssr")]
+use time::{Duration, OffsetDateTime};
+
+#[cfg(feature = "ssr")]
+use crate::totp;
+
+/// Name of the cookie linking a half-finished login to its challenge.
+#[cfg(feature = "ssr")]
+pub const CHALLENGE_COOKIE: &str = "login_challenge";
+#[cfg(feature = "ssr")]
+const CHALLENGE_LIFETIME: Duration = Duration::minutes(5);
+/// Wrong codes a challenge takes before the login has to start over.
+#[cfg(feature = "ssr")]
+pub const MAX_CODE_ATTEMPTS: u32 = 5;
+#[cfg(feature = "ssr")]
+const RECOVERY_CODES: usize = 10;
+#[cfg(feature = "ssr")]
+const ISSUER: &str = "Blabla";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+    pub recovery_codes_left: usize,
+    /// Whether the viewer is looking at their own account and may enroll.
+    pub is_self: bool,
+    /// Whether the viewer may reset this user's second factor.
+    pub can_reset: bool,
+}
+
+/// What a user needs to add the account to an authenticator app.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TotpEnrollment {
+    pub secret: String,
+    pub otpauth_uri: String,
+    pub qr_svg: String,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug)]
+struct Enrollment {
+    secret: Vec<u8>,
+    confirmed: bool,
+    /// SHA-256 hashes of the unused recovery codes.
+    recovery_codes: Vec<String>,
+    last_step: Option<u64>,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug)]
+struct Challenge {
+    user: Uuid,
+    expires: OffsetDateTime,
+    failures: u32,
+    /// Roles an identity provider mapped, given once the code is right.
+    roles: Option<HashSet<String>>,
+}
+
+/// Second-factor secrets and login challenges awaiting a code.
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Default)]
+pub struct TwoFactorStore {
+    enrollments: Arc<RwLock<HashMap<Uuid, Enrollment>>>,
+    challenges: Arc<RwLock<HashMap<Uuid, Challenge>>>,
+}
+
+#[cfg(feature = "ssr")]
+impl TwoFactorStore {
+    pub fn is_enabled(&self, user: Uuid) -> bool {
+        self.enrollments
+            .read()
+            .unwrap()
+            .get(&user)
+            .is_some_and(|enrollment| enrollment.confirmed)
+    }
+
+    pub fn recovery_codes_left(&self, user: Uuid) -> usize {
+        self.enrollments
+            .read()
+            .unwrap()
+            .get(&user)
+            .filter(|enrollment| enrollment.confirmed)
+            .map_or(0, |enrollment| enrollment.recovery_codes.len())
+    }
+
+    /// Starts enrollment with a fresh secret, replacing any unconfirmed one.
+    pub fn begin(&self, user: Uuid) -> Option<Vec<u8>> {
+        let mut enrollments = self.enrollments.write().unwrap();
+        if enrollments.get(&user).is_some_and(|e| e.confirmed) {
+            return None;
+        }
+        let secret = totp::generate_secret();
+        enrollments.insert(
+            user,
+            Enrollment {
+                secret: secret.clone(),
+                confirmed: false,
+                recovery_codes: Vec::new(),
+                last_step: None,
+            },
+        );
+        Some(secret)
+    }
+
+    /// Confirms enrollment with a code from the app and returns the plaintext
+    /// recovery codes, which are only ever shown this once.
+    pub fn confirm(&self, user: Uuid, code: &str, now: OffsetDateTime) -> Option<Vec<String>> {
+        let mut enrollments = self.enrollments.write().unwrap();
+        let enrollment = enrollments.get_mut(&user).filter(|e| !e.confirmed)?;
+        let step = totp::verify(&enrollment.secret, code, now.unix_timestamp(), None)?;
+        let codes = (0..RECOVERY_CODES)
+            .map(|_| generate_recovery_code())
+            .collect::<Vec<_>>();
+        enrollment.confirmed = true;
+        enrollment.last_step = Some(step);
+        enrollment.recovery_codes = codes.iter().map(|code| hash_recovery_code(code)).collect();
+        Some(codes)
+    }
+
+    /// Accepts either a current TOTP code or an unused recovery code, which
+    /// is consumed.
+    pub fn verify(&self, user: Uuid, code: &str, now: OffsetDateTime) -> bool {
+        let mut enrollments = self.enrollments.write().unwrap();
+        let Some(enrollment) = enrollments.get_mut(&user).filter(|e| e.confirmed) else {
+            return false;
+        };
+        if let Some(step) = totp::verify(
+            &enrollment.secret,
+            code,
+            now.unix_timestamp(),
+            enrollment.last_step,
+        ) {
+            enrollment.last_step = Some(step);
+            return true;
+        }
+        let hash = hash_recovery_code(code);
+        let before = enrollment.recovery_codes.len();
+        enrollment.recovery_codes.retain(|stored| *stored != hash);
+        enrollment.recovery_codes.len() < before
+    }
+
+    pub fn reset(&self, user: Uuid) -> bool {
+        self.enrollments.write().unwrap().remove(&user).is_some()
+    }
+
+    /// Records that `user` passed the password check and now owes a code.
+    pub fn challenge(&self, user: Uuid, now: OffsetDateTime) -> Uuid {
+        self.challenge_granting(user, None, now)
+    }
+
+    /// Like [`Self::challenge`], for a sign-in with an identity provider
+    /// that mapped `roles`, which [`Self::end_challenge`] hands back.
+    pub fn challenge_granting(
+        &self,
+        user: Uuid,
+        roles: Option<HashSet<String>>,
+        now: OffsetDateTime,
+    ) -> Uuid {
+        let id = Uuid::new_v4();
+        let mut challenges = self.challenges.write().unwrap();
+        challenges.retain(|_, challenge| challenge.expires > now);
+        challenges.insert(
+            id,
+            Challenge {
+                user,
+                expires: now + CHALLENGE_LIFETIME,
+                failures: 0,
+                roles,
+            },
+        );
+        id
+    }
+
+    pub fn challenged_user(&self, id: Uuid, now: OffsetDateTime) -> Option<Uuid> {
+        self.challenges
+            .read()
+            .unwrap()
+            .get(&id)
+            .filter(|challenge| challenge.expires > now)
+            .map(|challenge| challenge.user)
+    }
+
+    /// Counts a wrong code against the challenge, ending it after
+    /// [`MAX_CODE_ATTEMPTS`]. Returns whether it may still be answered.
+    pub fn fail_challenge(&self, id: Uuid) -> bool {
+        let mut challenges = self.challenges.write().unwrap();
+        let Some(challenge) = challenges.get_mut(&id) else {
+            return false;
+        };
+        challenge.failures += 1;
+        if challenge.failures < MAX_CODE_ATTEMPTS {
+            return true;
+        }
+        challenges.remove(&id);
+        false
+    }
+
+    /// Ends a challenge that was passed, returning the roles to give the
+    /// user if it was started with some.
+    pub fn end_challenge(&self, id: Uuid) -> Option<HashSet<String>> {
+        self.challenges
+            .write()
+            .unwrap()
+            .remove(&id)
+            .and_then(|challenge| challenge.roles)
+    }
+}
+
+/// Ten base32 characters, grouped as `xxxxx-xxxxx` for readability.
+#[cfg(feature = "ssr")]
+fn generate_recovery_code() -> String {
+    let mut bytes = [0; 7];
+    getrandom::fill(&mut bytes).expect("system RNG is available");
+    let encoded = totp::encode_secret(&bytes).to_lowercase();
+    format!("{}-{}", &encoded[..5], &encoded[5..10])
+}
+
+/// Recovery codes are compared case-insensitively and without separators.
+#[cfg(feature = "ssr")]
+fn hash_recovery_code(code: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    let normalized = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect::<String>();
+    Sha256::digest(normalized.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[server]
+pub async fn get_two_factor_status(user: Uuid) -> Result<TwoFactorStatus, ServerFnError> {
+    use crate::state::{app_state, authorize_self_or_admin, is_admin};
+
+    let session = authorize_self_or_admin(user)?;
+    let state = app_state()?;
+    Ok(TwoFactorStatus {
+        enabled: state.two_factor.is_enabled(user),
+        recovery_codes_left: state.two_factor.recovery_codes_left(user),
+        is_self: session.user == user,
+        can_reset: is_admin()?,
+    })
+}
+
+/// Starts authenticator app enrollment for the signed-in user.
+#[server]
+pub async fn begin_totp_enrollment() -> Result<TotpEnrollment, ServerFnError> {
+    use crate::state::{app_state, current_user, forbid_impersonation};
+
+    forbid_impersonation()?;
+    let user = current_user()?;
+    let secret = app_state()?
+        .two_factor
+        .begin(user.unid)
+        .ok_or_else(|| ServerFnError::new("Two-factor authentication is already enabled"))?;
+    let otpauth_uri = totp::otpauth_uri(ISSUER, &user.login, &secret);
+    Ok(TotpEnrollment {
+        secret: totp::encode_secret(&secret),
+        qr_svg: totp::qr_svg(&otpauth_uri),
+        otpauth_uri,
+    })
+}
+
+/// Finishes enrollment and returns the recovery codes.
+#[server]
+pub async fn confirm_totp_enrollment(code: String) -> Result<Vec<String>, ServerFnError> {
+    use crate::{
+        audit::{audit, AuditAction},
+        state::{app_state, current_user, forbid_impersonation},
+    };
+
+    forbid_impersonation()?;
+    let user = current_user()?;
+    let state = app_state()?;
+    let codes = state
+        .two_factor
+        .confirm(user.unid, &code, state.clock.now())
+        .ok_or_else(|| ServerFnError::new("The code is not valid"))?;
+    audit(user.unid, AuditAction::SecondFactorEnabled);
+    Ok(codes)
+}
+
+#[server]
+pub async fn reset_two_factor(user: Uuid) -> Result<(), ServerFnError> {
+    use crate::{
+        audit::{audit, AuditAction},
+        state::{app_state, authorize_self_or_admin, forbid_impersonation, is_admin},
+    };
+
+    authorize_self_or_admin(user)?;
+    if !is_admin()? {
+        return Err(ServerFnError::new("Forbidden"));
+    }
+    forbid_impersonation()?;
+    let state = app_state()?;
+    state.two_factor.reset(user);
+    state.trusted_devices.revoke_all(user);
+    audit(user, AuditAction::SecondFactorReset);
+    Ok(())
+}
+
+#[component]
+pub fn TwoFactorSettings(user: Uuid) -> impl IntoView {
+    let begin = ServerAction::<BeginTotpEnrollment>::new();
+    let confirm = ServerAction::<ConfirmTotpEnrollment>::new();
+    let reset = ServerAction::<ResetTwoFactor>::new();
+    let code = RwSignal::new(String::new());
+
+    let status = Resource::new(
+        move || (confirm.version().get(), reset.version().get()),
+        move |_| get_two_factor_status(user),
+    );
+
+    view! {
+        <Transition fallback=|| view! { <p>{t!("user-loading")}</p> }>
+            {move || match status.get() {
+                Some(Ok(status)) if status.enabled => {
+                    let left = status.recovery_codes_left as i64;
+                    view! {
+                        <p>{t!("two-factor-enabled", count = left)}</p>
+                        <Show when=move || status.can_reset>
+                            <button
+                                type="button"
+                                class="btn btn-outline-danger"
+                                on:click=move |_| {
+                                    reset.dispatch(ResetTwoFactor { user });
+                                }
+                            >
+                                {t!("two-factor-reset")}
+                            </button>
+                        </Show>
+                    }
+                        .into_any()
+                }
+                Some(Ok(status)) if status.is_self => {
+                    view! {
+                        <p>{t!("two-factor-disabled")}</p>
+                        {move || match begin.value().get() {
+                            Some(Ok(enrollment)) => {
+                                view! {
+                                    <p>
+                                        {t!("two-factor-scan")} " "
+                                        <code>{enrollment.secret}</code>
+                                    </p>
+                                    <div inner_html=enrollment.qr_svg></div>
+                                    <div class="input-group w-auto mt-3">
+                                        <input
+                                            type="text"
+                                            class="form-control"
+                                            inputmode="numeric"
+                                            autocomplete="one-time-code"
+                                            placeholder="123456"
+                                            bind:value=code
+                                        />
+                                        <button
+                                            type="button"
+                                            class="btn btn-primary"
+                                            on:click=move |_| {
+                                                confirm
+                                                    .dispatch(ConfirmTotpEnrollment {
+                                                        code: code.get(),
+                                                    });
+                                            }
+                                        >
+                                            {t!("two-factor-confirm")}
+                                        </button>
+                                    </div>
+                                }
+                                    .into_any()
+                            }
+                            _ => {
+                                view! {
+                                    <button
+                                        type="button"
+                                        class="btn btn-primary"
+                                        on:click=move |_| {
+                                            begin.dispatch(BeginTotpEnrollment {});
+                                        }
+                                    >
+                                        {t!("two-factor-set-up")}
+                                    </button>
+                                }
+                                    .into_any()
+                            }
+                        }}
+                    }
+                        .into_any()
+                }
+                Some(Ok(_)) => {
+                    view! { <p>{t!("two-factor-disabled")}</p> }.into_any()
+                }
+                Some(Err(err)) => {
+                    view! {
+                        <p>{t!("user-server-error")} " " <span>{format!("{err:?}")}</span></p>
+                    }
+                        .into_any()
+                }
+                None => view! { <div></div> }.into_any(),
+            }}
+        </Transition>
+        {move || match confirm.value().get() {
+            Some(Ok(codes)) => {
+                view! {
+                    <div class="alert alert-warning mt-3">
+                        <p>{t!("two-factor-recovery-codes")}</p>
+                        <ul class="list-unstyled font-monospace mb-0">
+                            {codes.into_iter().map(|code| view! { <li>{code}</li> }).collect_view()}
+                        </ul>
+                    </div>
+                }
+                    .into_any()
+            }
+            Some(Err(err)) => {
+                view! { <div class="alert alert-danger mt-3">{err.to_string()}</div> }.into_any()
+            }
+            None => ().into_any(),
+        }}
+    }
+}
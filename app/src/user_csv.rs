@@ -0,0 +1,784 @@
+//! Bulk export of users to CSV and staged import from it.
+//!
+//! An import is uploaded once and kept on the server while the columns are
+//! mapped and the rows checked; only a clean check can be committed, and the
+//! commit adds every row or none.
+
+use leptos::prelude::*;
+use leptos::server_fn::codec::{Json, MultipartData, MultipartFormData};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter, EnumString};
+use uuid::Uuid;
+
+#[cfg(feature = "ssr")]
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    i18n::{tr, tr_args},
+    t,
+};
+#[cfg(feature = "ssr")]
+use crate::{
+    state::{TenantRoles, TenantUsers},
+    tenant::Tenant,
+    User,
+};
+
+/// Largest CSV file accepted, in bytes.
+pub const MAX_IMPORT_BYTES: usize = 2 * 1024 * 1024;
+
+/// Columns written by the export, in order. An export can be imported again
+/// as is; `unid` and the timestamps are ignored then.
+pub const EXPORT_HEADERS: [&str; 9] = [
+    "unid",
+    "login",
+    "first_name",
+    "last_name",
+    "status",
+    "roles",
+    "language",
+    "created",
+    "last_login",
+];
+
+/// Separates several roles within one cell.
+pub const ROLE_SEPARATOR: char = ';';
+
+/// How long an upload is kept for its import to be checked and committed.
+#[cfg(feature = "ssr")]
+pub const IMPORT_LIFETIME: time::Duration = time::Duration::hours(1);
+
+/// [`User`](crate::User) fields an imported column can be mapped to.
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, Display, EnumIter, EnumString, PartialEq, Eq, Hash,
+)]
+pub enum ImportField {
+    Login,
+    FirstName,
+    LastName,
+    Status,
+    Roles,
+    Language,
+}
+
+impl ImportField {
+    /// Id of the field's label in the translations.
+    pub fn message_id(self) -> &'static str {
+        match self {
+            ImportField::Login => "user-login-email",
+            ImportField::FirstName => "user-first-name",
+            ImportField::LastName => "user-last-name",
+            ImportField::Status => "user-status",
+            ImportField::Roles => "users-roles",
+            ImportField::Language => "user-language",
+        }
+    }
+
+    /// Guesses the field a column holds from its header, e.g. `First name`,
+    /// `first_name` or `given name`.
+    pub fn guess(header: &str) -> Option<Self> {
+        let header: String = header
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .collect::<String>()
+            .to_ascii_lowercase();
+        match header.as_str() {
+            "login" | "email" | "loginemail" | "username" => Some(ImportField::Login),
+            "firstname" | "givenname" => Some(ImportField::FirstName),
+            "lastname" | "surname" | "familyname" => Some(ImportField::LastName),
+            "status" => Some(ImportField::Status),
+            "role" | "roles" => Some(ImportField::Roles),
+            "language" | "lang" => Some(ImportField::Language),
+            _ => None,
+        }
+    }
+}
+
+/// An uploaded file awaiting its column mapping.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportPreview {
+    pub id: Uuid,
+    pub headers: Vec<String>,
+    /// The guessed field of each column, `None` to ignore it.
+    pub mapping: Vec<Option<ImportField>>,
+    pub rows: usize,
+}
+
+/// Why a row cannot be imported.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImportProblem {
+    MissingLogin,
+    /// The login is not an email address.
+    InvalidLogin(String),
+    /// Another row of the file has the same login.
+    DuplicateLogin(String),
+    /// A user of the tenant already has the login.
+    LoginTaken(String),
+    UnknownStatus(String),
+    /// Imported users can only be active or banned, like users saved by
+    /// hand.
+    StatusNotAllowed(String),
+    UnknownRole(String),
+    UnknownLanguage(String),
+}
+
+impl ImportProblem {
+    /// Id of the problem's description in the translations, which takes the
+    /// offending value as `$value`.
+    pub fn message_id(&self) -> &'static str {
+        match self {
+            ImportProblem::MissingLogin => "import-missing-login",
+            ImportProblem::InvalidLogin(_) => "import-invalid-login",
+            ImportProblem::DuplicateLogin(_) => "import-duplicate-login",
+            ImportProblem::LoginTaken(_) => "import-login-taken",
+            ImportProblem::UnknownStatus(_) => "import-unknown-status",
+            ImportProblem::StatusNotAllowed(_) => "import-status-not-allowed",
+            ImportProblem::UnknownRole(_) => "import-unknown-role",
+            ImportProblem::UnknownLanguage(_) => "import-unknown-language",
+        }
+    }
+
+    pub fn value(&self) -> &str {
+        match self {
+            ImportProblem::MissingLogin => "",
+            ImportProblem::InvalidLogin(value)
+            | ImportProblem::DuplicateLogin(value)
+            | ImportProblem::LoginTaken(value)
+            | ImportProblem::UnknownStatus(value)
+            | ImportProblem::StatusNotAllowed(value)
+            | ImportProblem::UnknownRole(value)
+            | ImportProblem::UnknownLanguage(value) => value,
+        }
+    }
+}
+
+/// Why a column mapping cannot be imported with.
+#[cfg(feature = "ssr")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum MappingError {
+    #[error("Map a column to the login")]
+    NoLogin,
+    #[error("Map only one column to {0}")]
+    MappedTwice(ImportField),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportRow {
+    /// Line of the file, counting the header as line 1.
+    pub line: usize,
+    pub login: String,
+    pub problems: Vec<ImportProblem>,
+}
+
+/// Outcome of checking or committing an import.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub rows: Vec<ImportRow>,
+    pub committed: bool,
+}
+
+impl ImportReport {
+    pub fn invalid_rows(&self) -> usize {
+        self.rows
+            .iter()
+            .filter(|row| !row.problems.is_empty())
+            .count()
+    }
+}
+
+/// The users as CSV with the [`EXPORT_HEADERS`].
+#[cfg(feature = "ssr")]
+pub fn export_csv(users: &[User]) -> Result<String, csv::Error> {
+    use time::format_description::well_known::Rfc3339;
+
+    let timestamp = |value: time::OffsetDateTime| value.format(&Rfc3339).unwrap_or_default();
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(EXPORT_HEADERS)?;
+    for user in users {
+        let mut roles: Vec<&str> = user.roles.iter().map(String::as_str).collect();
+        roles.sort_unstable();
+        writer.write_record([
+            user.unid.to_string(),
+            user.login.clone(),
+            user.first_name.clone().unwrap_or_default(),
+            user.last_name.clone().unwrap_or_default(),
+            user.status.to_string(),
+            roles.join(&ROLE_SEPARATOR.to_string()),
+            user.language.clone().unwrap_or_default(),
+            timestamp(user.created),
+            user.last_login.map(timestamp).unwrap_or_default(),
+        ])?;
+    }
+    let bytes = writer.into_inner().map_err(|err| err.into_error())?;
+    Ok(String::from_utf8(bytes).expect("CSV of strings is UTF-8"))
+}
+
+/// An uploaded file, readable only by whoever uploaded it and only on the
+/// tenant it was uploaded to.
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug)]
+struct PendingImport {
+    owner: Uuid,
+    tenant: Tenant,
+    records: Vec<Vec<String>>,
+    expires: time::OffsetDateTime,
+}
+
+/// Imports uploaded but not yet committed.
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Default)]
+pub struct ImportStore(Arc<RwLock<HashMap<Uuid, PendingImport>>>);
+
+#[cfg(feature = "ssr")]
+impl ImportStore {
+    /// Keeps the data rows of an upload for [`IMPORT_LIFETIME`], replacing
+    /// the owner's previous one. Expired uploads are dropped.
+    pub fn stage(
+        &self,
+        owner: Uuid,
+        tenant: Tenant,
+        records: Vec<Vec<String>>,
+        now: time::OffsetDateTime,
+    ) -> Uuid {
+        let id = Uuid::new_v4();
+        let mut imports = self.0.write().unwrap();
+        imports.retain(|_, pending| pending.owner != owner && pending.expires > now);
+        imports.insert(
+            id,
+            PendingImport {
+                owner,
+                tenant,
+                records,
+                expires: now + IMPORT_LIFETIME,
+            },
+        );
+        id
+    }
+
+    pub fn records(
+        &self,
+        id: Uuid,
+        owner: Uuid,
+        tenant: &Tenant,
+        now: time::OffsetDateTime,
+    ) -> Option<Vec<Vec<String>>> {
+        self.0
+            .read()
+            .unwrap()
+            .get(&id)
+            .filter(|pending| {
+                pending.owner == owner && pending.tenant == *tenant && pending.expires > now
+            })
+            .map(|pending| pending.records.clone())
+    }
+
+    pub fn remove(&self, id: Uuid) {
+        self.0.write().unwrap().remove(&id);
+    }
+}
+
+/// Reads a CSV file whose first row names the columns. Short rows are padded
+/// with empty cells.
+#[cfg(feature = "ssr")]
+pub fn parse_csv(bytes: &[u8]) -> Result<(Vec<String>, Vec<Vec<String>>), csv::Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(bytes);
+    let headers: Vec<String> = reader.headers()?.iter().map(str::to_string).collect();
+    let mut records = Vec::new();
+    for record in reader.records() {
+        let mut record: Vec<String> = record?.iter().map(str::to_string).collect();
+        record.resize(record.len().max(headers.len()), String::new());
+        records.push(record);
+    }
+    Ok((headers, records))
+}
+
+/// Turns each record into a new user of the tenant, reporting the rows that
+/// cannot be imported. Logins and statuses follow the rules of
+/// [`crate::user_update`]. A mapping without a login column, or with two
+/// columns for the same field, is refused outright.
+#[cfg(feature = "ssr")]
+pub fn check_records(
+    records: &[Vec<String>],
+    mapping: &[Option<ImportField>],
+    existing: &TenantUsers,
+    catalog: &TenantRoles,
+    now: time::OffsetDateTime,
+) -> Result<(Vec<ImportRow>, Vec<User>), MappingError> {
+    use std::collections::HashSet;
+
+    use crate::{
+        i18n,
+        user_update::{check_login, UpdateError},
+        UserStatus,
+    };
+
+    let mut mapped = HashSet::new();
+    if let Some(field) = mapping.iter().flatten().find(|field| !mapped.insert(**field)) {
+        return Err(MappingError::MappedTwice(*field));
+    }
+    if !mapped.contains(&ImportField::Login) {
+        return Err(MappingError::NoLogin);
+    }
+
+    let mut seen = HashSet::new();
+    let mut rows = Vec::new();
+    let mut users = Vec::new();
+    for (index, record) in records.iter().enumerate() {
+        let cell = |field: ImportField| {
+            mapping
+                .iter()
+                .position(|mapped| *mapped == Some(field))
+                .and_then(|column| record.get(column))
+                .map(String::as_str)
+                .filter(|value| !value.is_empty())
+        };
+        let mut problems = Vec::new();
+
+        let login = cell(ImportField::Login).unwrap_or_default().to_string();
+        if login.is_empty() {
+            problems.push(ImportProblem::MissingLogin);
+        } else if !seen.insert(login.to_lowercase()) {
+            problems.push(ImportProblem::DuplicateLogin(login.clone()));
+        } else if let Err(err) = check_login(existing, Uuid::nil(), &login) {
+            problems.push(match err {
+                UpdateError::LoginTaken(_) => ImportProblem::LoginTaken(login.clone()),
+                _ => ImportProblem::InvalidLogin(login.clone()),
+            });
+        }
+
+        let status = match cell(ImportField::Status) {
+            Some(value) => match value.parse::<UserStatus>() {
+                Ok(status @ (UserStatus::Active | UserStatus::Banned)) => status,
+                Ok(UserStatus::Pending | UserStatus::Deactivated) => {
+                    problems.push(ImportProblem::StatusNotAllowed(value.to_string()));
+                    UserStatus::Active
+                }
+                Err(_) => {
+                    problems.push(ImportProblem::UnknownStatus(value.to_string()));
+                    UserStatus::Active
+                }
+            },
+            None => UserStatus::Active,
+        };
+
+        let mut roles = HashSet::new();
+        for role in cell(ImportField::Roles)
+            .unwrap_or_default()
+            .split(ROLE_SEPARATOR)
+            .map(str::trim)
+            .filter(|role| !role.is_empty())
+        {
+            match catalog.find(role) {
+                Some(known) => {
+                    roles.insert(known);
+                }
+                None => problems.push(ImportProblem::UnknownRole(role.to_string())),
+            }
+        }
+
+        let language = cell(ImportField::Language).and_then(|value| {
+            let language = i18n::language(value);
+            if language.is_none() {
+                problems.push(ImportProblem::UnknownLanguage(value.to_string()));
+            }
+            language.map(str::to_string)
+        });
+
+        if problems.is_empty() {
+            users.push(User {
+                unid: Uuid::new_v4(),
+                created: now,
+                first_name: cell(ImportField::FirstName).map(str::to_string),
+                // No password yet: the account cannot be signed in to until
+                // one is set.
+                hash: String::new(),
+                last_failed_login: None,
+                last_login: None,
+                last_password_change: now,
+                last_name: cell(ImportField::LastName).map(str::to_string),
+                login: login.clone(),
+                avatar: None,
+                roles,
+                site_schema: None,
+                status,
+                theme: Default::default(),
+                language,
+            });
+        }
+        rows.push(ImportRow {
+            line: index + 2,
+            login,
+            problems,
+        });
+    }
+    Ok((rows, users))
+}
+
+/// Stages an uploaded CSV file for import. Expects a `csv` file field.
+#[server(input = MultipartFormData)]
+pub async fn upload_import(data: MultipartData) -> Result<ImportPreview, ServerFnError> {
+    use crate::{
+        state::{app_state, authorize_admin},
+        tenant::current_tenant,
+    };
+
+    let session = authorize_admin()?;
+    let mut data = data
+        .into_inner()
+        .ok_or_else(|| ServerFnError::new("Expected a multipart form"))?;
+    let mut bytes = Vec::new();
+    while let Some(mut field) = data.next_field().await? {
+        if field.name() != Some("csv") {
+            continue;
+        }
+        while let Some(chunk) = field.chunk().await? {
+            if bytes.len() + chunk.len() > MAX_IMPORT_BYTES {
+                return Err(ServerFnError::new(format!(
+                    "The file must be at most {} MB",
+                    MAX_IMPORT_BYTES / 1024 / 1024
+                )));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+    }
+
+    let (headers, records) = parse_csv(&bytes).map_err(ServerFnError::new)?;
+    let rows = records.len();
+    let state = app_state()?;
+    let id = state
+        .imports
+        .stage(session.user, current_tenant(), records, state.clock.now());
+    Ok(ImportPreview {
+        id,
+        mapping: headers
+            .iter()
+            .map(|header| ImportField::guess(header))
+            .collect(),
+        headers,
+        rows,
+    })
+}
+
+#[cfg(feature = "ssr")]
+fn staged_records(id: Uuid) -> Result<Vec<Vec<String>>, ServerFnError> {
+    use crate::{
+        state::{app_state, authorize_admin},
+        tenant::current_tenant,
+    };
+
+    let session = authorize_admin()?;
+    let state = app_state()?;
+    state
+        .imports
+        .records(id, session.user, &current_tenant(), state.clock.now())
+        .ok_or_else(|| ServerFnError::new("The upload has expired; upload the file again"))
+}
+
+/// Checks every row of a staged import without changing anything.
+#[server(input = Json)]
+pub async fn check_import(
+    id: Uuid,
+    mapping: Vec<Option<ImportField>>,
+) -> Result<ImportReport, ServerFnError> {
+    use crate::state::{app_state, tenant_roles, tenant_users};
+
+    let records = staged_records(id)?;
+    let (rows, _) = check_records(
+        &records,
+        &mapping,
+        &tenant_users()?,
+        &tenant_roles()?,
+        app_state()?.clock.now(),
+    )
+    .map_err(ServerFnError::new)?;
+    Ok(ImportReport {
+        rows,
+        committed: false,
+    })
+}
+
+/// Adds the users of a staged import, all of them or, if any row has a
+/// problem, none. Each one added is audited as created.
+#[server(input = Json)]
+pub async fn commit_import(
+    id: Uuid,
+    mapping: Vec<Option<ImportField>>,
+) -> Result<ImportReport, ServerFnError> {
+    use crate::{
+        audit::{audit, AuditAction},
+        state::{app_state, tenant_roles, tenant_users},
+    };
+
+    let records = staged_records(id)?;
+    let state = app_state()?;
+    let users = tenant_users()?;
+    let (mut rows, new_users) = check_records(
+        &records,
+        &mapping,
+        &users,
+        &tenant_roles()?,
+        state.clock.now(),
+    )
+    .map_err(ServerFnError::new)?;
+    if rows.iter().any(|row| !row.problems.is_empty()) {
+        return Ok(ImportReport {
+            rows,
+            committed: false,
+        });
+    }
+    let created: Vec<Uuid> = new_users.iter().map(|user| user.unid).collect();
+    if let Err(taken) = users.insert_all(new_users) {
+        // Someone took the login between the check and the commit.
+        if let Some(row) = rows
+            .iter_mut()
+            .find(|row| row.login.eq_ignore_ascii_case(&taken))
+        {
+            row.problems.push(ImportProblem::LoginTaken(taken));
+        }
+        return Ok(ImportReport {
+            rows,
+            committed: false,
+        });
+    }
+    for unid in created {
+        audit(unid, AuditAction::Created);
+    }
+    state.imports.remove(id);
+    Ok(ImportReport {
+        rows,
+        committed: true,
+    })
+}
+
+/// The rows with problems, or a summary if there are none.
+#[component]
+fn ImportReportView(report: ImportReport) -> impl IntoView {
+    let count = report.rows.len() as i64;
+    let invalid = report.invalid_rows() as i64;
+
+    if report.committed {
+        return view! {
+            <div class="alert alert-success">
+                {t!("import-done", count = count)} " " <a href="/users">{t!("users-title")}</a>
+            </div>
+        }
+        .into_any();
+    }
+    if invalid == 0 {
+        return view! {
+            <div class="alert alert-info">
+                {t!("import-rows", count = count)} " " {t!("import-ready")}
+            </div>
+        }
+        .into_any();
+    }
+
+    view! {
+        <div class="alert alert-warning">{t!("import-invalid", count = invalid)}</div>
+        <table class="table table-sm">
+            <thead>
+                <tr>
+                    <th>{t!("import-line")}</th>
+                    <th>{t!("user-login-email")}</th>
+                    <th>{t!("import-problems")}</th>
+                </tr>
+            </thead>
+            <tbody>
+                {report
+                    .rows
+                    .into_iter()
+                    .filter(|row| !row.problems.is_empty())
+                    .map(|row| {
+                        view! {
+                            <tr>
+                                <td>{row.line}</td>
+                                <td>{row.login}</td>
+                                <td>
+                                    <ul class="list-unstyled mb-0">
+                                        {row
+                                            .problems
+                                            .into_iter()
+                                            .map(|problem| {
+                                                view! {
+                                                    <li>
+                                                        {move || {
+                                                            tr_args(
+                                                                problem.message_id(),
+                                                                &[("value", problem.value().into())],
+                                                            )
+                                                        }}
+                                                    </li>
+                                                }
+                                            })
+                                            .collect_view()}
+                                    </ul>
+                                </td>
+                            </tr>
+                        }
+                    })
+                    .collect_view()}
+            </tbody>
+        </table>
+    }
+        .into_any()
+}
+
+/// Upload a CSV file, map its columns, check it and import it.
+#[component]
+pub fn UserImport() -> impl IntoView {
+    use leptos::wasm_bindgen::JsCast;
+    use web_sys::{FormData, HtmlFormElement};
+
+    let upload = Action::new_local(|data: &FormData| upload_import(data.clone().into()));
+    let check = ServerAction::<CheckImport>::new();
+    let commit = ServerAction::<CommitImport>::new();
+    let mapping = RwSignal::new(Vec::<Option<ImportField>>::new());
+
+    Effect::new(move || {
+        if let Some(Ok(preview)) = upload.value().get() {
+            mapping.set(preview.mapping);
+            check.value().set(None);
+            commit.value().set(None);
+        }
+    });
+
+    // Only a check of the current mapping that found no problems may be
+    // committed.
+    let checked = move || {
+        check
+            .value()
+            .get()
+            .and_then(Result::ok)
+            .is_some_and(|report| report.invalid_rows() == 0)
+    };
+
+    view! {
+        <div class="mt-3 px-3">
+            <h1 class="h3 mb-3">{t!("import-title")}</h1>
+            <form
+                class="mb-3"
+                on:submit=move |ev| {
+                    ev.prevent_default();
+                    let form = ev.current_target().unwrap().unchecked_into::<HtmlFormElement>();
+                    if let Ok(data) = FormData::new_with_form(&form) {
+                        upload.dispatch_local(data);
+                    }
+                }
+            >
+                <label for="csv" class="form-label">
+                    {t!("import-file")}
+                </label>
+                <div class="input-group">
+                    <input id="csv" type="file" name="csv" class="form-control" accept=".csv,text/csv" required />
+                    <button type="submit" class="btn btn-secondary">
+                        {t!("import-upload")}
+                    </button>
+                </div>
+                <div class="form-text">{t!("import-help")}</div>
+            </form>
+            {move || match upload.value().get() {
+                Some(Ok(preview)) => {
+                    let id = preview.id;
+                    view! {
+                        <table class="table table-sm align-middle w-auto">
+                            <thead>
+                                <tr>
+                                    <th>{t!("import-column")}</th>
+                                    <th>{t!("import-field")}</th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                {preview
+                                    .headers
+                                    .into_iter()
+                                    .enumerate()
+                                    .map(|(column, header)| {
+                                        view! {
+                                            <tr>
+                                                <td>{header}</td>
+                                                <td>
+                                                    <select
+                                                        class="form-select form-select-sm"
+                                                        on:change=move |ev| {
+                                                            let field = event_target_value(&ev).parse().ok();
+                                                            mapping.update(|mapping| mapping[column] = field);
+                                                            check.value().set(None);
+                                                            commit.value().set(None);
+                                                        }
+                                                    >
+                                                        <option value="">{t!("import-ignore")}</option>
+                                                        {ImportField::iter()
+                                                            .map(|field| {
+                                                                view! {
+                                                                    <option
+                                                                        value=field.to_string()
+                                                                        prop:selected=move || {
+                                                                            mapping.with(|mapping| mapping.get(column) == Some(&Some(field)))
+                                                                        }
+                                                                    >
+                                                                        {move || tr(field.message_id())}
+                                                                    </option>
+                                                                }
+                                                            })
+                                                            .collect_view()}
+                                                    </select>
+                                                </td>
+                                            </tr>
+                                        }
+                                    })
+                                    .collect_view()}
+                            </tbody>
+                        </table>
+                        <div class="d-flex gap-2 mb-3">
+                            <button
+                                type="button"
+                                class="btn btn-secondary"
+                                on:click=move |_| {
+                                    commit.value().set(None);
+                                    check.dispatch(CheckImport { id, mapping: mapping.get() });
+                                }
+                            >
+                                {t!("import-check")}
+                            </button>
+                            <button
+                                type="button"
+                                class="btn btn-primary"
+                                prop:disabled=move || !checked() || commit.pending().get()
+                                on:click=move |_| {
+                                    commit.dispatch(CommitImport { id, mapping: mapping.get() });
+                                }
+                            >
+                                {t!("import-commit")}
+                            </button>
+                        </div>
+                    }
+                        .into_any()
+                }
+                Some(Err(err)) => {
+                    view! { <div class="alert alert-danger">{err.to_string()}</div> }.into_any()
+                }
+                None => ().into_any(),
+            }}
+            {move || {
+                commit
+                    .value()
+                    .get()
+                    .or_else(|| check.value().get())
+                    .map(|report| match report {
+                        Ok(report) => view! { <ImportReportView report=report /> }.into_any(),
+                        Err(err) => {
+                            view! { <div class="alert alert-danger">{err.to_string()}</div> }
+                                .into_any()
+                        }
+                    })
+            }}
+        </div>
+    }
+}
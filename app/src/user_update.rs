@@ -0,0 +1,286 @@
+//! Changing an existing user. [`crate::save_user`] and the REST API both go
+//! through [`update_user`], so a change is checked, applied and recorded the
+//! same way whichever way it comes in.
+
+use std::{collections::HashSet, fmt};
+
+use uuid::Uuid;
+
+use crate::{
+    audit::{AuditAction, AuditEntry},
+    i18n::LANGUAGES,
+    password::{hash_password, verify_password},
+    session::CurrentSession,
+    state::{AppState, TenantRoles, TenantUsers},
+    theme::Theme,
+    User, UserStatus, MIN_PASSWORD_LENGTH,
+};
+
+/// The fields to change. Those left `None` stay as they are, and an empty
+/// name clears it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UserUpdate {
+    pub login: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub password: Option<String>,
+    /// Required for users changing their own login or password.
+    pub current_password: Option<String>,
+    pub status: Option<UserStatus>,
+    pub roles: Option<HashSet<String>>,
+    pub language: Option<String>,
+    pub theme: Option<Theme>,
+}
+
+/// Why a change was refused. Nothing is changed then.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UpdateError {
+    NotFound,
+    Forbidden(String),
+    Invalid(String),
+    /// Another user of the tenant has the login.
+    LoginTaken(String),
+}
+
+impl fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpdateError::NotFound => f.write_str("User not found"),
+            UpdateError::Forbidden(message) | UpdateError::Invalid(message) => f.write_str(message),
+            UpdateError::LoginTaken(login) => write!(f, "The login {login} is taken"),
+        }
+    }
+}
+
+impl std::error::Error for UpdateError {}
+
+fn forbidden(message: &str) -> UpdateError {
+    UpdateError::Forbidden(message.to_string())
+}
+
+fn invalid(message: impl Into<String>) -> UpdateError {
+    UpdateError::Invalid(message.into())
+}
+
+/// Applies `update` to `unid` on behalf of `editor`, whose request came from
+/// `ip`, and returns the changed user. `editor` is `None` for a service
+/// with an API token, which may do what administrators may.
+///
+/// Users may change their own names, login, password, language and theme,
+/// the login and password only with their current password; administrators
+/// may change anything of anyone's but their own status. Erased users
+/// cannot be changed, and credentials and statuses not while
+/// impersonating. Only active users can be banned and only banned ones
+/// reactivated; pending and deactivated users have their own ways there.
+///
+/// A ban or a new login or password ends the user's other sessions, trusted
+/// browsers and grants to other applications. Those changes are audited,
+/// and everyone else on the user's page is told about the save.
+pub fn update_user(
+    state: &AppState,
+    users: &TenantUsers,
+    roles: &TenantRoles,
+    editor: Option<&CurrentSession>,
+    unid: Uuid,
+    update: UserUpdate,
+    ip: Option<String>,
+) -> Result<User, UpdateError> {
+    let before = users.get(unid).ok_or(UpdateError::NotFound)?;
+    let is_self = editor.is_some_and(|editor| editor.user == unid);
+    let is_admin = editor.is_none_or(|editor| users.is_admin(editor.user));
+    let impersonating = editor.is_some_and(|editor| editor.impersonator.is_some());
+    if !is_self && !is_admin {
+        return Err(forbidden("Forbidden"));
+    }
+    if before.is_erased() {
+        return Err(forbidden("Erased users cannot be changed"));
+    }
+
+    // Everything is checked before anything is changed.
+    let login = match update.login.as_deref().map(str::trim) {
+        Some(login) if login != before.login => Some(check_login(users, unid, login)?),
+        _ => None,
+    };
+    let password = update.password.filter(|password| !password.is_empty());
+    if let Some(password) = &password {
+        check_password(password)?;
+    }
+    let credentials = login.is_some() || password.is_some();
+    if credentials && impersonating {
+        return Err(forbidden(
+            "The login and password cannot be changed while impersonating",
+        ));
+    }
+    if credentials
+        && is_self
+        && !before.hash.is_empty()
+        && !update
+            .current_password
+            .is_some_and(|current| verify_password(&current, &before.hash))
+    {
+        return Err(forbidden("The current password is not correct"));
+    }
+    let status = update.status.filter(|status| *status != before.status);
+    if let Some(status) = status {
+        if !is_admin || impersonating {
+            return Err(forbidden("Only administrators may change the status"));
+        }
+        if is_self {
+            return Err(forbidden("You cannot change your own status"));
+        }
+        if !matches!(
+            (before.status, status),
+            (UserStatus::Active, UserStatus::Banned) | (UserStatus::Banned, UserStatus::Active)
+        ) {
+            return Err(invalid("This status cannot be set here"));
+        }
+    }
+    let roles = match update.roles {
+        Some(_) if !is_admin => {
+            return Err(forbidden("Only administrators may change the roles"));
+        }
+        Some(wanted) => Some(check_roles(roles, &wanted)?),
+        None => None,
+    };
+    let language = update.language.as_deref().map(check_language).transpose()?;
+    let hash = password.as_deref().map(hash_password);
+    let now = state.clock.now();
+
+    let after = users
+        .update(unid, |user| {
+            if let Some(login) = login {
+                user.login = login;
+            }
+            if let Some(first_name) = &update.first_name {
+                user.first_name = name(first_name);
+            }
+            if let Some(last_name) = &update.last_name {
+                user.last_name = name(last_name);
+            }
+            if let Some(hash) = hash {
+                user.hash = hash;
+                user.last_password_change = now;
+            }
+            if let Some(status) = status {
+                user.status = status;
+            }
+            if let Some(roles) = roles {
+                user.roles = roles;
+            }
+            if let Some(language) = language {
+                user.language = language;
+            }
+            if let Some(theme) = update.theme {
+                user.theme = theme;
+            }
+        })
+        .ok_or(UpdateError::NotFound)?;
+
+    let audit = |action| {
+        state.audit.record(AuditEntry {
+            at: now,
+            actor: editor.map(|editor| editor.impersonator.unwrap_or(editor.user)),
+            subject: unid,
+            action,
+            ip: ip.clone(),
+        })
+    };
+    if after.login != before.login {
+        audit(AuditAction::LoginChanged);
+    }
+    if password.is_some() {
+        audit(AuditAction::PasswordChanged);
+    }
+    match status {
+        Some(UserStatus::Banned) => audit(AuditAction::Banned),
+        Some(_) => audit(AuditAction::Activated),
+        None => {}
+    }
+    if status == Some(UserStatus::Banned) {
+        revoke_access(state, unid, None);
+    } else if credentials {
+        revoke_access(
+            state,
+            unid,
+            editor.filter(|_| is_self).map(|editor| editor.id),
+        );
+    }
+    if let Some(by) = editor.and_then(|editor| users.get(editor.user)) {
+        state.presence.saved(&after, &by);
+    }
+    Ok(after)
+}
+
+/// Signs `user` out of every session but `keep`, forgets their trusted
+/// browsers and revokes what they granted other applications, after a ban
+/// or when their credentials changed.
+pub fn revoke_access(state: &AppState, user: Uuid, keep: Option<Uuid>) {
+    match keep {
+        Some(keep) => state.sessions.revoke_others(user, keep),
+        None => state.sessions.revoke_all(user),
+    };
+    state.trusted_devices.revoke_all(user);
+    state.oauth.revoke_all(user);
+}
+
+/// A login for `unid`, which must look like an email address and not be
+/// another user's.
+pub fn check_login(users: &TenantUsers, unid: Uuid, login: &str) -> Result<String, UpdateError> {
+    let login = login.trim();
+    if login.is_empty() {
+        return Err(invalid("The login is required"));
+    }
+    if !login.contains('@') {
+        return Err(invalid("The login must be an email address"));
+    }
+    if users
+        .find_by_login(login)
+        .is_some_and(|other| other.unid != unid)
+    {
+        return Err(UpdateError::LoginTaken(login.to_string()));
+    }
+    Ok(login.to_string())
+}
+
+/// Refuses passwords shorter than [`MIN_PASSWORD_LENGTH`].
+pub fn check_password(password: &str) -> Result<(), UpdateError> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(invalid(format!(
+            "Use at least {MIN_PASSWORD_LENGTH} characters for the password"
+        )));
+    }
+    Ok(())
+}
+
+/// The roles named, as spelled in the tenant's catalog.
+pub fn check_roles(
+    catalog: &TenantRoles,
+    roles: &HashSet<String>,
+) -> Result<HashSet<String>, UpdateError> {
+    roles
+        .iter()
+        .map(|role| {
+            catalog
+                .find(role.trim())
+                .ok_or_else(|| invalid(format!("Unknown role {role}")))
+        })
+        .collect()
+}
+
+/// The language code for `language`, or `None` to follow the browser when
+/// it is empty.
+pub fn check_language(language: &str) -> Result<Option<String>, UpdateError> {
+    let language = language.trim();
+    if language.is_empty() {
+        return Ok(None);
+    }
+    LANGUAGES
+        .iter()
+        .find(|(code, _)| code.eq_ignore_ascii_case(language))
+        .map(|(code, _)| Some(code.to_string()))
+        .ok_or_else(|| invalid(format!("Unknown language {language}")))
+}
+
+fn name(name: &str) -> Option<String> {
+    Some(name.trim().to_string()).filter(|name| !name.is_empty())
+}
@@ -0,0 +1,296 @@
+use std::collections::{HashMap, HashSet};
+
+use leptos::prelude::*;
+use leptos_router::{components::Form, hooks::use_query_map};
+use strum::IntoEnumIterator;
+use uuid::Uuid;
+
+use crate::{
+    avatar::Avatar,
+    bulk::{BulkActionBar, BulkUpdateUsers},
+    datetime::DateTime,
+    i18n::{tr, tr_args},
+    live::use_user_events,
+    t, User, UserStatus,
+};
+
+/// Roles every tenant starts out with; the administrator role cannot be
+/// removed.
+pub const ROLES: [&str; 3] = ["admin", "editor", "viewer"];
+
+pub use model::UserFilter;
+
+/// The users of the current tenant matching `filter`, sorted by login.
+#[cfg(feature = "ssr")]
+pub fn filtered_users(users: &crate::state::TenantUsers, filter: &UserFilter) -> Vec<User> {
+    let mut users: Vec<User> = users
+        .all()
+        .into_iter()
+        .filter(|user| filter.matches(user))
+        .collect();
+    users.sort_by_key(|user| user.login.to_lowercase());
+    users
+}
+
+/// Brings a list from [`filtered_users`] up to date with `changes` since it
+/// was loaded, `None` marking a removed user.
+pub fn patch(
+    users: Vec<User>,
+    changes: &HashMap<Uuid, Option<User>>,
+    filter: &UserFilter,
+) -> Vec<User> {
+    let mut users: Vec<User> = users
+        .into_iter()
+        .filter(|user| !changes.contains_key(&user.unid))
+        .chain(changes.values().flatten().filter(|user| filter.matches(user)).cloned())
+        .collect();
+    users.sort_by_key(|user| user.login.to_lowercase());
+    users
+}
+
+#[server]
+pub async fn list_users(filter: UserFilter) -> Result<Vec<User>, ServerFnError> {
+    use crate::state::{authorize_admin, tenant_users};
+
+    authorize_admin()?;
+    Ok(filtered_users(&tenant_users()?, &filter)
+        .into_iter()
+        .map(User::public)
+        .collect())
+}
+
+/// The roles users of the current tenant can be granted.
+#[server]
+pub async fn list_roles() -> Result<Vec<String>, ServerFnError> {
+    use crate::state::{current_session, tenant_roles};
+
+    current_session()?;
+    Ok(tenant_roles()?.all())
+}
+
+/// The tenant's users, filtered by the query string, with links to export
+/// them as CSV, to import more and to invite someone. Selected users can be changed at once.
+/// Changes made elsewhere show up as they happen.
+#[component]
+pub fn UserList() -> impl IntoView {
+    let query = use_query_map();
+    let filter =
+        Memo::new(move |_| query.with(|query| UserFilter::from_query(|key| query.get(key))));
+    let update = ServerAction::<BulkUpdateUsers>::new();
+    let users = Resource::new(
+        move || (filter.get(), update.version().get()),
+        |(filter, _)| list_users(filter),
+    );
+    let roles = Resource::new(|| (), |_| list_roles());
+    let role_names =
+        Signal::derive(move || roles.get().and_then(Result::ok).unwrap_or_default());
+    // Only users on display stay selected.
+    let selected = RwSignal::new(HashSet::<Uuid>::new());
+    // Changes streamed in since the list was loaded.
+    let live = RwSignal::new(HashMap::<Uuid, Option<User>>::new());
+    Effect::new(move || {
+        filter.track();
+        selected.set(HashSet::new());
+        live.set(HashMap::new());
+    });
+    use_user_events(move |event| {
+        live.update(|live| {
+            live.insert(event.unid, event.user);
+        });
+    });
+
+    view! {
+        <div class="mt-3 px-3">
+            <div class="d-flex justify-content-between align-items-center mb-3">
+                <h1 class="h3">{t!("users-title")}</h1>
+                <div class="d-flex gap-2">
+                    <a
+                        class="btn btn-outline-secondary"
+                        href=move || format!("/users/export.csv?{}", filter.get().to_query())
+                        rel="external"
+                        download="users.csv"
+                    >
+                        {t!("users-export")}
+                    </a>
+                    <a class="btn btn-outline-secondary" href="/users/import">
+                        {t!("users-import")}
+                    </a>
+                    <a class="btn btn-primary" href="/users/invite">
+                        {t!("users-invite")}
+                    </a>
+                </div>
+            </div>
+            <Form method="GET" action="">
+                <div class="row g-2 mb-3">
+                    <div class="col-sm">
+                        <input
+                            type="search"
+                            name="q"
+                            class="form-control"
+                            placeholder=move || tr("users-search")
+                            prop:value=move || filter.get().q
+                        />
+                    </div>
+                    <div class="col-sm-auto">
+                        <select name="status" class="form-select">
+                            <option value="">{t!("users-any-status")}</option>
+                            {UserStatus::iter()
+                                .map(|status| {
+                                    view! {
+                                        <option
+                                            value=status.to_string()
+                                            prop:selected=move || filter.get().status == Some(status)
+                                        >
+                                            {move || tr(status.message_id())}
+                                        </option>
+                                    }
+                                })
+                                .collect_view()}
+                        </select>
+                    </div>
+                    <div class="col-sm-auto">
+                        <select name="role" class="form-select">
+                            <option value="">{t!("users-any-role")}</option>
+                            <Transition>
+                                {move || {
+                                    roles
+                                        .get()
+                                        .and_then(Result::ok)
+                                        .unwrap_or_default()
+                                        .into_iter()
+                                        .map(|role| {
+                                            let selected = filter.get().role.as_ref() == Some(&role);
+                                            let value = role.clone();
+                                            view! {
+                                                <option value=value prop:selected=selected>
+                                                    {role}
+                                                </option>
+                                            }
+                                        })
+                                        .collect_view()
+                                }}
+                            </Transition>
+                        </select>
+                    </div>
+                    <div class="col-sm-auto">
+                        <button type="submit" class="btn btn-secondary">
+                            {t!("users-filter")}
+                        </button>
+                    </div>
+                </div>
+            </Form>
+            <BulkActionBar selected roles=role_names update />
+            <Transition fallback=|| view! { <p>{t!("user-loading")}</p> }>
+                {move || match users.get() {
+                    Some(Ok(users)) => {
+                        let users = live.with(|live| patch(users, live, &filter.get_untracked()));
+                        let shown: Vec<Uuid> = users.iter().map(|user| user.unid).collect();
+                        let all_selected = {
+                            let shown = shown.clone();
+                            move || {
+                                !shown.is_empty()
+                                    && selected.with(|selected| shown.iter().all(|unid| selected.contains(unid)))
+                            }
+                        };
+                        view! {
+                            <table class="table table-sm align-middle">
+                                <thead>
+                                    <tr>
+                                        <th>
+                                            <input
+                                                type="checkbox"
+                                                class="form-check-input"
+                                                aria-label=move || tr("bulk-select-all")
+                                                prop:checked=all_selected.clone()
+                                                on:change=move |_| {
+                                                    let select = !all_selected();
+                                                    selected
+                                                        .update(|selected| {
+                                                            for unid in &shown {
+                                                                if select {
+                                                                    selected.insert(*unid);
+                                                                } else {
+                                                                    selected.remove(unid);
+                                                                }
+                                                            }
+                                                        });
+                                                }
+                                            />
+                                        </th>
+                                        <th></th>
+                                        <th>{t!("user-login-email")}</th>
+                                        <th>{t!("user-first-name")}</th>
+                                        <th>{t!("user-last-name")}</th>
+                                        <th>{t!("user-status")}</th>
+                                        <th>{t!("users-roles")}</th>
+                                        <th>{t!("user-last-login")}</th>
+                                    </tr>
+                                </thead>
+                                <tbody>
+                                    {users
+                                        .into_iter()
+                                        .map(|user| {
+                                            let mut roles: Vec<String> = user.roles.into_iter().collect();
+                                            roles.sort();
+                                            let unid = user.unid;
+                                            let label = user.login.clone();
+                                            view! {
+                                                <tr>
+                                                    <td>
+                                                        <input
+                                                            type="checkbox"
+                                                            class="form-check-input"
+                                                            aria-label=move || {
+                                                                tr_args("bulk-select", &[("login", label.clone().into())])
+                                                            }
+                                                            prop:checked=move || selected.with(|selected| selected.contains(&unid))
+                                                            on:change=move |ev| {
+                                                                let checked = event_target_checked(&ev);
+                                                                selected
+                                                                    .update(|selected| {
+                                                                        if checked {
+                                                                            selected.insert(unid);
+                                                                        } else {
+                                                                            selected.remove(&unid);
+                                                                        }
+                                                                    });
+                                                            }
+                                                        />
+                                                    </td>
+                                                    <td>
+                                                        <Avatar
+                                                            unid=user.unid
+                                                            first_name=user.first_name.clone()
+                                                            last_name=user.last_name.clone()
+                                                            avatar=user.avatar
+                                                            size=32
+                                                        />
+                                                    </td>
+                                                    <td>
+                                                        <a href=format!("/users/{}", user.unid)>{user.login}</a>
+                                                    </td>
+                                                    <td>{user.first_name}</td>
+                                                    <td>{user.last_name}</td>
+                                                    <td>{move || tr(user.status.message_id())}</td>
+                                                    <td>{roles.join(", ")}</td>
+                                                    <td>
+                                                        <DateTime value=user.last_login />
+                                                    </td>
+                                                </tr>
+                                            }
+                                        })
+                                        .collect_view()}
+                                </tbody>
+                            </table>
+                        }
+                            .into_any()
+                    }
+                    Some(Err(err)) => {
+                        view! { <div class="alert alert-danger">{err.to_string()}</div> }.into_any()
+                    }
+                    None => ().into_any(),
+                }}
+            </Transition>
+        </div>
+    }
+}
@@ -0,0 +1,927 @@
+//! Webhooks telling other systems when users are created, updated, banned
+//! or deleted.
+//!
+//! Administrators subscribe a URL to some of the [`WebhookEvent`]s of their
+//! tenant. Each event is POSTed to it as JSON, signed with the
+//! subscription's secret: `X-Webhook-Signature` is `sha256=` and the hex
+//! HMAC-SHA256 of `X-Webhook-Timestamp`, a dot and the body. Failed
+//! deliveries are retried with exponential backoff and, after
+//! [`MAX_ATTEMPTS`], set aside as dead letters until an administrator
+//! retries them or [`DEAD_LETTER_LIFETIME`] has passed.
+
+use leptos::prelude::*;
+use leptos::server_fn::codec::Json;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{datetime::DateTime, i18n::tr, t};
+
+#[cfg(feature = "ssr")]
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+#[cfg(feature = "ssr")]
+use crate::{
+    state::{AppState, UserChange, UserObserver},
+    tenant::Tenant,
+    User, UserStatus,
+};
+
+/// Attempts at a delivery before it becomes a dead letter.
+pub const MAX_ATTEMPTS: u32 = 8;
+/// Wait before the first retry, doubled for each one after.
+#[cfg(feature = "ssr")]
+const FIRST_RETRY: time::Duration = time::Duration::seconds(30);
+#[cfg(feature = "ssr")]
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often the worker looks for retries due, when not woken by an event.
+#[cfg(feature = "ssr")]
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Delivered events kept per tenant to show.
+#[cfg(feature = "ssr")]
+const RECENT_DELIVERIES: usize = 100;
+/// Dead letters kept per tenant to retry; older ones are dropped first.
+#[cfg(feature = "ssr")]
+const DEAD_LETTERS: usize = 100;
+/// How long a dead letter is kept after its last attempt.
+#[cfg(feature = "ssr")]
+pub const DEAD_LETTER_LIFETIME: time::Duration = time::Duration::days(30);
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, EnumIter,
+)]
+pub enum WebhookEvent {
+    #[serde(rename = "user.created")]
+    UserCreated,
+    #[serde(rename = "user.updated")]
+    UserUpdated,
+    #[serde(rename = "user.banned")]
+    UserBanned,
+    /// The user was erased, or their invitation revoked.
+    #[serde(rename = "user.deleted")]
+    UserDeleted,
+}
+
+impl WebhookEvent {
+    /// The name receivers see, in the payload and `X-Webhook-Event`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WebhookEvent::UserCreated => "user.created",
+            WebhookEvent::UserUpdated => "user.updated",
+            WebhookEvent::UserBanned => "user.banned",
+            WebhookEvent::UserDeleted => "user.deleted",
+        }
+    }
+}
+
+/// A subscription as administrators see it, without its secret.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebhookSubscription {
+    pub id: Uuid,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created: OffsetDateTime,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeliveryStatus {
+    /// Not attempted yet.
+    Pending,
+    Sending,
+    /// Failed, and attempted again at `next_attempt`.
+    Retrying,
+    Delivered,
+    /// Failed [`MAX_ATTEMPTS`] times.
+    Dead,
+}
+
+impl DeliveryStatus {
+    pub fn message_id(self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "webhook-status-pending",
+            DeliveryStatus::Sending => "webhook-status-sending",
+            DeliveryStatus::Retrying => "webhook-status-retrying",
+            DeliveryStatus::Delivered => "webhook-status-delivered",
+            DeliveryStatus::Dead => "webhook-status-dead",
+        }
+    }
+}
+
+/// One event sent, or to be sent, to one subscription.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub subscription: Uuid,
+    pub url: String,
+    pub event: WebhookEvent,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    /// HTTP status of the last response, if there was one.
+    pub last_response: Option<u16>,
+    pub last_error: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_attempt: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub next_attempt: Option<OffsetDateTime>,
+}
+
+/// A delivery about a user with the body it sends, for their personal data.
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Serialize)]
+pub struct WebhookPayload {
+    pub delivery: WebhookDelivery,
+    pub body: serde_json::Value,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug)]
+struct Subscription {
+    id: Uuid,
+    schema: Option<String>,
+    url: String,
+    events: Vec<WebhookEvent>,
+    secret: String,
+    created: OffsetDateTime,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug)]
+struct Delivery {
+    schema: Option<String>,
+    /// Whom the event is about.
+    user: Uuid,
+    /// Sent as is on every attempt.
+    body: String,
+    secret: String,
+    info: WebhookDelivery,
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Debug, Default)]
+struct Hooks {
+    subscriptions: Vec<Subscription>,
+    /// Oldest first.
+    deliveries: Vec<Delivery>,
+}
+
+/// Subscriptions, and the deliveries of events to them, for all tenants.
+///
+/// As a [`UserObserver`] it queues a delivery for every subscription to a
+/// change; [`spawn_worker`] sends them.
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug, Default)]
+pub struct Webhooks {
+    hooks: Arc<RwLock<Hooks>>,
+    http: reqwest::Client,
+    wake: Arc<tokio::sync::Notify>,
+}
+
+/// The event `change` is, with the user it is about.
+#[cfg(feature = "ssr")]
+fn event_for<'a>(change: UserChange<'a>) -> Option<(WebhookEvent, &'a User)> {
+    match (change.before, change.after) {
+        (None, None) => None,
+        (Some(before), None) => Some((WebhookEvent::UserDeleted, before)),
+        (None, Some(after)) => Some((WebhookEvent::UserCreated, after)),
+        (Some(before), Some(after)) if after.is_erased() && !before.is_erased() => {
+            Some((WebhookEvent::UserDeleted, after))
+        }
+        (Some(before), Some(after))
+            if after.status == UserStatus::Banned && before.status != UserStatus::Banned =>
+        {
+            Some((WebhookEvent::UserBanned, after))
+        }
+        // Signing in is not an update to anyone downstream.
+        (Some(before), Some(after)) => {
+            let comparable = |user: &User| User {
+                last_login: None,
+                last_failed_login: None,
+                ..user.clone()
+            };
+            (comparable(before) != comparable(after)).then_some((WebhookEvent::UserUpdated, after))
+        }
+    }
+}
+
+/// The hex HMAC-SHA256 receivers check `X-Webhook-Signature` against.
+#[cfg(feature = "ssr")]
+pub fn signature(secret: &str, timestamp: i64, body: &str) -> String {
+    use data_encoding::HEXLOWER;
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("{timestamp}.{body}").as_bytes());
+    HEXLOWER.encode(&mac.finalize().into_bytes())
+}
+
+#[cfg(feature = "ssr")]
+fn random_secret() -> String {
+    use data_encoding::BASE64URL_NOPAD;
+
+    let mut bytes = [0; 32];
+    getrandom::fill(&mut bytes).expect("system RNG is available");
+    format!("whsec_{}", BASE64URL_NOPAD.encode(&bytes))
+}
+
+#[cfg(feature = "ssr")]
+impl Webhooks {
+    /// Subscribes `url` to `events` of the tenant, returning the
+    /// subscription and the secret its deliveries are signed with.
+    pub fn subscribe(
+        &self,
+        tenant: &Tenant,
+        url: &str,
+        events: Vec<WebhookEvent>,
+    ) -> (WebhookSubscription, String) {
+        let subscription = Subscription {
+            id: Uuid::new_v4(),
+            schema: tenant.schema.clone(),
+            url: url.to_string(),
+            events,
+            secret: random_secret(),
+            created: OffsetDateTime::now_utc(),
+        };
+        let secret = subscription.secret.clone();
+        let info = Self::info(&subscription);
+        self.hooks.write().unwrap().subscriptions.push(subscription);
+        (info, secret)
+    }
+
+    fn info(subscription: &Subscription) -> WebhookSubscription {
+        WebhookSubscription {
+            id: subscription.id,
+            url: subscription.url.clone(),
+            events: subscription.events.clone(),
+            created: subscription.created,
+        }
+    }
+
+    /// Removes the subscription with its deliveries still to be made and
+    /// its dead letters; those delivered stay listed.
+    pub fn unsubscribe(&self, tenant: &Tenant, id: Uuid) -> bool {
+        let mut hooks = self.hooks.write().unwrap();
+        let before = hooks.subscriptions.len();
+        hooks
+            .subscriptions
+            .retain(|subscription| subscription.id != id || subscription.schema != tenant.schema);
+        if hooks.subscriptions.len() == before {
+            return false;
+        }
+        hooks.deliveries.retain(|delivery| {
+            delivery.info.subscription != id || delivery.info.status == DeliveryStatus::Delivered
+        });
+        true
+    }
+
+    pub fn subscriptions(&self, tenant: &Tenant) -> Vec<WebhookSubscription> {
+        self.hooks
+            .read()
+            .unwrap()
+            .subscriptions
+            .iter()
+            .filter(|subscription| subscription.schema == tenant.schema)
+            .map(Self::info)
+            .collect()
+    }
+
+    /// The tenant's deliveries, newest first.
+    pub fn deliveries(&self, tenant: &Tenant) -> Vec<WebhookDelivery> {
+        self.hooks
+            .read()
+            .unwrap()
+            .deliveries
+            .iter()
+            .rev()
+            .filter(|delivery| delivery.schema == tenant.schema)
+            .map(|delivery| delivery.info.clone())
+            .collect()
+    }
+
+    /// Queues a dead letter to be sent again, starting over its attempts.
+    pub fn retry(&self, tenant: &Tenant, id: Uuid) -> bool {
+        let mut hooks = self.hooks.write().unwrap();
+        let Some(delivery) = hooks.deliveries.iter_mut().find(|delivery| {
+            delivery.info.id == id
+                && delivery.schema == tenant.schema
+                && delivery.info.status == DeliveryStatus::Dead
+        }) else {
+            return false;
+        };
+        delivery.info.status = DeliveryStatus::Pending;
+        delivery.info.attempts = 0;
+        delivery.info.next_attempt = None;
+        drop(hooks);
+        self.wake.notify_one();
+        true
+    }
+
+    /// The deliveries about `unid` still kept, oldest first, with their
+    /// bodies.
+    pub fn payloads(&self, unid: Uuid) -> Vec<WebhookPayload> {
+        self.hooks
+            .read()
+            .unwrap()
+            .deliveries
+            .iter()
+            .filter(|delivery| delivery.user == unid)
+            .map(|delivery| WebhookPayload {
+                delivery: delivery.info.clone(),
+                body: serde_json::from_str(&delivery.body).expect("bodies are JSON"),
+            })
+            .collect()
+    }
+
+    /// Drops every delivery about `unid`, sent or not, so that none of
+    /// their data is kept or sent any more, as when they are erased.
+    pub fn forget_user(&self, unid: Uuid) {
+        self.hooks
+            .write()
+            .unwrap()
+            .deliveries
+            .retain(|delivery| delivery.user != unid);
+    }
+
+    /// Makes every delivery due at `now`, returning how many were attempted.
+    pub async fn run_due(&self, now: OffsetDateTime) -> usize {
+        let due: Vec<Delivery> = {
+            let mut hooks = self.hooks.write().unwrap();
+            hooks.deliveries.retain(|delivery| {
+                delivery.info.status != DeliveryStatus::Dead
+                    || delivery
+                        .info
+                        .last_attempt
+                        .is_some_and(|at| now - at < DEAD_LETTER_LIFETIME)
+            });
+            hooks
+                .deliveries
+                .iter_mut()
+                .filter(|delivery| {
+                    matches!(
+                        delivery.info.status,
+                        DeliveryStatus::Pending | DeliveryStatus::Retrying
+                    ) && delivery.info.next_attempt.is_none_or(|at| at <= now)
+                })
+                .map(|delivery| {
+                    delivery.info.status = DeliveryStatus::Sending;
+                    delivery.clone()
+                })
+                .collect()
+        };
+        for delivery in &due {
+            let outcome = self.send(delivery, now).await;
+            self.record(delivery.info.id, now, outcome);
+        }
+        due.len()
+    }
+
+    /// POSTs `delivery`, returning the response status if it was a success
+    /// or else what went wrong.
+    async fn send(
+        &self,
+        delivery: &Delivery,
+        now: OffsetDateTime,
+    ) -> Result<u16, (Option<u16>, String)> {
+        let timestamp = now.unix_timestamp();
+        let response = self
+            .http
+            .post(&delivery.info.url)
+            .timeout(DELIVERY_TIMEOUT)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .header("X-Webhook-Id", delivery.info.id.to_string())
+            .header("X-Webhook-Event", delivery.info.event.as_str())
+            .header("X-Webhook-Timestamp", timestamp.to_string())
+            .header(
+                "X-Webhook-Signature",
+                format!(
+                    "sha256={}",
+                    signature(&delivery.secret, timestamp, &delivery.body)
+                ),
+            )
+            .body(delivery.body.clone())
+            .send()
+            .await
+            .map_err(|err| (None, err.to_string()))?;
+        let status = response.status();
+        if status.is_success() {
+            Ok(status.as_u16())
+        } else {
+            Err((Some(status.as_u16()), status.to_string()))
+        }
+    }
+
+    fn record(&self, id: Uuid, now: OffsetDateTime, outcome: Result<u16, (Option<u16>, String)>) {
+        let mut hooks = self.hooks.write().unwrap();
+        let Some(delivery) = hooks
+            .deliveries
+            .iter_mut()
+            .find(|delivery| delivery.info.id == id)
+        else {
+            return;
+        };
+        let info = &mut delivery.info;
+        info.attempts += 1;
+        info.last_attempt = Some(now);
+        match outcome {
+            Ok(status) => {
+                info.status = DeliveryStatus::Delivered;
+                info.last_response = Some(status);
+                info.last_error = None;
+                info.next_attempt = None;
+            }
+            Err((status, error)) => {
+                info.last_response = status;
+                info.last_error = Some(error);
+                if info.attempts >= MAX_ATTEMPTS {
+                    info.status = DeliveryStatus::Dead;
+                    info.next_attempt = None;
+                } else {
+                    info.status = DeliveryStatus::Retrying;
+                    info.next_attempt = Some(now + FIRST_RETRY * 2_i32.pow(info.attempts - 1));
+                }
+            }
+        }
+        let schema = delivery.schema.clone();
+        Self::prune(&mut hooks, &schema);
+    }
+
+    /// Forgets the tenant's oldest deliveries beyond [`RECENT_DELIVERIES`]
+    /// and its oldest dead letters beyond [`DEAD_LETTERS`].
+    fn prune(hooks: &mut Hooks, schema: &Option<String>) {
+        for (status, kept) in [
+            (DeliveryStatus::Delivered, RECENT_DELIVERIES),
+            (DeliveryStatus::Dead, DEAD_LETTERS),
+        ] {
+            let matches =
+                |delivery: &Delivery| delivery.schema == *schema && delivery.info.status == status;
+            let mut excess = hooks
+                .deliveries
+                .iter()
+                .filter(|delivery| matches(delivery))
+                .count()
+                .saturating_sub(kept);
+            hooks.deliveries.retain(|delivery| {
+                if excess > 0 && matches(delivery) {
+                    excess -= 1;
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl UserObserver for Webhooks {
+    fn changed(&self, change: UserChange<'_>) {
+        use serde_json::json;
+
+        let Some((event, user)) = event_for(change) else {
+            return;
+        };
+        let mut hooks = self.hooks.write().unwrap();
+        let created = OffsetDateTime::now_utc();
+        let queued: Vec<Delivery> = hooks
+            .subscriptions
+            .iter()
+            .filter(|subscription| {
+                subscription.schema == user.site_schema && subscription.events.contains(&event)
+            })
+            .map(|subscription| {
+                let id = Uuid::new_v4();
+                let body = json!({
+                    "id": id,
+                    "type": event,
+                    "created": created.format(&time::format_description::well_known::Rfc3339).ok(),
+                    "data": user.clone().public(),
+                });
+                Delivery {
+                    schema: subscription.schema.clone(),
+                    user: user.unid,
+                    body: body.to_string(),
+                    secret: subscription.secret.clone(),
+                    info: WebhookDelivery {
+                        id,
+                        subscription: subscription.id,
+                        url: subscription.url.clone(),
+                        event,
+                        status: DeliveryStatus::Pending,
+                        attempts: 0,
+                        last_response: None,
+                        last_error: None,
+                        created,
+                        last_attempt: None,
+                        next_attempt: None,
+                    },
+                }
+            })
+            .collect();
+        if !queued.is_empty() {
+            hooks.deliveries.extend(queued);
+            self.wake.notify_one();
+        }
+    }
+}
+
+/// Sends deliveries as they are queued, and retries when they are due.
+#[cfg(feature = "ssr")]
+pub fn spawn_worker(state: AppState) {
+    tokio::spawn(async move {
+        loop {
+            state.webhooks.run_due(state.clock.now()).await;
+            tokio::select! {
+                _ = state.webhooks.wake.notified() => {}
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            }
+        }
+    });
+}
+
+/// What [`create_webhook`] returns: the secret is only ever shown then.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NewWebhook {
+    pub subscription: WebhookSubscription,
+    pub secret: String,
+}
+
+#[server]
+pub async fn list_webhooks() -> Result<Vec<WebhookSubscription>, ServerFnError> {
+    use crate::{
+        state::{app_state, authorize_admin},
+        tenant::current_tenant,
+    };
+
+    authorize_admin()?;
+    Ok(app_state()?.webhooks.subscriptions(&current_tenant()))
+}
+
+#[server(input = Json)]
+pub async fn create_webhook(
+    url: String,
+    events: Vec<WebhookEvent>,
+) -> Result<NewWebhook, ServerFnError> {
+    use crate::{
+        state::{app_state, authorize_admin},
+        tenant::current_tenant,
+    };
+
+    authorize_admin()?;
+    let url = url.trim();
+    if !reqwest::Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https")) {
+        return Err(ServerFnError::new("Enter an http or https URL"));
+    }
+    if events.is_empty() {
+        return Err(ServerFnError::new("Choose at least one event"));
+    }
+    let (subscription, secret) = app_state()?
+        .webhooks
+        .subscribe(&current_tenant(), url, events);
+    Ok(NewWebhook {
+        subscription,
+        secret,
+    })
+}
+
+#[server]
+pub async fn delete_webhook(id: Uuid) -> Result<(), ServerFnError> {
+    use crate::{
+        state::{app_state, authorize_admin},
+        tenant::current_tenant,
+    };
+
+    authorize_admin()?;
+    match app_state()?.webhooks.unsubscribe(&current_tenant(), id) {
+        true => Ok(()),
+        false => Err(ServerFnError::new("Webhook not found")),
+    }
+}
+
+/// The tenant's recent deliveries and dead letters, newest first.
+#[server]
+pub async fn list_deliveries() -> Result<Vec<WebhookDelivery>, ServerFnError> {
+    use crate::{
+        state::{app_state, authorize_admin},
+        tenant::current_tenant,
+    };
+
+    authorize_admin()?;
+    Ok(app_state()?.webhooks.deliveries(&current_tenant()))
+}
+
+#[server]
+pub async fn retry_delivery(id: Uuid) -> Result<(), ServerFnError> {
+    use crate::{
+        state::{app_state, authorize_admin},
+        tenant::current_tenant,
+    };
+
+    authorize_admin()?;
+    match app_state()?.webhooks.retry(&current_tenant(), id) {
+        true => Ok(()),
+        false => Err(ServerFnError::new("Delivery not found")),
+    }
+}
+
+/// Administration of the tenant's webhooks: subscriptions, recent
+/// deliveries and dead letters to retry.
+#[component]
+pub fn WebhooksPage() -> impl IntoView {
+    let create = ServerAction::<CreateWebhook>::new();
+    let delete = ServerAction::<DeleteWebhook>::new();
+    let retry = ServerAction::<RetryDelivery>::new();
+    let subscriptions = Resource::new(
+        move || (create.version().get(), delete.version().get()),
+        |_| list_webhooks(),
+    );
+    let deliveries = Resource::new(move || retry.version().get(), |_| list_deliveries());
+    let url = RwSignal::new(String::new());
+    let events = RwSignal::new(Vec::<WebhookEvent>::new());
+    Effect::new(move || {
+        if let Some(Ok(_)) = create.value().get() {
+            url.set(String::new());
+            events.set(Vec::new());
+        }
+    });
+    let error = move || {
+        [
+            create.value().get().and_then(Result::err),
+            delete.value().get().and_then(Result::err),
+            retry.value().get().and_then(Result::err),
+        ]
+        .into_iter()
+        .flatten()
+        .next()
+        .map(|err| view! { <div class="alert alert-danger">{err.to_string()}</div> })
+    };
+
+    view! {
+        <div class="mt-3 px-3">
+            <h1 class="h3 mb-3">{t!("webhooks-title")}</h1>
+            {error}
+            {move || {
+                create
+                    .value()
+                    .get()
+                    .and_then(Result::ok)
+                    .map(|new| {
+                        view! {
+                            <div class="alert alert-success">
+                                <p>{t!("webhooks-secret", url = new.subscription.url)}</p>
+                                <code class="user-select-all">{new.secret}</code>
+                            </div>
+                        }
+                    })
+            }}
+            <form
+                class="mb-4"
+                style="max-width: 40rem"
+                on:submit=move |ev| {
+                    ev.prevent_default();
+                    create
+                        .dispatch(CreateWebhook {
+                            url: url.get_untracked(),
+                            events: events.get_untracked(),
+                        });
+                }
+            >
+                <div class="mb-3">
+                    <label for="webhook-url" class="form-label">
+                        {t!("webhooks-url")}
+                    </label>
+                    <input
+                        id="webhook-url"
+                        type="url"
+                        class="form-control"
+                        placeholder="https://"
+                        required
+                        prop:value=url
+                        on:input=move |ev| url.set(event_target_value(&ev))
+                    />
+                </div>
+                <fieldset class="mb-3">
+                    <legend class="form-label fs-6">{t!("webhooks-events")}</legend>
+                    {WebhookEvent::iter()
+                        .map(|event| {
+                            let id = format!("webhook-event-{}", event.as_str());
+                            view! {
+                                <div class="form-check form-check-inline">
+                                    <input
+                                        id=id.clone()
+                                        type="checkbox"
+                                        class="form-check-input"
+                                        prop:checked=move || events.with(|events| events.contains(&event))
+                                        on:change=move |ev| {
+                                            let on = event_target_checked(&ev);
+                                            events
+                                                .update(|events| {
+                                                    events.retain(|e| *e != event);
+                                                    if on {
+                                                        events.push(event);
+                                                        events.sort();
+                                                    }
+                                                });
+                                        }
+                                    />
+                                    <label for=id class="form-check-label">
+                                        <code>{event.as_str()}</code>
+                                    </label>
+                                </div>
+                            }
+                        })
+                        .collect_view()}
+                </fieldset>
+                <button type="submit" class="btn btn-primary" prop:disabled=move || create.pending().get()>
+                    {t!("webhooks-add")}
+                </button>
+            </form>
+            <Transition>
+                {move || {
+                    subscriptions
+                        .get()
+                        .and_then(Result::ok)
+                        .map(|subscriptions| {
+                            view! {
+                                <table class="table table-sm align-middle mb-4">
+                                    <thead>
+                                        <tr>
+                                            <th>{t!("webhooks-url")}</th>
+                                            <th>{t!("webhooks-events")}</th>
+                                            <th>{t!("webhooks-created")}</th>
+                                            <th></th>
+                                        </tr>
+                                    </thead>
+                                    <tbody>
+                                        {subscriptions
+                                            .into_iter()
+                                            .map(|subscription| {
+                                                let id = subscription.id;
+                                                let events = subscription
+                                                    .events
+                                                    .iter()
+                                                    .map(|event| event.as_str())
+                                                    .collect::<Vec<_>>()
+                                                    .join(", ");
+                                                view! {
+                                                    <tr>
+                                                        <td>{subscription.url}</td>
+                                                        <td>{events}</td>
+                                                        <td>
+                                                            <DateTime value=subscription.created />
+                                                        </td>
+                                                        <td class="text-end">
+                                                            <button
+                                                                type="button"
+                                                                class="btn btn-sm btn-outline-danger"
+                                                                prop:disabled=move || delete.pending().get()
+                                                                on:click=move |_| {
+                                                                    delete.dispatch(DeleteWebhook { id });
+                                                                }
+                                                            >
+                                                                {t!("webhooks-delete")}
+                                                            </button>
+                                                        </td>
+                                                    </tr>
+                                                }
+                                            })
+                                            .collect_view()}
+                                    </tbody>
+                                </table>
+                            }
+                        })
+                }}
+            </Transition>
+            <div class="d-flex justify-content-between align-items-center">
+                <h2 class="h5">{t!("webhooks-deliveries")}</h2>
+                <button
+                    type="button"
+                    class="btn btn-sm btn-link"
+                    on:click=move |_| deliveries.refetch()
+                >
+                    {t!("webhooks-refresh")}
+                </button>
+            </div>
+            <Transition fallback=|| view! { <p>{t!("user-loading")}</p> }>
+                {move || {
+                    deliveries
+                        .get()
+                        .map(|deliveries| match deliveries {
+                            Ok(deliveries) => {
+                                let (dead, recent): (Vec<_>, Vec<_>) = deliveries
+                                    .into_iter()
+                                    .partition(|delivery| delivery.status == DeliveryStatus::Dead);
+                                view! {
+                                    {(!dead.is_empty())
+                                        .then(|| {
+                                            view! {
+                                                <h3 class="h6 text-danger">{t!("webhooks-dead-letters")}</h3>
+                                                <DeliveryTable deliveries=dead retry />
+                                            }
+                                        })}
+                                    {if recent.is_empty() {
+                                        view! {
+                                            <p class="text-body-secondary">{t!("webhooks-no-deliveries")}</p>
+                                        }
+                                            .into_any()
+                                    } else {
+                                        view! { <DeliveryTable deliveries=recent retry /> }.into_any()
+                                    }}
+                                }
+                                    .into_any()
+                            }
+                            Err(err) => {
+                                view! { <div class="alert alert-danger">{err.to_string()}</div> }
+                                    .into_any()
+                            }
+                        })
+                }}
+            </Transition>
+        </div>
+    }
+}
+
+/// Deliveries with their outcome; dead letters can be retried.
+#[component]
+fn DeliveryTable(
+    deliveries: Vec<WebhookDelivery>,
+    retry: ServerAction<RetryDelivery>,
+) -> impl IntoView {
+    view! {
+        <table class="table table-sm align-middle">
+            <thead>
+                <tr>
+                    <th>{t!("webhooks-created")}</th>
+                    <th>{t!("webhooks-event")}</th>
+                    <th>{t!("webhooks-url")}</th>
+                    <th>{t!("webhooks-status")}</th>
+                    <th>{t!("webhooks-attempts")}</th>
+                    <th>{t!("webhooks-last-result")}</th>
+                    <th>{t!("webhooks-next-attempt")}</th>
+                    <th></th>
+                </tr>
+            </thead>
+            <tbody>
+                {deliveries
+                    .into_iter()
+                    .map(|delivery| {
+                        let id = delivery.id;
+                        let status = delivery.status;
+                        let badge = match status {
+                            DeliveryStatus::Delivered => "badge text-bg-success",
+                            DeliveryStatus::Dead => "badge text-bg-danger",
+                            DeliveryStatus::Retrying => "badge text-bg-warning",
+                            DeliveryStatus::Pending | DeliveryStatus::Sending => {
+                                "badge text-bg-secondary"
+                            }
+                        };
+                        let result = delivery
+                            .last_error
+                            .or(delivery.last_response.map(|code| code.to_string()));
+                        view! {
+                            <tr>
+                                <td>
+                                    <DateTime value=delivery.created />
+                                </td>
+                                <td>
+                                    <code>{delivery.event.as_str()}</code>
+                                </td>
+                                <td class="text-break">{delivery.url}</td>
+                                <td>
+                                    <span class=badge>{move || tr(status.message_id())}</span>
+                                </td>
+                                <td>{delivery.attempts} " / " {MAX_ATTEMPTS}</td>
+                                <td class="small">{result}</td>
+                                <td>
+                                    <DateTime value=delivery.next_attempt />
+                                </td>
+                                <td class="text-end">
+                                    <Show when=move || status == DeliveryStatus::Dead>
+                                        <button
+                                            type="button"
+                                            class="btn btn-sm btn-outline-secondary"
+                                            prop:disabled=move || retry.pending().get()
+                                            on:click=move |_| {
+                                                retry.dispatch(RetryDelivery { id });
+                                            }
+                                        >
+                                            {t!("webhooks-retry")}
+                                        </button>
+                                    </Show>
+                                </td>
+                            </tr>
+                        }
+                    })
+                    .collect_view()}
+            </tbody>
+        </table>
+    }
+}
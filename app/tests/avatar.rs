@@ -0,0 +1,66 @@
+//! Uploaded profile pictures and the initials shown without one.
+
+use std::io::Cursor;
+
+use app::avatar::{initials, AvatarError, AvatarStore, AVATAR_SIZES, MAX_AVATAR_BYTES};
+use image::{ImageFormat, RgbImage};
+use uuid::Uuid;
+
+fn store() -> AvatarStore {
+    AvatarStore::new(std::env::temp_dir().join(format!("avatars-{}", Uuid::new_v4())))
+}
+
+fn picture(width: u32, height: u32, format: ImageFormat) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    RgbImage::from_fn(width, height, |x, _| image::Rgb([(x % 256) as u8, 0, 0]))
+        .write_to(&mut Cursor::new(&mut bytes), format)
+        .unwrap();
+    bytes
+}
+
+#[test]
+fn pictures_are_cropped_square_at_every_size() {
+    let store = store();
+    let bytes = picture(300, 200, ImageFormat::Jpeg);
+
+    let hash = store.save(&bytes).unwrap();
+    for size in AVATAR_SIZES {
+        let path = store.dir().join(&hash).join(format!("{size}.png"));
+        let resized = image::open(path).unwrap();
+        assert_eq!((resized.width(), resized.height()), (size, size));
+    }
+    // The same file is stored once, under the same name.
+    assert_eq!(store.save(&bytes).unwrap(), hash);
+    let other = store.save(&picture(200, 300, ImageFormat::Png)).unwrap();
+    assert_ne!(other, hash);
+
+    std::fs::remove_dir_all(store.dir()).unwrap();
+}
+
+#[test]
+fn only_pictures_of_limited_size_are_accepted() {
+    let store = store();
+
+    assert!(matches!(
+        store.save(b"%PDF-1.7 not a picture"),
+        Err(AvatarError::UnsupportedType)
+    ));
+    // A bitmap, which browsers show but uploads do not take.
+    assert!(matches!(
+        store.save(b"BM\x3a\0\0\0\0\0\0\0\x36\0\0\0"),
+        Err(AvatarError::UnsupportedType)
+    ));
+    assert!(matches!(
+        store.save(&vec![0; MAX_AVATAR_BYTES + 1]),
+        Err(AvatarError::TooLarge)
+    ));
+    assert!(!store.dir().exists());
+}
+
+#[test]
+fn initials_stand_in_for_missing_pictures() {
+    assert_eq!(initials(Some("ada"), Some("Lovelace")), "AL");
+    assert_eq!(initials(Some(" Ștefan "), None), "Ș");
+    assert_eq!(initials(None, Some("Hopper")), "H");
+    assert_eq!(initials(Some(""), None), "");
+}
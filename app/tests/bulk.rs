@@ -0,0 +1,170 @@
+//! Bulk updates from the user list: all or nothing, and as thorough as
+//! the same change made to one user.
+
+use app::{
+    audit::AuditAction,
+    bulk::{bulk_update, BulkOperation, BulkProblem},
+    password::hash_password,
+    session::{ClientInfo, CurrentSession},
+    state::{AppState, ADMIN_ROLE},
+    tenant::Tenant,
+    User, UserStatus,
+};
+use uuid::Uuid;
+
+struct Setup {
+    state: AppState,
+    admin: Uuid,
+    ada: Uuid,
+    grace: Uuid,
+}
+
+fn setup() -> Setup {
+    let mut admin = User::get_user();
+    admin.login = "admin@example.com".to_string();
+    admin.roles.insert(ADMIN_ROLE.to_string());
+    let mut ada = User::get_user();
+    ada.login = "ada@example.com".to_string();
+    ada.hash = hash_password("correct horse");
+    let mut grace = User::get_user();
+    grace.login = "grace@example.com".to_string();
+    grace.hash = hash_password("battery staple");
+    let setup = Setup {
+        state: AppState::default(),
+        admin: admin.unid,
+        ada: ada.unid,
+        grace: grace.unid,
+    };
+    for user in [admin, ada, grace] {
+        setup.state.users.insert(user);
+    }
+    setup
+}
+
+impl Setup {
+    fn sign_in(&self, user: Uuid) -> CurrentSession {
+        self.state
+            .sessions
+            .create(user, "tests", None, self.state.clock.now())
+    }
+
+    fn run(&self, unids: &[Uuid], operation: BulkOperation) -> Vec<Option<BulkProblem>> {
+        let admin = self.sign_in(self.admin);
+        let (report, changed) = bulk_update(
+            &self.state,
+            &self.state.users.scoped(&Tenant::default()),
+            &admin,
+            unids,
+            &operation,
+            None,
+        );
+        assert_eq!(report.applied, changed.len() == unids.len());
+        report
+            .results
+            .into_iter()
+            .map(|result| result.problem)
+            .collect()
+    }
+
+    fn get(&self, unid: Uuid) -> User {
+        self.state
+            .users
+            .scoped(&Tenant::default())
+            .get(unid)
+            .unwrap()
+    }
+
+    fn trust_browser(&self, user: Uuid) {
+        self.state.trusted_devices.trust(
+            &self.state.signing_key,
+            user,
+            &ClientInfo::default(),
+            self.state.clock.now(),
+        );
+    }
+
+    fn signed_in(&self, user: Uuid) -> bool {
+        let now = self.state.clock.now();
+        !self
+            .state
+            .sessions
+            .for_user(user, Uuid::nil(), now)
+            .is_empty()
+            || !self
+                .state
+                .trusted_devices
+                .for_user(user, None, now)
+                .is_empty()
+    }
+}
+
+#[test]
+fn bans_revoke_sessions_and_trusted_browsers() {
+    let setup = setup();
+    for user in [setup.ada, setup.grace] {
+        setup.sign_in(user);
+        setup.trust_browser(user);
+    }
+
+    let problems = setup.run(&[setup.ada, setup.grace], BulkOperation::Ban);
+    assert_eq!(problems, [None, None]);
+    for user in [setup.ada, setup.grace] {
+        assert_eq!(setup.get(user).status, UserStatus::Banned);
+        assert!(!setup.signed_in(user));
+        let entry = setup
+            .state
+            .audit
+            .for_user(user)
+            .into_iter()
+            .find(|entry| entry.action == AuditAction::Banned)
+            .unwrap();
+        assert_eq!(entry.actor, Some(setup.admin));
+    }
+}
+
+#[test]
+fn one_problem_stops_the_whole_update() {
+    let setup = setup();
+    setup.sign_in(setup.ada);
+    let missing = Uuid::new_v4();
+
+    let problems = setup.run(&[setup.ada, setup.admin, missing], BulkOperation::Ban);
+    assert_eq!(
+        problems,
+        [
+            None,
+            Some(BulkProblem::Yourself),
+            Some(BulkProblem::NotFound)
+        ]
+    );
+    assert_eq!(setup.get(setup.ada).status, UserStatus::Active);
+    assert!(setup.signed_in(setup.ada));
+    assert!(setup.state.audit.for_user(setup.ada).is_empty());
+}
+
+#[test]
+fn only_banned_users_are_activated() {
+    let setup = setup();
+    setup.run(&[setup.ada], BulkOperation::Ban);
+
+    let problems = setup.run(&[setup.ada, setup.grace], BulkOperation::Activate);
+    assert_eq!(problems, [None, Some(BulkProblem::NotBanned)]);
+    assert_eq!(setup.get(setup.ada).status, UserStatus::Banned);
+
+    assert_eq!(setup.run(&[setup.ada], BulkOperation::Activate), [None]);
+    assert_eq!(setup.get(setup.ada).status, UserStatus::Active);
+}
+
+#[test]
+fn password_resets_clear_the_password_and_sign_out() {
+    let setup = setup();
+    setup.sign_in(setup.ada);
+    setup.trust_browser(setup.ada);
+
+    let problems = setup.run(&[setup.ada], BulkOperation::ForcePasswordReset);
+    assert_eq!(problems, [None]);
+    let ada = setup.get(setup.ada);
+    assert!(ada.hash.is_empty());
+    assert_eq!(ada.status, UserStatus::Active);
+    assert!(!setup.signed_in(setup.ada));
+}
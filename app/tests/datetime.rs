@@ -0,0 +1,142 @@
+//! Timestamps in the viewer's timezone and locale, with relative hints.
+
+use app::{
+    datetime::DisplayPrefs,
+    i18n::{translate, FluentValue, LANGUAGES},
+};
+use time::{Date, Duration, Month, OffsetDateTime, UtcOffset};
+
+/// `year-month-day hour:minute` at `offset` hours from UTC.
+fn at(year: i32, month: Month, day: u8, hour: u8, minute: u8, offset: i8) -> OffsetDateTime {
+    Date::from_calendar_date(year, month, day)
+        .unwrap()
+        .with_hms(hour, minute, 0)
+        .unwrap()
+        .assume_offset(UtcOffset::from_hms(offset, 0, 0).unwrap())
+}
+
+fn prefs(locale: &str, timezone: &str) -> DisplayPrefs {
+    DisplayPrefs {
+        timezone: timezone.to_string(),
+        locale: locale.to_string(),
+        now: at(2024, Month::March, 5, 15, 4, 0),
+    }
+}
+
+#[test]
+fn formats_for_the_locale() {
+    let value = at(2024, Month::March, 5, 15, 4, 0);
+    for (locale, formatted) in [
+        ("en-US", "Mar 5, 2024, 3:04 PM"),
+        ("en-GB", "5 Mar 2024, 15:04"),
+        ("de", "05.03.2024, 15:04"),
+        ("ro", "05.03.2024, 15:04"),
+        ("fr", "05/03/2024 15:04"),
+    ] {
+        assert_eq!(prefs(locale, "UTC").format(value), formatted);
+    }
+    assert_eq!(
+        prefs("en-US", "UTC").format(at(2024, Month::December, 31, 0, 30, 0)),
+        "Dec 31, 2024, 12:30 AM"
+    );
+}
+
+#[test]
+fn formats_in_the_viewers_timezone() {
+    let value = at(2024, Month::March, 5, 15, 4, 0);
+    assert_eq!(
+        prefs("de", "Europe/Berlin").format(value),
+        "05.03.2024, 16:04"
+    );
+    // Far enough west, it is still the day before.
+    assert_eq!(
+        prefs("en-US", "America/New_York").format(at(2024, Month::March, 5, 3, 4, 0)),
+        "Mar 4, 2024, 10:04 PM"
+    );
+    // The same instant given in another offset shows the same.
+    assert_eq!(
+        prefs("fr", "Europe/Paris").format(at(2024, Month::March, 5, 10, 4, -5)),
+        "05/03/2024 16:04"
+    );
+    // Unknown zones show UTC.
+    assert_eq!(
+        prefs("de", "Mars/Olympus_Mons").format(value),
+        "05.03.2024, 15:04"
+    );
+}
+
+#[test]
+fn each_instant_gets_the_offset_it_had() {
+    // Viewed in March, a timestamp from summer still shows summer time.
+    let berlin = prefs("de", "Europe/Berlin");
+    assert_eq!(
+        berlin.format(at(2024, Month::January, 15, 12, 0, 0)),
+        "15.01.2024, 13:00"
+    );
+    assert_eq!(
+        berlin.format(at(2023, Month::July, 15, 12, 0, 0)),
+        "15.07.2023, 14:00"
+    );
+    // Either side of the switch on the last Sunday of October.
+    assert_eq!(
+        berlin.format(at(2024, Month::October, 27, 0, 30, 0)),
+        "27.10.2024, 02:30"
+    );
+    assert_eq!(
+        berlin.format(at(2024, Month::October, 27, 1, 30, 0)),
+        "27.10.2024, 02:30"
+    );
+}
+
+#[test]
+fn hints_how_long_ago() {
+    let prefs = prefs("en-US", "UTC");
+    let now = prefs.now;
+    assert_eq!(
+        prefs.relative(now - Duration::seconds(30)),
+        ("date-just-now", 0)
+    );
+    assert_eq!(
+        prefs.relative(now - Duration::minutes(5)),
+        ("date-minutes-ago", 5)
+    );
+    assert_eq!(
+        prefs.relative(now - Duration::days(3)),
+        ("date-days-ago", 3)
+    );
+    assert_eq!(
+        prefs.relative(now - Duration::days(400)),
+        ("date-years-ago", 1)
+    );
+    assert_eq!(
+        prefs.relative(now + Duration::hours(2)),
+        ("date-in-hours", 2)
+    );
+    assert_eq!(
+        prefs.relative(now + Duration::days(61)),
+        ("date-in-months", 2)
+    );
+}
+
+#[test]
+fn every_language_has_the_hints() {
+    for (language, _) in LANGUAGES {
+        for id in [
+            "date-years-ago",
+            "date-months-ago",
+            "date-days-ago",
+            "date-hours-ago",
+            "date-minutes-ago",
+            "date-in-years",
+            "date-in-months",
+            "date-in-days",
+            "date-in-hours",
+            "date-in-minutes",
+        ] {
+            let message = translate(language, id, &[("count", FluentValue::from(7))]);
+            assert!(message.contains('7'), "{language} {id}: {message}");
+        }
+        assert_ne!(translate(language, "date-just-now", &[]), "date-just-now");
+        assert_ne!(translate(language, "date-never", &[]), "date-never");
+    }
+}
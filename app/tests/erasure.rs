@@ -0,0 +1,151 @@
+//! Erasing a user's personal data on request.
+
+use app::{
+    audit::{AuditAction, AuditEntry},
+    avatar::AvatarStore,
+    erasure::{erase, erased_login, pseudonym},
+    session::ClientInfo,
+    state::AppState,
+    tenant::Tenant,
+    totp,
+    webhook::WebhookEvent,
+    User, UserStatus,
+};
+use uuid::Uuid;
+
+fn ada() -> User {
+    let mut ada = User::get_user();
+    ada.login = "ada@example.com".to_string();
+    ada.first_name = Some("Ada".to_string());
+    ada.last_name = Some("Lovelace".to_string());
+    ada.roles.insert("editor".to_string());
+    ada.language = Some("fr".to_string());
+    ada
+}
+
+fn record(state: &AppState, subject: Uuid, action: AuditAction) {
+    state.audit.record(AuditEntry {
+        at: state.clock.now(),
+        actor: Some(subject),
+        subject,
+        action,
+        ip: Some("203.0.113.9".to_string()),
+    });
+}
+
+#[test]
+fn scrubs_personal_data_and_keeps_the_account() {
+    let state = AppState::default();
+    state.users.observe(state.webhooks.clone());
+    let tenant = Tenant::default();
+    state.webhooks.subscribe(
+        &tenant,
+        "http://127.0.0.1:9/hook",
+        vec![WebhookEvent::UserCreated, WebhookEvent::UserDeleted],
+    );
+    let users = state.users.scoped(&tenant);
+    let ada = ada();
+    users.insert_all(vec![ada.clone()]).unwrap();
+    state
+        .oidc
+        .link("https://id.example.com", "ada-subject", ada.unid);
+    let now = state.clock.now();
+    state.sessions.create(ada.unid, "tests", None, now);
+    state
+        .trusted_devices
+        .trust(&state.signing_key, ada.unid, &ClientInfo::default(), now);
+    let secret = state.two_factor.begin(ada.unid).unwrap();
+    let code = totp::code_at(&secret, now.unix_timestamp());
+    state.two_factor.confirm(ada.unid, &code, now).unwrap();
+    record(&state, ada.unid, AuditAction::SignedIn);
+
+    assert_eq!(erase(&state, &users, ada.unid), Some(true));
+    let erased = users.get(ada.unid).unwrap();
+    assert!(erased.is_erased());
+    assert_eq!(erased.login, erased_login(ada.unid));
+    assert_eq!(erased.first_name, Some(pseudonym(ada.unid)));
+    assert_eq!(erased.last_name, None);
+    assert!(erased.hash.is_empty());
+    assert!(erased.roles.is_empty());
+    assert_eq!(erased.language, None);
+    assert_eq!(erased.status, UserStatus::Deactivated);
+    assert_eq!(erased.created, ada.created);
+
+    assert!(state
+        .sessions
+        .for_user(ada.unid, Uuid::nil(), now)
+        .is_empty());
+    assert!(state
+        .trusted_devices
+        .for_user(ada.unid, None, now)
+        .is_empty());
+    assert!(!state.two_factor.is_enabled(ada.unid));
+    assert!(state.oidc.identities(ada.unid).is_empty());
+    // Only the deletion is still to be sent, not the creation with the
+    // old data.
+    let events: Vec<_> = state
+        .webhooks
+        .deliveries(&tenant)
+        .into_iter()
+        .map(|delivery| delivery.event)
+        .collect();
+    assert_eq!(events, [WebhookEvent::UserDeleted]);
+    // The audit trail stays, without where the requests came from.
+    let trail = state.audit.for_user(ada.unid);
+    assert_eq!(trail.len(), 1);
+    assert_eq!(trail[0].action, AuditAction::SignedIn);
+    assert_eq!(trail[0].ip, None);
+    // The login is free for someone else.
+    assert!(users.find_by_login("ada@example.com").is_none());
+}
+
+#[test]
+fn erasing_twice_changes_nothing() {
+    let state = AppState::default();
+    let users = state.users.scoped(&Tenant::default());
+    let ada = ada();
+    users.insert_all(vec![ada.clone()]).unwrap();
+
+    assert_eq!(erase(&state, &users, ada.unid), Some(true));
+    let erased = users.get(ada.unid).unwrap();
+    assert_eq!(erase(&state, &users, ada.unid), Some(false));
+    assert_eq!(users.get(ada.unid).unwrap(), erased);
+    assert_eq!(erase(&state, &users, Uuid::new_v4()), None);
+    // Another tenant cannot reach the user.
+    let acme = state.users.scoped(&Tenant {
+        schema: Some("acme".to_string()),
+    });
+    assert_eq!(erase(&state, &acme, ada.unid), None);
+}
+
+#[test]
+fn pictures_are_deleted_unless_shared() {
+    let state = AppState {
+        avatars: AvatarStore::new(std::env::temp_dir().join(format!("avatars-{}", Uuid::new_v4()))),
+        ..AppState::default()
+    };
+    let users = state.users.scoped(&Tenant::default());
+    let (shared, own) = ("a".repeat(64), "b".repeat(64));
+    for hash in [&shared, &own] {
+        std::fs::create_dir_all(state.avatars.dir().join(hash)).unwrap();
+    }
+    let mut ada = ada();
+    ada.avatar = Some(own.clone());
+    let mut grace = User::get_user();
+    grace.login = "grace@example.com".to_string();
+    grace.avatar = Some(shared.clone());
+    let mut hopper = grace.clone();
+    hopper.unid = Uuid::new_v4();
+    hopper.login = "hopper@example.com".to_string();
+    users
+        .insert_all(vec![ada.clone(), grace.clone(), hopper])
+        .unwrap();
+
+    erase(&state, &users, ada.unid);
+    erase(&state, &users, grace.unid);
+    assert_eq!(users.get(ada.unid).unwrap().avatar, None);
+    assert!(!state.avatars.dir().join(&own).exists());
+    assert!(state.avatars.dir().join(&shared).exists());
+
+    std::fs::remove_dir_all(state.avatars.dir()).unwrap();
+}
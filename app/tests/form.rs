@@ -0,0 +1,115 @@
+//! The form fields as the server renders them.
+
+use app::{
+    form::{PasswordField, ReadonlyField, SelectField, TextField},
+    UserStatus,
+};
+use leptos::prelude::*;
+
+fn render<V: IntoView + 'static>(view: impl FnOnce() -> V) -> String {
+    Owner::new().with(|| view().to_html())
+}
+
+#[test]
+fn text_fields_are_labelled_and_named() {
+    let html = render(|| {
+        view! {
+            <TextField
+                label="user-login-email"
+                name="login"
+                value="ada@example.com"
+                input_type="email"
+                required=true
+            />
+        }
+    });
+    assert!(html.contains(r#"id="user-login-email-label""#), "{html}");
+    assert!(html.contains(r#"for="user-login-email""#), "{html}");
+    assert!(html.contains(r#"id="user-login-email""#), "{html}");
+    assert!(html.contains(r#"name="login""#), "{html}");
+    assert!(html.contains(r#"type="email""#), "{html}");
+    assert!(html.contains(r#"value="ada@example.com""#), "{html}");
+    assert!(html.contains("pristine"), "{html}");
+    assert!(html.contains("required"), "{html}");
+}
+
+#[test]
+fn problems_are_rendered_with_the_field() {
+    let missing = render(|| view! { <TextField label="user-first-name" required=true /> });
+    assert!(missing.contains("This field is required"), "{missing}");
+
+    let short = render(|| {
+        view! { <TextField label="user-first-name" value="ada" min_length=8 /> }
+    });
+    assert!(short.contains("Use at least"), "{short}");
+    assert!(short.contains('8'), "{short}");
+
+    let fine = render(|| view! { <TextField label="user-last-name" /> });
+    assert!(!fine.contains("This field is required"), "{fine}");
+}
+
+#[test]
+fn passwords_are_typed_again_while_enabled() {
+    let html = render(|| {
+        view! {
+            <PasswordField
+                label="user-new-password"
+                name="password"
+                confirm="user-retype-password"
+            />
+        }
+    });
+    assert!(html.contains(r#"name="password""#), "{html}");
+    assert!(html.contains(r#"name="password_again""#), "{html}");
+    assert!(html.contains("Retype new password"), "{html}");
+    assert!(html.contains("new-password"), "{html}");
+
+    let disabled = render(|| {
+        view! {
+            <PasswordField
+                label="user-new-password"
+                name="password"
+                disabled=true
+                confirm="user-retype-password"
+            />
+        }
+    });
+    assert!(!disabled.contains("password_again"), "{disabled}");
+    assert!(disabled.contains("disabled"), "{disabled}");
+}
+
+#[test]
+fn selects_offer_every_value_by_its_name() {
+    let html = render(|| {
+        view! {
+            <SelectField
+                label="user-status"
+                name="status"
+                value=UserStatus::Banned
+                message_id=UserStatus::message_id
+            />
+        }
+    });
+    assert!(html.contains(r#"name="status""#), "{html}");
+    let options: Vec<usize> = ["Active", "Banned", "Deactivated", "Pending"]
+        .iter()
+        .map(|value| {
+            html.find(&format!(r#"value="{value}""#))
+                .unwrap_or_else(|| panic!("{value} missing from {html}"))
+        })
+        .collect();
+    assert!(options.is_sorted(), "{html}");
+}
+
+#[test]
+fn readonly_fields_are_labelled_by_their_label() {
+    let html = render(|| {
+        view! { <ReadonlyField label="user-registered-on">"yesterday"</ReadonlyField> }
+    });
+    assert!(
+        html.contains(r#"aria-labelledby="user-registered-on-label""#),
+        "{html}"
+    );
+    assert!(html.contains("yesterday"), "{html}");
+    assert!(!html.contains("<input"), "{html}");
+}
@@ -0,0 +1,41 @@
+//! Language negotiation and the translations of the UI's messages.
+
+use app::i18n::{negotiate, translate, FluentValue, LANGUAGES};
+
+const SUPPORTED: [&str; 4] = ["en-US", "de", "fr", "ro"];
+
+#[test]
+fn prefers_higher_q_values() {
+    assert_eq!(negotiate("fr;q=0.5, de", &SUPPORTED), Some("de"));
+    assert_eq!(
+        negotiate("en-US;q=0.3, ro;q=0.9, fr", &SUPPORTED),
+        Some("fr")
+    );
+    // Equal weights keep the order they are listed in.
+    assert_eq!(negotiate("ro;q=0.8, de;q=0.8", &SUPPORTED), Some("ro"));
+    assert_eq!(negotiate("de-AT, en;q=0.8", &SUPPORTED), Some("de"));
+}
+
+#[test]
+fn skips_unacceptable_languages() {
+    assert_eq!(negotiate("de;q=0, fr;q=0.1", &SUPPORTED), Some("fr"));
+    assert_eq!(negotiate("de;q=0.0", &SUPPORTED), None);
+    assert_eq!(negotiate("de;q=high, ro", &SUPPORTED), Some("ro"));
+    assert_eq!(negotiate("ja, *;q=0.5", &SUPPORTED), None);
+}
+
+#[test]
+fn every_language_counts_recovery_codes() {
+    for (language, _) in LANGUAGES {
+        let message = translate(
+            language,
+            "two-factor-enabled",
+            &[("count", FluentValue::from(3))],
+        );
+        assert!(message.contains('3'), "{language}: {message}");
+        assert_ne!(
+            translate(language, "browsers-sign-out-everywhere", &[]),
+            "browsers-sign-out-everywhere"
+        );
+    }
+}
@@ -0,0 +1,101 @@
+//! Administrators acting as another user, and what they cannot do then.
+
+use app::{
+    oauth::{router, AuthorizationServer, OAuthClient, OAUTH_PATH},
+    session::SessionStore,
+    state::AppState,
+    tenant::Tenant,
+    User,
+};
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use time::OffsetDateTime;
+use tower::ServiceExt;
+use uuid::Uuid;
+
+#[test]
+fn impersonating_sessions_remember_the_administrator() {
+    let sessions = SessionStore::default();
+    let (admin, ada) = (Uuid::new_v4(), Uuid::new_v4());
+    let now = OffsetDateTime::now_utc();
+    let own = sessions.create(admin, "tests", None, now);
+    assert_eq!(own.impersonator, None);
+
+    let acting = sessions.impersonate(own, ada, "tests", None, now);
+    let touched = sessions.touch(acting.id, None, now).unwrap();
+    assert_eq!(touched.user, ada);
+    assert_eq!(touched.impersonator, Some(admin));
+    // The administrator's own session carries on meanwhile.
+    assert!(sessions.touch(own.id, None, now).is_some());
+
+    assert_eq!(sessions.end_impersonation(acting.id), Some(own.id));
+    assert!(sessions.touch(acting.id, None, now).is_none());
+    // Only impersonating sessions can be ended that way.
+    assert_eq!(sessions.end_impersonation(own.id), None);
+    assert!(sessions.touch(own.id, None, now).is_some());
+}
+
+#[test]
+fn signing_the_administrator_out_ends_their_impersonations() {
+    let sessions = SessionStore::default();
+    let (admin, ada) = (Uuid::new_v4(), Uuid::new_v4());
+    let now = OffsetDateTime::now_utc();
+    let own = sessions.create(admin, "tests", None, now);
+    let acting = sessions.impersonate(own, ada, "tests", None, now);
+    let adas_own = sessions.create(ada, "tests", None, now);
+
+    assert_eq!(sessions.revoke_all(admin), 2);
+    assert!(sessions.touch(acting.id, None, now).is_none());
+    assert!(sessions.touch(adas_own.id, None, now).is_some());
+
+    // Stopping after the administrator's session ended has nowhere to
+    // return to.
+    let own = sessions.create(admin, "tests", None, now);
+    let acting = sessions.impersonate(own, ada, "tests", None, now);
+    sessions.revoke(own.id);
+    assert_eq!(sessions.end_impersonation(acting.id), None);
+    assert!(sessions.touch(acting.id, None, now).is_none());
+}
+
+#[tokio::test]
+async fn impersonators_cannot_sign_in_to_other_applications() {
+    let state = AppState {
+        oauth: AuthorizationServer::default().with_client(OAuthClient {
+            id: "wiki".to_string(),
+            name: "Wiki".to_string(),
+            schema: None,
+            secret: Some("s3cret".to_string()),
+            redirect_uris: vec!["http://localhost/callback".to_string()],
+        }),
+        ..AppState::default()
+    };
+    let (admin, ada) = (User::get_user(), User::get_user());
+    let now = state.clock.now();
+    let own = state.sessions.create(admin.unid, "tests", None, now);
+    let acting = state
+        .sessions
+        .impersonate(own, ada.unid, "tests", None, now);
+    state.users.insert(admin);
+    state.users.insert(ada);
+
+    let mut request = Request::get(format!(
+        "{OAUTH_PATH}/authorize?response_type=code&client_id=wiki&scope=openid"
+    ))
+    .body(Body::empty())
+    .unwrap();
+    request.extensions_mut().insert(Tenant::default());
+    request.extensions_mut().insert(acting);
+    let response = Router::new()
+        .nest(OAUTH_PATH, router(state))
+        .oneshot(request)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(
+        response.headers()[header::LOCATION],
+        "http://localhost/callback?error=access_denied"
+    );
+}
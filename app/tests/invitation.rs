@@ -0,0 +1,60 @@
+//! Invitation links: single use, for a week, and replaced when resent.
+
+use app::invitation::{Invitations, INVITATION_LIFETIME};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+#[test]
+fn links_work_once() {
+    let invitations = Invitations::default();
+    let user = Uuid::new_v4();
+    let now = OffsetDateTime::now_utc();
+    let token = invitations.issue(user, now);
+
+    assert_eq!(invitations.find(&token, now), Some(user));
+    assert_eq!(invitations.expires(user), Some(now + INVITATION_LIFETIME));
+    assert_eq!(invitations.redeem(&token, now), Some(user));
+    assert_eq!(invitations.redeem(&token, now), None);
+    assert_eq!(invitations.find(&token, now), None);
+    assert_eq!(invitations.expires(user), None);
+    assert_eq!(invitations.find("made-up", now), None);
+}
+
+#[test]
+fn links_expire_after_a_week() {
+    let invitations = Invitations::default();
+    let user = Uuid::new_v4();
+    let now = OffsetDateTime::now_utc();
+    let token = invitations.issue(user, now);
+
+    let last_moment = now + INVITATION_LIFETIME - Duration::SECOND;
+    assert_eq!(invitations.find(&token, last_moment), Some(user));
+    let expired = now + INVITATION_LIFETIME;
+    assert_eq!(invitations.find(&token, expired), None);
+    assert_eq!(invitations.redeem(&token, expired), None);
+    // Resending gives a new week.
+    let resent = invitations.issue(user, expired);
+    assert_eq!(invitations.redeem(&resent, expired), Some(user));
+}
+
+#[test]
+fn resending_replaces_the_link_and_revoking_ends_it() {
+    let invitations = Invitations::default();
+    let (ada, grace) = (Uuid::new_v4(), Uuid::new_v4());
+    let now = OffsetDateTime::now_utc();
+    let first = invitations.issue(ada, now);
+    let second = invitations.issue(ada, now + Duration::DAY);
+    let graces = invitations.issue(grace, now);
+
+    assert_eq!(invitations.find(&first, now + Duration::DAY), None);
+    assert_eq!(invitations.find(&second, now + Duration::DAY), Some(ada));
+    assert_eq!(
+        invitations.expires(ada),
+        Some(now + Duration::DAY + INVITATION_LIFETIME)
+    );
+
+    assert!(invitations.revoke(ada));
+    assert!(!invitations.revoke(ada));
+    assert_eq!(invitations.find(&second, now), None);
+    assert_eq!(invitations.find(&graces, now), Some(grace));
+}
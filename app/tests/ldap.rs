@@ -0,0 +1,343 @@
+//! Syncs and signs in against an in-process stand-in for a directory
+//! server.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use app::{
+    ldap::{Directory, DirectoryEntry, LdapConfig, LdapConnectors, LdapError, SyncReport},
+    password::hash_password,
+    state::AppState,
+    tenant::Tenant,
+    User, UserStatus,
+};
+use serde_json::json;
+use uuid::Uuid;
+
+const BASE_DN: &str = "ou=people,dc=example,dc=com";
+const ALICE: &str = "uid=alice,ou=people,dc=example,dc=com";
+const BOB: &str = "uid=bob,ou=people,dc=example,dc=com";
+
+#[derive(Debug, Default)]
+struct Entries {
+    entries: Mutex<Vec<DirectoryEntry>>,
+    passwords: Mutex<HashMap<String, String>>,
+    down: AtomicBool,
+}
+
+/// Answers searches and binds from memory, as a directory server would.
+#[derive(Clone, Debug, Default)]
+struct StandIn(Arc<Entries>);
+
+impl StandIn {
+    fn add(&self, dn: &str, attributes: &[(&str, &[&str])]) {
+        let entry = DirectoryEntry {
+            dn: dn.to_string(),
+            attributes: attributes
+                .iter()
+                .map(|(name, values)| {
+                    (
+                        name.to_string(),
+                        values.iter().map(|v| v.to_string()).collect(),
+                    )
+                })
+                .collect(),
+        };
+        let mut entries = self.0.entries.lock().unwrap();
+        entries.retain(|existing| existing.dn != dn);
+        entries.push(entry);
+    }
+
+    fn remove(&self, dn: &str) {
+        self.0
+            .entries
+            .lock()
+            .unwrap()
+            .retain(|entry| entry.dn != dn);
+    }
+
+    fn set_password(&self, dn: &str, password: &str) {
+        self.0
+            .passwords
+            .lock()
+            .unwrap()
+            .insert(dn.to_string(), password.to_string());
+    }
+
+    fn go_down(&self) {
+        self.0.down.store(true, Ordering::SeqCst);
+    }
+
+    fn check_up(&self) -> Result<(), LdapError> {
+        match self.0.down.load(Ordering::SeqCst) {
+            true => Err(LdapError("connection refused".to_string())),
+            false => Ok(()),
+        }
+    }
+}
+
+impl Directory for StandIn {
+    fn search(
+        &self,
+        base_dn: &str,
+        filter: &str,
+        attributes: &[&str],
+    ) -> Result<Vec<DirectoryEntry>, LdapError> {
+        self.check_up()?;
+        assert_eq!(filter, "(objectClass=person)");
+        let suffix = format!(",{base_dn}");
+        Ok(self
+            .0
+            .entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|entry| entry.dn.ends_with(&suffix))
+            .map(|entry| DirectoryEntry {
+                dn: entry.dn.clone(),
+                attributes: entry
+                    .attributes
+                    .iter()
+                    .filter(|(name, _)| attributes.contains(&name.as_str()))
+                    .map(|(name, values)| (name.clone(), values.clone()))
+                    .collect(),
+            })
+            .collect())
+    }
+
+    fn bind(&self, dn: &str, password: &str) -> Result<bool, LdapError> {
+        self.check_up()?;
+        // Like a real server, an empty password is an anonymous bind.
+        Ok(password.is_empty()
+            || self
+                .0
+                .passwords
+                .lock()
+                .unwrap()
+                .get(dn)
+                .is_some_and(|p| p == password))
+    }
+}
+
+fn config(authenticate: bool) -> LdapConfig {
+    serde_json::from_value(json!({
+        "url": "ldap://directory.invalid",
+        "base_dn": BASE_DN,
+        "role_map": { "cn=staff,ou=groups,dc=example,dc=com": "editor" },
+        "authenticate": authenticate,
+    }))
+    .unwrap()
+}
+
+fn setup(authenticate: bool) -> (AppState, StandIn) {
+    let directory = StandIn::default();
+    directory.add(
+        ALICE,
+        &[
+            ("mail", &["alice@example.com"]),
+            ("givenName", &["Alice"]),
+            ("sn", &["Liddell"]),
+            (
+                "memberOf",
+                &[
+                    "cn=staff,ou=groups,dc=example,dc=com",
+                    "cn=Admin,ou=groups,dc=example,dc=com",
+                    "cn=chess club,ou=groups,dc=example,dc=com",
+                ],
+            ),
+        ],
+    );
+    directory.add(
+        BOB,
+        &[("mail", &["bob@example.com"]), ("givenName", &["Bob"])],
+    );
+    let state = AppState {
+        ldap: LdapConnectors::default().with_directory(config(authenticate), directory.clone()),
+        ..AppState::default()
+    };
+    (state, directory)
+}
+
+fn account(state: &AppState, login: &str) -> User {
+    state
+        .users
+        .scoped(&Tenant::default())
+        .find_by_login(login)
+        .unwrap()
+}
+
+#[tokio::test]
+async fn creates_accounts_with_mapped_attributes_and_roles() {
+    let (state, _) = setup(false);
+    let report = state.ldap.sync(&state, &Tenant::default()).await.unwrap();
+    assert_eq!(
+        report,
+        SyncReport {
+            created: 2,
+            ..SyncReport::default()
+        }
+    );
+
+    let alice = account(&state, "alice@example.com");
+    assert_eq!(alice.first_name.as_deref(), Some("Alice"));
+    assert_eq!(alice.last_name.as_deref(), Some("Liddell"));
+    assert_eq!(alice.status, UserStatus::Active);
+    // Mapped explicitly, by common name, and ignored for want of a role.
+    let mut roles: Vec<_> = alice.roles.into_iter().collect();
+    roles.sort();
+    assert_eq!(roles, ["admin", "editor"]);
+    assert_eq!(state.ldap.linked_dn(alice.unid).as_deref(), Some(ALICE));
+
+    let bob = account(&state, "bob@example.com");
+    assert_eq!(bob.last_name, None);
+    assert!(bob.roles.is_empty());
+}
+
+#[tokio::test]
+async fn follows_changes_and_deactivates_removed_entries() {
+    let (state, directory) = setup(false);
+    let tenant = Tenant::default();
+    state.ldap.sync(&state, &tenant).await.unwrap();
+    let alice = account(&state, "alice@example.com");
+
+    let report = state.ldap.sync(&state, &tenant).await.unwrap();
+    assert_eq!(report, SyncReport::default());
+
+    // A new address is a new login for the same account.
+    directory.add(
+        ALICE,
+        &[
+            ("mail", &["alice@wonderland.example"]),
+            ("givenName", &["Alice"]),
+        ],
+    );
+    directory.remove(BOB);
+    let report = state.ldap.sync(&state, &tenant).await.unwrap();
+    assert_eq!(
+        report,
+        SyncReport {
+            updated: 1,
+            deactivated: 1,
+            ..SyncReport::default()
+        }
+    );
+    let renamed = account(&state, "alice@wonderland.example");
+    assert_eq!(renamed.unid, alice.unid);
+    assert_eq!(renamed.last_name, None);
+    assert!(renamed.roles.is_empty());
+    assert_eq!(
+        account(&state, "bob@example.com").status,
+        UserStatus::Deactivated
+    );
+
+    directory.add(BOB, &[("mail", &["bob@example.com"])]);
+    state.ldap.sync(&state, &tenant).await.unwrap();
+    assert_eq!(
+        account(&state, "bob@example.com").status,
+        UserStatus::Active
+    );
+}
+
+#[tokio::test]
+async fn links_existing_accounts_by_login() {
+    let (state, _) = setup(false);
+    let tenant = Tenant::default();
+    let mut existing = User::get_user();
+    existing.login = "Alice@Example.com".to_string();
+    existing.hash = hash_password("local");
+    existing.status = UserStatus::Banned;
+    state.users.scoped(&tenant).insert(existing.clone());
+
+    let report = state.ldap.sync(&state, &tenant).await.unwrap();
+    assert_eq!((report.created, report.updated), (1, 1));
+    let alice = account(&state, "alice@example.com");
+    assert_eq!(alice.unid, existing.unid);
+    assert_eq!(alice.hash, existing.hash);
+    assert_eq!(alice.status, UserStatus::Banned);
+    assert_eq!(state.users.scoped(&tenant).all().len(), 2);
+}
+
+#[tokio::test]
+async fn an_empty_or_unreachable_directory_deactivates_nobody() {
+    let (state, directory) = setup(false);
+    let tenant = Tenant::default();
+    state.ldap.sync(&state, &tenant).await.unwrap();
+
+    directory.remove(ALICE);
+    directory.remove(BOB);
+    let report = state.ldap.sync(&state, &tenant).await.unwrap();
+    assert_eq!(report, SyncReport::default());
+
+    directory.go_down();
+    assert!(state.ldap.sync(&state, &tenant).await.is_err());
+    assert!(state
+        .users
+        .scoped(&tenant)
+        .all()
+        .iter()
+        .all(|user| user.status == UserStatus::Active));
+}
+
+#[tokio::test]
+async fn entries_without_a_free_login_are_skipped() {
+    let (state, directory) = setup(false);
+    let tenant = Tenant::default();
+    directory.add(
+        "uid=nomail,ou=people,dc=example,dc=com",
+        &[("givenName", &["Nobody"])],
+    );
+    directory.add(
+        "uid=alice2,ou=people,dc=example,dc=com",
+        &[("mail", &["ALICE@example.com"])],
+    );
+    let report = state.ldap.sync(&state, &tenant).await.unwrap();
+    assert_eq!((report.created, report.skipped), (2, 2));
+
+    // Only the directory of the tenant is synced.
+    let other = Tenant {
+        schema: Some("acme".to_string()),
+    };
+    assert_eq!(
+        state.ldap.sync(&state, &other).await.unwrap(),
+        SyncReport::default()
+    );
+    assert!(state.users.scoped(&other).all().is_empty());
+}
+
+#[tokio::test]
+async fn binds_as_the_entry_to_check_passwords() {
+    let (state, directory) = setup(true);
+    let tenant = Tenant::default();
+    directory.set_password(ALICE, "rabbit hole");
+    state.ldap.sync(&state, &tenant).await.unwrap();
+    let alice = account(&state, "alice@example.com");
+
+    let check = |password: &'static str| state.ldap.authenticate(&tenant, alice.unid, password);
+    assert_eq!(check("rabbit hole").await.unwrap(), Some(true));
+    assert_eq!(check("looking glass").await.unwrap(), Some(false));
+    assert_eq!(check("").await.unwrap(), Some(false));
+
+    // Accounts from elsewhere keep their stored password.
+    let local = state.ldap.authenticate(&tenant, Uuid::new_v4(), "demo");
+    assert_eq!(local.await.unwrap(), None);
+
+    directory.go_down();
+    assert!(check("rabbit hole").await.is_err());
+}
+
+#[tokio::test]
+async fn leaves_passwords_alone_unless_configured() {
+    let (state, directory) = setup(false);
+    let tenant = Tenant::default();
+    directory.set_password(ALICE, "rabbit hole");
+    state.ldap.sync(&state, &tenant).await.unwrap();
+    let alice = account(&state, "alice@example.com");
+
+    let result = state.ldap.authenticate(&tenant, alice.unid, "rabbit hole");
+    assert_eq!(result.await.unwrap(), None);
+}
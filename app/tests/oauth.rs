@@ -0,0 +1,317 @@
+//! Other applications signing people in through the authorization server,
+//! driven request by request against its router.
+
+use app::{
+    oauth::{router, AuthorizationServer, OAuthClient, OAUTH_PATH},
+    session::CurrentSession,
+    state::AppState,
+    tenant::Tenant,
+    User, UserStatus,
+};
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Request, StatusCode},
+    response::Response,
+    Router,
+};
+use data_encoding::{BASE64, BASE64URL_NOPAD};
+use reqwest::Url;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tower::ServiceExt;
+
+const WIKI: &str = "wiki";
+const WIKI_SECRET: &str = "s3cret";
+const SPA: &str = "spa";
+const REDIRECT_URI: &str = "http://localhost/callback";
+
+struct Setup {
+    state: AppState,
+    app: Router,
+    session: CurrentSession,
+}
+
+fn setup() -> Setup {
+    let client = |id: &str, secret: Option<&str>| OAuthClient {
+        id: id.to_string(),
+        name: id.to_string(),
+        schema: None,
+        secret: secret.map(str::to_string),
+        redirect_uris: vec![REDIRECT_URI.to_string()],
+    };
+    let state = AppState {
+        oauth: AuthorizationServer::default()
+            .with_client(client(WIKI, Some(WIKI_SECRET)))
+            .with_client(client(SPA, None)),
+        ..AppState::default()
+    };
+    let mut ada = User::get_user();
+    ada.login = "ada@example.com".to_string();
+    ada.roles.insert("editor".to_string());
+    let session = state
+        .sessions
+        .create(ada.unid, "tests", None, state.clock.now());
+    state.users.insert(ada);
+    Setup {
+        app: Router::new().nest(OAUTH_PATH, router(state.clone())),
+        state,
+        session,
+    }
+}
+
+impl Setup {
+    async fn send(&self, mut request: Request<Body>, signed_in: bool) -> Response {
+        request.extensions_mut().insert(Tenant::default());
+        if signed_in {
+            request.extensions_mut().insert(self.session);
+        }
+        self.app.clone().oneshot(request).await.unwrap()
+    }
+
+    /// Starts an authorization as the signed-in user and returns where the
+    /// browser is sent.
+    async fn authorize(&self, client: &str, scope: &str, extra: &[(&str, &str)]) -> String {
+        let url = Url::parse_with_params(
+            &format!("http://localhost{OAUTH_PATH}/authorize"),
+            [
+                ("response_type", "code"),
+                ("client_id", client),
+                ("redirect_uri", REDIRECT_URI),
+                ("scope", scope),
+                ("state", "xyz"),
+            ]
+            .iter()
+            .chain(extra),
+        )
+        .unwrap();
+        let uri = &url.as_str()["http://localhost".len()..];
+        let response = self
+            .send(Request::get(uri).body(Body::empty()).unwrap(), true)
+            .await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        response.headers()[header::LOCATION]
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    /// Authorizes `client`, consenting unless that was remembered, and
+    /// returns the code.
+    async fn code(&self, client: &str, scope: &str, extra: &[(&str, &str)]) -> String {
+        let location = self.authorize(client, scope, extra).await;
+        let back = match location.strip_prefix("/consent/") {
+            Some(request) => self
+                .state
+                .oauth
+                .decide(
+                    &Tenant::default(),
+                    request,
+                    self.session.user,
+                    true,
+                    self.state.clock.now(),
+                )
+                .unwrap(),
+            None => location,
+        };
+        assert_eq!(param(&back, "state").as_deref(), Some("xyz"));
+        param(&back, "code").unwrap()
+    }
+
+    /// Posts `form` to the token endpoint as `client`.
+    async fn token(&self, client: &str, form: &str) -> (StatusCode, Value) {
+        let mut request = Request::post(format!("{OAUTH_PATH}/token"))
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded");
+        request = match client {
+            WIKI => request.header(
+                header::AUTHORIZATION,
+                format!(
+                    "Basic {}",
+                    BASE64.encode(format!("{WIKI}:{WIKI_SECRET}").as_bytes())
+                ),
+            ),
+            _ => request,
+        };
+        let form = match client {
+            WIKI => form.to_string(),
+            _ => format!("{form}&client_id={client}"),
+        };
+        json(
+            self.send(request.body(Body::from(form)).unwrap(), false)
+                .await,
+        )
+        .await
+    }
+
+    async fn redeem(
+        &self,
+        client: &str,
+        code: &str,
+        verifier: Option<&str>,
+    ) -> (StatusCode, Value) {
+        let mut form = format!(
+            "grant_type=authorization_code&code={code}&redirect_uri={}",
+            encode(REDIRECT_URI)
+        );
+        if let Some(verifier) = verifier {
+            form.push_str(&format!("&code_verifier={verifier}"));
+        }
+        self.token(client, &form).await
+    }
+
+    async fn refresh(&self, token: &str) -> (StatusCode, Value) {
+        self.token(
+            WIKI,
+            &format!("grant_type=refresh_token&refresh_token={token}"),
+        )
+        .await
+    }
+
+    async fn userinfo(&self, access_token: &str) -> (StatusCode, Value) {
+        let request = Request::get(format!("{OAUTH_PATH}/userinfo"))
+            .header(header::AUTHORIZATION, format!("Bearer {access_token}"))
+            .body(Body::empty())
+            .unwrap();
+        json(self.send(request, false).await).await
+    }
+}
+
+fn param(url: &str, name: &str) -> Option<String> {
+    Url::parse(url)
+        .unwrap()
+        .query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+fn encode(value: &str) -> String {
+    value.replace(':', "%3A").replace('/', "%2F")
+}
+
+async fn json(response: Response) -> (StatusCode, Value) {
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+/// The claims of a JWT, unverified.
+fn claims(token: &str) -> Value {
+    let payload = token.split('.').nth(1).unwrap();
+    serde_json::from_slice(&BASE64URL_NOPAD.decode(payload.as_bytes()).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn codes_are_redeemed_once_for_tokens() {
+    let setup = setup();
+    let code = setup
+        .code(WIKI, "openid profile roles", &[("nonce", "n-0S6")])
+        .await;
+
+    let (status, tokens) = setup.redeem(WIKI, &code, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let id_token = claims(tokens["id_token"].as_str().unwrap());
+    assert_eq!(id_token["sub"], setup.session.user.to_string());
+    assert_eq!(id_token["aud"], WIKI);
+    assert_eq!(id_token["nonce"], "n-0S6");
+    assert_eq!(id_token["roles"], serde_json::json!(["editor"]));
+    // Without offline_access there is no refresh token.
+    assert!(tokens.get("refresh_token").is_none());
+
+    let (status, userinfo) = setup
+        .userinfo(tokens["access_token"].as_str().unwrap())
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(userinfo["preferred_username"], "ada@example.com");
+    assert!(userinfo.get("email").is_none());
+
+    let (status, error) = setup.redeem(WIKI, &code, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["error"], "invalid_grant");
+}
+
+#[tokio::test]
+async fn consent_is_remembered() {
+    let setup = setup();
+    setup.code(WIKI, "openid email", &[]).await;
+
+    let back = setup.authorize(WIKI, "openid email", &[]).await;
+    assert!(back.starts_with(REDIRECT_URI));
+    assert!(param(&back, "code").is_some());
+    // Asking for more needs consent again.
+    let more = setup.authorize(WIKI, "openid email roles", &[]).await;
+    assert!(more.starts_with("/consent/"));
+}
+
+#[tokio::test]
+async fn public_clients_prove_themselves_with_pkce() {
+    let setup = setup();
+    let refused = setup.authorize(SPA, "openid", &[]).await;
+    assert_eq!(param(&refused, "error").as_deref(), Some("invalid_request"));
+
+    let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+    let challenge = BASE64URL_NOPAD.encode(&Sha256::digest(verifier.as_bytes()));
+    let pkce = [
+        ("code_challenge", challenge.as_str()),
+        ("code_challenge_method", "S256"),
+    ];
+
+    let code = setup.code(SPA, "openid", &pkce).await;
+    let (status, error) = setup.redeem(SPA, &code, Some("not-the-verifier")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["error"], "invalid_grant");
+    // A wrong guess uses the code up.
+    let (status, _) = setup.redeem(SPA, &code, Some(verifier)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let code = setup.code(SPA, "openid", &pkce).await;
+    let (status, _) = setup.redeem(SPA, &code, None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let code = setup.code(SPA, "openid", &pkce).await;
+    let (status, _) = setup.redeem(SPA, &code, Some(verifier)).await;
+    assert_eq!(status, StatusCode::OK);
+    // Another client cannot redeem the code.
+    let code = setup.code(SPA, "openid", &pkce).await;
+    let (status, _) = setup.redeem(WIKI, &code, Some(verifier)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn reusing_a_refresh_token_revokes_its_family() {
+    let setup = setup();
+    let code = setup.code(WIKI, "openid offline_access", &[]).await;
+    let (_, tokens) = setup.redeem(WIKI, &code, None).await;
+    let first = tokens["refresh_token"].as_str().unwrap().to_string();
+
+    let (status, rotated) = setup.refresh(&first).await;
+    assert_eq!(status, StatusCode::OK);
+    let second = rotated["refresh_token"].as_str().unwrap().to_string();
+    assert_ne!(second, first);
+
+    // Replaying the first token, as a thief would, ends the second too.
+    let (status, error) = setup.refresh(&first).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["error"], "invalid_grant");
+    let (status, _) = setup.refresh(&second).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn banned_users_get_no_more_tokens() {
+    let setup = setup();
+    let code = setup.code(WIKI, "openid offline_access", &[]).await;
+    let (_, tokens) = setup.redeem(WIKI, &code, None).await;
+
+    setup
+        .state
+        .users
+        .scoped(&Tenant::default())
+        .update(setup.session.user, |user| user.status = UserStatus::Banned);
+    let (status, _) = setup
+        .userinfo(tokens["access_token"].as_str().unwrap())
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, error) = setup
+        .refresh(tokens["refresh_token"].as_str().unwrap())
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["error"], "invalid_grant");
+}
@@ -0,0 +1,505 @@
+//! Signs in through a small identity provider served in-process on a local
+//! port, driving the browser's side of the redirects by hand.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use app::{
+    oidc::{router, OidcLogins, OidcProvider, OIDC_PATH},
+    session::SESSION_COOKIE,
+    state::{AppState, ADMIN_ROLE},
+    tenant::Tenant,
+    totp,
+    two_factor::CHALLENGE_COOKIE,
+    User, UserStatus,
+};
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::{header, Request, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    routing::{get, post},
+    Extension, Form, Json, Router,
+};
+use data_encoding::BASE64URL_NOPAD;
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use rsa::{
+    pkcs1::{EncodeRsaPrivateKey, LineEnding},
+    rand_core::OsRng,
+    traits::PublicKeyParts,
+    RsaPrivateKey,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tower::ServiceExt;
+
+const CLIENT_ID: &str = "users-app";
+const CLIENT_SECRET: &str = "s3cret";
+const KEY_ID: &str = "key-1";
+const REDIRECT_URL: &str = "http://localhost/auth/oidc/mock/callback";
+
+/// Generating a key is slow in debug builds, so the tests share one.
+fn signing_key() -> &'static RsaPrivateKey {
+    static KEY: OnceLock<RsaPrivateKey> = OnceLock::new();
+    KEY.get_or_init(|| RsaPrivateKey::new(&mut OsRng, 2048).unwrap())
+}
+
+/// A code handed out by `/authorize`, waiting to be redeemed.
+struct Grant {
+    nonce: String,
+    challenge: String,
+    claims: Value,
+}
+
+#[derive(Default)]
+struct IdpState {
+    issuer: String,
+    /// Claims of whoever signs in next.
+    user: Mutex<Value>,
+    grants: Mutex<HashMap<String, Grant>>,
+    key_fetches: AtomicUsize,
+}
+
+#[derive(Clone)]
+struct Idp(Arc<IdpState>);
+
+impl Idp {
+    async fn start() -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let idp = Idp(Arc::new(IdpState {
+            issuer: format!("http://{}", listener.local_addr().unwrap()),
+            ..Default::default()
+        }));
+        let router = Router::new()
+            .route("/.well-known/openid-configuration", get(discovery))
+            .route("/authorize", get(authorize))
+            .route("/token", post(token))
+            .route("/jwks", get(jwks))
+            .with_state(idp.clone());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        idp
+    }
+
+    fn sign_in_as(&self, claims: Value) {
+        *self.0.user.lock().unwrap() = claims;
+    }
+
+    fn key_fetches(&self) -> usize {
+        self.0.key_fetches.load(Ordering::SeqCst)
+    }
+
+    fn provider(&self) -> OidcProvider {
+        serde_json::from_value(json!({
+            "id": "mock",
+            "name": "Mock",
+            "issuer": self.0.issuer,
+            "client_id": CLIENT_ID,
+            "client_secret": CLIENT_SECRET,
+            "redirect_url": REDIRECT_URL,
+        }))
+        .unwrap()
+    }
+}
+
+async fn discovery(State(idp): State<Idp>) -> Json<Value> {
+    let issuer = &idp.0.issuer;
+    Json(json!({
+        "issuer": issuer,
+        "authorization_endpoint": format!("{issuer}/authorize"),
+        "token_endpoint": format!("{issuer}/token"),
+        "jwks_uri": format!("{issuer}/jwks"),
+    }))
+}
+
+/// Signs the current user in without asking, as if they had a session.
+async fn authorize(
+    State(idp): State<Idp>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Response {
+    assert_eq!(params["client_id"], CLIENT_ID);
+    assert_eq!(params["response_type"], "code");
+    assert_eq!(params["code_challenge_method"], "S256");
+    assert!(params["scope"].split(' ').any(|scope| scope == "openid"));
+    let code = uuid::Uuid::new_v4().to_string();
+    idp.0.grants.lock().unwrap().insert(
+        code.clone(),
+        Grant {
+            nonce: params["nonce"].clone(),
+            challenge: params["code_challenge"].clone(),
+            claims: idp.0.user.lock().unwrap().clone(),
+        },
+    );
+    Redirect::to(&format!(
+        "{}?code={code}&state={}",
+        params["redirect_uri"], params["state"]
+    ))
+    .into_response()
+}
+
+#[derive(Deserialize)]
+struct TokenRequest {
+    grant_type: String,
+    code: String,
+    redirect_uri: String,
+    code_verifier: String,
+}
+
+async fn token(State(idp): State<Idp>, Form(request): Form<TokenRequest>) -> Response {
+    let grant = idp.0.grants.lock().unwrap().remove(&request.code);
+    let Some(grant) = grant else {
+        return (StatusCode::BAD_REQUEST, "invalid_grant").into_response();
+    };
+    assert_eq!(request.grant_type, "authorization_code");
+    assert_eq!(request.redirect_uri, REDIRECT_URL);
+    let challenge = BASE64URL_NOPAD.encode(&Sha256::digest(request.code_verifier.as_bytes()));
+    if challenge != grant.challenge {
+        return (StatusCode::BAD_REQUEST, "invalid_grant").into_response();
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let mut claims = json!({
+        "iss": idp.0.issuer,
+        "aud": CLIENT_ID,
+        "iat": now,
+        "exp": now + 300,
+        "nonce": grant.nonce,
+    });
+    claims
+        .as_object_mut()
+        .unwrap()
+        .extend(grant.claims.as_object().cloned().unwrap_or_default());
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(KEY_ID.to_string());
+    let pem = signing_key().to_pkcs1_pem(LineEnding::LF).unwrap();
+    let id_token = encode(
+        &header,
+        &claims,
+        &EncodingKey::from_rsa_pem(pem.as_bytes()).unwrap(),
+    )
+    .unwrap();
+    Json(json!({
+        "access_token": "unused",
+        "token_type": "Bearer",
+        "id_token": id_token,
+    }))
+    .into_response()
+}
+
+async fn jwks(State(idp): State<Idp>) -> Json<Value> {
+    idp.0.key_fetches.fetch_add(1, Ordering::SeqCst);
+    let key = signing_key().to_public_key();
+    Json(json!({
+        "keys": [{
+            "kty": "RSA",
+            "use": "sig",
+            "alg": "RS256",
+            "kid": KEY_ID,
+            "n": BASE64URL_NOPAD.encode(&key.n().to_bytes_be()),
+            "e": BASE64URL_NOPAD.encode(&key.e().to_bytes_be()),
+        }]
+    }))
+}
+
+fn app(state: &AppState) -> Router {
+    Router::new()
+        .nest(OIDC_PATH, router(state.clone()))
+        .layer(Extension(Tenant::default()))
+}
+
+fn state_with(provider: OidcProvider) -> AppState {
+    AppState {
+        oidc: OidcLogins::default().with_provider(provider),
+        ..AppState::default()
+    }
+}
+
+async fn get_app(app: &Router, uri: &str, cookie: Option<&str>) -> Response {
+    let mut request = Request::get(uri);
+    if let Some(cookie) = cookie {
+        request = request.header(header::COOKIE, cookie);
+    }
+    app.clone()
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap()
+}
+
+fn location(response: &Response) -> &str {
+    response.headers()[header::LOCATION].to_str().unwrap()
+}
+
+/// The `name=value` part of the cookie `name` set by `response`.
+fn set_cookie(response: &Response, name: &str) -> Option<String> {
+    response
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|cookie| cookie.to_str().ok()?.split(';').next())
+        .find(|cookie| {
+            cookie
+                .strip_prefix(name)
+                .and_then(|rest| rest.strip_prefix('='))
+                .is_some_and(|value| !value.is_empty())
+        })
+        .map(str::to_string)
+}
+
+/// Goes through the whole sign-in and returns the app's final response.
+async fn sign_in(app: &Router) -> Response {
+    let start = get_app(app, &format!("{OIDC_PATH}/mock/login"), None).await;
+    assert_eq!(start.status(), StatusCode::SEE_OTHER);
+    let state_cookie = set_cookie(&start, "oidc_state").unwrap();
+    finish_sign_in(app, location(&start), &state_cookie).await
+}
+
+/// Follows the provider's redirect back to the app's callback, with
+/// `cookie` as the browser's cookies.
+async fn finish_sign_in(app: &Router, authorize_url: &str, cookie: &str) -> Response {
+    let http = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    let back = http.get(authorize_url).send().await.unwrap();
+    let callback = back.headers()[header::LOCATION].to_str().unwrap();
+    let callback = callback.strip_prefix("http://localhost").unwrap();
+    get_app(app, callback, Some(cookie)).await
+}
+
+/// The user signed in by a successful sign-in's response.
+fn signed_in_user(state: &AppState, response: &Response) -> User {
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(location(response), "/");
+    let session = set_cookie(response, SESSION_COOKIE).unwrap();
+    let id = session.split_once('=').unwrap().1.parse().unwrap();
+    let owner = state.sessions.owner(id).unwrap();
+    state.users.scoped(&Tenant::default()).get(owner).unwrap()
+}
+
+fn assert_failed(response: &Response) {
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(location(response), "/login?oidc_failed=1");
+    assert!(set_cookie(response, SESSION_COOKIE).is_none());
+}
+
+#[tokio::test]
+async fn provisions_an_account_with_mapped_roles() {
+    let idp = Idp::start().await;
+    let state = state_with(OidcProvider {
+        provision: true,
+        role_claim: Some("groups".to_string()),
+        role_map: HashMap::from([("engineering".to_string(), "editor".to_string())]),
+        ..idp.provider()
+    });
+    let app = app(&state);
+    idp.sign_in_as(json!({
+        "sub": "grace",
+        "email": "grace@example.com",
+        "email_verified": true,
+        "given_name": "Grace",
+        "family_name": "Hopper",
+        "groups": ["engineering", "Viewer", "marketing"],
+    }));
+
+    let user = signed_in_user(&state, &sign_in(&app).await);
+    assert_eq!(user.login, "grace@example.com");
+    assert_eq!(user.first_name.as_deref(), Some("Grace"));
+    assert_eq!(user.last_name.as_deref(), Some("Hopper"));
+    assert_eq!(user.status, UserStatus::Active);
+    assert!(user.hash.is_empty());
+    assert!(user.last_login.is_some());
+    let mut roles: Vec<_> = user.roles.into_iter().collect();
+    roles.sort();
+    assert_eq!(roles, ["editor", "viewer"]);
+
+    // The next sign-in finds the linked account and replaces its roles.
+    idp.sign_in_as(json!({
+        "sub": "grace",
+        "email": "hopper@example.com",
+        "email_verified": true,
+        "groups": "viewer",
+    }));
+    let again = signed_in_user(&state, &sign_in(&app).await);
+    assert_eq!(again.unid, user.unid);
+    assert_eq!(again.login, "grace@example.com");
+    assert_eq!(again.roles.into_iter().collect::<Vec<_>>(), ["viewer"]);
+    assert_eq!(state.users.scoped(&Tenant::default()).all().len(), 1);
+}
+
+#[tokio::test]
+async fn links_an_existing_account_by_verified_email() {
+    let idp = Idp::start().await;
+    let state = state_with(idp.provider());
+    let mut ada = User::get_user();
+    ada.login = "ada@example.com".to_string();
+    state.users.insert(ada.clone());
+    let app = app(&state);
+
+    idp.sign_in_as(json!({
+        "sub": "ada-unverified",
+        "email": "ada@example.com",
+        "email_verified": false,
+    }));
+    assert_failed(&sign_in(&app).await);
+
+    idp.sign_in_as(json!({
+        "sub": "ada",
+        "email": "ADA@example.com",
+        "email_verified": true,
+    }));
+    let user = signed_in_user(&state, &sign_in(&app).await);
+    assert_eq!(user.unid, ada.unid);
+    assert_eq!(user.roles, ada.roles);
+
+    // Without provisioning, strangers get no account.
+    idp.sign_in_as(json!({
+        "sub": "mallory",
+        "email": "mallory@example.com",
+        "email_verified": true,
+    }));
+    assert_failed(&sign_in(&app).await);
+    assert!(state
+        .users
+        .scoped(&Tenant::default())
+        .find_by_login("mallory@example.com")
+        .is_none());
+}
+
+#[tokio::test]
+async fn caches_the_providers_keys() {
+    let idp = Idp::start().await;
+    let state = state_with(OidcProvider {
+        provision: true,
+        ..idp.provider()
+    });
+    let app = app(&state);
+    idp.sign_in_as(json!({
+        "sub": "linus",
+        "email": "linus@example.com",
+        "email_verified": true,
+    }));
+
+    for _ in 0..3 {
+        signed_in_user(&state, &sign_in(&app).await);
+    }
+    assert_eq!(idp.key_fetches(), 1);
+}
+
+#[tokio::test]
+async fn refuses_a_callback_from_another_browser() {
+    let idp = Idp::start().await;
+    let state = state_with(OidcProvider {
+        provision: true,
+        ..idp.provider()
+    });
+    let app = app(&state);
+    idp.sign_in_as(json!({
+        "sub": "eve",
+        "email": "eve@example.com",
+        "email_verified": true,
+    }));
+
+    let start = get_app(&app, &format!("{OIDC_PATH}/mock/login"), None).await;
+    let response = finish_sign_in(&app, location(&start), "oidc_state=forged").await;
+    assert_failed(&response);
+
+    // The browser that started still can, but only once.
+    let state_cookie = set_cookie(&start, "oidc_state").unwrap();
+    let response = finish_sign_in(&app, location(&start), &state_cookie).await;
+    signed_in_user(&state, &response);
+    let replay = finish_sign_in(&app, location(&start), &state_cookie).await;
+    assert_failed(&replay);
+}
+
+#[tokio::test]
+async fn refuses_banned_accounts() {
+    let idp = Idp::start().await;
+    let state = state_with(idp.provider());
+    let mut banned = User::get_user();
+    banned.login = "banned@example.com".to_string();
+    banned.status = UserStatus::Banned;
+    state.users.insert(banned);
+    let app = app(&state);
+    idp.sign_in_as(json!({
+        "sub": "banned",
+        "email": "banned@example.com",
+        "email_verified": true,
+    }));
+
+    assert_failed(&sign_in(&app).await);
+}
+
+#[tokio::test]
+async fn asks_for_the_second_factor() {
+    let idp = Idp::start().await;
+    let state = state_with(OidcProvider {
+        role_claim: Some("groups".to_string()),
+        ..idp.provider()
+    });
+    let mut ada = User::get_user();
+    ada.login = "ada@example.com".to_string();
+    ada.roles = HashSet::from(["viewer".to_string()]);
+    state.users.insert(ada.clone());
+    let secret = state.two_factor.begin(ada.unid).unwrap();
+    let now = state.clock.now();
+    let code = totp::code_at(&secret, now.unix_timestamp());
+    state.two_factor.confirm(ada.unid, &code, now).unwrap();
+    let app = app(&state);
+    idp.sign_in_as(json!({
+        "sub": "ada",
+        "email": "ada@example.com",
+        "email_verified": true,
+        "groups": ["editor"],
+    }));
+
+    let response = sign_in(&app).await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert_eq!(location(&response), "/login?second_factor=1");
+    assert!(set_cookie(&response, SESSION_COOKIE).is_none());
+    let challenge = set_cookie(&response, CHALLENGE_COOKIE).unwrap();
+    let id = challenge.split_once('=').unwrap().1.parse().unwrap();
+    assert_eq!(state.two_factor.challenged_user(id, now), Some(ada.unid));
+    // The provider's roles wait for the code.
+    let users = state.users.scoped(&Tenant::default());
+    assert_eq!(users.get(ada.unid).unwrap().roles, ada.roles);
+    assert_eq!(users.get(ada.unid).unwrap().last_login, ada.last_login);
+    assert_eq!(
+        state.two_factor.end_challenge(id),
+        Some(HashSet::from(["editor".to_string()]))
+    );
+}
+
+#[tokio::test]
+async fn does_not_link_administrators_without_a_second_factor() {
+    let idp = Idp::start().await;
+    let state = state_with(idp.provider());
+    let mut admin = User::get_user();
+    admin.login = "admin@example.com".to_string();
+    admin.roles.insert(ADMIN_ROLE.to_string());
+    state.users.insert(admin);
+    let app = app(&state);
+    idp.sign_in_as(json!({
+        "sub": "admin",
+        "email": "admin@example.com",
+        "email_verified": true,
+    }));
+
+    assert_failed(&sign_in(&app).await);
+}
+
+#[tokio::test]
+async fn unknown_providers_are_not_found() {
+    let idp = Idp::start().await;
+    let state = state_with(idp.provider());
+    let response = get_app(&app(&state), &format!("{OIDC_PATH}/other/login"), None).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
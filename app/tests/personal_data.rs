@@ -0,0 +1,173 @@
+//! The archive users download of everything held on them.
+
+use std::io::{Cursor, Read};
+
+use app::{
+    oauth::{router, AuthorizationServer, OAuthClient, OAUTH_PATH},
+    personal_data::archive,
+    state::AppState,
+    tenant::Tenant,
+    webhook::WebhookEvent,
+    User, UserStatus,
+};
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Request, StatusCode},
+    Router,
+};
+use data_encoding::BASE64;
+use reqwest::Url;
+use serde_json::Value;
+use tower::ServiceExt;
+use zip::ZipArchive;
+
+const WIKI: &str = "wiki";
+const WIKI_SECRET: &str = "s3cret";
+const REDIRECT_URI: &str = "http://localhost/callback";
+
+fn setup() -> AppState {
+    let state = AppState {
+        oauth: AuthorizationServer::default().with_client(OAuthClient {
+            id: WIKI.to_string(),
+            name: "Wiki".to_string(),
+            schema: None,
+            secret: Some(WIKI_SECRET.to_string()),
+            redirect_uris: vec![REDIRECT_URI.to_string()],
+        }),
+        ..AppState::default()
+    };
+    state.users.observe(state.webhooks.clone());
+    state.webhooks.subscribe(
+        &Tenant::default(),
+        "http://127.0.0.1:9/hook",
+        vec![WebhookEvent::UserCreated],
+    );
+    state
+}
+
+/// Signs `user` in to the wiki, consenting to `scope`, and redeems the code.
+async fn sign_in_to_wiki(state: &AppState, user: &User, scope: &str) {
+    let app = Router::new().nest(OAUTH_PATH, router(state.clone()));
+    let session = state
+        .sessions
+        .create(user.unid, "tests", None, state.clock.now());
+    let url = Url::parse_with_params(
+        &format!("http://localhost{OAUTH_PATH}/authorize"),
+        [
+            ("response_type", "code"),
+            ("client_id", WIKI),
+            ("redirect_uri", REDIRECT_URI),
+            ("scope", scope),
+        ],
+    )
+    .unwrap();
+    let mut request = Request::get(&url.as_str()["http://localhost".len()..])
+        .body(Body::empty())
+        .unwrap();
+    request.extensions_mut().insert(Tenant::default());
+    request.extensions_mut().insert(session);
+    let response = app.clone().oneshot(request).await.unwrap();
+    let location = response.headers()[header::LOCATION].to_str().unwrap();
+    let back = state
+        .oauth
+        .decide(
+            &Tenant::default(),
+            location.strip_prefix("/consent/").unwrap(),
+            user.unid,
+            true,
+            state.clock.now(),
+        )
+        .unwrap();
+    let code = Url::parse(&back)
+        .unwrap()
+        .query_pairs()
+        .find(|(key, _)| key == "code")
+        .unwrap()
+        .1
+        .into_owned();
+
+    let mut request = Request::post(format!("{OAUTH_PATH}/token"))
+        .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .header(
+            header::AUTHORIZATION,
+            format!(
+                "Basic {}",
+                BASE64.encode(format!("{WIKI}:{WIKI_SECRET}").as_bytes())
+            ),
+        )
+        .body(Body::from(format!(
+            "grant_type=authorization_code&code={code}&redirect_uri={}",
+            REDIRECT_URI.replace(':', "%3A").replace('/', "%2F")
+        )))
+        .unwrap();
+    request.extensions_mut().insert(Tenant::default());
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    to_bytes(response.into_body(), usize::MAX).await.unwrap();
+}
+
+/// The names of the archive's entries and its `personal-data.json`.
+fn open(archive: Vec<u8>) -> (Vec<String>, Value) {
+    let mut zip = ZipArchive::new(Cursor::new(archive)).unwrap();
+    let names = zip.file_names().map(str::to_string).collect();
+    let mut json = String::new();
+    zip.by_name("personal-data.json")
+        .unwrap()
+        .read_to_string(&mut json)
+        .unwrap();
+    (names, serde_json::from_str(&json).unwrap())
+}
+
+#[tokio::test]
+async fn lists_what_the_app_holds_on_the_user() {
+    let state = setup();
+    let users = state.users.scoped(&Tenant::default());
+    let mut ada = User::get_user();
+    ada.login = "ada@example.com".to_string();
+    ada.hash = "secret hash".to_string();
+    users.insert_all(vec![ada.clone()]).unwrap();
+    state
+        .oidc
+        .link("https://id.example.com", "ada-subject", ada.unid);
+    sign_in_to_wiki(&state, &ada, "openid offline_access").await;
+
+    let (names, data) = open(archive(&state, &users, ada.unid).unwrap().unwrap());
+    assert_eq!(names, ["personal-data.json"]);
+    assert_eq!(data["profile"]["login"], "ada@example.com");
+    assert_eq!(
+        data["linked_identities"],
+        serde_json::json!([{"issuer": "https://id.example.com", "subject": "ada-subject"}])
+    );
+    assert_eq!(
+        data["oauth_consents"],
+        serde_json::json!([{"client": WIKI, "scopes": ["offline_access", "openid"]}])
+    );
+    let grants = data["oauth_grants"].as_array().unwrap();
+    assert_eq!(grants.len(), 1);
+    assert_eq!(grants[0]["client"], WIKI);
+    assert_eq!(data["invitation"], Value::Null);
+    let deliveries = data["webhook_deliveries"].as_array().unwrap();
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0]["delivery"]["event"], "user.created");
+    assert_eq!(deliveries[0]["body"]["data"]["login"], "ada@example.com");
+
+    let json = data.to_string();
+    assert!(!json.contains("secret hash"), "{json}");
+    assert!(!json.contains(WIKI_SECRET), "{json}");
+}
+
+#[tokio::test]
+async fn invitees_see_their_invitation() {
+    let state = setup();
+    let users = state.users.scoped(&Tenant::default());
+    let mut grace = User::get_user();
+    grace.login = "grace@example.com".to_string();
+    grace.status = UserStatus::Pending;
+    users.insert_all(vec![grace.clone()]).unwrap();
+    let token = state.invitations.issue(grace.unid, state.clock.now());
+
+    let (_, data) = open(archive(&state, &users, grace.unid).unwrap().unwrap());
+    assert_eq!(data["invitation"]["login"], "grace@example.com");
+    assert_eq!(data["invitation"]["expired"], false);
+    assert!(!data.to_string().contains(&token));
+}
@@ -0,0 +1,145 @@
+//! Administrators seeing each other on a user's page, over websockets to a
+//! server on a local port.
+
+use std::time::Duration;
+
+use app::{
+    presence::{connect, PresenceMessage, PRESENCE_PATH},
+    state::{AppState, ADMIN_ROLE},
+    tenant::Tenant,
+    User,
+};
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::{self, Next},
+    response::Response,
+    routing::get,
+    Router,
+};
+use tokio::net::TcpStream;
+use tokio_stream::StreamExt;
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{self, client::IntoClientRequest},
+    MaybeTlsStream, WebSocketStream,
+};
+use uuid::Uuid;
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Stands in for the session middleware: the session is named by the
+/// `x-session` header.
+async fn sign_in(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    let session = request
+        .headers()
+        .get("x-session")
+        .and_then(|value| value.to_str().ok()?.parse().ok())
+        .and_then(|id| state.sessions.touch(id, None, state.clock.now()));
+    request.extensions_mut().insert(Tenant::default());
+    if let Some(session) = session {
+        request.extensions_mut().insert(session);
+    }
+    next.run(request).await
+}
+
+struct Server {
+    state: AppState,
+    address: String,
+}
+
+impl Server {
+    async fn start() -> Self {
+        let state = AppState::default();
+        let app = Router::new()
+            .route(PRESENCE_PATH, get(connect).with_state(state.clone()))
+            .layer(middleware::from_fn_with_state(state.clone(), sign_in));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        Server { state, address }
+    }
+
+    fn user(&self, login: &str, admin: bool) -> User {
+        let mut user = User::get_user();
+        user.login = login.to_string();
+        user.first_name = None;
+        if admin {
+            user.roles.insert(ADMIN_ROLE.to_string());
+        }
+        self.state.users.insert(user.clone());
+        user
+    }
+
+    /// Opens `page`'s presence as `viewer`.
+    async fn open(&self, viewer: &User, page: &User) -> Result<Socket, tungstenite::Error> {
+        let session =
+            self.state
+                .sessions
+                .create(viewer.unid, "tests", None, self.state.clock.now());
+        let path = PRESENCE_PATH.replace("{user}", &page.unid.to_string());
+        let mut request = format!("ws://{}{path}", self.address)
+            .into_client_request()
+            .unwrap();
+        request
+            .headers_mut()
+            .insert("x-session", session.id.to_string().parse().unwrap());
+        connect_async(request).await.map(|(socket, _)| socket)
+    }
+}
+
+async fn next_message(socket: &mut Socket) -> PresenceMessage {
+    let message = tokio::time::timeout(Duration::from_secs(5), socket.next())
+        .await
+        .expect("a message within 5 seconds")
+        .unwrap()
+        .unwrap();
+    serde_json::from_str(message.to_text().unwrap()).unwrap()
+}
+
+fn viewers(message: PresenceMessage) -> Vec<Uuid> {
+    match message {
+        PresenceMessage::Viewers(viewers) => viewers.iter().map(|viewer| viewer.unid).collect(),
+        other => panic!("expected viewers, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn viewers_see_each_other_come_save_and_go() {
+    let server = Server::start().await;
+    let ada = server.user("ada@example.com", false);
+    let alan = server.user("alan@example.com", true);
+    let grace = server.user("grace@example.com", true);
+
+    let mut alans = server.open(&alan, &ada).await.unwrap();
+    assert!(viewers(next_message(&mut alans).await).is_empty());
+
+    let mut graces = server.open(&grace, &ada).await.unwrap();
+    assert_eq!(viewers(next_message(&mut graces).await), [alan.unid]);
+    assert_eq!(viewers(next_message(&mut alans).await), [grace.unid]);
+
+    server.state.presence.saved(&ada, &grace);
+    match next_message(&mut alans).await {
+        PresenceMessage::Saved(by) => assert_eq!(by.login, "grace@example.com"),
+        other => panic!("expected a save, got {other:?}"),
+    }
+
+    drop(graces);
+    assert!(viewers(next_message(&mut alans).await).is_empty());
+}
+
+#[tokio::test]
+async fn only_administrators_open_other_users_pages() {
+    let server = Server::start().await;
+    let ada = server.user("ada@example.com", false);
+    let grace = server.user("grace@example.com", false);
+
+    match server.open(&grace, &ada).await {
+        Err(tungstenite::Error::Http(response)) => {
+            assert_eq!(response.status(), StatusCode::FORBIDDEN)
+        }
+        other => panic!("expected to be refused, got {:?}", other.map(|_| ())),
+    }
+    let mut own = server.open(&ada, &ada).await.unwrap();
+    assert!(viewers(next_message(&mut own).await).is_empty());
+}
@@ -0,0 +1,565 @@
+//! Conformance checks for the SCIM 2.0 endpoint, run against the router
+//! in-process. They follow the request and response shapes of RFC 7644.
+
+use app::{
+    audit::AuditAction,
+    scim::{router, GROUP_SCHEMA, USER_SCHEMA},
+    session::ClientInfo,
+    state::AppState,
+    tenant::{ServiceTokens, Tenant},
+    theme::Theme,
+    UserStatus,
+};
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Request, StatusCode},
+    Router,
+};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+const TOKEN: &str = "default-token";
+const ACME_TOKEN: &str = "acme-token";
+const PATCH_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:PatchOp";
+const ERROR_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:Error";
+
+fn setup() -> (AppState, Router) {
+    let state = AppState {
+        scim_tokens: ServiceTokens::default()
+            .with_token(Tenant::default(), TOKEN)
+            .with_token(
+                Tenant {
+                    schema: Some("acme".to_string()),
+                },
+                ACME_TOKEN,
+            ),
+        ..AppState::default()
+    };
+    (state.clone(), router(state))
+}
+
+struct Reply {
+    status: StatusCode,
+    content_type: Option<String>,
+    location: Option<String>,
+    body: Value,
+}
+
+async fn send(
+    router: &Router,
+    token: Option<&str>,
+    method: &str,
+    uri: &str,
+    body: Option<Value>,
+) -> Reply {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/scim+json");
+    if let Some(token) = token {
+        request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+    }
+    let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+    let response = router
+        .clone()
+        .oneshot(request.body(body).unwrap())
+        .await
+        .unwrap();
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .map(|value| value.to_str().unwrap().to_string())
+    };
+    let (content_type, location) = (header(header::CONTENT_TYPE), header(header::LOCATION));
+    let status = response.status();
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    Reply {
+        status,
+        content_type,
+        location,
+        body: if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&bytes).unwrap()
+        },
+    }
+}
+
+async fn call(router: &Router, method: &str, uri: &str, body: Option<Value>) -> Reply {
+    send(router, Some(TOKEN), method, uri, body).await
+}
+
+async fn create_user(router: &Router, user_name: &str) -> String {
+    let reply = call(
+        router,
+        "POST",
+        "/Users",
+        Some(json!({
+            "schemas": [USER_SCHEMA],
+            "userName": user_name,
+            "name": { "givenName": "Barbara", "familyName": "Jensen" },
+            "active": true,
+        })),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::CREATED);
+    reply.body["id"].as_str().unwrap().to_string()
+}
+
+fn patch(operations: Value) -> Option<Value> {
+    Some(json!({ "schemas": [PATCH_SCHEMA], "Operations": operations }))
+}
+
+fn assert_error(reply: &Reply, status: StatusCode, scim_type: Option<&str>) {
+    assert_eq!(reply.status, status);
+    assert_eq!(reply.body["schemas"], json!([ERROR_SCHEMA]));
+    assert_eq!(reply.body["status"], json!(status.as_u16().to_string()));
+    assert_eq!(reply.body["scimType"].as_str(), scim_type);
+}
+
+#[tokio::test]
+async fn requests_without_a_valid_token_are_unauthorized() {
+    let (_, router) = setup();
+    for token in [None, Some("wrong")] {
+        let reply = send(&router, token, "GET", "/Users", None).await;
+        assert_error(&reply, StatusCode::UNAUTHORIZED, None);
+    }
+}
+
+#[tokio::test]
+async fn service_provider_config_advertises_patch_and_filter() {
+    let (_, router) = setup();
+    let reply = call(&router, "GET", "/ServiceProviderConfig", None).await;
+    assert_eq!(reply.status, StatusCode::OK);
+    assert_eq!(reply.body["patch"]["supported"], json!(true));
+    assert_eq!(reply.body["filter"]["supported"], json!(true));
+    assert_eq!(reply.body["bulk"]["supported"], json!(false));
+}
+
+#[tokio::test]
+async fn created_user_can_be_read_back() {
+    let (state, router) = setup();
+    let reply = call(
+        &router,
+        "POST",
+        "/Users",
+        Some(json!({
+            "schemas": [USER_SCHEMA],
+            "userName": "bjensen@example.com",
+            "name": { "givenName": "Barbara", "familyName": "Jensen" },
+            "password": "t1meMa$heen",
+        })),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::CREATED);
+    assert_eq!(reply.content_type.as_deref(), Some("application/scim+json"));
+    let id = reply.body["id"].as_str().unwrap();
+    assert_eq!(reply.location, Some(format!("/scim/v2/Users/{id}")));
+    assert_eq!(reply.body["meta"]["location"], json!(reply.location));
+    assert!(reply.body.get("password").is_none());
+
+    let reply = call(&router, "GET", &format!("/Users/{id}"), None).await;
+    assert_eq!(reply.status, StatusCode::OK);
+    assert_eq!(reply.body["schemas"], json!([USER_SCHEMA]));
+    assert_eq!(reply.body["userName"], json!("bjensen@example.com"));
+    assert_eq!(reply.body["name"]["givenName"], json!("Barbara"));
+    assert_eq!(reply.body["name"]["familyName"], json!("Jensen"));
+    assert_eq!(reply.body["active"], json!(true));
+    assert_eq!(reply.body["meta"]["resourceType"], json!("User"));
+
+    let stored = state
+        .users
+        .scoped(&Tenant::default())
+        .get(id.parse().unwrap())
+        .unwrap();
+    assert!(!stored.hash.is_empty());
+}
+
+#[tokio::test]
+async fn user_names_are_unique_ignoring_case() {
+    let (_, router) = setup();
+    create_user(&router, "bjensen@example.com").await;
+    let reply = call(
+        &router,
+        "POST",
+        "/Users",
+        Some(json!({ "schemas": [USER_SCHEMA], "userName": "BJensen@example.com" })),
+    )
+    .await;
+    assert_error(&reply, StatusCode::CONFLICT, Some("uniqueness"));
+}
+
+#[tokio::test]
+async fn malformed_bodies_are_invalid_syntax() {
+    let (_, router) = setup();
+    let reply = call(&router, "POST", "/Users", Some(json!({ "name": {} }))).await;
+    assert_error(&reply, StatusCode::BAD_REQUEST, Some("invalidSyntax"));
+}
+
+#[tokio::test]
+async fn unknown_users_are_not_found() {
+    let (_, router) = setup();
+    for id in ["not-a-uuid", "00000000-0000-0000-0000-000000000000"] {
+        let reply = call(&router, "GET", &format!("/Users/{id}"), None).await;
+        assert_error(&reply, StatusCode::NOT_FOUND, None);
+    }
+}
+
+#[tokio::test]
+async fn users_are_filtered() {
+    let (_, router) = setup();
+    create_user(&router, "alice@example.com").await;
+    let bob = create_user(&router, "bob@example.com").await;
+    create_user(&router, "carol@example.org").await;
+
+    let list = |filter: &str| {
+        let uri = format!(
+            "/Users?filter={}",
+            filter.replace(' ', "%20").replace('"', "%22")
+        );
+        let router = router.clone();
+        async move { call(&router, "GET", &uri, None).await }
+    };
+
+    let reply = list(r#"userName eq "BOB@example.com""#).await;
+    assert_eq!(reply.status, StatusCode::OK);
+    assert_eq!(
+        reply.body["schemas"],
+        json!(["urn:ietf:params:scim:api:messages:2.0:ListResponse"])
+    );
+    assert_eq!(reply.body["totalResults"], json!(1));
+    assert_eq!(reply.body["Resources"][0]["id"], json!(bob));
+
+    let reply = list(r#"userName ew "example.com" and not (userName sw "a")"#).await;
+    assert_eq!(reply.body["totalResults"], json!(1));
+
+    let reply = list(r#"userName co "carol" or userName eq "alice@example.com""#).await;
+    assert_eq!(reply.body["totalResults"], json!(2));
+
+    let reply = list("name.givenName pr").await;
+    assert_eq!(reply.body["totalResults"], json!(3));
+
+    let reply = list("userName zz 1").await;
+    assert_error(&reply, StatusCode::BAD_REQUEST, Some("invalidFilter"));
+}
+
+#[tokio::test]
+async fn users_are_paged() {
+    let (_, router) = setup();
+    for login in ["a@example.com", "b@example.com", "c@example.com"] {
+        create_user(&router, login).await;
+    }
+    let reply = call(&router, "GET", "/Users?startIndex=2&count=1", None).await;
+    assert_eq!(reply.body["totalResults"], json!(3));
+    assert_eq!(reply.body["startIndex"], json!(2));
+    assert_eq!(reply.body["itemsPerPage"], json!(1));
+    assert_eq!(
+        reply.body["Resources"][0]["userName"],
+        json!("b@example.com")
+    );
+}
+
+#[tokio::test]
+async fn replacing_a_user_overwrites_its_attributes() {
+    let (state, router) = setup();
+    let id = create_user(&router, "bjensen@example.com").await;
+    let reply = call(
+        &router,
+        "PUT",
+        &format!("/Users/{id}"),
+        Some(json!({
+            "schemas": [USER_SCHEMA],
+            "userName": "babs@example.com",
+            "name": { "givenName": "Babs" },
+            "active": false,
+        })),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::OK);
+    assert_eq!(reply.body["userName"], json!("babs@example.com"));
+    assert_eq!(reply.body["name"]["givenName"], json!("Babs"));
+    assert!(reply.body["name"].get("familyName").is_none());
+    assert_eq!(reply.body["active"], json!(false));
+
+    let stored = state
+        .users
+        .scoped(&Tenant::default())
+        .get(id.parse().unwrap())
+        .unwrap();
+    assert_eq!(stored.status, UserStatus::Banned);
+}
+
+#[tokio::test]
+async fn patching_a_user() {
+    let (_, router) = setup();
+    let id = create_user(&router, "bjensen@example.com").await;
+    let uri = format!("/Users/{id}");
+
+    // Pathless and with a string boolean, as some identity providers send it.
+    let reply = call(
+        &router,
+        "PATCH",
+        &uri,
+        patch(json!([{ "op": "Replace", "value": { "active": "False" } }])),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::OK);
+    assert_eq!(reply.body["active"], json!(false));
+
+    let reply = call(
+        &router,
+        "PATCH",
+        &uri,
+        patch(json!([
+            { "op": "replace", "path": "active", "value": true },
+            { "op": "replace", "path": "name.givenName", "value": "Babs" },
+            { "op": "remove", "path": "name.familyName" },
+        ])),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::OK);
+    assert_eq!(reply.body["active"], json!(true));
+    assert_eq!(reply.body["name"]["givenName"], json!("Babs"));
+    assert!(reply.body["name"].get("familyName").is_none());
+
+    let reply = call(
+        &router,
+        "PATCH",
+        &uri,
+        patch(json!([{ "op": "replace", "path": "nickName", "value": "B" }])),
+    )
+    .await;
+    assert_error(&reply, StatusCode::BAD_REQUEST, Some("invalidPath"));
+
+    let reply = call(
+        &router,
+        "PATCH",
+        &uri,
+        Some(json!({ "Operations": [{ "op": "remove", "path": "name" }] })),
+    )
+    .await;
+    assert_error(&reply, StatusCode::BAD_REQUEST, Some("invalidSyntax"));
+}
+
+#[tokio::test]
+async fn deactivated_users_are_banned_and_signed_out() {
+    let (state, router) = setup();
+    let id = create_user(&router, "bjensen@example.com").await;
+    let unid = id.parse().unwrap();
+    let now = state.clock.now();
+    let session = state.sessions.create(unid, "tests", None, now);
+    state
+        .trusted_devices
+        .trust(&state.signing_key, unid, &ClientInfo::default(), now);
+    // What SCIM does not manage is left as it is.
+    state
+        .users
+        .scoped(&Tenant::default())
+        .update(unid, |user| user.theme = Theme::Dark);
+
+    let reply = call(
+        &router,
+        "PATCH",
+        &format!("/Users/{id}"),
+        patch(json!([{ "op": "replace", "path": "active", "value": false }])),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::OK);
+    let stored = state.users.scoped(&Tenant::default()).get(unid).unwrap();
+    assert_eq!(stored.status, UserStatus::Banned);
+    assert_eq!(stored.theme, Theme::Dark);
+    assert!(state.sessions.touch(session.id, None, now).is_none());
+    assert!(state.trusted_devices.for_user(unid, None, now).is_empty());
+    assert!(state
+        .audit
+        .for_user(unid)
+        .iter()
+        .any(|entry| entry.action == AuditAction::Banned));
+}
+
+#[tokio::test]
+async fn deleted_users_are_gone() {
+    let (state, router) = setup();
+    let id = create_user(&router, "bjensen@example.com").await;
+    let uri = format!("/Users/{id}");
+
+    let reply = call(&router, "DELETE", &uri, None).await;
+    assert_eq!(reply.status, StatusCode::NO_CONTENT);
+    assert_error(
+        &call(&router, "GET", &uri, None).await,
+        StatusCode::NOT_FOUND,
+        None,
+    );
+    assert_error(
+        &call(&router, "DELETE", &uri, None).await,
+        StatusCode::NOT_FOUND,
+        None,
+    );
+    let reply = call(&router, "GET", "/Users", None).await;
+    assert_eq!(reply.body["totalResults"], json!(0));
+
+    // The account is erased rather than removed, and its login can be reused.
+    let stored = state
+        .users
+        .scoped(&Tenant::default())
+        .get(id.parse().unwrap())
+        .unwrap();
+    assert!(stored.is_erased());
+    create_user(&router, "bjensen@example.com").await;
+}
+
+#[tokio::test]
+async fn tenants_only_see_their_own_users() {
+    let (_, router) = setup();
+    let id = create_user(&router, "bjensen@example.com").await;
+
+    let reply = send(
+        &router,
+        Some(ACME_TOKEN),
+        "GET",
+        &format!("/Users/{id}"),
+        None,
+    )
+    .await;
+    assert_error(&reply, StatusCode::NOT_FOUND, None);
+    let reply = send(&router, Some(ACME_TOKEN), "GET", "/Users", None).await;
+    assert_eq!(reply.body["totalResults"], json!(0));
+}
+
+#[tokio::test]
+async fn groups_are_the_tenant_roles() {
+    let (_, router) = setup();
+    let reply = call(&router, "GET", "/Groups", None).await;
+    assert_eq!(reply.status, StatusCode::OK);
+    let names: Vec<&str> = reply.body["Resources"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|group| group["displayName"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["admin", "editor", "viewer"]);
+
+    let reply = call(&router, "GET", "/Groups/editor", None).await;
+    assert_eq!(reply.body["schemas"], json!([GROUP_SCHEMA]));
+    assert_eq!(reply.body["id"], json!("editor"));
+    assert_eq!(reply.body["meta"]["resourceType"], json!("Group"));
+}
+
+#[tokio::test]
+async fn group_membership_grants_roles() {
+    let (_, router) = setup();
+    let alice = create_user(&router, "alice@example.com").await;
+    let bob = create_user(&router, "bob@example.com").await;
+
+    let reply = call(
+        &router,
+        "POST",
+        "/Groups",
+        Some(json!({
+            "schemas": [GROUP_SCHEMA],
+            "displayName": "auditors",
+            "members": [{ "value": alice }],
+        })),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::CREATED);
+    assert_eq!(reply.location.as_deref(), Some("/scim/v2/Groups/auditors"));
+    assert_eq!(reply.body["members"][0]["value"], json!(alice));
+
+    let reply = call(
+        &router,
+        "POST",
+        "/Groups",
+        Some(json!({ "schemas": [GROUP_SCHEMA], "displayName": "Auditors" })),
+    )
+    .await;
+    assert_error(&reply, StatusCode::CONFLICT, Some("uniqueness"));
+
+    let reply = call(
+        &router,
+        "PATCH",
+        "/Groups/auditors",
+        patch(json!([{ "op": "add", "path": "members", "value": [{ "value": bob }] }])),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::OK);
+    assert_eq!(reply.body["members"].as_array().unwrap().len(), 2);
+
+    let reply = call(&router, "GET", &format!("/Users/{bob}"), None).await;
+    assert_eq!(reply.body["groups"][0]["value"], json!("auditors"));
+
+    let reply = call(
+        &router,
+        "GET",
+        &format!("/Groups?filter=members%20eq%20%22{bob}%22"),
+        None,
+    )
+    .await;
+    assert_eq!(reply.body["totalResults"], json!(1));
+
+    let reply = call(
+        &router,
+        "PATCH",
+        "/Groups/auditors",
+        patch(json!([{
+            "op": "remove",
+            "path": format!("members[value eq \"{alice}\"]"),
+        }])),
+    )
+    .await;
+    assert_eq!(
+        reply.body["members"],
+        json!([{
+            "value": bob,
+            "display": "bob@example.com",
+            "$ref": format!("/scim/v2/Users/{bob}"),
+        }])
+    );
+
+    let reply = call(
+        &router,
+        "PUT",
+        "/Groups/auditors",
+        Some(json!({ "schemas": [GROUP_SCHEMA], "displayName": "reviewers", "members": [] })),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::OK);
+    assert_eq!(reply.body["id"], json!("reviewers"));
+    assert!(reply.body.get("members").is_none_or(|m| m == &json!([])));
+
+    let reply = call(
+        &router,
+        "PATCH",
+        "/Groups/reviewers",
+        patch(json!([{ "op": "add", "path": "members", "value": [{ "value": "nobody" }] }])),
+    )
+    .await;
+    assert_error(&reply, StatusCode::BAD_REQUEST, Some("invalidValue"));
+}
+
+#[tokio::test]
+async fn deleting_a_group_revokes_its_role() {
+    let (_, router) = setup();
+    let alice = create_user(&router, "alice@example.com").await;
+    call(
+        &router,
+        "PATCH",
+        "/Groups/editor",
+        patch(json!([{ "op": "add", "value": { "members": [{ "value": alice }] } }])),
+    )
+    .await;
+
+    let reply = call(&router, "DELETE", "/Groups/editor", None).await;
+    assert_eq!(reply.status, StatusCode::NO_CONTENT);
+    assert_error(
+        &call(&router, "GET", "/Groups/editor", None).await,
+        StatusCode::NOT_FOUND,
+        None,
+    );
+    let reply = call(&router, "GET", &format!("/Users/{alice}"), None).await;
+    assert!(reply.body.get("groups").is_none_or(|g| g == &json!([])));
+
+    let reply = call(&router, "DELETE", "/Groups/admin", None).await;
+    assert_error(&reply, StatusCode::BAD_REQUEST, Some("mutability"));
+}
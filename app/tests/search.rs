@@ -0,0 +1,92 @@
+//! Searching users by words of their login and name.
+
+use app::{state::AppState, tenant::Tenant, User};
+
+fn user(login: &str, first_name: &str, last_name: &str) -> User {
+    let mut user = User::get_user();
+    user.login = login.to_string();
+    user.first_name = Some(first_name.to_string());
+    user.last_name = Some(last_name.to_string());
+    user
+}
+
+fn logins(users: Vec<User>) -> Vec<String> {
+    users.into_iter().map(|user| user.login).collect()
+}
+
+#[test]
+fn matches_parts_of_names_without_accents() {
+    let state = AppState::default();
+    let users = state.users.scoped(&Tenant::default());
+    users.insert(user("adrian@example.com", "Adrian-Mihai", "Popescu"));
+    users.insert(user("stefan@example.com", "Ștefan", "Ionescu"));
+    users.insert(user("maria@example.com", "Maria", "Mihăilescu"));
+
+    assert_eq!(
+        logins(users.search("adrian mih", 8)),
+        ["adrian@example.com"]
+    );
+    assert_eq!(logins(users.search("STEFAN", 8)), ["stefan@example.com"]);
+    assert_eq!(logins(users.search("mihailescu", 8)), ["maria@example.com"]);
+    // Every word has to match.
+    assert!(users.search("adrian ionescu", 8).is_empty());
+    assert!(users.search("", 8).is_empty());
+}
+
+#[test]
+fn ranks_exact_over_prefix_over_typo() {
+    let state = AppState::default();
+    let users = state.users.scoped(&Tenant::default());
+    users.insert(user("hanna@example.com", "Hanna", "Berg"));
+    users.insert(user("annabel@example.com", "Annabel", "Lee"));
+    users.insert(user("anna@example.com", "Anna", "Jones"));
+
+    assert_eq!(
+        logins(users.search("anna", 8)),
+        [
+            "anna@example.com",
+            "annabel@example.com",
+            "hanna@example.com"
+        ]
+    );
+    assert_eq!(logins(users.search("anna", 2)).len(), 2);
+}
+
+#[test]
+fn forgives_typos_in_longer_words() {
+    let state = AppState::default();
+    let users = state.users.scoped(&Tenant::default());
+    users.insert(user("ada@example.com", "Ada", "Lovelace"));
+
+    // A swap of neighbours counts as one typo.
+    assert_eq!(logins(users.search("lovelcae", 8)), ["ada@example.com"]);
+    assert_eq!(logins(users.search("lovleace", 8)), ["ada@example.com"]);
+    // Typed halfway, with a typo.
+    assert_eq!(logins(users.search("lobel", 8)), ["ada@example.com"]);
+    // Short words must be exact or a prefix.
+    assert!(users.search("ado", 8).is_empty());
+    assert!(users.search("lxvxlxce", 8).is_empty());
+}
+
+#[test]
+fn follows_changes_to_users() {
+    let state = AppState::default();
+    let users = state.users.scoped(&Tenant::default());
+    let grace = user("grace@example.com", "Grace", "Murray");
+    users.insert(grace.clone());
+
+    users.update(grace.unid, |grace| {
+        grace.last_name = Some("Hopper".to_string());
+    });
+    assert!(users.search("murray", 8).is_empty());
+    assert_eq!(logins(users.search("hopper", 8)), ["grace@example.com"]);
+
+    // Other tenants do not see the user.
+    let acme = state.users.scoped(&Tenant {
+        schema: Some("acme".to_string()),
+    });
+    assert!(acme.search("hopper", 8).is_empty());
+
+    users.remove(grace.unid);
+    assert!(users.search("hopper", 8).is_empty());
+}
@@ -0,0 +1,74 @@
+//! Session expiry and the client addresses recorded for sessions.
+
+use std::net::IpAddr;
+
+use app::session::{SessionStore, TrustedProxies, SESSION_IDLE_TIMEOUT, SESSION_LIFETIME};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+fn ip(ip: &str) -> IpAddr {
+    ip.parse().unwrap()
+}
+
+#[test]
+fn idle_sessions_expire() {
+    let sessions = SessionStore::default();
+    let user = Uuid::new_v4();
+    let start = OffsetDateTime::now_utc();
+    let session = sessions.create(user, "tests", None, start);
+
+    let active = start + SESSION_IDLE_TIMEOUT - Duration::SECOND;
+    assert!(sessions.touch(session.id, None, active).is_some());
+    assert_eq!(sessions.for_user(user, session.id, active).len(), 1);
+
+    let idle = active + SESSION_IDLE_TIMEOUT;
+    assert!(sessions.for_user(user, session.id, idle).is_empty());
+    assert!(sessions.touch(session.id, None, idle).is_none());
+    // Expired sessions are gone for good.
+    assert!(sessions.touch(session.id, None, active).is_none());
+}
+
+#[test]
+fn sessions_end_after_their_lifetime_however_active() {
+    let sessions = SessionStore::default();
+    let start = OffsetDateTime::now_utc();
+    let session = sessions.create(Uuid::new_v4(), "tests", None, start);
+
+    let mut now = start;
+    while now + Duration::days(1) < start + SESSION_LIFETIME {
+        now += Duration::days(1);
+        assert!(sessions.touch(session.id, None, now).is_some());
+    }
+    assert!(sessions
+        .touch(session.id, None, start + SESSION_LIFETIME)
+        .is_none());
+}
+
+#[test]
+fn forwarded_addresses_are_only_believed_from_trusted_proxies() {
+    let none = TrustedProxies::default();
+    assert_eq!(
+        none.client_ip(Some(ip("203.0.113.9")), Some("198.51.100.1")),
+        Some("203.0.113.9".to_string())
+    );
+    assert_eq!(none.client_ip(None, Some("198.51.100.1")), None);
+
+    let proxies = TrustedProxies::new([ip("10.0.0.1"), ip("10.0.0.2")]);
+    // A client can prepend whatever it likes; only the hops the proxies
+    // added count.
+    assert_eq!(
+        proxies.client_ip(
+            Some(ip("10.0.0.1")),
+            Some("1.2.3.4, 198.51.100.1, 10.0.0.2")
+        ),
+        Some("198.51.100.1".to_string())
+    );
+    assert_eq!(
+        proxies.client_ip(Some(ip("10.0.0.1")), Some("garbage")),
+        Some("10.0.0.1".to_string())
+    );
+    assert_eq!(
+        proxies.client_ip(Some(ip("203.0.113.9")), Some("198.51.100.1")),
+        Some("203.0.113.9".to_string())
+    );
+}
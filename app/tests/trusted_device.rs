@@ -0,0 +1,92 @@
+//! Browsers trusted to skip the second factor.
+
+use app::{
+    session::ClientInfo,
+    state::SigningKey,
+    trusted_device::{TrustedDeviceStore, TRUST_LIFETIME},
+};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+const FIREFOX_124: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:124.0) Gecko/20100101 Firefox/124.0";
+const FIREFOX_125: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:125.0) Gecko/20100101 Firefox/125.0";
+const CHROME: &str = "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 \
+                      (KHTML, like Gecko) Chrome/124.0.6367.60 Safari/537.36";
+
+fn client(user_agent: &str) -> ClientInfo {
+    ClientInfo {
+        ip: Some("203.0.113.9".to_string()),
+        user_agent: user_agent.to_string(),
+    }
+}
+
+#[test]
+fn tokens_work_for_their_user_on_the_same_kind_of_browser() {
+    let devices = TrustedDeviceStore::default();
+    let key = SigningKey::default();
+    let user = Uuid::new_v4();
+    let now = OffsetDateTime::now_utc();
+    let token = devices.trust(&key, user, &client(FIREFOX_124), now);
+
+    assert!(devices.verify(&key, &token, user, &client(FIREFOX_124), now));
+    // Updating the browser keeps the trust.
+    assert!(devices.verify(&key, &token, user, &client(FIREFOX_125), now));
+    assert!(!devices.verify(&key, &token, user, &client(CHROME), now));
+    assert!(!devices.verify(&key, &token, Uuid::new_v4(), &client(FIREFOX_124), now));
+    assert!(!devices.verify(
+        &SigningKey::default(),
+        &token,
+        user,
+        &client(FIREFOX_124),
+        now
+    ));
+    assert!(!devices.verify(
+        &key,
+        &token,
+        user,
+        &client(FIREFOX_124),
+        now + TRUST_LIFETIME
+    ));
+}
+
+#[test]
+fn tokens_cannot_be_extended() {
+    let devices = TrustedDeviceStore::default();
+    let key = SigningKey::default();
+    let user = Uuid::new_v4();
+    let now = OffsetDateTime::now_utc();
+    let token = devices.trust(&key, user, &client(FIREFOX_124), now);
+
+    let mut parts = token.splitn(3, '.');
+    let (id, expires, signature) = (
+        parts.next().unwrap(),
+        parts.next().unwrap().parse::<i64>().unwrap(),
+        parts.next().unwrap(),
+    );
+    let later = format!("{id}.{}.{signature}", expires + 86_400);
+    assert!(!devices.verify(&key, &later, user, &client(FIREFOX_124), now));
+}
+
+#[test]
+fn trust_is_revoked_one_browser_at_a_time() {
+    let devices = TrustedDeviceStore::default();
+    let key = SigningKey::default();
+    let user = Uuid::new_v4();
+    let now = OffsetDateTime::now_utc();
+    let firefox = devices.trust(&key, user, &client(FIREFOX_124), now);
+    let chrome = devices.trust(&key, user, &client(CHROME), now + Duration::MINUTE);
+
+    let listed = devices.for_user(user, None, now + Duration::MINUTE);
+    assert_eq!(listed.len(), 2);
+    // Most recently used first.
+    assert!(listed[0].browser.starts_with("Chrome"));
+    assert_eq!(devices.owner(listed[0].id), Some(user));
+
+    assert!(devices.revoke(listed[0].id));
+    assert!(!devices.verify(&key, &chrome, user, &client(CHROME), now));
+    assert!(devices.verify(&key, &firefox, user, &client(FIREFOX_124), now));
+
+    assert_eq!(devices.revoke_all(user), 1);
+    assert!(!devices.verify(&key, &firefox, user, &client(FIREFOX_124), now));
+    assert!(devices.for_user(user, None, now).is_empty());
+}
@@ -0,0 +1,129 @@
+//! Time-based codes and the second step of a login.
+
+use app::{
+    state::Clock,
+    totp,
+    two_factor::{TwoFactorStore, MAX_CODE_ATTEMPTS},
+};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+/// The SHA1 seed of RFC 6238's test vectors.
+const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+fn at(unix_time: i64) -> OffsetDateTime {
+    OffsetDateTime::from_unix_timestamp(unix_time).unwrap()
+}
+
+/// A store with `user` enrolled, and their recovery codes.
+fn enrolled(user: Uuid, clock: &Clock) -> (TwoFactorStore, Vec<u8>, Vec<String>) {
+    let store = TwoFactorStore::default();
+    let secret = store.begin(user).unwrap();
+    let code = totp::code_at(&secret, clock.now().unix_timestamp());
+    let recovery_codes = store.confirm(user, &code, clock.now()).unwrap();
+    (store, secret, recovery_codes)
+}
+
+#[test]
+fn codes_match_the_rfc_test_vectors() {
+    // The last six digits of the eight-digit codes in RFC 6238, appendix B.
+    for (unix_time, code) in [
+        (59, "287082"),
+        (1111111109, "081804"),
+        (1111111111, "050471"),
+        (1234567890, "005924"),
+        (2000000000, "279037"),
+    ] {
+        assert_eq!(totp::code_at(RFC_SECRET, unix_time), code, "at {unix_time}");
+        assert_eq!(
+            totp::verify(RFC_SECRET, code, unix_time, None),
+            Some(unix_time as u64 / 30)
+        );
+    }
+}
+
+#[test]
+fn codes_from_neighbouring_steps_are_accepted() {
+    let now = 1111111111;
+    let code = totp::code_at(RFC_SECRET, now);
+    assert!(totp::verify(RFC_SECRET, &code, now - 30, None).is_some());
+    assert!(totp::verify(RFC_SECRET, &code, now + 30, None).is_some());
+    assert!(totp::verify(RFC_SECRET, &code, now - 60, None).is_none());
+    assert!(totp::verify(RFC_SECRET, &code, now + 60, None).is_none());
+
+    assert!(totp::verify(RFC_SECRET, "12345", now, None).is_none());
+    assert!(totp::verify(RFC_SECRET, "12345x", now, None).is_none());
+}
+
+#[test]
+fn codes_cannot_be_replayed() {
+    let now = 1111111111;
+    let code = totp::code_at(RFC_SECRET, now);
+    let step = totp::verify(RFC_SECRET, &code, now, None).unwrap();
+    assert!(totp::verify(RFC_SECRET, &code, now, Some(step)).is_none());
+    // Nor can the previous step's code, still within the skew.
+    let earlier = totp::code_at(RFC_SECRET, now - 30);
+    assert!(totp::verify(RFC_SECRET, &earlier, now, Some(step)).is_none());
+
+    let user = Uuid::new_v4();
+    let clock = Clock::fixed(at(now));
+    let (store, secret, _) = enrolled(user, &clock);
+    // The code that confirmed enrollment is spent too.
+    let code = totp::code_at(&secret, now);
+    assert!(!store.verify(user, &code, clock.now()));
+    let later = clock.now() + Duration::seconds(30);
+    let next = totp::code_at(&secret, later.unix_timestamp());
+    assert!(store.verify(user, &next, later));
+    assert!(!store.verify(user, &next, later));
+}
+
+#[test]
+fn recovery_codes_work_once() {
+    let user = Uuid::new_v4();
+    let clock = Clock::fixed(at(1234567890));
+    let (store, _, recovery_codes) = enrolled(user, &clock);
+    assert_eq!(store.recovery_codes_left(user), recovery_codes.len());
+
+    let code = &recovery_codes[0];
+    assert!(store.verify(user, code, clock.now()));
+    assert!(!store.verify(user, code, clock.now()));
+    assert_eq!(store.recovery_codes_left(user), recovery_codes.len() - 1);
+    assert!(store.verify(user, &recovery_codes[1], clock.now()));
+
+    assert!(!store.verify(Uuid::new_v4(), &recovery_codes[2], clock.now()));
+}
+
+#[test]
+fn challenges_end_after_too_many_wrong_codes() {
+    let user = Uuid::new_v4();
+    let clock = Clock::fixed(at(1234567890));
+    let (store, _, _) = enrolled(user, &clock);
+    let challenge = store.challenge(user, clock.now());
+
+    for _ in 1..MAX_CODE_ATTEMPTS {
+        assert!(store.fail_challenge(challenge));
+        assert_eq!(store.challenged_user(challenge, clock.now()), Some(user));
+    }
+    assert!(!store.fail_challenge(challenge));
+    assert_eq!(store.challenged_user(challenge, clock.now()), None);
+
+    // A new login starts with a clean slate.
+    let challenge = store.challenge(user, clock.now());
+    assert!(store.fail_challenge(challenge));
+}
+
+#[test]
+fn challenges_expire() {
+    let user = Uuid::new_v4();
+    let clock = Clock::fixed(at(1234567890));
+    let (store, _, _) = enrolled(user, &clock);
+    let challenge = store.challenge(user, clock.now());
+    assert_eq!(
+        store.challenged_user(challenge, clock.now() + Duration::minutes(4)),
+        Some(user)
+    );
+    assert_eq!(
+        store.challenged_user(challenge, clock.now() + Duration::minutes(5)),
+        None
+    );
+}
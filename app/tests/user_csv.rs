@@ -0,0 +1,156 @@
+//! Exporting users to CSV and importing them again.
+
+use app::{
+    state::AppState,
+    tenant::Tenant,
+    user_csv::{
+        check_records, export_csv, parse_csv, ImportField, ImportProblem, ImportStore,
+        MappingError, IMPORT_LIFETIME,
+    },
+    User, UserStatus,
+};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+fn user(login: &str) -> User {
+    let mut user = User::get_user();
+    user.login = login.to_string();
+    user
+}
+
+/// Checks `csv` for importing into the default tenant of `state`, with the
+/// columns mapped by their headers.
+fn check(state: &AppState, csv: &str) -> (Vec<Vec<ImportProblem>>, Vec<User>) {
+    let (headers, records) = parse_csv(csv.as_bytes()).unwrap();
+    let mapping: Vec<Option<ImportField>> = headers
+        .iter()
+        .map(|header| ImportField::guess(header))
+        .collect();
+    let tenant = Tenant::default();
+    let (rows, users) = check_records(
+        &records,
+        &mapping,
+        &state.users.scoped(&tenant),
+        &state.roles.scoped(&tenant),
+        state.clock.now(),
+    )
+    .unwrap();
+    (rows.into_iter().map(|row| row.problems).collect(), users)
+}
+
+#[test]
+fn exports_import_again_as_they_are() {
+    let mut ada = user("ada@example.com");
+    ada.first_name = Some("Ada".to_string());
+    ada.last_name = Some("Lovelace, Countess".to_string());
+    ada.roles = ["editor".to_string(), "viewer".to_string()].into();
+    ada.language = Some("de".to_string());
+    let mut grace = user("grace@example.com");
+    grace.status = UserStatus::Banned;
+    let exported = export_csv(&[ada.clone(), grace.clone()]).unwrap();
+
+    let (problems, imported) = check(&AppState::default(), &exported);
+    assert!(problems.iter().all(Vec::is_empty));
+    assert_eq!(imported.len(), 2);
+    for (before, after) in [ada, grace].iter().zip(&imported) {
+        assert_ne!(after.unid, before.unid);
+        assert_eq!(after.login, before.login);
+        assert_eq!(after.first_name, before.first_name);
+        assert_eq!(after.last_name, before.last_name);
+        assert_eq!(after.status, before.status);
+        assert_eq!(after.roles, before.roles);
+        assert_eq!(after.language, before.language);
+        assert!(after.hash.is_empty());
+    }
+
+    // Into a tenant that has the users already, every login is taken.
+    let state = AppState::default();
+    for user in imported {
+        state.users.insert(user);
+    }
+    let (problems, imported) = check(&state, &exported);
+    assert_eq!(
+        problems,
+        [
+            vec![ImportProblem::LoginTaken("ada@example.com".to_string())],
+            vec![ImportProblem::LoginTaken("grace@example.com".to_string())],
+        ]
+    );
+    assert!(imported.is_empty());
+}
+
+#[test]
+fn rows_follow_the_rules_of_saving_a_user() {
+    let csv = "login,status\n\
+               ada,active\n\
+               grace@example.com,Pending\n\
+               alan@example.com,Deactivated\n\
+               edsger@example.com,Sleeping\n\
+               barbara@example.com,Banned\n";
+
+    let (problems, users) = check(&AppState::default(), csv);
+    assert_eq!(
+        problems,
+        [
+            vec![ImportProblem::InvalidLogin("ada".to_string())],
+            vec![ImportProblem::StatusNotAllowed("Pending".to_string())],
+            vec![ImportProblem::StatusNotAllowed("Deactivated".to_string())],
+            vec![ImportProblem::UnknownStatus("Sleeping".to_string())],
+            vec![],
+        ]
+    );
+    assert_eq!(users.len(), 1);
+    assert_eq!(users[0].status, UserStatus::Banned);
+}
+
+#[test]
+fn each_field_is_mapped_once_and_the_login_always() {
+    let state = AppState::default();
+    let tenant = Tenant::default();
+    let (_, records) = parse_csv(b"email,login,name\nada@example.com,ada,Ada\n").unwrap();
+    let check = |mapping: &[Option<ImportField>]| {
+        check_records(
+            &records,
+            mapping,
+            &state.users.scoped(&tenant),
+            &state.roles.scoped(&tenant),
+            state.clock.now(),
+        )
+        .map(|(_, users)| users.len())
+    };
+
+    assert_eq!(
+        check(&[
+            Some(ImportField::Login),
+            Some(ImportField::Login),
+            Some(ImportField::FirstName),
+        ]),
+        Err(MappingError::MappedTwice(ImportField::Login))
+    );
+    assert_eq!(
+        check(&[None, None, Some(ImportField::FirstName)]),
+        Err(MappingError::NoLogin)
+    );
+    assert_eq!(
+        check(&[Some(ImportField::Login), None, Some(ImportField::FirstName)]),
+        Ok(1)
+    );
+}
+
+#[test]
+fn uploads_expire() {
+    let imports = ImportStore::default();
+    let owner = Uuid::new_v4();
+    let tenant = Tenant::default();
+    let now = OffsetDateTime::now_utc();
+    let records = vec![vec!["ada@example.com".to_string()]];
+    let id = imports.stage(owner, tenant.clone(), records.clone(), now);
+
+    let later = now + IMPORT_LIFETIME;
+    assert_eq!(
+        imports.records(id, owner, &tenant, later - time::Duration::SECOND),
+        Some(records)
+    );
+    assert_eq!(imports.records(id, owner, &tenant, later), None);
+    assert_eq!(imports.records(id, Uuid::new_v4(), &tenant, now), None);
+}
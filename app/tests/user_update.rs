@@ -0,0 +1,367 @@
+//! The rules every change to a user follows, whether saved on the user's
+//! page or through the API.
+
+use app::{
+    audit::AuditAction,
+    erasure::erase,
+    password::{hash_password, verify_password},
+    session::{ClientInfo, CurrentSession},
+    state::{AppState, ADMIN_ROLE},
+    tenant::Tenant,
+    theme::Theme,
+    user_update::{update_user, UpdateError, UserUpdate},
+    User, UserStatus,
+};
+use uuid::Uuid;
+
+const PASSWORD: &str = "correct horse";
+
+struct Setup {
+    state: AppState,
+    admin: Uuid,
+    ada: Uuid,
+}
+
+fn setup() -> Setup {
+    let mut admin = User::get_user();
+    admin.login = "admin@example.com".to_string();
+    admin.roles.insert(ADMIN_ROLE.to_string());
+    let mut ada = User::get_user();
+    ada.login = "ada@example.com".to_string();
+    ada.hash = hash_password(PASSWORD);
+    let setup = Setup {
+        state: AppState::default(),
+        admin: admin.unid,
+        ada: ada.unid,
+    };
+    setup.state.users.insert(admin);
+    setup.state.users.insert(ada);
+    setup
+}
+
+impl Setup {
+    fn sign_in(&self, user: Uuid) -> CurrentSession {
+        self.state
+            .sessions
+            .create(user, "tests", None, self.state.clock.now())
+    }
+
+    fn update(
+        &self,
+        editor: &CurrentSession,
+        unid: Uuid,
+        update: UserUpdate,
+    ) -> Result<User, UpdateError> {
+        let tenant = Tenant::default();
+        update_user(
+            &self.state,
+            &self.state.users.scoped(&tenant),
+            &self.state.roles.scoped(&tenant),
+            Some(editor),
+            unid,
+            update,
+            None,
+        )
+    }
+
+    fn get(&self, unid: Uuid) -> User {
+        self.state
+            .users
+            .scoped(&Tenant::default())
+            .get(unid)
+            .unwrap()
+    }
+
+    fn audited(&self, unid: Uuid, action: AuditAction) -> bool {
+        self.state
+            .audit
+            .for_user(unid)
+            .iter()
+            .any(|entry| entry.subject == unid && entry.action == action)
+    }
+
+    fn sessions(&self, user: Uuid) -> usize {
+        self.state
+            .sessions
+            .for_user(user, Uuid::nil(), self.state.clock.now())
+            .len()
+    }
+
+    fn trust_browser(&self, user: Uuid) {
+        self.state.trusted_devices.trust(
+            &self.state.signing_key,
+            user,
+            &ClientInfo::default(),
+            self.state.clock.now(),
+        );
+    }
+
+    fn trusted_browsers(&self, user: Uuid) -> usize {
+        self.state
+            .trusted_devices
+            .for_user(user, None, self.state.clock.now())
+            .len()
+    }
+}
+
+fn new_password(current_password: Option<&str>) -> UserUpdate {
+    UserUpdate {
+        password: Some("battery staple".to_string()),
+        current_password: current_password.map(str::to_string),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn users_confirm_their_own_credentials_with_the_current_password() {
+    let setup = setup();
+    let session = setup.sign_in(setup.ada);
+    let other = setup.sign_in(setup.ada);
+    setup.trust_browser(setup.ada);
+
+    for current in [None, Some("wrong")] {
+        assert!(matches!(
+            setup.update(&session, setup.ada, new_password(current)),
+            Err(UpdateError::Forbidden(_))
+        ));
+    }
+    let new_login = UserUpdate {
+        login: Some("lovelace@example.com".to_string()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        setup.update(&session, setup.ada, new_login.clone()),
+        Err(UpdateError::Forbidden(_))
+    ));
+    assert!(verify_password(PASSWORD, &setup.get(setup.ada).hash));
+
+    setup
+        .update(&session, setup.ada, new_password(Some(PASSWORD)))
+        .unwrap();
+    assert!(verify_password(
+        "battery staple",
+        &setup.get(setup.ada).hash
+    ));
+    assert!(setup.audited(setup.ada, AuditAction::PasswordChanged));
+    // The browser that made the change stays signed in, the others not.
+    let now = setup.state.clock.now();
+    assert!(setup.state.sessions.touch(session.id, None, now).is_some());
+    assert!(setup.state.sessions.touch(other.id, None, now).is_none());
+    assert_eq!(setup.trusted_browsers(setup.ada), 0);
+
+    setup
+        .update(
+            &session,
+            setup.ada,
+            UserUpdate {
+                current_password: Some("battery staple".to_string()),
+                ..new_login
+            },
+        )
+        .unwrap();
+    assert_eq!(setup.get(setup.ada).login, "lovelace@example.com");
+    assert!(setup.audited(setup.ada, AuditAction::LoginChanged));
+}
+
+#[test]
+fn administrators_set_passwords_without_knowing_the_old_one() {
+    let setup = setup();
+    let admin = setup.sign_in(setup.admin);
+    setup.sign_in(setup.ada);
+
+    setup.update(&admin, setup.ada, new_password(None)).unwrap();
+    assert_eq!(setup.sessions(setup.ada), 0);
+    let entry = setup
+        .state
+        .audit
+        .for_user(setup.ada)
+        .into_iter()
+        .find(|entry| entry.action == AuditAction::PasswordChanged)
+        .unwrap();
+    assert_eq!(entry.actor, Some(setup.admin));
+}
+
+#[test]
+fn logins_are_unique_email_addresses_and_passwords_long_enough() {
+    let setup = setup();
+    let admin = setup.sign_in(setup.admin);
+    let login = |login: &str| UserUpdate {
+        login: Some(login.to_string()),
+        ..Default::default()
+    };
+
+    assert!(matches!(
+        setup.update(&admin, setup.ada, login("ada")),
+        Err(UpdateError::Invalid(_))
+    ));
+    assert!(matches!(
+        setup.update(&admin, setup.ada, login("ADMIN@example.com")),
+        Err(UpdateError::LoginTaken(_))
+    ));
+    assert!(matches!(
+        setup.update(
+            &admin,
+            setup.ada,
+            UserUpdate {
+                password: Some("short".to_string()),
+                ..Default::default()
+            }
+        ),
+        Err(UpdateError::Invalid(_))
+    ));
+    // Saving the login unchanged is no change at all.
+    setup
+        .update(&admin, setup.ada, login("ada@example.com"))
+        .unwrap();
+    assert!(!setup.audited(setup.ada, AuditAction::LoginChanged));
+}
+
+#[test]
+fn bans_end_everything_the_user_is_signed_in_to() {
+    let setup = setup();
+    let admin = setup.sign_in(setup.admin);
+    setup.sign_in(setup.ada);
+    setup.trust_browser(setup.ada);
+    let status = |status| UserUpdate {
+        status: Some(status),
+        ..Default::default()
+    };
+
+    setup
+        .update(&admin, setup.ada, status(UserStatus::Banned))
+        .unwrap();
+    assert_eq!(setup.get(setup.ada).status, UserStatus::Banned);
+    assert_eq!(setup.sessions(setup.ada), 0);
+    assert_eq!(setup.trusted_browsers(setup.ada), 0);
+    assert!(setup.audited(setup.ada, AuditAction::Banned));
+
+    setup
+        .update(&admin, setup.ada, status(UserStatus::Active))
+        .unwrap();
+    assert!(setup.audited(setup.ada, AuditAction::Activated));
+
+    for status_set in [UserStatus::Pending, UserStatus::Deactivated] {
+        assert!(matches!(
+            setup.update(&admin, setup.ada, status(status_set)),
+            Err(UpdateError::Invalid(_))
+        ));
+    }
+    assert!(matches!(
+        setup.update(&admin, setup.admin, status(UserStatus::Banned)),
+        Err(UpdateError::Forbidden(_))
+    ));
+    let own = setup.sign_in(setup.ada);
+    assert!(matches!(
+        setup.update(&own, setup.admin, status(UserStatus::Banned)),
+        Err(UpdateError::Forbidden(_))
+    ));
+}
+
+#[test]
+fn impersonators_cannot_change_credentials_or_statuses() {
+    let setup = setup();
+    let admin = setup.sign_in(setup.admin);
+    let acting =
+        setup
+            .state
+            .sessions
+            .impersonate(admin, setup.ada, "tests", None, setup.state.clock.now());
+
+    assert!(matches!(
+        setup.update(&acting, setup.ada, new_password(Some(PASSWORD))),
+        Err(UpdateError::Forbidden(_))
+    ));
+    setup
+        .update(
+            &acting,
+            setup.ada,
+            UserUpdate {
+                first_name: Some("Augusta".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(setup.get(setup.ada).first_name.as_deref(), Some("Augusta"));
+}
+
+#[test]
+fn erased_users_cannot_be_changed() {
+    let setup = setup();
+    let admin = setup.sign_in(setup.admin);
+    erase(
+        &setup.state,
+        &setup.state.users.scoped(&Tenant::default()),
+        setup.ada,
+    );
+
+    assert!(matches!(
+        setup.update(
+            &admin,
+            setup.ada,
+            UserUpdate {
+                first_name: Some("Ada".to_string()),
+                ..Default::default()
+            }
+        ),
+        Err(UpdateError::Forbidden(_))
+    ));
+}
+
+#[test]
+fn themes_are_chosen_by_the_user_or_an_administrator() {
+    let setup = setup();
+    let ada = setup.sign_in(setup.ada);
+    let admin = setup.sign_in(setup.admin);
+    let admins = setup.get(setup.admin).theme;
+    let theme = |theme| UserUpdate {
+        theme: Some(theme),
+        ..Default::default()
+    };
+
+    setup.update(&ada, setup.ada, theme(Theme::Dark)).unwrap();
+    assert_eq!(setup.get(setup.ada).theme, Theme::Dark);
+    assert!(matches!(
+        setup.update(&ada, setup.admin, theme(admins.toggled())),
+        Err(UpdateError::Forbidden(_))
+    ));
+    assert_eq!(setup.get(setup.admin).theme, admins);
+    setup
+        .update(&admin, setup.ada, theme(Theme::Light))
+        .unwrap();
+    assert_eq!(setup.get(setup.ada).theme, Theme::Light);
+    // A theme is no reason to sign anyone out.
+    assert_eq!(setup.sessions(setup.ada), 1);
+
+    erase(
+        &setup.state,
+        &setup.state.users.scoped(&Tenant::default()),
+        setup.ada,
+    );
+    assert!(matches!(
+        setup.update(&admin, setup.ada, theme(Theme::Dark)),
+        Err(UpdateError::Forbidden(_))
+    ));
+}
+
+#[test]
+fn languages_are_supported_ones_or_none() {
+    let setup = setup();
+    let ada = setup.sign_in(setup.ada);
+    let language = |language: &str| UserUpdate {
+        language: Some(language.to_string()),
+        ..Default::default()
+    };
+
+    setup.update(&ada, setup.ada, language("FR")).unwrap();
+    assert_eq!(setup.get(setup.ada).language.as_deref(), Some("fr"));
+    assert!(matches!(
+        setup.update(&ada, setup.ada, language("tlh")),
+        Err(UpdateError::Invalid(_))
+    ));
+    assert!(matches!(
+        setup.update(&ada, setup.admin, language("de")),
+        Err(UpdateError::Forbidden(_))
+    ));
+    setup.update(&ada, setup.ada, language("")).unwrap();
+    assert_eq!(setup.get(setup.ada).language, None);
+}
@@ -0,0 +1,134 @@
+//! The user list's filter, and patching a loaded list with live changes.
+
+use std::collections::HashMap;
+
+use app::{
+    users::{patch, UserFilter},
+    User, UserStatus,
+};
+use reqwest::Url;
+
+fn user(login: &str, status: UserStatus, role: Option<&str>) -> User {
+    let mut user = User::get_user();
+    user.login = login.to_string();
+    user.status = status;
+    user.roles = role.into_iter().map(str::to_string).collect();
+    user
+}
+
+fn logins(users: &[User]) -> Vec<&str> {
+    users.iter().map(|user| user.login.as_str()).collect()
+}
+
+#[test]
+fn filters_round_trip_through_the_query_string() {
+    let filter = UserFilter {
+        q: "Ana María & co".to_string(),
+        status: Some(UserStatus::Banned),
+        role: Some("editor".to_string()),
+    };
+    let query = filter.to_query();
+    assert_eq!(
+        query,
+        "q=Ana%20Mar%C3%ADa%20%26%20co&status=Banned&role=editor"
+    );
+
+    let url = Url::parse(&format!("http://localhost/users?{query}")).unwrap();
+    let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+    assert_eq!(
+        UserFilter::from_query(|key| params.get(key).cloned()),
+        filter
+    );
+
+    assert_eq!(UserFilter::default().to_query(), "");
+    let ignored = UserFilter::from_query(|key| match key {
+        "q" => Some("  ada ".to_string()),
+        "status" => Some("sleeping".to_string()),
+        _ => Some(String::new()),
+    });
+    assert_eq!(
+        ignored,
+        UserFilter {
+            q: "ada".to_string(),
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn filters_match_login_or_name_status_and_role() {
+    let mut ada = user("ada@example.com", UserStatus::Active, Some("editor"));
+    ada.first_name = Some("Ada".to_string());
+    ada.last_name = Some("Lovelace".to_string());
+    let grace = user("grace@example.com", UserStatus::Banned, None);
+
+    let matching = |filter: UserFilter| -> Vec<&str> {
+        [&ada, &grace]
+            .into_iter()
+            .filter(|user| filter.matches(user))
+            .map(|user| user.login.as_str())
+            .collect()
+    };
+    assert_eq!(matching(UserFilter::default()).len(), 2);
+    assert_eq!(
+        matching(UserFilter {
+            q: "LOVE".to_string(),
+            ..Default::default()
+        }),
+        ["ada@example.com"]
+    );
+    assert_eq!(
+        matching(UserFilter {
+            status: Some(UserStatus::Banned),
+            ..Default::default()
+        }),
+        ["grace@example.com"]
+    );
+    assert_eq!(
+        matching(UserFilter {
+            q: "example".to_string(),
+            role: Some("editor".to_string()),
+            ..Default::default()
+        }),
+        ["ada@example.com"]
+    );
+}
+
+#[test]
+fn patches_bring_a_loaded_list_up_to_date() {
+    let ada = user("ada@example.com", UserStatus::Active, None);
+    let grace = user("grace@example.com", UserStatus::Active, None);
+    let alan = user("alan@example.com", UserStatus::Active, None);
+    let barbara = user("barbara@example.com", UserStatus::Active, None);
+    let loaded = vec![ada.clone(), alan.clone(), grace.clone()];
+    let active = UserFilter {
+        status: Some(UserStatus::Active),
+        ..Default::default()
+    };
+
+    let mut banned = grace.clone();
+    banned.status = UserStatus::Banned;
+    let mut renamed = ada.clone();
+    renamed.login = "lovelace@example.com".to_string();
+    let changes = HashMap::from([
+        (grace.unid, Some(banned)),
+        (ada.unid, Some(renamed)),
+        (alan.unid, None),
+        (barbara.unid, Some(barbara.clone())),
+    ]);
+
+    // The ban takes Grace off a list of active users, and the renamed
+    // account moves to where its new login sorts.
+    assert_eq!(
+        logins(&patch(loaded.clone(), &changes, &active)),
+        ["barbara@example.com", "lovelace@example.com"]
+    );
+    assert_eq!(
+        logins(&patch(loaded, &changes, &UserFilter::default())),
+        [
+            "barbara@example.com",
+            "grace@example.com",
+            "lovelace@example.com"
+        ]
+    );
+}
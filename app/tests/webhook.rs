@@ -0,0 +1,302 @@
+//! Delivers user events to a receiver listening on a local port.
+
+use std::sync::{Arc, Mutex};
+
+use app::{
+    erasure,
+    state::AppState,
+    tenant::Tenant,
+    webhook::{DeliveryStatus, WebhookEvent, DEAD_LETTER_LIFETIME, MAX_ATTEMPTS},
+    User, UserStatus,
+};
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    routing::post,
+    Router,
+};
+use hmac::{Hmac, Mac};
+use serde_json::Value as JsonValue;
+use sha2::Sha256;
+use time::{Duration, OffsetDateTime};
+
+#[derive(Debug)]
+struct Request {
+    headers: HeaderMap,
+    body: String,
+}
+
+/// Records what it is sent, answering with an error while `failing` is
+/// above zero.
+#[derive(Clone, Debug, Default)]
+struct Receiver {
+    requests: Arc<Mutex<Vec<Request>>>,
+    failing: Arc<Mutex<usize>>,
+}
+
+impl Receiver {
+    fn requests(&self) -> Vec<(String, JsonValue)> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|request| {
+                let event = request.headers["x-webhook-event"].to_str().unwrap();
+                (
+                    event.to_string(),
+                    serde_json::from_str(&request.body).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    fn fail(&self, times: usize) {
+        *self.failing.lock().unwrap() = times;
+    }
+}
+
+async fn receive(State(receiver): State<Receiver>, headers: HeaderMap, body: String) -> StatusCode {
+    receiver
+        .requests
+        .lock()
+        .unwrap()
+        .push(Request { headers, body });
+    let mut failing = receiver.failing.lock().unwrap();
+    if *failing > 0 {
+        *failing -= 1;
+        StatusCode::INTERNAL_SERVER_ERROR
+    } else {
+        StatusCode::NO_CONTENT
+    }
+}
+
+/// Serves a [`Receiver`], returning the URL to subscribe.
+async fn listen() -> (String, Receiver) {
+    let receiver = Receiver::default();
+    let app = Router::new()
+        .route("/hook", post(receive))
+        .with_state(receiver.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (url, receiver)
+}
+
+fn setup() -> AppState {
+    let state = AppState::default();
+    state.users.observe(state.webhooks.clone());
+    state
+}
+
+fn all_events() -> Vec<WebhookEvent> {
+    vec![
+        WebhookEvent::UserCreated,
+        WebhookEvent::UserUpdated,
+        WebhookEvent::UserBanned,
+        WebhookEvent::UserDeleted,
+    ]
+}
+
+#[tokio::test]
+async fn delivers_signed_events() {
+    let state = setup();
+    let tenant = Tenant::default();
+    let (url, receiver) = listen().await;
+    let (_, secret) = state.webhooks.subscribe(&tenant, &url, all_events());
+
+    let mut user = User::get_user();
+    user.hash = "secret hash".to_string();
+    state.users.scoped(&tenant).insert(user.clone());
+    assert_eq!(state.webhooks.run_due(OffsetDateTime::now_utc()).await, 1);
+
+    let requests = receiver.requests.lock().unwrap();
+    let request = &requests[0];
+    let header = |name: &str| request.headers[name].to_str().unwrap().to_string();
+    assert_eq!(header("x-webhook-event"), "user.created");
+    assert_eq!(header("content-type"), "application/json");
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(format!("{}.{}", header("x-webhook-timestamp"), request.body).as_bytes());
+    let expected = data_encoding::HEXLOWER.encode(&mac.finalize().into_bytes());
+    assert_eq!(header("x-webhook-signature"), format!("sha256={expected}"));
+
+    let payload: JsonValue = serde_json::from_str(&request.body).unwrap();
+    assert_eq!(payload["type"], "user.created");
+    assert_eq!(payload["id"], header("x-webhook-id"));
+    assert_eq!(payload["data"]["unid"], user.unid.to_string());
+    assert_eq!(payload["data"]["login"], user.login);
+    assert!(payload["data"].get("hash").is_none());
+
+    let deliveries = state.webhooks.deliveries(&tenant);
+    assert_eq!(deliveries[0].status, DeliveryStatus::Delivered);
+    assert_eq!(deliveries[0].last_response, Some(204));
+}
+
+#[tokio::test]
+async fn sends_only_the_subscribed_events_of_the_tenant() {
+    let state = setup();
+    let tenant = Tenant::default();
+    let other = Tenant {
+        schema: Some("acme".to_string()),
+    };
+    let (url, receiver) = listen().await;
+    state
+        .webhooks
+        .subscribe(&tenant, &url, vec![WebhookEvent::UserBanned]);
+    state.webhooks.subscribe(&other, &url, all_events());
+
+    let users = state.users.scoped(&tenant);
+    let user = User::get_user();
+    users.insert(user.clone());
+    users.update(user.unid, |user| {
+        user.last_login = Some(OffsetDateTime::now_utc())
+    });
+    users.update(user.unid, |user| {
+        user.first_name = Some("Robert".to_string())
+    });
+    users.update(user.unid, |user| user.status = UserStatus::Banned);
+    state.webhooks.run_due(OffsetDateTime::now_utc()).await;
+
+    let requests = receiver.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].0, "user.banned");
+    assert_eq!(requests[0].1["data"]["first_name"], "Robert");
+    assert!(state.webhooks.deliveries(&other).is_empty());
+}
+
+#[tokio::test]
+async fn erasing_or_removing_a_user_is_a_deletion() {
+    let state = setup();
+    let tenant = Tenant::default();
+    let (url, receiver) = listen().await;
+    state
+        .webhooks
+        .subscribe(&tenant, &url, vec![WebhookEvent::UserDeleted]);
+
+    let users = state.users.scoped(&tenant);
+    let (erased, removed) = (User::get_user(), User::get_user());
+    users.insert(erased.clone());
+    users.insert(removed.clone());
+    erasure::erase(&state, &users, erased.unid);
+    users.remove(removed.unid);
+    state.webhooks.run_due(OffsetDateTime::now_utc()).await;
+
+    let deleted: Vec<JsonValue> = receiver
+        .requests()
+        .into_iter()
+        .map(|(event, payload)| {
+            assert_eq!(event, "user.deleted");
+            payload["data"]["unid"].clone()
+        })
+        .collect();
+    assert_eq!(deleted, [erased.unid.to_string(), removed.unid.to_string()]);
+}
+
+#[tokio::test]
+async fn retries_with_backoff_until_dead_then_on_request() {
+    let state = setup();
+    let tenant = Tenant::default();
+    let (url, receiver) = listen().await;
+    state.webhooks.subscribe(&tenant, &url, all_events());
+    receiver.fail(usize::MAX);
+
+    state.users.scoped(&tenant).insert(User::get_user());
+    let mut at = OffsetDateTime::now_utc();
+    for attempt in 1..=MAX_ATTEMPTS {
+        assert_eq!(state.webhooks.run_due(at).await, 1, "attempt {attempt}");
+        let delivery = state.webhooks.deliveries(&tenant).remove(0);
+        assert_eq!(delivery.attempts, attempt);
+        assert_eq!(delivery.last_response, Some(500));
+        if attempt < MAX_ATTEMPTS {
+            assert_eq!(delivery.status, DeliveryStatus::Retrying);
+            let wait = Duration::seconds(30) * 2_i32.pow(attempt - 1);
+            assert_eq!(delivery.next_attempt, Some(at + wait));
+            // Not a moment early.
+            assert_eq!(
+                state.webhooks.run_due(at + wait - Duration::SECOND).await,
+                0
+            );
+            at += wait;
+        }
+    }
+    let delivery = state.webhooks.deliveries(&tenant).remove(0);
+    assert_eq!(delivery.status, DeliveryStatus::Dead);
+    assert_eq!(state.webhooks.run_due(at + Duration::days(1)).await, 0);
+    assert_eq!(receiver.requests().len(), MAX_ATTEMPTS as usize);
+
+    receiver.fail(0);
+    assert!(state.webhooks.retry(&tenant, delivery.id));
+    assert_eq!(state.webhooks.run_due(at).await, 1);
+    let delivery = state.webhooks.deliveries(&tenant).remove(0);
+    assert_eq!(delivery.status, DeliveryStatus::Delivered);
+    assert_eq!(delivery.attempts, 1);
+    assert!(!state.webhooks.retry(&tenant, delivery.id));
+}
+
+#[tokio::test]
+async fn dead_letters_are_dropped_after_a_while() {
+    let state = setup();
+    let tenant = Tenant::default();
+    let (url, receiver) = listen().await;
+    state.webhooks.subscribe(&tenant, &url, all_events());
+    receiver.fail(usize::MAX);
+
+    state.users.scoped(&tenant).insert(User::get_user());
+    let mut at = OffsetDateTime::now_utc();
+    for _ in 0..MAX_ATTEMPTS {
+        state.webhooks.run_due(at).await;
+        at = state.webhooks.deliveries(&tenant)[0]
+            .next_attempt
+            .unwrap_or(at);
+    }
+    assert_eq!(
+        state.webhooks.deliveries(&tenant)[0].status,
+        DeliveryStatus::Dead
+    );
+
+    state
+        .webhooks
+        .run_due(at + DEAD_LETTER_LIFETIME - Duration::SECOND)
+        .await;
+    assert_eq!(state.webhooks.deliveries(&tenant).len(), 1);
+    state.webhooks.run_due(at + DEAD_LETTER_LIFETIME).await;
+    assert!(state.webhooks.deliveries(&tenant).is_empty());
+}
+
+#[tokio::test]
+async fn unsubscribing_drops_what_is_still_to_be_sent() {
+    let state = setup();
+    let tenant = Tenant::default();
+    let (url, receiver) = listen().await;
+    let (subscription, _) = state.webhooks.subscribe(&tenant, &url, all_events());
+
+    let users = state.users.scoped(&tenant);
+    users.insert(User::get_user());
+    state.webhooks.run_due(OffsetDateTime::now_utc()).await;
+    users.insert(User::get_user());
+    assert!(state.webhooks.unsubscribe(&tenant, subscription.id));
+
+    let deliveries = state.webhooks.deliveries(&tenant);
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0].status, DeliveryStatus::Delivered);
+    assert_eq!(state.webhooks.run_due(OffsetDateTime::now_utc()).await, 0);
+    assert_eq!(receiver.requests().len(), 1);
+    assert!(!state.webhooks.unsubscribe(&tenant, subscription.id));
+}
+
+#[tokio::test]
+async fn unreachable_receivers_are_retried() {
+    let state = setup();
+    let tenant = Tenant::default();
+    // A port that was free a moment ago, with nothing listening now.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    drop(listener);
+    state.webhooks.subscribe(&tenant, &url, all_events());
+
+    state.users.scoped(&tenant).insert(User::get_user());
+    state.webhooks.run_due(OffsetDateTime::now_utc()).await;
+    let delivery = state.webhooks.deliveries(&tenant).remove(0);
+    assert_eq!(delivery.status, DeliveryStatus::Retrying);
+    assert_eq!(delivery.last_response, None);
+    assert!(delivery.last_error.is_some());
+}
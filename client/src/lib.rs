@@ -0,0 +1,199 @@
+//! Typed async client for the JSON API served under [`API_PATH`].
+//!
+//! It speaks the wire types of the `model` crate, so a service sees the same
+//! [`User`] the server stores without building the UI. Services
+//! authenticate with a token from the server's `API_TOKENS`:
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), client::Error> {
+//! use client::{Client, UserFilter};
+//!
+//! let client = Client::new("https://users.example.com").with_token("s3cret");
+//! for user in client.all_users(&UserFilter::default()).await? {
+//!     println!("{}", user.login);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use reqwest::{header::COOKIE, Method, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use uuid::Uuid;
+
+use model::SESSION_COOKIE;
+pub use model::{
+    api::{ApiError, ApiErrorCode, NewUser, Page, PageLinks, UserPatch, API_PATH},
+    Theme, User, UserFilter, UserStatus,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The server answered with an error body.
+    #[error(transparent)]
+    Api(#[from] ApiError),
+    /// The request could not be sent or the response not read.
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    /// The server answered with an error that is not the API's, say from a
+    /// proxy in between.
+    #[error("unexpected {status} response: {body}")]
+    UnexpectedStatus { status: StatusCode, body: String },
+}
+
+impl Error {
+    /// The API's error code, if the server sent one.
+    pub fn code(&self) -> Option<ApiErrorCode> {
+        match self {
+            Error::Api(error) => Some(error.code),
+            _ => None,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Asks for one page of users.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UserQuery {
+    pub filter: UserFilter,
+    pub offset: usize,
+    /// The server's default page size if `None`.
+    pub limit: Option<usize>,
+}
+
+impl UserQuery {
+    fn to_query(&self) -> String {
+        let mut query = self.filter.to_query();
+        if !query.is_empty() {
+            query.push('&');
+        }
+        query.push_str(&format!("offset={}", self.offset));
+        if let Some(limit) = self.limit {
+            query.push_str(&format!("&limit={limit}"));
+        }
+        query
+    }
+}
+
+/// How requests are authenticated.
+#[derive(Clone, Debug)]
+enum Credentials {
+    /// The session cookie of a signed-in user.
+    Session(Uuid),
+    /// A bearer token from the server's `API_TOKENS`.
+    Token(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct Client {
+    http: reqwest::Client,
+    /// Scheme, host and port of the server, without a trailing slash.
+    base_url: String,
+    credentials: Option<Credentials>,
+}
+
+impl Client {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_http_client(reqwest::Client::new(), base_url)
+    }
+
+    /// A client sending its requests through `http`, e.g. to set timeouts.
+    pub fn with_http_client(http: reqwest::Client, base_url: impl Into<String>) -> Self {
+        Client {
+            http,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            credentials: None,
+        }
+    }
+
+    /// Acts as the user signed in on `session`.
+    pub fn with_session(mut self, session: Uuid) -> Self {
+        self.credentials = Some(Credentials::Session(session));
+        self
+    }
+
+    /// Acts as a service with `token`, one of the server's `API_TOKENS`. It
+    /// may do what the administrators of the token's tenant may.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.credentials = Some(Credentials::Token(token.into()));
+        self
+    }
+
+    /// `path` is relative to the server, as in the API's page links.
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self
+            .http
+            .request(method, format!("{}{path}", self.base_url));
+        match &self.credentials {
+            Some(Credentials::Session(session)) => {
+                request.header(COOKIE, format!("{SESSION_COOKIE}={session}"))
+            }
+            Some(Credentials::Token(token)) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// Sends `request` and fails on any status but a success.
+    async fn send(request: RequestBuilder) -> Result<Response> {
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let body = response.text().await?;
+        Err(match serde_json::from_str::<ApiError>(&body) {
+            Ok(error) => Error::Api(error),
+            Err(_) => Error::UnexpectedStatus { status, body },
+        })
+    }
+
+    async fn json<T: DeserializeOwned>(request: RequestBuilder) -> Result<T> {
+        Ok(Self::send(request).await?.json().await?)
+    }
+
+    /// One page of the tenant's users, sorted by login. Administrators only.
+    pub async fn list_users(&self, query: &UserQuery) -> Result<Page<User>> {
+        let path = format!("{API_PATH}/users?{}", query.to_query());
+        Self::json(self.request(Method::GET, &path)).await
+    }
+
+    /// Every user matching `filter`, following the page links.
+    pub async fn all_users(&self, filter: &UserFilter) -> Result<Vec<User>> {
+        let mut page = self
+            .list_users(&UserQuery {
+                filter: filter.clone(),
+                ..Default::default()
+            })
+            .await?;
+        let mut users = std::mem::take(&mut page.items);
+        while let Some(next) = page.links.next.take() {
+            page = Self::json(self.request(Method::GET, &next)).await?;
+            users.append(&mut page.items);
+        }
+        Ok(users)
+    }
+
+    pub async fn get_user(&self, unid: Uuid) -> Result<User> {
+        Self::json(self.request(Method::GET, &format!("{API_PATH}/users/{unid}"))).await
+    }
+
+    pub async fn create_user(&self, user: &NewUser) -> Result<User> {
+        Self::json(
+            self.request(Method::POST, &format!("{API_PATH}/users"))
+                .json(user),
+        )
+        .await
+    }
+
+    pub async fn update_user(&self, unid: Uuid, patch: &UserPatch) -> Result<User> {
+        let path = format!("{API_PATH}/users/{unid}");
+        Self::json(self.request(Method::PATCH, &path).json(patch)).await
+    }
+
+    /// Erases the user's personal data; the account stays, deactivated.
+    pub async fn delete_user(&self, unid: Uuid) -> Result<()> {
+        let path = format!("{API_PATH}/users/{unid}");
+        Self::send(self.request(Method::DELETE, &path)).await?;
+        Ok(())
+    }
+}
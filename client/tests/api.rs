@@ -0,0 +1,397 @@
+//! Runs the client against the server's API router, served in-process on a
+//! local port.
+
+use std::collections::HashSet;
+
+use app::{
+    state::{AppState, ADMIN_ROLE},
+    tenant::{ServiceTokens, Tenant},
+};
+use axum::{middleware, Router};
+use client::{
+    ApiErrorCode, Client, NewUser, UserFilter, UserPatch, UserQuery, UserStatus, API_PATH,
+};
+use uuid::Uuid;
+
+const TOKEN: &str = "service-token";
+const ACME_TOKEN: &str = "acme-token";
+
+struct Server {
+    state: AppState,
+    base_url: String,
+    admin: Uuid,
+}
+
+impl Server {
+    async fn start() -> Self {
+        let state = AppState {
+            api_tokens: ServiceTokens::default()
+                .with_token(Tenant::default(), TOKEN)
+                .with_token(
+                    Tenant {
+                        schema: Some("acme".to_string()),
+                    },
+                    ACME_TOKEN,
+                ),
+            ..AppState::default()
+        };
+        let mut admin = app::User::get_user();
+        admin.login = "admin@example.com".to_string();
+        admin.roles.insert(ADMIN_ROLE.to_string());
+        state.users.insert(admin.clone());
+
+        let router = Router::new()
+            .nest(API_PATH, server::api::router(state.clone()))
+            .layer(middleware::from_fn_with_state(
+                state.clone(),
+                server::session::track_session,
+            ))
+            .layer(middleware::from_fn_with_state(
+                state.clone(),
+                server::tenant::resolve_tenant,
+            ));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        Server {
+            state,
+            base_url,
+            admin: admin.unid,
+        }
+    }
+
+    /// A client signed in as `user`.
+    fn client(&self, user: Uuid) -> Client {
+        let session =
+            self.state
+                .sessions
+                .create(user, "client tests", None, self.state.clock.now());
+        Client::new(&self.base_url).with_session(session.id)
+    }
+
+    fn admin(&self) -> Client {
+        self.client(self.admin)
+    }
+}
+
+fn new_user(login: &str) -> NewUser {
+    NewUser {
+        login: login.to_string(),
+        first_name: Some("Ada".to_string()),
+        last_name: Some("Lovelace".to_string()),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn creates_reads_updates_and_deletes_a_user() {
+    let server = Server::start().await;
+    let client = server.admin();
+
+    let created = client
+        .create_user(&NewUser {
+            password: Some("analytical engine".to_string()),
+            roles: HashSet::from(["Editor".to_string()]),
+            ..new_user("ada@example.com")
+        })
+        .await
+        .unwrap();
+    assert_eq!(created.login, "ada@example.com");
+    assert_eq!(created.status, UserStatus::Active);
+    assert_eq!(created.roles, HashSet::from(["editor".to_string()]));
+    assert!(created.hash.is_empty());
+
+    let fetched = client.get_user(created.unid).await.unwrap();
+    assert_eq!(fetched, created);
+
+    let updated = client
+        .update_user(
+            created.unid,
+            &UserPatch {
+                status: Some(UserStatus::Banned),
+                last_name: Some(String::new()),
+                language: Some("de".to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(updated.status, UserStatus::Banned);
+    assert_eq!(updated.first_name.as_deref(), Some("Ada"));
+    assert_eq!(updated.last_name, None);
+    assert_eq!(updated.language.as_deref(), Some("de"));
+
+    client.delete_user(created.unid).await.unwrap();
+    let erased = client.get_user(created.unid).await.unwrap();
+    assert_eq!(erased.status, UserStatus::Deactivated);
+    assert_ne!(erased.login, "ada@example.com");
+}
+
+#[tokio::test]
+async fn pages_through_filtered_users() {
+    let server = Server::start().await;
+    let client = server.admin();
+    for n in 0..5 {
+        client
+            .create_user(&new_user(&format!("user{n}@example.com")))
+            .await
+            .unwrap();
+    }
+
+    let filter = UserFilter {
+        q: "user".to_string(),
+        ..Default::default()
+    };
+    let page = client
+        .list_users(&UserQuery {
+            filter: filter.clone(),
+            offset: 2,
+            limit: Some(2),
+        })
+        .await
+        .unwrap();
+    assert_eq!(page.total, 5);
+    assert_eq!(
+        page.items
+            .iter()
+            .map(|user| user.login.as_str())
+            .collect::<Vec<_>>(),
+        ["user2@example.com", "user3@example.com"]
+    );
+    assert!(page.links.prev.is_some());
+    assert!(page.links.next.is_some());
+
+    let all = client.all_users(&filter).await.unwrap();
+    assert_eq!(all.len(), 5);
+    let everyone = client.all_users(&UserFilter::default()).await.unwrap();
+    assert_eq!(everyone.len(), 6);
+}
+
+#[tokio::test]
+async fn errors_are_typed() {
+    let server = Server::start().await;
+    let admin = server.admin();
+    let ada = admin
+        .create_user(&new_user("ada@example.com"))
+        .await
+        .unwrap();
+
+    let taken = admin.create_user(&new_user("ADA@example.com")).await;
+    assert_eq!(taken.unwrap_err().code(), Some(ApiErrorCode::Conflict));
+
+    let unknown_role = admin
+        .create_user(&NewUser {
+            roles: HashSet::from(["pilot".to_string()]),
+            ..new_user("grace@example.com")
+        })
+        .await;
+    assert_eq!(
+        unknown_role.unwrap_err().code(),
+        Some(ApiErrorCode::InvalidInput)
+    );
+
+    let missing = admin.get_user(Uuid::new_v4()).await;
+    assert_eq!(missing.unwrap_err().code(), Some(ApiErrorCode::NotFound));
+
+    let anonymous = Client::new(&server.base_url).get_user(ada.unid).await;
+    assert_eq!(
+        anonymous.unwrap_err().code(),
+        Some(ApiErrorCode::Unauthorized)
+    );
+
+    // Users may read and rename themselves, but not list others or promote
+    // themselves.
+    let own = server.client(ada.unid);
+    assert_eq!(own.get_user(ada.unid).await.unwrap().unid, ada.unid);
+    let renamed = own
+        .update_user(
+            ada.unid,
+            &UserPatch {
+                first_name: Some("Augusta".to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(renamed.first_name.as_deref(), Some("Augusta"));
+    let list = own.list_users(&UserQuery::default()).await;
+    assert_eq!(list.unwrap_err().code(), Some(ApiErrorCode::Forbidden));
+    let promote = own
+        .update_user(
+            ada.unid,
+            &UserPatch {
+                roles: Some(HashSet::from([ADMIN_ROLE.to_string()])),
+                ..Default::default()
+            },
+        )
+        .await;
+    assert_eq!(promote.unwrap_err().code(), Some(ApiErrorCode::Forbidden));
+    let others = own.get_user(server.admin).await;
+    assert_eq!(others.unwrap_err().code(), Some(ApiErrorCode::Forbidden));
+}
+
+#[tokio::test]
+async fn updates_follow_the_rules_of_the_app() {
+    let server = Server::start().await;
+    let admin = server.admin();
+    let ada = admin
+        .create_user(&NewUser {
+            password: Some("analytical engine".to_string()),
+            ..new_user("ada@example.com")
+        })
+        .await
+        .unwrap();
+
+    let pending = admin
+        .create_user(&NewUser {
+            status: Some(UserStatus::Pending),
+            ..new_user("grace@example.com")
+        })
+        .await;
+    assert_eq!(
+        pending.unwrap_err().code(),
+        Some(ApiErrorCode::InvalidInput)
+    );
+    let short = admin
+        .create_user(&NewUser {
+            password: Some("short".to_string()),
+            ..new_user("grace@example.com")
+        })
+        .await;
+    assert_eq!(short.unwrap_err().code(), Some(ApiErrorCode::InvalidInput));
+    let not_email = admin.create_user(&new_user("grace")).await;
+    assert_eq!(
+        not_email.unwrap_err().code(),
+        Some(ApiErrorCode::InvalidInput)
+    );
+
+    // Users changing their own password prove they know the current one,
+    // and their other browsers are signed out.
+    let own = server.client(ada.unid);
+    let elsewhere = server.client(ada.unid);
+    let change = |current_password: &str| UserPatch {
+        password: Some("difference engine".to_string()),
+        current_password: Some(current_password.to_string()),
+        ..Default::default()
+    };
+    let wrong = own.update_user(ada.unid, &change("wrong")).await;
+    assert_eq!(wrong.unwrap_err().code(), Some(ApiErrorCode::Forbidden));
+    own.update_user(ada.unid, &change("analytical engine"))
+        .await
+        .unwrap();
+    assert!(own.get_user(ada.unid).await.is_ok());
+    let signed_out = elsewhere.get_user(ada.unid).await;
+    assert_eq!(
+        signed_out.unwrap_err().code(),
+        Some(ApiErrorCode::Unauthorized)
+    );
+
+    let pend = admin
+        .update_user(
+            ada.unid,
+            &UserPatch {
+                status: Some(UserStatus::Pending),
+                ..Default::default()
+            },
+        )
+        .await;
+    assert_eq!(pend.unwrap_err().code(), Some(ApiErrorCode::InvalidInput));
+    let ban_self = admin
+        .update_user(
+            server.admin,
+            &UserPatch {
+                status: Some(UserStatus::Banned),
+                ..Default::default()
+            },
+        )
+        .await;
+    assert_eq!(ban_self.unwrap_err().code(), Some(ApiErrorCode::Forbidden));
+
+    // A ban signs the user out everywhere.
+    admin
+        .update_user(
+            ada.unid,
+            &UserPatch {
+                status: Some(UserStatus::Banned),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    let banned = own.get_user(ada.unid).await;
+    assert_eq!(banned.unwrap_err().code(), Some(ApiErrorCode::Unauthorized));
+    assert!(server
+        .state
+        .audit
+        .for_user(ada.unid)
+        .iter()
+        .any(|entry| entry.action == app::audit::AuditAction::Banned
+            && entry.actor == Some(server.admin)));
+
+    // Erased users stay as they are.
+    admin.delete_user(ada.unid).await.unwrap();
+    let rename = admin
+        .update_user(
+            ada.unid,
+            &UserPatch {
+                first_name: Some("Ada".to_string()),
+                ..Default::default()
+            },
+        )
+        .await;
+    assert_eq!(rename.unwrap_err().code(), Some(ApiErrorCode::Forbidden));
+}
+
+#[tokio::test]
+async fn services_authenticate_with_a_token() {
+    let server = Server::start().await;
+    let service = Client::new(&server.base_url).with_token(TOKEN);
+
+    let ada = service
+        .create_user(&new_user("ada@example.com"))
+        .await
+        .unwrap();
+    assert_eq!(
+        service
+            .all_users(&UserFilter::default())
+            .await
+            .unwrap()
+            .len(),
+        2
+    );
+    let banned = service
+        .update_user(
+            ada.unid,
+            &UserPatch {
+                status: Some(UserStatus::Banned),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    assert_eq!(banned.status, UserStatus::Banned);
+    assert!(server
+        .state
+        .audit
+        .for_user(ada.unid)
+        .iter()
+        .any(|entry| entry.action == app::audit::AuditAction::Banned && entry.actor.is_none()));
+
+    // A token only sees the tenant it is for.
+    let acme = Client::new(&server.base_url).with_token(ACME_TOKEN);
+    assert!(acme
+        .all_users(&UserFilter::default())
+        .await
+        .unwrap()
+        .is_empty());
+    let elsewhere = acme.get_user(ada.unid).await;
+    assert_eq!(elsewhere.unwrap_err().code(), Some(ApiErrorCode::NotFound));
+
+    let unknown = Client::new(&server.base_url).with_token("guess");
+    let refused = unknown.get_user(ada.unid).await;
+    assert_eq!(
+        refused.unwrap_err().code(),
+        Some(ApiErrorCode::Unauthorized)
+    );
+}
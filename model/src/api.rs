@@ -0,0 +1,171 @@
+//! Wire types of the versioned JSON API under [`API_PATH`], for services
+//! managing users without going through the server functions.
+//!
+//! Users are returned as [`crate::User::public`] leaves them, without the
+//! password hash.
+//!
+//! Browsers and users' own tools authenticate with the session cookie,
+//! other services with a bearer token from `API_TOKENS`.
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+
+use crate::{Theme, UserStatus};
+
+pub const API_PATH: &str = "/api/v1";
+
+/// Page size when the request does not ask for one.
+pub const DEFAULT_LIMIT: usize = 50;
+/// Largest page a request can ask for.
+pub const MAX_LIMIT: usize = 200;
+
+/// Body of `POST /api/v1/users`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub struct NewUser {
+    pub login: String,
+    #[serde(default)]
+    pub first_name: Option<String>,
+    #[serde(default)]
+    pub last_name: Option<String>,
+    /// Without one the account cannot be signed in to until one is set.
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Defaults to [`UserStatus::Active`].
+    #[serde(default)]
+    pub status: Option<UserStatus>,
+    #[serde(default)]
+    pub roles: HashSet<String>,
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+/// Body of `PATCH /api/v1/users/{unid}`. Fields left out are unchanged; an
+/// empty string clears an optional one.
+///
+/// Only administrators may change the status or roles. Users changing their
+/// own login or password also give their `current_password`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub struct UserPatch {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub login: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_password: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<UserStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roles: Option<HashSet<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub theme: Option<Theme>,
+}
+
+/// One page of a list, with links to its neighbours.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Items across all pages.
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub links: PageLinks,
+}
+
+/// URLs of other pages of the same list, relative to the server.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub struct PageLinks {
+    #[serde(rename = "self")]
+    pub this: String,
+    pub first: String,
+    pub prev: Option<String>,
+    pub next: Option<String>,
+    pub last: String,
+}
+
+impl<T> Page<T> {
+    /// The page starting at `offset` of `items`. `url` makes the link to a
+    /// page from its offset.
+    pub fn new(items: Vec<T>, offset: usize, limit: usize, url: impl Fn(usize) -> String) -> Self {
+        let limit = limit.max(1);
+        let total = items.len();
+        let last = total.saturating_sub(1) / limit * limit;
+        Page {
+            links: PageLinks {
+                this: url(offset),
+                first: url(0),
+                prev: (offset > 0).then(|| url(offset.saturating_sub(limit))),
+                next: (offset + limit < total).then(|| url(offset + limit)),
+                last: url(last),
+            },
+            items: items.into_iter().skip(offset).take(limit).collect(),
+            total,
+            offset,
+            limit,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, Display)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum ApiErrorCode {
+    /// Not signed in, or with an unknown token.
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    /// The login is taken.
+    Conflict,
+    InvalidInput,
+    Internal,
+}
+
+/// Body of every error response.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+#[error("{code}: {message}")]
+pub struct ApiError {
+    /// The HTTP status, repeated for clients that only see the body.
+    pub status: u16,
+    pub code: ApiErrorCode,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(code: ApiErrorCode, message: impl Into<String>) -> Self {
+        let status = match code {
+            ApiErrorCode::Unauthorized => 401,
+            ApiErrorCode::Forbidden => 403,
+            ApiErrorCode::NotFound => 404,
+            ApiErrorCode::Conflict => 409,
+            ApiErrorCode::InvalidInput => 422,
+            ApiErrorCode::Internal => 500,
+        };
+        ApiError {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl axum::response::IntoResponse for ApiError {
+    fn into_response(self) -> axum::response::Response {
+        let status = axum::http::StatusCode::from_u16(self.status)
+            .unwrap_or(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+        (status, axum::Json(self)).into_response()
+    }
+}
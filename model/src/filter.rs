@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{User, UserStatus};
+
+/// Narrows the user list; every field left empty matches everyone.
+///
+/// It round-trips through the query string, so a filtered list can be
+/// bookmarked and exported as shown.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserFilter {
+    /// Part of the login or name, ignoring case.
+    pub q: String,
+    pub status: Option<UserStatus>,
+    pub role: Option<String>,
+}
+
+impl UserFilter {
+    /// Reads the filter from query parameters, ignoring values that name no
+    /// status.
+    pub fn from_query(get: impl Fn(&str) -> Option<String>) -> Self {
+        UserFilter {
+            q: get("q").unwrap_or_default().trim().to_string(),
+            status: get("status").and_then(|status| status.parse().ok()),
+            role: get("role").filter(|role| !role.is_empty()),
+        }
+    }
+
+    pub fn to_query(&self) -> String {
+        let mut query = Vec::new();
+        if !self.q.is_empty() {
+            query.push(format!("q={}", urlencoding(&self.q)));
+        }
+        if let Some(status) = self.status {
+            query.push(format!("status={status}"));
+        }
+        if let Some(role) = &self.role {
+            query.push(format!("role={}", urlencoding(role)));
+        }
+        query.join("&")
+    }
+
+    pub fn matches(&self, user: &User) -> bool {
+        let q = self.q.to_lowercase();
+        let text_matches = q.is_empty()
+            || [
+                Some(&user.login),
+                user.first_name.as_ref(),
+                user.last_name.as_ref(),
+            ]
+            .into_iter()
+            .flatten()
+            .any(|text| text.to_lowercase().contains(&q));
+        text_matches
+            && self.status.is_none_or(|status| user.status == status)
+            && self
+                .role
+                .as_ref()
+                .is_none_or(|role| user.roles.contains(role))
+    }
+}
+
+/// Percent-encodes everything but unreserved characters.
+fn urlencoding(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
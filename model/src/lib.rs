@@ -0,0 +1,126 @@
+//! The user as the app stores it and as the JSON API and its clients see
+//! it, shared so that a service does not have to build the UI to talk to
+//! the server.
+
+pub mod api;
+mod filter;
+mod theme;
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter, EnumString};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+pub use filter::UserFilter;
+pub use theme::Theme;
+
+/// Name of the cookie carrying the session id.
+pub const SESSION_COOKIE: &str = "session";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub struct User {
+    pub unid: Uuid,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created: OffsetDateTime,
+    pub first_name: Option<String>,
+    /// Password hash; empty once it leaves the server.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    #[cfg_attr(feature = "ssr", schema(ignore))]
+    pub hash: String,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_failed_login: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_login: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub last_password_change: OffsetDateTime,
+    pub last_name: Option<String>,
+    pub login: String,
+    /// Hash naming the uploaded profile picture, served at
+    /// `/avatars/{hash}`.
+    pub avatar: Option<String>,
+    pub roles: HashSet<String>,
+    pub site_schema: Option<String>,
+    pub status: UserStatus,
+    pub theme: Theme,
+    /// UI language code such as `de`; `None` follows the browser.
+    pub language: Option<String>,
+}
+
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, Display, PartialEq, Eq, Hash, EnumIter, EnumString,
+)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+#[strum(ascii_case_insensitive)]
+pub enum UserStatus {
+    Active,
+    Banned,
+    /// Erased on request, see [`User::is_erased`].
+    Deactivated,
+    /// Invited and yet to accept.
+    Pending,
+}
+
+impl UserStatus {
+    /// Id of the status' name in the translations.
+    pub fn message_id(self) -> &'static str {
+        match self {
+            UserStatus::Active => "user-status-active",
+            UserStatus::Banned => "user-status-banned",
+            UserStatus::Deactivated => "user-status-deactivated",
+            UserStatus::Pending => "user-status-pending",
+        }
+    }
+}
+
+impl User {
+    pub fn get_user() -> Self {
+        User {
+            unid: Uuid::new_v4(),
+            created: OffsetDateTime::now_utc(),
+            first_name: Some("Bob".to_string()),
+            hash: "asdf".to_string(),
+            last_failed_login: None,
+            last_login: None,
+            last_password_change: OffsetDateTime::now_utc(),
+            last_name: None,
+            login: "bob@bob.bob".to_string(),
+            avatar: None,
+            roles: HashSet::new(),
+            site_schema: None,
+            status: UserStatus::Active,
+            theme: Theme::Dark,
+            language: None,
+        }
+    }
+
+    /// The user as it may leave the server, without the password hash.
+    /// Every user sent to a browser, an API client or a webhook goes
+    /// through here.
+    pub fn public(self) -> Self {
+        User {
+            hash: String::new(),
+            ..self
+        }
+    }
+
+    /// Whether the user's personal data was erased, leaving the account
+    /// deactivated under [`erased_login`].
+    pub fn is_erased(&self) -> bool {
+        self.status == UserStatus::Deactivated && self.login == erased_login(self.unid)
+    }
+}
+
+/// Name standing in for an erased user. It is derived from the `unid` alone,
+/// so it is the same every time and reveals nothing about the person.
+pub fn pseudonym(unid: Uuid) -> String {
+    format!("erased-{}", &unid.simple().to_string()[..12])
+}
+
+/// Login of an erased user; the reserved `.invalid` domain keeps it from
+/// ever colliding with a real address.
+pub fn erased_login(unid: Uuid) -> String {
+    format!("{}@erased.invalid", pseudonym(unid))
+}
@@ -0,0 +1,471 @@
+//! The versioned JSON API under [`API_PATH`], with its OpenAPI document at
+//! `/api/v1/openapi.json`.
+//!
+//! Requests are authenticated by the session cookie and authorized like the
+//! server functions: administrators manage the tenant's users, everyone else
+//! only their own account. Other services send a bearer token from
+//! `API_TOKENS` instead, which picks the tenant and may do what
+//! administrators may.
+
+use app::api::{
+    ApiError, ApiErrorCode, NewUser, Page, PageLinks, UserPatch, API_PATH, DEFAULT_LIMIT, MAX_LIMIT,
+};
+use app::audit::{AuditAction, AuditEntry};
+use app::erasure::erase;
+use app::password::hash_password;
+use app::session::{ClientInfo, CurrentSession};
+use app::state::{AppState, TenantUsers};
+use app::tenant::Tenant;
+use app::theme::Theme;
+use app::user_update::{
+    check_language, check_login, check_password, check_roles, UpdateError, UserUpdate,
+};
+use app::users::{filtered_users, UserFilter};
+use app::{User, UserStatus};
+use axum::{
+    extract::{
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+        Path, Query, Request, State,
+    },
+    http::{
+        header::{AUTHORIZATION, LINK, WWW_AUTHENTICATE},
+        HeaderValue, StatusCode,
+    },
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Json, Router,
+};
+use serde::Deserialize;
+use utoipa::{IntoParams, OpenApi};
+use uuid::Uuid;
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "User management API", version = "1"),
+    servers((url = "/api/v1")),
+    paths(list_users, create_user, get_user, update_user, delete_user),
+    components(schemas(
+        User,
+        UserStatus,
+        Theme,
+        NewUser,
+        UserPatch,
+        Page<User>,
+        PageLinks,
+        ApiError,
+        ApiErrorCode
+    ))
+)]
+struct ApiDoc;
+
+/// The API routes, to be nested at [`API_PATH`].
+pub fn router<S>(state: AppState) -> Router<S> {
+    Router::new()
+        .route("/openapi.json", get(openapi))
+        .route("/users", get(list_users).post(create_user))
+        .route(
+            "/users/{unid}",
+            get(get_user).patch(update_user).delete(delete_user),
+        )
+        .layer(middleware::from_fn_with_state(state.clone(), authenticate))
+        .with_state(state)
+}
+
+/// Who a request is from.
+#[derive(Clone, Debug)]
+enum Caller {
+    User(CurrentSession),
+    /// A service with a token from `API_TOKENS`.
+    Service,
+}
+
+impl Caller {
+    /// The signed-in user's session, `None` for a service.
+    fn session(&self) -> Option<&CurrentSession> {
+        match self {
+            Caller::User(session) => Some(session),
+            Caller::Service => None,
+        }
+    }
+}
+
+/// Tells the handlers who the request is from. A bearer token replaces the
+/// tenant of the host with its own; an unknown one is refused, even if the
+/// request also carries a session.
+async fn authenticate(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    let caller = if request.headers().contains_key(AUTHORIZATION) {
+        let Some(tenant) = state.api_tokens.authenticate(request.headers()) else {
+            let mut response =
+                ApiError::new(ApiErrorCode::Unauthorized, "Unknown bearer token").into_response();
+            response
+                .headers_mut()
+                .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+            return response;
+        };
+        request.extensions_mut().insert(tenant);
+        Some(Caller::Service)
+    } else {
+        request
+            .extensions()
+            .get::<CurrentSession>()
+            .cloned()
+            .map(Caller::User)
+    };
+    if let Some(caller) = caller {
+        request.extensions_mut().insert(caller);
+    }
+    next.run(request).await
+}
+
+async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+type ApiResult<T> = Result<T, ApiError>;
+
+fn not_found(unid: Uuid) -> ApiError {
+    ApiError::new(ApiErrorCode::NotFound, format!("User {unid} not found"))
+}
+
+fn invalid(message: impl Into<String>) -> ApiError {
+    ApiError::new(ApiErrorCode::InvalidInput, message)
+}
+
+// axum answers malformed requests in plain text; these turn its rejections
+// into the API's error bodies.
+
+fn json_body<T>(body: Result<Json<T>, JsonRejection>) -> ApiResult<T> {
+    body.map(|Json(body)| body)
+        .map_err(|rejection| invalid(rejection.body_text()))
+}
+
+fn unid(path: Result<Path<Uuid>, PathRejection>) -> ApiResult<Uuid> {
+    path.map(|Path(unid)| unid)
+        .map_err(|rejection| ApiError::new(ApiErrorCode::NotFound, rejection.body_text()))
+}
+
+fn authenticated(caller: Option<Extension<Caller>>) -> ApiResult<Caller> {
+    caller
+        .map(|Extension(caller)| caller)
+        .ok_or_else(|| ApiError::new(ApiErrorCode::Unauthorized, "Not signed in"))
+}
+
+fn authorize_admin(users: &TenantUsers, caller: Option<Extension<Caller>>) -> ApiResult<Caller> {
+    let caller = authenticated(caller)?;
+    match &caller {
+        Caller::User(session) if !users.is_admin(session.user) => {
+            Err(ApiError::new(ApiErrorCode::Forbidden, "Forbidden"))
+        }
+        _ => Ok(caller),
+    }
+}
+
+/// Like [`app::state::authorize_self_or_admin`]: users outside the tenant
+/// are not found, and only administrators and services may act on others.
+fn authorize_self_or_admin(
+    users: &TenantUsers,
+    caller: Option<Extension<Caller>>,
+    unid: Uuid,
+) -> ApiResult<Caller> {
+    let caller = authenticated(caller)?;
+    if users.get(unid).is_none() {
+        return Err(not_found(unid));
+    }
+    match &caller {
+        Caller::User(session) if session.user != unid && !users.is_admin(session.user) => {
+            Err(ApiError::new(ApiErrorCode::Forbidden, "Forbidden"))
+        }
+        _ => Ok(caller),
+    }
+}
+
+/// The API's error for a change [`app::user_update`] refused.
+fn refused(unid: Uuid, error: UpdateError) -> ApiError {
+    let code = match error {
+        UpdateError::NotFound => return not_found(unid),
+        UpdateError::Forbidden(_) => ApiErrorCode::Forbidden,
+        UpdateError::Invalid(_) => ApiErrorCode::InvalidInput,
+        UpdateError::LoginTaken(_) => ApiErrorCode::Conflict,
+    };
+    ApiError::new(code, error.to_string())
+}
+
+fn name(name: &str) -> Option<String> {
+    Some(name.trim().to_string()).filter(|name| !name.is_empty())
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ListParams {
+    /// Part of the login or name, ignoring case.
+    q: Option<String>,
+    status: Option<UserStatus>,
+    role: Option<String>,
+    /// Items to skip, 0 by default.
+    offset: Option<usize>,
+    /// Page size, 50 by default and 200 at most.
+    limit: Option<usize>,
+}
+
+/// Lists the tenant's users, sorted by login. Administrators only.
+#[utoipa::path(
+    get,
+    path = "/users",
+    params(ListParams),
+    responses(
+        (status = 200, description = "A page of users", body = Page<User>),
+        (status = 401, body = ApiError),
+        (status = 403, body = ApiError),
+    )
+)]
+async fn list_users(
+    State(state): State<AppState>,
+    Extension(tenant): Extension<Tenant>,
+    caller: Option<Extension<Caller>>,
+    params: Result<Query<ListParams>, QueryRejection>,
+) -> ApiResult<Response> {
+    let Query(params) = params.map_err(|rejection| invalid(rejection.body_text()))?;
+    let users = state.users.scoped(&tenant);
+    authorize_admin(&users, caller)?;
+
+    let filter = UserFilter {
+        q: params.q.unwrap_or_default().trim().to_string(),
+        status: params.status,
+        role: params.role.filter(|role| !role.is_empty()),
+    };
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let query = filter.to_query();
+    let page = Page::new(
+        filtered_users(&users, &filter)
+            .into_iter()
+            .map(User::public)
+            .collect(),
+        params.offset.unwrap_or(0),
+        limit,
+        |offset| {
+            let separator = if query.is_empty() { "" } else { "&" };
+            format!("{API_PATH}/users?{query}{separator}offset={offset}&limit={limit}")
+        },
+    );
+
+    let links = [
+        Some(("first", &page.links.first)),
+        page.links.prev.as_ref().map(|url| ("prev", url)),
+        page.links.next.as_ref().map(|url| ("next", url)),
+        Some(("last", &page.links.last)),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|(rel, url)| format!("<{url}>; rel=\"{rel}\""))
+    .collect::<Vec<_>>()
+    .join(", ");
+    let mut response = Json(page).into_response();
+    if let Ok(links) = HeaderValue::from_str(&links) {
+        response.headers_mut().insert(LINK, links);
+    }
+    Ok(response)
+}
+
+/// Creates a user in the tenant. Administrators only.
+#[utoipa::path(
+    post,
+    path = "/users",
+    request_body = NewUser,
+    responses(
+        (status = 201, description = "The created user", body = User),
+        (status = 401, body = ApiError),
+        (status = 403, body = ApiError),
+        (status = 409, description = "The login is taken", body = ApiError),
+        (status = 422, body = ApiError),
+    )
+)]
+async fn create_user(
+    State(state): State<AppState>,
+    Extension(tenant): Extension<Tenant>,
+    caller: Option<Extension<Caller>>,
+    new: Result<Json<NewUser>, JsonRejection>,
+) -> ApiResult<(StatusCode, Json<User>)> {
+    let new = json_body(new)?;
+    let users = state.users.scoped(&tenant);
+    authorize_admin(&users, caller)?;
+
+    let unid = Uuid::new_v4();
+    let status = new.status.unwrap_or(UserStatus::Active);
+    if !matches!(status, UserStatus::Active | UserStatus::Banned) {
+        return Err(invalid(
+            "Users are created active or banned; invite them to have them pending",
+        ));
+    }
+    if let Some(password) = &new.password {
+        check_password(password).map_err(|error| refused(unid, error))?;
+    }
+    let now = state.clock.now();
+    let user = User {
+        unid,
+        created: now,
+        first_name: new.first_name.as_deref().and_then(name),
+        hash: new
+            .password
+            .as_deref()
+            .map(hash_password)
+            .unwrap_or_default(),
+        last_failed_login: None,
+        last_login: None,
+        last_password_change: now,
+        last_name: new.last_name.as_deref().and_then(name),
+        login: check_login(&users, unid, &new.login).map_err(|error| refused(unid, error))?,
+        avatar: None,
+        roles: check_roles(&state.roles.scoped(&tenant), &new.roles)
+            .map_err(|error| refused(unid, error))?,
+        site_schema: None,
+        status,
+        theme: Default::default(),
+        language: new
+            .language
+            .as_deref()
+            .map(check_language)
+            .transpose()
+            .map_err(|error| refused(unid, error))?
+            .flatten(),
+    };
+    users.insert_all(vec![user.clone()]).map_err(|login| {
+        ApiError::new(
+            ApiErrorCode::Conflict,
+            format!("The login {login} is taken"),
+        )
+    })?;
+    Ok((StatusCode::CREATED, Json(user.public())))
+}
+
+/// A user of the tenant. Users may read their own account, administrators
+/// anyone's.
+#[utoipa::path(
+    get,
+    path = "/users/{unid}",
+    params(("unid" = Uuid, Path)),
+    responses(
+        (status = 200, body = User),
+        (status = 401, body = ApiError),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError),
+    )
+)]
+async fn get_user(
+    State(state): State<AppState>,
+    Extension(tenant): Extension<Tenant>,
+    caller: Option<Extension<Caller>>,
+    path: Result<Path<Uuid>, PathRejection>,
+) -> ApiResult<Json<User>> {
+    let unid = unid(path)?;
+    let users = state.users.scoped(&tenant);
+    authorize_self_or_admin(&users, caller, unid)?;
+    let user = users.get(unid).ok_or_else(|| not_found(unid))?;
+    Ok(Json(user.public()))
+}
+
+/// Changes some of a user's fields, by the rules of
+/// [`app::user_update::update_user`]: users may change their own names,
+/// login, password, language and theme, the login and password only with
+/// `current_password`; administrators anything of anyone's but their own
+/// status.
+#[utoipa::path(
+    patch,
+    path = "/users/{unid}",
+    params(("unid" = Uuid, Path)),
+    request_body = UserPatch,
+    responses(
+        (status = 200, description = "The updated user", body = User),
+        (status = 401, body = ApiError),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError),
+        (status = 409, description = "The login is taken", body = ApiError),
+        (status = 422, body = ApiError),
+    )
+)]
+async fn update_user(
+    State(state): State<AppState>,
+    Extension(tenant): Extension<Tenant>,
+    client: Option<Extension<ClientInfo>>,
+    caller: Option<Extension<Caller>>,
+    path: Result<Path<Uuid>, PathRejection>,
+    patch: Result<Json<UserPatch>, JsonRejection>,
+) -> ApiResult<Json<User>> {
+    let (unid, patch) = (unid(path)?, json_body(patch)?);
+    let users = state.users.scoped(&tenant);
+    let caller = authorize_self_or_admin(&users, caller, unid)?;
+    let user = app::user_update::update_user(
+        &state,
+        &users,
+        &state.roles.scoped(&tenant),
+        caller.session(),
+        unid,
+        UserUpdate {
+            login: patch.login,
+            first_name: patch.first_name,
+            last_name: patch.last_name,
+            password: patch.password,
+            current_password: patch.current_password,
+            status: patch.status,
+            roles: patch.roles,
+            language: patch.language,
+            theme: patch.theme,
+        },
+        client.and_then(|Extension(client)| client.ip),
+    )
+    .map_err(|error| refused(unid, error))?;
+    Ok(Json(user.public()))
+}
+
+/// Erases a user's personal data, see [`app::erasure`]. The account stays
+/// behind, deactivated. Administrators only, and not on themselves.
+#[utoipa::path(
+    delete,
+    path = "/users/{unid}",
+    params(("unid" = Uuid, Path)),
+    responses(
+        (status = 204, description = "The user was erased"),
+        (status = 401, body = ApiError),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError),
+    )
+)]
+async fn delete_user(
+    State(state): State<AppState>,
+    Extension(tenant): Extension<Tenant>,
+    client: Option<Extension<ClientInfo>>,
+    caller: Option<Extension<Caller>>,
+    path: Result<Path<Uuid>, PathRejection>,
+) -> ApiResult<StatusCode> {
+    let unid = unid(path)?;
+    let users = state.users.scoped(&tenant);
+    let caller = authorize_admin(&users, caller)?;
+    let session = caller.session();
+    if session.is_some_and(|session| session.user == unid) {
+        return Err(ApiError::new(
+            ApiErrorCode::Forbidden,
+            "You cannot erase your own account",
+        ));
+    }
+    if session.is_some_and(|session| session.impersonator.is_some()) {
+        return Err(ApiError::new(
+            ApiErrorCode::Forbidden,
+            "Accounts cannot be erased while impersonating",
+        ));
+    }
+    match erase(&state, &users, unid) {
+        None => Err(not_found(unid)),
+        Some(erased) => {
+            if erased {
+                state.audit.record(AuditEntry {
+                    at: state.clock.now(),
+                    actor: session.map(|session| session.user),
+                    subject: unid,
+                    action: AuditAction::Erased,
+                    ip: client.and_then(|Extension(client)| client.ip),
+                });
+            }
+            Ok(StatusCode::NO_CONTENT)
+        }
+    }
+}
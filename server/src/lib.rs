@@ -0,0 +1,7 @@
+//! The axum side of the server: middleware and the routes served outside of
+//! Leptos, shared by the binary and by tests running the router in-process.
+
+pub mod api;
+pub mod session;
+pub mod tenant;
+pub mod users;
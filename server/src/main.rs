@@ -1,8 +1,22 @@
-use axum::Router;
+#![recursion_limit = "256"]
+
+use std::net::SocketAddr;
+
+use axum::{
+    http::{header::CACHE_CONTROL, HeaderValue},
+    middleware,
+    routing::get,
+    Router,
+};
 use leptos::prelude::*;
 use leptos_axum::{generate_route_list, LeptosRoutes};
 use app::*;
+use app::state::AppState;
 use leptos::logging::log;
+use tower::ServiceBuilder;
+use tower_http::{services::ServeDir, set_header::SetResponseHeaderLayer};
+
+use server::{api, session, tenant, users};
 
 #[tokio::main]
 async fn main() {
@@ -10,22 +24,73 @@ async fn main() {
     let conf = get_configuration(None).unwrap();
     let addr = conf.leptos_options.site_addr;
     let leptos_options = conf.leptos_options;
+    let state = AppState::new();
+    app::ldap::spawn_sync(state.clone());
+    app::webhook::spawn_worker(state.clone());
     // Generate the list of routes in your Leptos App
     let routes = generate_route_list(App);
 
+    // Profile pictures are content-addressed, so they never change once served
+    let avatars = ServiceBuilder::new()
+        .layer(SetResponseHeaderLayer::overriding(
+            CACHE_CONTROL,
+            HeaderValue::from_static("public, max-age=31536000, immutable"),
+        ))
+        .service(ServeDir::new(state.avatars.dir()));
+
     let app = Router::new()
-        .leptos_routes(&leptos_options, routes, {
-            let leptos_options = leptos_options.clone();
-            move || shell(leptos_options.clone())
-        })
-        .fallback(leptos_axum::file_and_error_handler(shell))
+        .nest_service("/avatars", avatars)
+        .nest(app::scim::BASE_PATH, app::scim::router(state.clone()))
+        .nest(app::api::API_PATH, api::router(state.clone()))
+        .nest(app::oidc::OIDC_PATH, app::oidc::router(state.clone()))
+        .nest(app::oauth::OAUTH_PATH, app::oauth::router(state.clone()))
+        .route(
+            app::live::EVENTS_PATH,
+            get(app::live::stream).with_state(state.clone()),
+        )
+        .route(
+            app::presence::PRESENCE_PATH,
+            get(app::presence::connect).with_state(state.clone()),
+        )
+        .route(
+            "/users/export.csv",
+            get(users::export).with_state(state.clone()),
+        )
+        .route(
+            "/users/{unid}/personal-data.zip",
+            get(users::personal_data).with_state(state.clone()),
+        )
+        .leptos_routes_with_context(
+            &leptos_options,
+            routes,
+            {
+                let state = state.clone();
+                move || provide_context(state.clone())
+            },
+            {
+                let leptos_options = leptos_options.clone();
+                move || shell(leptos_options.clone())
+            },
+        )
+        .fallback(leptos_axum::file_and_error_handler_with_context(
+            {
+                let state = state.clone();
+                move || provide_context(state.clone())
+            },
+            shell,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            session::track_session,
+        ))
+        .layer(middleware::from_fn_with_state(state, tenant::resolve_tenant))
         .with_state(leptos_options);
 
     // run our app with hyper
     // `axum::Server` is a re-export of `hyper::Server`
     log!("listening on http://{}", &addr);
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    axum::serve(listener, app.into_make_service())
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .unwrap();
 }
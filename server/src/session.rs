@@ -0,0 +1,72 @@
+use std::net::SocketAddr;
+
+use app::session::{cookie_value, ClientInfo, SESSION_COOKIE};
+use app::state::AppState;
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, State},
+    http::{
+        header::{SET_COOKIE, USER_AGENT},
+        HeaderValue, Request,
+    },
+    middleware::Next,
+    response::Response,
+};
+use uuid::Uuid;
+
+/// Attaches the [`ClientInfo`] and, for signed-in browsers, the
+/// [`app::session::CurrentSession`] to every page and server function
+/// request.
+///
+/// A cookie naming an unknown or revoked session is cleared, so revocation
+/// takes effect on the very next request.
+pub async fn track_session(
+    State(state): State<AppState>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response {
+    let path = req.uri().path();
+    if path.starts_with("/pkg/") || path.starts_with("/avatars/") || path == "/favicon.ico" {
+        return next.run(req).await;
+    }
+
+    let client = ClientInfo {
+        ip: state.proxies.client_ip(
+            req.extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip()),
+            req.headers()
+                .get("x-forwarded-for")
+                .and_then(|value| value.to_str().ok()),
+        ),
+        user_agent: req
+            .headers()
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string(),
+    };
+    let cookie = cookie_value(req.headers(), SESSION_COOKIE).map(str::to_string);
+    let session = cookie
+        .as_deref()
+        .and_then(|value| value.parse::<Uuid>().ok())
+        .and_then(|id| {
+            state
+                .sessions
+                .touch(id, client.ip.clone(), state.clock.now())
+        });
+
+    req.extensions_mut().insert(client);
+    if let Some(session) = session {
+        req.extensions_mut().insert(session);
+    }
+
+    let mut res = next.run(req).await;
+    if cookie.is_some() && session.is_none() {
+        let expired = format!("{SESSION_COOKIE}=; Path=/; Max-Age=0");
+        if let Ok(value) = HeaderValue::from_str(&expired) {
+            res.headers_mut().append(SET_COOKIE, value);
+        }
+    }
+    res
+}
@@ -0,0 +1,26 @@
+use app::state::AppState;
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header::HOST, Request},
+    middleware::Next,
+    response::Response,
+};
+
+/// Attaches the [`app::tenant::Tenant`] named by the request's host to the
+/// request, for server functions and rendering to scope their queries by.
+pub async fn resolve_tenant(
+    State(state): State<AppState>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response {
+    let host = req
+        .headers()
+        .get(HOST)
+        .and_then(|value| value.to_str().ok())
+        .or_else(|| req.uri().authority().map(|authority| authority.as_str()))
+        .unwrap_or_default();
+    let tenant = state.tenants.resolve(host);
+    req.extensions_mut().insert(tenant);
+    next.run(req).await
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use app::audit::{AuditAction, AuditEntry};
+use app::personal_data::archive;
+use app::session::{ClientInfo, CurrentSession};
+use app::state::AppState;
+use app::tenant::Tenant;
+use app::user_csv::export_csv;
+use app::users::{filtered_users, UserFilter};
+use axum::{
+    extract::{Path, Query, State},
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        StatusCode,
+    },
+    response::{IntoResponse, Response},
+    Extension,
+};
+use uuid::Uuid;
+
+/// Downloads the tenant's users matching the [`UserFilter`] in the query
+/// string as CSV. Administrators only.
+pub async fn export(
+    State(state): State<AppState>,
+    Extension(tenant): Extension<Tenant>,
+    session: Option<Extension<CurrentSession>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let users = state.users.scoped(&tenant);
+    let Some(Extension(session)) = session else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    if !users.is_admin(session.user) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let filter = UserFilter::from_query(|key| query.get(key).cloned());
+    match export_csv(&filtered_users(&users, &filter)) {
+        Ok(csv) => (
+            [
+                (CONTENT_TYPE, "text/csv; charset=utf-8"),
+                (CONTENT_DISPOSITION, "attachment; filename=\"users.csv\""),
+            ],
+            csv,
+        )
+            .into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
+    }
+}
+
+/// Downloads the archive of everything held on a user. Users may download
+/// their own; administrators anyone's in the tenant.
+pub async fn personal_data(
+    State(state): State<AppState>,
+    Extension(tenant): Extension<Tenant>,
+    Extension(client): Extension<ClientInfo>,
+    session: Option<Extension<CurrentSession>>,
+    Path(unid): Path<Uuid>,
+) -> Response {
+    let users = state.users.scoped(&tenant);
+    let Some(Extension(session)) = session else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    if session.user != unid && !users.is_admin(session.user) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    match archive(&state, &users, unid) {
+        None => StatusCode::NOT_FOUND.into_response(),
+        Some(Ok(zip)) => {
+            state.audit.record(AuditEntry {
+                at: state.clock.now(),
+                actor: Some(session.impersonator.unwrap_or(session.user)),
+                subject: unid,
+                action: AuditAction::PersonalDataExported,
+                ip: client.ip,
+            });
+            let disposition = format!("attachment; filename=\"personal-data-{unid}.zip\"");
+            (
+                [
+                    (CONTENT_TYPE, "application/zip".to_string()),
+                    (CONTENT_DISPOSITION, disposition),
+                ],
+                zip,
+            )
+                .into_response()
+        }
+        Some(Err(err)) => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
+    }
+}